@@ -1,8 +1,7 @@
-use nom::{
-  bytes::complete::take,
-  IResult
-};
+use nom::bytes::complete::take;
 use nom_leb128::leb128_u32;
+use super::encode::{write_bytes, write_u32};
+use super::error::{fail, with_item, IResult};
 use super::instructions::Instructions;
 use super::value_type::ValueType;
 
@@ -24,48 +23,56 @@ impl Code {
     let (mut input, func_count) = leb128_u32(input)?;
     let mut codes: Vec<Code> = Vec::new();
 
-    for _ in 0..func_count {
-      let size: u32;
-      let mut code_body: &[u8];
-      let local_decls: u32;
-      let mut local_bytes: &[u8];
-      let mut locals: Vec<LocalVar> = Vec::new();
-
-      (input, size) = leb128_u32(input)?;
-
-      (input, code_body) = take(size as usize)(input)?;
-
-      (code_body, local_decls) = leb128_u32(code_body)?;
-      (code_body, local_bytes) = take(local_decls*2)(code_body)?;
-
-      for _ in 0..local_decls {
-        let count: u32;
-        let types: &[u8];
-
-        (local_bytes, count) = leb128_u32(local_bytes)?;
-        (local_bytes, types) = take(1_usize)(local_bytes)?;
+    for i in 0..func_count {
+      let code: Code;
+      (input, code) = with_item(i, Code::parse_single(input))?;
+      codes.push(code);
+    }
 
-        let value_type = ValueType::parse(types[0]);
+    Ok((input, codes))
+  }
 
-        locals.push(LocalVar { count, value_type });
-      }
-      
-      // (input, instrs_bytes) = take_till(|c| c == 0x0b)(input)?;
-      // (input, _) = tag([0x0b])(input)?;
-      // (input, instrs_bytes) = take(size as usize)(input)?;
+  fn parse_single(input: &[u8]) -> IResult<&[u8], Code> {
+    let (input, size) = leb128_u32(input)?;
+    let (input, mut code_body) = take(size as usize)(input)?;
+
+    let local_decls: u32;
+    let mut locals: Vec<LocalVar> = Vec::new();
+    (code_body, local_decls) = leb128_u32(code_body)?;
+
+    // countはLEB128なので1宣言あたりのバイト数は固定ではない
+    for _ in 0..local_decls {
+      let count: u32;
+      let value_type: ValueType;
+      (code_body, count) = leb128_u32(code_body)?;
+      (code_body, value_type) = ValueType::parse(code_body)?;
+      locals.push(LocalVar { count, value_type });
+    }
 
-      let (_, mut instrs) = Instructions::parse(code_body)?;
+    let (rest, mut instrs) = Instructions::parse(code_body)?;
 
-      match instrs.pop() {
-        Some(Instructions::End) => {},
-        _ => panic!("Code must end with End instruction"),
-      }
+    match instrs.pop() {
+      Some(Instructions::End) => {},
+      _ => return fail(rest, "an end instruction at the end of the function body"),
+    }
 
+    Ok((input, Code { size, locals, instrs }))
+  }
 
-      codes.push(Code { size, locals, instrs });
+  // sizeは読んだときの値なので使わず、書き出した本体の長さを前置する
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    let mut body = Vec::new();
+    write_u32(&mut body, self.locals.len() as u32);
+    for local in &self.locals {
+      write_u32(&mut body, local.count);
+      local.value_type.encode(&mut body);
     }
-
-    Ok((input, codes))
+    for instr in &self.instrs {
+      instr.encode(&mut body);
+    }
+    // parseで取り除いた関数末尾のend
+    Instructions::End.encode(&mut body);
+    write_bytes(buf, &body);
   }
 }
 
@@ -0,0 +1,24 @@
+use nom::bytes::complete::take;
+
+use super::encode::write_name;
+use super::error::IResult;
+use super::name_sec::parse_name;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomSec {
+  pub name: String,
+  pub data: Vec<u8>,
+}
+
+impl CustomSec {
+  pub fn parse(input: &[u8]) -> IResult<&[u8], CustomSec> {
+    let (input, name) = parse_name(input)?;
+    let (input, data) = take(input.len())(input)?;
+    Ok((input, CustomSec { name, data: data.to_vec() }))
+  }
+
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    write_name(buf, &self.name);
+    buf.extend_from_slice(&self.data);
+  }
+}
@@ -1,45 +1,69 @@
-use nom::{bytes::complete::{tag, take}, IResult};
-use nom_leb128::{leb128_u32, leb128_u64};
+use nom::bytes::complete::take;
+use nom_leb128::leb128_u32;
+
+use super::encode::{encode_expr, write_bytes, write_u32};
+use super::error::{fail, with_item, IResult};
+use super::instructions::Instructions;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Data {
-  pub memory_index: u32,
-  pub offset: u32,
+  pub mode: DataMode,
   pub init: Vec<u8>,
 }
 
+// passiveなセグメントはmemory.initで明示的にコピーされるまでメモリに配置されない
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataMode {
+  Passive,
+  Active { memory_index: u32, offset: Vec<Instructions> },
+}
+
 impl Data {
   pub fn parse(input: &[u8]) -> IResult<&[u8], Vec<Data>> {
     let (mut input, count) = leb128_u32(input)?;
     let mut data = vec![];
-    for _ in 0..count {
-        let (rest, memory_index) = leb128_u32(input)?;
-        let (rest, offset) = decode_expr(rest)?;
-        let (rest, size) = leb128_u32(rest)?;
-        let (rest, init) = take(size)(rest)?;
-        data.push(Data {
-            memory_index,
-            offset,
-            init: init.into(),
-        });
-        input = rest;
+    for i in 0..count {
+        let segment: Data;
+        (input, segment) = with_item(i, Data::parse_single(input))?;
+        data.push(segment);
     }
     Ok((input, data))
   }
-}
 
-fn decode_expr(input: &[u8]) -> IResult<&[u8], u32> {
-  let (mut input, expr) = take(1usize)(input)?;
-  let offset: u32;
-  match expr[0] {
-    0x41 => {(input, offset) = leb128_u32(input)?;},
-    0x42 => { 
-      let tmp: u64;
-      (input, tmp) = leb128_u64(input)?;
-      offset = tmp as u32;
+  fn parse_single(input: &[u8]) -> IResult<&[u8], Data> {
+    let (rest, flags) = leb128_u32(input)?;
+    let (rest, mode) = match flags {
+      0x00 => {
+        let (rest, offset) = Instructions::parse_init(rest)?;
+        (rest, DataMode::Active { memory_index: 0, offset })
+      },
+      0x01 => (rest, DataMode::Passive),
+      0x02 => {
+        let (rest, memory_index) = leb128_u32(rest)?;
+        let (rest, offset) = Instructions::parse_init(rest)?;
+        (rest, DataMode::Active { memory_index, offset })
+      },
+      _ => return fail(input, format!("a data segment flag of 0, 1 or 2 (found {})", flags)),
+    };
+    let (rest, size) = leb128_u32(rest)?;
+    let (rest, init) = take(size)(rest)?;
+    Ok((rest, Data { mode, init: init.into() }))
+  }
+
+  // メモリ0へのactiveなセグメントはメモリインデックスを省いたフラグ0で書く
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    match &self.mode {
+      DataMode::Active { memory_index: 0, offset } => {
+        buf.push(0x00);
+        encode_expr(buf, offset);
+      },
+      DataMode::Passive => buf.push(0x01),
+      DataMode::Active { memory_index, offset } => {
+        buf.push(0x02);
+        write_u32(buf, *memory_index);
+        encode_expr(buf, offset);
+      },
     }
-    _ => panic!("unknown expression")
+    write_bytes(buf, &self.init);
   }
-  let (input, _) = tag([0x0b])(input)?;
-  Ok((input, offset))
-}
\ No newline at end of file
+}
@@ -0,0 +1,177 @@
+use nom::bytes::complete::tag;
+use nom_leb128::leb128_u32;
+
+use super::encode::{encode_expr, write_u32};
+use super::error::{fail, with_item, IResult};
+
+use super::instructions::Instructions;
+use super::table_sec::RefType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Elem {
+  pub mode: ElemMode,
+  pub reftype: RefType,
+  pub init: ElemInit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElemMode {
+  Passive,
+  Active { table_idx: u32, offset: Vec<Instructions> },
+  Declarative,
+}
+
+// 要素はfuncidxのベクタか定数式のベクタのどちらかで与えられる
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElemInit {
+  FuncIndices(Vec<u32>),
+  Exprs(Vec<Vec<Instructions>>),
+}
+
+impl Elem {
+  pub fn parse(input: &[u8]) -> IResult<&[u8], Vec<Elem>> {
+    let (mut input, count) = leb128_u32(input)?;
+    let mut elems = Vec::new();
+
+    for i in 0..count {
+      let elem: Elem;
+      (input, elem) = with_item(i, Elem::parse_single(input))?;
+      elems.push(elem);
+    }
+
+    Ok((input, elems))
+  }
+
+  fn parse_single(input: &[u8]) -> IResult<&[u8], Elem> {
+      let (rest, flags) = leb128_u32(input)?;
+      let (rest, elem) = match flags {
+        0x00 => {
+          let (rest, offset) = Instructions::parse_init(rest)?;
+          let (rest, funcs) = parse_func_indices(rest)?;
+          (rest, Elem {
+            mode: ElemMode::Active { table_idx: 0, offset },
+            reftype: RefType::FuncRef,
+            init: ElemInit::FuncIndices(funcs),
+          })
+        },
+        0x01 | 0x03 => {
+          let (rest, _) = tag([0x00])(rest)?;
+          let (rest, funcs) = parse_func_indices(rest)?;
+          let mode = if flags == 0x01 { ElemMode::Passive } else { ElemMode::Declarative };
+          (rest, Elem { mode, reftype: RefType::FuncRef, init: ElemInit::FuncIndices(funcs) })
+        },
+        0x02 => {
+          let (rest, table_idx) = leb128_u32(rest)?;
+          let (rest, offset) = Instructions::parse_init(rest)?;
+          let (rest, _) = tag([0x00])(rest)?;
+          let (rest, funcs) = parse_func_indices(rest)?;
+          (rest, Elem {
+            mode: ElemMode::Active { table_idx, offset },
+            reftype: RefType::FuncRef,
+            init: ElemInit::FuncIndices(funcs),
+          })
+        },
+        0x04 => {
+          let (rest, offset) = Instructions::parse_init(rest)?;
+          let (rest, exprs) = parse_exprs(rest)?;
+          (rest, Elem {
+            mode: ElemMode::Active { table_idx: 0, offset },
+            reftype: RefType::FuncRef,
+            init: ElemInit::Exprs(exprs),
+          })
+        },
+        0x05 | 0x07 => {
+          let (rest, reftype) = RefType::parse(rest)?;
+          let (rest, exprs) = parse_exprs(rest)?;
+          let mode = if flags == 0x05 { ElemMode::Passive } else { ElemMode::Declarative };
+          (rest, Elem { mode, reftype, init: ElemInit::Exprs(exprs) })
+        },
+        0x06 => {
+          let (rest, table_idx) = leb128_u32(rest)?;
+          let (rest, offset) = Instructions::parse_init(rest)?;
+          let (rest, reftype) = RefType::parse(rest)?;
+          let (rest, exprs) = parse_exprs(rest)?;
+          (rest, Elem {
+            mode: ElemMode::Active { table_idx, offset },
+            reftype,
+            init: ElemInit::Exprs(exprs),
+          })
+        },
+        _ => return fail(input, format!("element segment flags 0..7 (found {:#x})", flags)),
+      };
+      Ok((rest, elem))
+  }
+
+  // テーブル0でfuncrefのactiveなセグメントはテーブルインデックスと型を省いたフラグで書く
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    let exprs = matches!(self.init, ElemInit::Exprs(_));
+    let flags = match &self.mode {
+      ElemMode::Active { table_idx: 0, .. } if self.reftype == RefType::FuncRef => 0x00,
+      ElemMode::Passive => 0x01,
+      ElemMode::Active { .. } => 0x02,
+      ElemMode::Declarative => 0x03,
+    } | if exprs { 0x04 } else { 0x00 };
+    buf.push(flags);
+    if let ElemMode::Active { table_idx, offset } = &self.mode {
+      if flags & 0x02 != 0 {
+        write_u32(buf, *table_idx);
+      }
+      encode_expr(buf, offset);
+    }
+    // フラグ0と4以外は要素の種類を書く。funcidxのベクタではelemkindの0x00になる
+    if flags & 0x03 != 0 {
+      if exprs {
+        self.reftype.encode(buf);
+      } else {
+        buf.push(0x00);
+      }
+    }
+    match &self.init {
+      ElemInit::FuncIndices(funcs) => {
+        write_u32(buf, funcs.len() as u32);
+        for func_idx in funcs {
+          write_u32(buf, *func_idx);
+        }
+      },
+      ElemInit::Exprs(exprs) => {
+        write_u32(buf, exprs.len() as u32);
+        for expr in exprs {
+          encode_expr(buf, expr);
+        }
+      },
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    match &self.init {
+      ElemInit::FuncIndices(funcs) => funcs.len(),
+      ElemInit::Exprs(exprs) => exprs.len(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+fn parse_func_indices(input: &[u8]) -> IResult<&[u8], Vec<u32>> {
+  let (mut input, count) = leb128_u32(input)?;
+  let mut funcs = Vec::new();
+  for _ in 0..count {
+    let func_idx: u32;
+    (input, func_idx) = leb128_u32(input)?;
+    funcs.push(func_idx);
+  }
+  Ok((input, funcs))
+}
+
+fn parse_exprs(input: &[u8]) -> IResult<&[u8], Vec<Vec<Instructions>>> {
+  let (mut input, count) = leb128_u32(input)?;
+  let mut exprs = Vec::new();
+  for _ in 0..count {
+    let expr: Vec<Instructions>;
+    (input, expr) = Instructions::parse_init(input)?;
+    exprs.push(expr);
+  }
+  Ok((input, exprs))
+}
@@ -0,0 +1,93 @@
+// Wasm::encodeと各セクションのencodeが共有する書き出し処理。整数は最短のLEB128で書く
+
+use super::instructions::Instructions;
+
+pub(crate) fn write_u32(buf: &mut Vec<u8>, value: u32) {
+  write_u64(buf, value.into());
+}
+
+pub(crate) fn write_u64(buf: &mut Vec<u8>, mut value: u64) {
+  loop {
+    let byte = (value & 0x7f) as u8;
+    value >>= 7;
+    if value == 0 {
+      buf.push(byte);
+      return;
+    }
+    buf.push(byte | 0x80);
+  }
+}
+
+pub(crate) fn write_i32(buf: &mut Vec<u8>, value: i32) {
+  write_i64(buf, value.into());
+}
+
+// 残りが符号ビットだけになり、最後のバイトのビット6が符号と一致したところで止める
+pub(crate) fn write_i64(buf: &mut Vec<u8>, mut value: i64) {
+  loop {
+    let byte = (value & 0x7f) as u8;
+    value >>= 7;
+    if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+      buf.push(byte);
+      return;
+    }
+    buf.push(byte | 0x80);
+  }
+}
+
+pub(crate) fn write_name(buf: &mut Vec<u8>, name: &str) {
+  write_bytes(buf, name.as_bytes());
+}
+
+// 長さを前置したバイト列
+pub(crate) fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+  write_u32(buf, bytes.len() as u32);
+  buf.extend_from_slice(bytes);
+}
+
+// alignのビット6でメモリインデックスの有無を示す。メモリ0は省いてmulti-memory以前と同じ形にする
+pub(crate) fn write_memarg(buf: &mut Vec<u8>, align: u32, memory: u32, offset: u64) {
+  if memory == 0 {
+    write_u32(buf, align);
+  } else {
+    write_u32(buf, align | 0x40);
+    write_u32(buf, memory);
+  }
+  write_u64(buf, offset);
+}
+
+// 定数式はparse_initが末尾のendまで含めて返すので、そのまま並べればよい
+pub(crate) fn encode_expr(buf: &mut Vec<u8>, expr: &[Instructions]) {
+  for instr in expr {
+    instr.encode(buf);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use nom_leb128::{leb128_i64, leb128_u64};
+
+  use super::*;
+
+  #[test]
+  fn test_write_leb128() {
+    let mut buf = Vec::new();
+    write_u32(&mut buf, 624485);
+    assert_eq!(buf, [0xe5, 0x8e, 0x26]);
+
+    let mut buf = Vec::new();
+    write_i64(&mut buf, -123456);
+    assert_eq!(buf, [0xc0, 0xbb, 0x78]);
+
+    for value in [0, 63, 64, -64, -65, i64::MIN, i64::MAX] {
+      let mut buf = Vec::new();
+      write_i64(&mut buf, value);
+      assert_eq!(leb128_i64::<_, ()>(&buf[..]), Ok((&[][..], value)));
+    }
+    for value in [0, 127, 128, u64::MAX] {
+      let mut buf = Vec::new();
+      write_u64(&mut buf, value);
+      assert_eq!(leb128_u64::<_, ()>(&buf[..]), Ok((&[][..], value)));
+    }
+  }
+}
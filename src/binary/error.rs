@@ -0,0 +1,90 @@
+use std::fmt;
+
+use nom::error::{ContextError, ErrorKind};
+
+pub type IResult<I, O> = nom::IResult<I, O, DecodeError<I>>;
+
+// 各セクションのデコードで起きたエラー。inputは失敗した時点の残りの入力で、Wasm::newが先頭からのオフセットに直す
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeError<I> {
+  pub input: I,
+  pub section: Option<&'static str>,
+  pub item: Option<u32>,
+  pub expected: String,
+}
+
+// 不正なバイナリに対してWasm::newが返すエラー
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+  pub section: Option<String>,
+  pub item: Option<u32>,
+  pub offset: usize,
+  pub expected: String,
+}
+
+impl<I> DecodeError<I> {
+  pub fn new(input: I, expected: impl Into<String>) -> DecodeError<I> {
+    DecodeError { input, section: None, item: None, expected: expected.into() }
+  }
+
+  pub fn in_section(self, section: &'static str) -> DecodeError<I> {
+    DecodeError { section: Some(section), ..self }
+  }
+
+  pub fn in_item(self, item: u32) -> DecodeError<I> {
+    DecodeError { item: Some(item), ..self }
+  }
+}
+
+impl<I> nom::error::ParseError<I> for DecodeError<I> {
+  fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+    let expected = match kind {
+      ErrorKind::Eof => "more bytes (unexpected end)".to_string(),
+      ErrorKind::Tag => "a specific byte sequence".to_string(),
+      ErrorKind::TooLarge => "an integer that fits in its type".to_string(),
+      _ => format!("{:?}", kind),
+    };
+    DecodeError::new(input, expected)
+  }
+
+  fn append(_: I, _: ErrorKind, other: Self) -> Self {
+    other
+  }
+}
+
+impl<I> ContextError<I> for DecodeError<I> {
+  fn add_context(_input: I, ctx: &'static str, other: Self) -> Self {
+    DecodeError { expected: ctx.to_string(), ..other }
+  }
+}
+
+// inputの位置で何を期待していたかを持つデコードエラーを返す
+pub fn fail<I, O>(input: I, expected: impl Into<String>) -> IResult<I, O> {
+  Err(nom::Err::Error(DecodeError::new(input, expected)))
+}
+
+// ベクタのitem番目の要素をデコード中に起きたエラーに印を付ける。入れ子のときは外側の番号で上書きされる
+pub fn with_item<I, O>(item: u32, result: IResult<I, O>) -> IResult<I, O> {
+  result.map_err(|e| e.map(|e| e.in_item(item)))
+}
+
+impl ParseError {
+  pub fn new(offset: usize, expected: impl Into<String>) -> ParseError {
+    ParseError { section: None, item: None, offset, expected: expected.into() }
+  }
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "malformed module at offset {:#x}", self.offset)?;
+    if let Some(section) = &self.section {
+      write!(f, " in {} section", section)?;
+    }
+    if let Some(item) = self.item {
+      write!(f, " (item {})", item)?;
+    }
+    write!(f, ": expected {}", self.expected)
+  }
+}
+
+impl std::error::Error for ParseError {}
@@ -1,13 +1,16 @@
-use nom::{
-  bytes::complete::take, number::complete::le_u8, IResult
-};
+use nom::number::complete::le_u8;
 use nom_leb128::leb128_u32;
 
+use super::encode::{write_name, write_u32};
+use super::error::{fail, with_item, IResult};
+use super::name_sec::parse_name;
+
 #[derive(Debug, PartialEq)]
 pub struct ExportFunc{
   pub name: String,
   pub desc: ExportDesc,
-  pub func_idx: u32,
+  // descの種類ごとのインデックス空間でのインデックス
+  pub idx: u32,
 }
 
 #[derive(Debug, PartialEq)]
@@ -16,6 +19,7 @@ pub enum ExportDesc {
   Table,
   Mem,
   Global,
+  Tag,
 }
 
 impl ExportFunc {
@@ -23,37 +27,46 @@ impl ExportFunc {
     let (mut input, func_count) = leb128_u32(input)?;
     let mut funcs: Vec<ExportFunc> = Vec::new();
 
-    for _ in 0..func_count {
-      let string_len: u32;
-      let name_buf: &[u8];
-      let desc: ExportDesc;
-      let func_idx: u32;
-
-      (input, string_len) = leb128_u32(input)?;
-      (input, name_buf) = take(string_len)(input)?;
-
-      let name = String::from_utf8(name_buf.to_vec()).unwrap();
-
-      (input, desc) = ExportDesc::parse(input)?;
-      (input, func_idx) = leb128_u32(input)?;
-
-      funcs.push(ExportFunc { name, desc, func_idx });
+    for i in 0..func_count {
+      let func: ExportFunc;
+      (input, func) = with_item(i, ExportFunc::parse_single(input))?;
+      funcs.push(func);
     }
 
     Ok((input, funcs))
   }
+
+  fn parse_single(input: &[u8]) -> IResult<&[u8], ExportFunc> {
+    let (input, name) = parse_name(input)?;
+    let (input, desc) = ExportDesc::parse(input)?;
+    let (input, idx) = leb128_u32(input)?;
+    Ok((input, ExportFunc { name, desc, idx }))
+  }
+
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    write_name(buf, &self.name);
+    buf.push(match self.desc {
+      ExportDesc::Func => 0x00,
+      ExportDesc::Table => 0x01,
+      ExportDesc::Mem => 0x02,
+      ExportDesc::Global => 0x03,
+      ExportDesc::Tag => 0x04,
+    });
+    write_u32(buf, self.idx);
+  }
 }
 
 impl ExportDesc {
   pub fn parse(input: &[u8]) -> IResult<&[u8], ExportDesc> {
-    let (input, desc) = le_u8(input)?;
+    let (rest, desc) = le_u8(input)?;
 
     match desc {
-      0x00 => Ok((input, ExportDesc::Func)),
-      0x01 => Ok((input, ExportDesc::Table)),
-      0x02 => Ok((input, ExportDesc::Mem)),
-      0x03 => Ok((input, ExportDesc::Global)),
-      _ => panic!("Unknown export description: {:#x?}", desc),
+      0x00 => Ok((rest, ExportDesc::Func)),
+      0x01 => Ok((rest, ExportDesc::Table)),
+      0x02 => Ok((rest, ExportDesc::Mem)),
+      0x03 => Ok((rest, ExportDesc::Global)),
+      0x04 => Ok((rest, ExportDesc::Tag)),
+      _ => fail(input, format!("an export kind (found {:#04x})", desc)),
     }
   }
-}
\ No newline at end of file
+}
@@ -1,6 +1,7 @@
-use nom::IResult;
 use nom_leb128::leb128_u32;
 
+use super::error::{with_item, IResult};
+
 
 #[derive(Debug, PartialEq)]
 pub struct Func {
@@ -12,9 +13,9 @@ impl Func {
     let (mut input, func_count) = leb128_u32(input)?;
     let mut funcs: Vec<Func> = Vec::new();
 
-    for _ in 0..func_count {
+    for i in 0..func_count {
       let type_idx: u32;
-      (input, type_idx) = leb128_u32(input)?;
+      (input, type_idx) = with_item(i, leb128_u32(input))?;
 
       funcs.push(Func { type_idx });
     }
@@ -1,6 +1,8 @@
-use nom::{bytes::complete::take, IResult};
+use nom::number::complete::le_u8;
 use nom_leb128::leb128_u32;
 
+use super::encode::encode_expr;
+use super::error::{fail, with_item, IResult};
 use super::{instructions::Instructions, value_type::ValueType};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -15,17 +17,38 @@ impl GlobalVar {
     let (mut input, count) = leb128_u32(input)?;
     let mut globals: Vec<GlobalVar> = Vec::new();
 
-    for _ in 0..count {
-      let (rest, valtype) = take(1usize)(input)?;
-      let valtype = ValueType::parse(valtype[0]);
-      let (rest, mutability) = take(1usize)(rest)?;
-      let mutability = mutability[0];
-      let (rest, init) = Instructions::parse_init(rest).unwrap();
-
-      globals.push(GlobalVar { valtype, mutability: mutability == 1, init });
-      input = rest;
+    for i in 0..count {
+      let global: GlobalVar;
+      (input, global) = with_item(i, GlobalVar::parse_single(input))?;
+      globals.push(global);
     }
     Ok((input, globals))
     
   }
-}
\ No newline at end of file
+
+  fn parse_single(input: &[u8]) -> IResult<&[u8], GlobalVar> {
+    let (rest, (valtype, mutability)) = GlobalVar::parse_type(input)?;
+    let (rest, init) = Instructions::parse_init(rest)?;
+    Ok((rest, GlobalVar { valtype, mutability, init }))
+  }
+
+  // グローバルの型 (値の型と可変性)。importでも同じ形で現れる
+  pub(crate) fn parse_type(input: &[u8]) -> IResult<&[u8], (ValueType, bool)> {
+    let (input, valtype) = ValueType::parse(input)?;
+    let (rest, mutability) = le_u8(input)?;
+    if mutability > 1 {
+      return fail(input, format!("mutability 0x00 or 0x01 (found {:#04x})", mutability));
+    }
+    Ok((rest, (valtype, mutability == 1)))
+  }
+
+  pub(crate) fn encode_type(valtype: &ValueType, mutability: bool, buf: &mut Vec<u8>) {
+    valtype.encode(buf);
+    buf.push(mutability as u8);
+  }
+
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    GlobalVar::encode_type(&self.valtype, self.mutability, buf);
+    encode_expr(buf, &self.init);
+  }
+}
@@ -1,7 +1,14 @@
-use nom::IResult;
-use nom::bytes::complete::take;
+use nom::number::complete::le_u8;
 use nom_leb128::leb128_u32;
 
+use super::encode::{write_name, write_u32};
+use super::error::{fail, with_item, IResult};
+use super::global_sec::GlobalVar;
+use super::memory_sec::MemorySec;
+use super::name_sec::parse_name;
+use super::table_sec::TableSec;
+use super::tag_sec::Tag;
+use super::value_type::ValueType;
 
 #[derive(Debug, PartialEq)]
 pub struct Import {
@@ -13,9 +20,11 @@ pub struct Import {
 #[derive(Debug, PartialEq)]
 pub enum ImportDesc {
   Func(u32),
-  Table,
-  Memory,
-  Global,
+  Table(TableSec),
+  Memory(MemorySec),
+  Global { valtype: ValueType, mutability: bool },
+  // タグの型インデックス
+  Tag(u32),
 }
 
 impl Import {
@@ -23,43 +32,69 @@ impl Import {
     let (mut input, import_count) = leb128_u32(input)?;
     let mut imports: Vec<Import> = Vec::new();
 
-    for _ in 0..import_count {
-      let module_len: u32;
-      let module: &[u8];
-      let field_len: u32;
-      let field: &[u8];
-      let kind: &[u8];
-      let type_idx: u32;
+    for i in 0..import_count {
+      let import: Import;
+      (input, import) = with_item(i, Import::parse_single(input))?;
+      imports.push(import);
+    }
 
-      (input, module_len) = leb128_u32(input)?;
-      (input, module) = take(module_len as usize)(input)?;
-      (input, field_len) = leb128_u32(input)?;
-      (input, field) = take(field_len as usize)(input)?;
-      (input, kind) = take(1_usize)(input)?;
+    Ok((input, imports))
+  }
 
-      let module = String::from_utf8(module.to_vec()).unwrap();
-      let field = String::from_utf8(field.to_vec()).unwrap();
-      let kind = kind[0];
+  fn parse_single(input: &[u8]) -> IResult<&[u8], Import> {
+    let (input, module) = parse_name(input)?;
+    let (input, field) = parse_name(input)?;
+    let (rest, kind) = le_u8(input)?;
 
-      match kind {
-        0x00 => {
-          (input, type_idx) = leb128_u32(input)?;
-          imports.push(Import { module, field, desc: ImportDesc::Func(type_idx) });
-        },
-        0x01 => {
-          imports.push(Import { module, field, desc: ImportDesc::Table });
-        },
-        0x02 => {
-          imports.push(Import { module, field, desc: ImportDesc::Memory });
-        },
-        0x03 => {
-          imports.push(Import { module, field, desc: ImportDesc::Global });
-        },
-        _ => panic!("Invalid import kind: {:#x?}", kind),
-      }
+    match kind {
+      0x00 => {
+        let (rest, type_idx) = leb128_u32(rest)?;
+        Ok((rest, Import { module, field, desc: ImportDesc::Func(type_idx) }))
+      },
+      0x01 => {
+        let (rest, table) = TableSec::parse_single(rest)?;
+        Ok((rest, Import { module, field, desc: ImportDesc::Table(table) }))
+      },
+      0x02 => {
+        let (rest, memory) = MemorySec::parse_single(rest)?;
+        Ok((rest, Import { module, field, desc: ImportDesc::Memory(memory) }))
+      },
+      0x03 => {
+        let (rest, (valtype, mutability)) = GlobalVar::parse_type(rest)?;
+        Ok((rest, Import { module, field, desc: ImportDesc::Global { valtype, mutability } }))
+      },
+      0x04 => {
+        let (rest, tag) = Tag::parse_single(rest)?;
+        Ok((rest, Import { module, field, desc: ImportDesc::Tag(tag.type_idx) }))
+      },
+      _ => fail(input, format!("an import kind (found {:#04x})", kind)),
     }
+  }
 
-    Ok((input, imports))
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    write_name(buf, &self.module);
+    write_name(buf, &self.field);
+    match &self.desc {
+      ImportDesc::Func(type_idx) => {
+        buf.push(0x00);
+        write_u32(buf, *type_idx);
+      },
+      ImportDesc::Table(table) => {
+        buf.push(0x01);
+        table.encode(buf);
+      },
+      ImportDesc::Memory(memory) => {
+        buf.push(0x02);
+        memory.encode(buf);
+      },
+      ImportDesc::Global { valtype, mutability } => {
+        buf.push(0x03);
+        GlobalVar::encode_type(valtype, *mutability, buf);
+      },
+      ImportDesc::Tag(type_idx) => {
+        buf.push(0x04);
+        Tag { type_idx: *type_idx }.encode(buf);
+      },
+    }
   }
 }
-
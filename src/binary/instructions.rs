@@ -1,27 +1,43 @@
-use core::panic;
 use serde::{Deserialize, Serialize};
 
 use nom::{
-  bytes::complete::{tag, take}, number::complete::le_u8, IResult
+  bytes::complete::take, number::complete::le_u8
 };
 
-use nom_leb128::{leb128_i32, leb128_i64, leb128_u32};
+use nom_leb128::{leb128_i32, leb128_i64, leb128_u32, leb128_u64};
 
+use super::encode::{write_i32, write_i64, write_memarg, write_u32};
+use super::error::{fail, IResult};
+use super::table_sec::RefType;
 use super::value_type::ValueType;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BlockType {
   Void,
   Value(ValueType),
+  // 型セクションの関数型で引数と返り値を与える (multi-value)
+  TypeIndex(u32),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Block {
   pub block_type: BlockType,
+  // brで飛ぶ先。block/if/try_tableは対応するend、loopはloop命令自身を指す
   pub jump_pc: usize,
+  // ifのみ、elseがあればその位置
+  pub else_pc: Option<usize>,
   pub is_loop: bool,
 }
 
+// try_tableの節。labelはtry_tableの外側から数えたラベル
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Catch {
+  Catch { tag: u32, label: u32 },
+  CatchRef { tag: u32, label: u32 },
+  CatchAll { label: u32 },
+  CatchAllRef { label: u32 },
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Instructions {
   Unreachable,
@@ -37,38 +53,45 @@ pub enum Instructions {
   Return,
   Call(u32),
   CallIndirect(u32, u32),
+  ReturnCall(u32),
+  ReturnCallIndirect(u32, u32),
+  Throw(u32),
+  ThrowRef,
+  TryTable(Block, Vec<Catch>),
   Drop,
   Select,
   SelectValtype(Vec<ValueType>),
-  I32Load{align: u32, offset: u32},
-  I64Load{align: u32, offset: u32},
-  F32Load{align: u32, offset: u32},
-  F64Load{align: u32, offset: u32},
-  I32Load8S{align: u32, offset: u32},
-  I32Load8U{align: u32, offset: u32},
-  I32Load16S{align: u32, offset: u32},
-  I32Load16U{align: u32, offset: u32},
-  I64Load8S{align: u32, offset: u32},
-  I64Load8U{align: u32, offset: u32},
-  I64Load16S{align: u32, offset: u32},
-  I64Load16U{align: u32, offset: u32},
-  I64Load32S{align: u32, offset: u32},
-  I64Load32U{align: u32, offset: u32},
-  I32Store{align: u32, offset: u32},
-  I64Store{align: u32, offset: u32},
-  F32Store{align: u32, offset: u32},
-  F64Store{align: u32, offset: u32},
-  I32Store8{align: u32, offset: u32},
-  I32Store16{align: u32, offset: u32},
-  I64Store8{align: u32, offset: u32},
-  I64Store16{align: u32, offset: u32},
-  I64Store32{align: u32, offset: u32},
-  MemorySize,
-  MemoryGrow,
-  MemoryInit(u32),
+  I32Load{align: u32, memory: u32, offset: u64},
+  I64Load{align: u32, memory: u32, offset: u64},
+  F32Load{align: u32, memory: u32, offset: u64},
+  F64Load{align: u32, memory: u32, offset: u64},
+  I32Load8S{align: u32, memory: u32, offset: u64},
+  I32Load8U{align: u32, memory: u32, offset: u64},
+  I32Load16S{align: u32, memory: u32, offset: u64},
+  I32Load16U{align: u32, memory: u32, offset: u64},
+  I64Load8S{align: u32, memory: u32, offset: u64},
+  I64Load8U{align: u32, memory: u32, offset: u64},
+  I64Load16S{align: u32, memory: u32, offset: u64},
+  I64Load16U{align: u32, memory: u32, offset: u64},
+  I64Load32S{align: u32, memory: u32, offset: u64},
+  I64Load32U{align: u32, memory: u32, offset: u64},
+  I32Store{align: u32, memory: u32, offset: u64},
+  I64Store{align: u32, memory: u32, offset: u64},
+  F32Store{align: u32, memory: u32, offset: u64},
+  F64Store{align: u32, memory: u32, offset: u64},
+  I32Store8{align: u32, memory: u32, offset: u64},
+  I32Store16{align: u32, memory: u32, offset: u64},
+  I64Store8{align: u32, memory: u32, offset: u64},
+  I64Store16{align: u32, memory: u32, offset: u64},
+  I64Store32{align: u32, memory: u32, offset: u64},
+  MemorySize(u32),
+  MemoryGrow(u32),
+  // (dataidx, memidx)
+  MemoryInit(u32, u32),
   DataDrop(u32),
-  MemoryCopy,
-  MemoryFill,
+  // (dst memidx, src memidx)
+  MemoryCopy(u32, u32),
+  MemoryFill(u32),
   I32Const(i32),
   I64Const(i64),
   F32Const(f32),
@@ -201,11 +224,335 @@ pub enum Instructions {
   I64Extend8S,
   I64Extend16S,
   I64Extend32S,
+  I32TruncSatF32S,
+  I32TruncSatF32U,
+  I32TruncSatF64S,
+  I32TruncSatF64U,
+  I64TruncSatF32S,
+  I64TruncSatF32U,
+  I64TruncSatF64S,
+  I64TruncSatF64U,
   LocalGet(u32),
   LocalSet(u32),
   LocalTee(u32),
   GlobalGet(u32),
   GlobalSet(u32),
+  TableGet(u32),
+  TableSet(u32),
+  // (elemidx, tableidx)
+  TableInit(u32, u32),
+  ElemDrop(u32),
+  // (dst tableidx, src tableidx)
+  TableCopy(u32, u32),
+  TableGrow(u32),
+  TableSize(u32),
+  TableFill(u32),
+  RefNull(RefType),
+  RefIsNull,
+  RefFunc(u32),
+  V128Load{align: u32, memory: u32, offset: u64},
+  V128Load8x8S{align: u32, memory: u32, offset: u64},
+  V128Load8x8U{align: u32, memory: u32, offset: u64},
+  V128Load16x4S{align: u32, memory: u32, offset: u64},
+  V128Load16x4U{align: u32, memory: u32, offset: u64},
+  V128Load32x2S{align: u32, memory: u32, offset: u64},
+  V128Load32x2U{align: u32, memory: u32, offset: u64},
+  V128Load8Splat{align: u32, memory: u32, offset: u64},
+  V128Load16Splat{align: u32, memory: u32, offset: u64},
+  V128Load32Splat{align: u32, memory: u32, offset: u64},
+  V128Load64Splat{align: u32, memory: u32, offset: u64},
+  V128Store{align: u32, memory: u32, offset: u64},
+  V128Const(u128),
+  I8x16Shuffle([u8; 16]),
+  I8x16Swizzle,
+  I8x16Splat,
+  I16x8Splat,
+  I32x4Splat,
+  I64x2Splat,
+  F32x4Splat,
+  F64x2Splat,
+  I8x16ExtractLaneS(u8),
+  I8x16ExtractLaneU(u8),
+  I8x16ReplaceLane(u8),
+  I16x8ExtractLaneS(u8),
+  I16x8ExtractLaneU(u8),
+  I16x8ReplaceLane(u8),
+  I32x4ExtractLane(u8),
+  I32x4ReplaceLane(u8),
+  I64x2ExtractLane(u8),
+  I64x2ReplaceLane(u8),
+  F32x4ExtractLane(u8),
+  F32x4ReplaceLane(u8),
+  F64x2ExtractLane(u8),
+  F64x2ReplaceLane(u8),
+  I8x16Eq,
+  I8x16Ne,
+  I8x16LtS,
+  I8x16LtU,
+  I8x16GtS,
+  I8x16GtU,
+  I8x16LeS,
+  I8x16LeU,
+  I8x16GeS,
+  I8x16GeU,
+  I16x8Eq,
+  I16x8Ne,
+  I16x8LtS,
+  I16x8LtU,
+  I16x8GtS,
+  I16x8GtU,
+  I16x8LeS,
+  I16x8LeU,
+  I16x8GeS,
+  I16x8GeU,
+  I32x4Eq,
+  I32x4Ne,
+  I32x4LtS,
+  I32x4LtU,
+  I32x4GtS,
+  I32x4GtU,
+  I32x4LeS,
+  I32x4LeU,
+  I32x4GeS,
+  I32x4GeU,
+  F32x4Eq,
+  F32x4Ne,
+  F32x4Lt,
+  F32x4Gt,
+  F32x4Le,
+  F32x4Ge,
+  F64x2Eq,
+  F64x2Ne,
+  F64x2Lt,
+  F64x2Gt,
+  F64x2Le,
+  F64x2Ge,
+  V128Not,
+  V128And,
+  V128Andnot,
+  V128Or,
+  V128Xor,
+  V128Bitselect,
+  V128AnyTrue,
+  V128Load8Lane{align: u32, memory: u32, offset: u64, lane: u8},
+  V128Store8Lane{align: u32, memory: u32, offset: u64, lane: u8},
+  V128Load16Lane{align: u32, memory: u32, offset: u64, lane: u8},
+  V128Store16Lane{align: u32, memory: u32, offset: u64, lane: u8},
+  V128Load32Lane{align: u32, memory: u32, offset: u64, lane: u8},
+  V128Store32Lane{align: u32, memory: u32, offset: u64, lane: u8},
+  V128Load64Lane{align: u32, memory: u32, offset: u64, lane: u8},
+  V128Store64Lane{align: u32, memory: u32, offset: u64, lane: u8},
+  V128Load32Zero{align: u32, memory: u32, offset: u64},
+  V128Load64Zero{align: u32, memory: u32, offset: u64},
+  F32x4DemoteF64x2Zero,
+  F64x2PromoteLowF32x4,
+  I8x16Abs,
+  I8x16Neg,
+  I8x16Popcnt,
+  I8x16AllTrue,
+  I8x16Bitmask,
+  I8x16NarrowI16x8S,
+  I8x16NarrowI16x8U,
+  F32x4Ceil,
+  F32x4Floor,
+  F32x4Trunc,
+  F32x4Nearest,
+  I8x16Shl,
+  I8x16ShrS,
+  I8x16ShrU,
+  I8x16Add,
+  I8x16AddSatS,
+  I8x16AddSatU,
+  I8x16Sub,
+  I8x16SubSatS,
+  I8x16SubSatU,
+  F64x2Ceil,
+  F64x2Floor,
+  I8x16MinS,
+  I8x16MinU,
+  I8x16MaxS,
+  I8x16MaxU,
+  F64x2Trunc,
+  I8x16AvgrU,
+  I16x8ExtaddPairwiseI8x16S,
+  I16x8ExtaddPairwiseI8x16U,
+  I32x4ExtaddPairwiseI16x8S,
+  I32x4ExtaddPairwiseI16x8U,
+  I16x8Abs,
+  I16x8Neg,
+  I16x8Q15mulrSatS,
+  I16x8AllTrue,
+  I16x8Bitmask,
+  I16x8NarrowI32x4S,
+  I16x8NarrowI32x4U,
+  I16x8ExtendLowI8x16S,
+  I16x8ExtendHighI8x16S,
+  I16x8ExtendLowI8x16U,
+  I16x8ExtendHighI8x16U,
+  I16x8Shl,
+  I16x8ShrS,
+  I16x8ShrU,
+  I16x8Add,
+  I16x8AddSatS,
+  I16x8AddSatU,
+  I16x8Sub,
+  I16x8SubSatS,
+  I16x8SubSatU,
+  F64x2Nearest,
+  I16x8Mul,
+  I16x8MinS,
+  I16x8MinU,
+  I16x8MaxS,
+  I16x8MaxU,
+  I16x8AvgrU,
+  I16x8ExtmulLowI8x16S,
+  I16x8ExtmulHighI8x16S,
+  I16x8ExtmulLowI8x16U,
+  I16x8ExtmulHighI8x16U,
+  I32x4Abs,
+  I32x4Neg,
+  I32x4AllTrue,
+  I32x4Bitmask,
+  I32x4ExtendLowI16x8S,
+  I32x4ExtendHighI16x8S,
+  I32x4ExtendLowI16x8U,
+  I32x4ExtendHighI16x8U,
+  I32x4Shl,
+  I32x4ShrS,
+  I32x4ShrU,
+  I32x4Add,
+  I32x4Sub,
+  I32x4Mul,
+  I32x4MinS,
+  I32x4MinU,
+  I32x4MaxS,
+  I32x4MaxU,
+  I32x4DotI16x8S,
+  I32x4ExtmulLowI16x8S,
+  I32x4ExtmulHighI16x8S,
+  I32x4ExtmulLowI16x8U,
+  I32x4ExtmulHighI16x8U,
+  I64x2Abs,
+  I64x2Neg,
+  I64x2AllTrue,
+  I64x2Bitmask,
+  I64x2ExtendLowI32x4S,
+  I64x2ExtendHighI32x4S,
+  I64x2ExtendLowI32x4U,
+  I64x2ExtendHighI32x4U,
+  I64x2Shl,
+  I64x2ShrS,
+  I64x2ShrU,
+  I64x2Add,
+  I64x2Sub,
+  I64x2Mul,
+  I64x2Eq,
+  I64x2Ne,
+  I64x2LtS,
+  I64x2GtS,
+  I64x2LeS,
+  I64x2GeS,
+  I64x2ExtmulLowI32x4S,
+  I64x2ExtmulHighI32x4S,
+  I64x2ExtmulLowI32x4U,
+  I64x2ExtmulHighI32x4U,
+  F32x4Abs,
+  F32x4Neg,
+  F32x4Sqrt,
+  F32x4Add,
+  F32x4Sub,
+  F32x4Mul,
+  F32x4Div,
+  F32x4Min,
+  F32x4Max,
+  F32x4Pmin,
+  F32x4Pmax,
+  F64x2Abs,
+  F64x2Neg,
+  F64x2Sqrt,
+  F64x2Add,
+  F64x2Sub,
+  F64x2Mul,
+  F64x2Div,
+  F64x2Min,
+  F64x2Max,
+  F64x2Pmin,
+  F64x2Pmax,
+  I32x4TruncSatF32x4S,
+  I32x4TruncSatF32x4U,
+  F32x4ConvertI32x4S,
+  F32x4ConvertI32x4U,
+  I32x4TruncSatF64x2SZero,
+  I32x4TruncSatF64x2UZero,
+  F64x2ConvertLowI32x4S,
+  F64x2ConvertLowI32x4U,
+  MemoryAtomicNotify{align: u32, memory: u32, offset: u64},
+  MemoryAtomicWait32{align: u32, memory: u32, offset: u64},
+  MemoryAtomicWait64{align: u32, memory: u32, offset: u64},
+  AtomicFence,
+  I32AtomicLoad{align: u32, memory: u32, offset: u64},
+  I64AtomicLoad{align: u32, memory: u32, offset: u64},
+  I32AtomicLoad8U{align: u32, memory: u32, offset: u64},
+  I32AtomicLoad16U{align: u32, memory: u32, offset: u64},
+  I64AtomicLoad8U{align: u32, memory: u32, offset: u64},
+  I64AtomicLoad16U{align: u32, memory: u32, offset: u64},
+  I64AtomicLoad32U{align: u32, memory: u32, offset: u64},
+  I32AtomicStore{align: u32, memory: u32, offset: u64},
+  I64AtomicStore{align: u32, memory: u32, offset: u64},
+  I32AtomicStore8{align: u32, memory: u32, offset: u64},
+  I32AtomicStore16{align: u32, memory: u32, offset: u64},
+  I64AtomicStore8{align: u32, memory: u32, offset: u64},
+  I64AtomicStore16{align: u32, memory: u32, offset: u64},
+  I64AtomicStore32{align: u32, memory: u32, offset: u64},
+  I32AtomicRmwAdd{align: u32, memory: u32, offset: u64},
+  I64AtomicRmwAdd{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw8AddU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw16AddU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw8AddU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw16AddU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw32AddU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmwSub{align: u32, memory: u32, offset: u64},
+  I64AtomicRmwSub{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw8SubU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw16SubU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw8SubU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw16SubU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw32SubU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmwAnd{align: u32, memory: u32, offset: u64},
+  I64AtomicRmwAnd{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw8AndU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw16AndU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw8AndU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw16AndU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw32AndU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmwOr{align: u32, memory: u32, offset: u64},
+  I64AtomicRmwOr{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw8OrU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw16OrU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw8OrU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw16OrU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw32OrU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmwXor{align: u32, memory: u32, offset: u64},
+  I64AtomicRmwXor{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw8XorU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw16XorU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw8XorU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw16XorU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw32XorU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmwXchg{align: u32, memory: u32, offset: u64},
+  I64AtomicRmwXchg{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw8XchgU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw16XchgU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw8XchgU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw16XchgU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw32XchgU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmwCmpxchg{align: u32, memory: u32, offset: u64},
+  I64AtomicRmwCmpxchg{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw8CmpxchgU{align: u32, memory: u32, offset: u64},
+  I32AtomicRmw16CmpxchgU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw8CmpxchgU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw16CmpxchgU{align: u32, memory: u32, offset: u64},
+  I64AtomicRmw32CmpxchgU{align: u32, memory: u32, offset: u64},
 }
 
 impl Instructions {
@@ -222,25 +569,24 @@ impl Instructions {
         },
         Instructions::Loop(lblock) => {
           label_stack.push(None);
-          let nlb = Block { jump_pc: instructions.len() + 1, ..lblock };
+          let nlb = Block { jump_pc: instructions.len(), ..lblock };
           Instructions::Loop(nlb)
         },
-        Instructions::If(_) => {
+        Instructions::If(_) | Instructions::TryTable(_, _) => {
           label_stack.push(Some(instructions.len()));
           instr
         },
         Instructions::Else => {
-          match label_stack.pop() {
-            Some(Some(pc)) => match &instructions[pc] {
-              Instructions::If(block) => {
-                let block = Block { jump_pc: instructions.len(), ..block.clone() };
-                instructions[pc] = Instructions::If(block);
+          match label_stack.last() {
+            Some(Some(pc)) => match &instructions[*pc] {
+              Instructions::If(block) if block.else_pc.is_none() => {
+                let block = Block { else_pc: Some(instructions.len()), ..block.clone() };
+                instructions[*pc] = Instructions::If(block);
               },
-              _ => panic!("Invalid block type"),
+              _ => return fail(input, "else inside an if block"),
             },
-            _ => panic!("Invalid block type"),
+            _ => return fail(input, "else inside an if block"),
           }
-          label_stack.push(None);
           Instructions::Else
         },
         Instructions::End => {
@@ -254,7 +600,11 @@ impl Instructions {
                 let block = Block { jump_pc: instructions.len(), ..block.clone() };
                 instructions[pc] = Instructions::If(block);
               },
-              _ => panic!("Invalid block type"),
+              Instructions::TryTable(block, catches) => {
+                let block = Block { jump_pc: instructions.len(), ..block.clone() };
+                instructions[pc] = Instructions::TryTable(block, catches.clone());
+              },
+              _ => return fail(input, "end matching a block, if or try_table"),
             },
             Some(None) => {},
             None => {},
@@ -278,7 +628,7 @@ impl Instructions {
     loop {
       let (i, instr) = Instructions::parse_single(input)?;
       input = i;
-      if &instr == &Instructions::End {
+      if instr == Instructions::End {
         instrs.push(instr);
         break;
       }
@@ -287,8 +637,8 @@ impl Instructions {
     Ok((input, instrs))
   }
 
-  fn parse_single(input: &[u8]) -> IResult<&[u8], Instructions> {
-    let (input, opcode) = le_u8(input)?;
+  fn parse_single(start: &[u8]) -> IResult<&[u8], Instructions> {
+    let (input, opcode) = le_u8(start)?;
 
     match opcode {
       0x00 => Ok((input, Instructions::Unreachable)),
@@ -306,6 +656,11 @@ impl Instructions {
         Ok((input, Instructions::If(block)))
       },
       0x05 => Ok((input, Instructions::Else)),
+      0x08 => {
+        let (input, tag_idx) = leb128_u32(input)?;
+        Ok((input, Instructions::Throw(tag_idx)))
+      },
+      0x0a => Ok((input, Instructions::ThrowRef)),
       0x0b => Ok((input, Instructions::End)),
       0x0c => {
         let (input, label_idx) = leb128_u32(input)?;
@@ -336,6 +691,26 @@ impl Instructions {
         let (input, table_idx) = leb128_u32(input)?;
         Ok((input, Instructions::CallIndirect(type_idx, table_idx)))
       }
+      0x12 => {
+        let (input, func_idx) = leb128_u32(input)?;
+        Ok((input, Instructions::ReturnCall(func_idx)))
+      }
+      0x13 => {
+        let (input, type_idx) = leb128_u32(input)?;
+        let (input, table_idx) = leb128_u32(input)?;
+        Ok((input, Instructions::ReturnCallIndirect(type_idx, table_idx)))
+      }
+      0x1f => {
+        let (input, block) = Block::parse(input, false)?;
+        let (mut input, count) = leb128_u32(input)?;
+        let mut catches = Vec::new();
+        for _ in 0..count {
+          let (i, catch) = Catch::parse(input)?;
+          catches.push(catch);
+          input = i;
+        }
+        Ok((input, Instructions::TryTable(block, catches)))
+      },
       0x1a => Ok((input, Instructions::Drop)),
       0x1b => Ok((input, Instructions::Select)),
       0x1c => {
@@ -363,128 +738,113 @@ impl Instructions {
         let (input, val) = leb128_u32(input)?;
         Ok((input, Instructions::GlobalSet(val)))
       },
+      0x25 => {
+        let (input, table_idx) = leb128_u32(input)?;
+        Ok((input, Instructions::TableGet(table_idx)))
+      },
+      0x26 => {
+        let (input, table_idx) = leb128_u32(input)?;
+        Ok((input, Instructions::TableSet(table_idx)))
+      },
       0x28 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I32Load { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I32Load { align, memory, offset }))
       },
       0x29 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Load { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Load { align, memory, offset }))
       },
       0x2a => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::F32Load { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::F32Load { align, memory, offset }))
       },
       0x2b => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::F64Load { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::F64Load { align, memory, offset }))
       },
       0x2c => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I32Load8S { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I32Load8S { align, memory, offset }))
       },
       0x2d => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I32Load8U { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I32Load8U { align, memory, offset }))
       },
       0x2e => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I32Load16S { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I32Load16S { align, memory, offset }))
       },
       0x2f => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I32Load16U { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I32Load16U { align, memory, offset }))
       },
       0x30 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Load8S { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Load8S { align, memory, offset }))
       },
       0x31 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Load8U { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Load8U { align, memory, offset }))
       },
       0x32 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Load16S { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Load16S { align, memory, offset }))
       },
       0x33 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Load16U { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Load16U { align, memory, offset }))
       },
       0x34 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Load32S { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Load32S { align, memory, offset }))
       },
       0x35 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Load32U { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Load32U { align, memory, offset }))
       },
       0x36 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I32Store { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I32Store { align, memory, offset }))
       },
       0x37 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Store { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Store { align, memory, offset }))
       },
       0x38 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::F32Store { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::F32Store { align, memory, offset }))
       },
       0x39 => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::F64Store { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::F64Store { align, memory, offset }))
       },
       0x3a => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I32Store8 { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I32Store8 { align, memory, offset }))
       },
       0x3b => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I32Store16 { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I32Store16 { align, memory, offset }))
       },
       0x3c => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Store8 { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Store8 { align, memory, offset }))
       },
       0x3d => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Store16 { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Store16 { align, memory, offset }))
       },
       0x3e => {
-        let (input, align) = leb128_u32(input)?;
-        let (input, offset) = leb128_u32(input)?;
-        Ok((input, Instructions::I64Store32 { align, offset }))
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::I64Store32 { align, memory, offset }))
       },
       0x3f => {
-        let (input, _) = tag([0x00])(input)?;
-        Ok((input, Instructions::MemorySize))
+        let (input, memory) = leb128_u32(input)?;
+        Ok((input, Instructions::MemorySize(memory)))
       },
       0x40 => {
-        let (input, _) = tag([0x00])(input)?;
-        Ok((input, Instructions::MemoryGrow))
+        let (input, memory) = leb128_u32(input)?;
+        Ok((input, Instructions::MemoryGrow(memory)))
       },
       0x41 => {
         let (input, val) = leb128_i32(input)?;
@@ -632,47 +992,1979 @@ impl Instructions {
       0xc2 => Ok((input, Instructions::I64Extend8S)),
       0xc3 => Ok((input, Instructions::I64Extend16S)),
       0xc4 => Ok((input, Instructions::I64Extend32S)),
+      0xd0 => {
+        let (input, reftype) = RefType::parse(input)?;
+        Ok((input, Instructions::RefNull(reftype)))
+      },
+      0xd1 => Ok((input, Instructions::RefIsNull)),
+      0xd2 => {
+        let (input, func_idx) = leb128_u32(input)?;
+        Ok((input, Instructions::RefFunc(func_idx)))
+      },
 
       0xfc => {
         let (input, byte) = leb128_u32(input)?;
         match byte {
+          0x00 => Ok((input, Instructions::I32TruncSatF32S)),
+          0x01 => Ok((input, Instructions::I32TruncSatF32U)),
+          0x02 => Ok((input, Instructions::I32TruncSatF64S)),
+          0x03 => Ok((input, Instructions::I32TruncSatF64U)),
+          0x04 => Ok((input, Instructions::I64TruncSatF32S)),
+          0x05 => Ok((input, Instructions::I64TruncSatF32U)),
+          0x06 => Ok((input, Instructions::I64TruncSatF64S)),
+          0x07 => Ok((input, Instructions::I64TruncSatF64U)),
           0x08 => {
             let (input, dataidx) = leb128_u32(input)?;
-            let (input, _) = tag([0x00])(input)?;
-            Ok((input, Instructions::MemoryInit(dataidx)))
+            let (input, memory) = leb128_u32(input)?;
+            Ok((input, Instructions::MemoryInit(dataidx, memory)))
           }
           0x09 => {
             let (input, dataidx) = leb128_u32(input)?;
-            let (input, _) = tag([0x00])(input)?;
             Ok((input, Instructions::DataDrop(dataidx)))
           }
           0x0a => {
-            let (input, _) = tag([0x00, 0x00])(input)?;
-            Ok((input, Instructions::MemoryCopy))
+            let (input, dst) = leb128_u32(input)?;
+            let (input, src) = leb128_u32(input)?;
+            Ok((input, Instructions::MemoryCopy(dst, src)))
           },
           0x0b => {
-            let (input, _) = tag([0x00])(input)?;
-            Ok((input, Instructions::MemoryFill))
+            let (input, memory) = leb128_u32(input)?;
+            Ok((input, Instructions::MemoryFill(memory)))
+          },
+          0x0c => {
+            let (input, elem_idx) = leb128_u32(input)?;
+            let (input, table_idx) = leb128_u32(input)?;
+            Ok((input, Instructions::TableInit(elem_idx, table_idx)))
+          },
+          0x0d => {
+            let (input, elem_idx) = leb128_u32(input)?;
+            Ok((input, Instructions::ElemDrop(elem_idx)))
           },
-          _ => panic!("Unknown opcode: 0xfc {:#x?}", byte),
+          0x0e => {
+            let (input, dst) = leb128_u32(input)?;
+            let (input, src) = leb128_u32(input)?;
+            Ok((input, Instructions::TableCopy(dst, src)))
+          },
+          0x0f => {
+            let (input, table_idx) = leb128_u32(input)?;
+            Ok((input, Instructions::TableGrow(table_idx)))
+          },
+          0x10 => {
+            let (input, table_idx) = leb128_u32(input)?;
+            Ok((input, Instructions::TableSize(table_idx)))
+          },
+          0x11 => {
+            let (input, table_idx) = leb128_u32(input)?;
+            Ok((input, Instructions::TableFill(table_idx)))
+          },
+          _ => fail(input, format!("a known 0xfc-prefixed opcode (found 0xfc {:#x})", byte)),
         }
       }
+      0xfd => Instructions::parse_simd(input),
+      0xfe => Instructions::parse_atomic(input),
       
-      _ => {
-        let next = input.iter().take(10).map(|x| format!("{:#x?}", x)).collect::<Vec<String>>().join(", ");
-        panic!("Unknown opcode: {:#x?}, next: {:#?}", opcode, next);
+      _ => fail(start, format!("a known opcode (found {:#04x})", opcode)),
+    }
+  }
+
+  // 0xfd以降はSIMD命令。サブオペコードはLEB128で符号化される
+  fn parse_simd(input: &[u8]) -> IResult<&[u8], Instructions> {
+    let (input, opcode) = leb128_u32(input)?;
+    match opcode {
+      0x00 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load { align, memory, offset }))
+      },
+      0x01 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load8x8S { align, memory, offset }))
+      },
+      0x02 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load8x8U { align, memory, offset }))
+      },
+      0x03 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load16x4S { align, memory, offset }))
+      },
+      0x04 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load16x4U { align, memory, offset }))
+      },
+      0x05 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load32x2S { align, memory, offset }))
+      },
+      0x06 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load32x2U { align, memory, offset }))
+      },
+      0x07 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load8Splat { align, memory, offset }))
+      },
+      0x08 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load16Splat { align, memory, offset }))
+      },
+      0x09 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load32Splat { align, memory, offset }))
+      },
+      0x0a => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load64Splat { align, memory, offset }))
+      },
+      0x0b => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Store { align, memory, offset }))
+      },
+      0x0c => {
+        let (input, bytes) = take(16usize)(input)?;
+        Ok((input, Instructions::V128Const(u128::from_le_bytes(bytes.try_into().unwrap()))))
+      },
+      0x0d => {
+        let (input, lanes) = take(16usize)(input)?;
+        Ok((input, Instructions::I8x16Shuffle(lanes.try_into().unwrap())))
+      },
+      0x0e => Ok((input, Instructions::I8x16Swizzle)),
+      0x0f => Ok((input, Instructions::I8x16Splat)),
+      0x10 => Ok((input, Instructions::I16x8Splat)),
+      0x11 => Ok((input, Instructions::I32x4Splat)),
+      0x12 => Ok((input, Instructions::I64x2Splat)),
+      0x13 => Ok((input, Instructions::F32x4Splat)),
+      0x14 => Ok((input, Instructions::F64x2Splat)),
+      0x15 => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::I8x16ExtractLaneS(lane)))
+      },
+      0x16 => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::I8x16ExtractLaneU(lane)))
+      },
+      0x17 => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::I8x16ReplaceLane(lane)))
+      },
+      0x18 => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::I16x8ExtractLaneS(lane)))
+      },
+      0x19 => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::I16x8ExtractLaneU(lane)))
+      },
+      0x1a => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::I16x8ReplaceLane(lane)))
+      },
+      0x1b => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::I32x4ExtractLane(lane)))
+      },
+      0x1c => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::I32x4ReplaceLane(lane)))
+      },
+      0x1d => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::I64x2ExtractLane(lane)))
+      },
+      0x1e => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::I64x2ReplaceLane(lane)))
+      },
+      0x1f => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::F32x4ExtractLane(lane)))
+      },
+      0x20 => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::F32x4ReplaceLane(lane)))
+      },
+      0x21 => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::F64x2ExtractLane(lane)))
+      },
+      0x22 => {
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::F64x2ReplaceLane(lane)))
+      },
+      0x23 => Ok((input, Instructions::I8x16Eq)),
+      0x24 => Ok((input, Instructions::I8x16Ne)),
+      0x25 => Ok((input, Instructions::I8x16LtS)),
+      0x26 => Ok((input, Instructions::I8x16LtU)),
+      0x27 => Ok((input, Instructions::I8x16GtS)),
+      0x28 => Ok((input, Instructions::I8x16GtU)),
+      0x29 => Ok((input, Instructions::I8x16LeS)),
+      0x2a => Ok((input, Instructions::I8x16LeU)),
+      0x2b => Ok((input, Instructions::I8x16GeS)),
+      0x2c => Ok((input, Instructions::I8x16GeU)),
+      0x2d => Ok((input, Instructions::I16x8Eq)),
+      0x2e => Ok((input, Instructions::I16x8Ne)),
+      0x2f => Ok((input, Instructions::I16x8LtS)),
+      0x30 => Ok((input, Instructions::I16x8LtU)),
+      0x31 => Ok((input, Instructions::I16x8GtS)),
+      0x32 => Ok((input, Instructions::I16x8GtU)),
+      0x33 => Ok((input, Instructions::I16x8LeS)),
+      0x34 => Ok((input, Instructions::I16x8LeU)),
+      0x35 => Ok((input, Instructions::I16x8GeS)),
+      0x36 => Ok((input, Instructions::I16x8GeU)),
+      0x37 => Ok((input, Instructions::I32x4Eq)),
+      0x38 => Ok((input, Instructions::I32x4Ne)),
+      0x39 => Ok((input, Instructions::I32x4LtS)),
+      0x3a => Ok((input, Instructions::I32x4LtU)),
+      0x3b => Ok((input, Instructions::I32x4GtS)),
+      0x3c => Ok((input, Instructions::I32x4GtU)),
+      0x3d => Ok((input, Instructions::I32x4LeS)),
+      0x3e => Ok((input, Instructions::I32x4LeU)),
+      0x3f => Ok((input, Instructions::I32x4GeS)),
+      0x40 => Ok((input, Instructions::I32x4GeU)),
+      0x41 => Ok((input, Instructions::F32x4Eq)),
+      0x42 => Ok((input, Instructions::F32x4Ne)),
+      0x43 => Ok((input, Instructions::F32x4Lt)),
+      0x44 => Ok((input, Instructions::F32x4Gt)),
+      0x45 => Ok((input, Instructions::F32x4Le)),
+      0x46 => Ok((input, Instructions::F32x4Ge)),
+      0x47 => Ok((input, Instructions::F64x2Eq)),
+      0x48 => Ok((input, Instructions::F64x2Ne)),
+      0x49 => Ok((input, Instructions::F64x2Lt)),
+      0x4a => Ok((input, Instructions::F64x2Gt)),
+      0x4b => Ok((input, Instructions::F64x2Le)),
+      0x4c => Ok((input, Instructions::F64x2Ge)),
+      0x4d => Ok((input, Instructions::V128Not)),
+      0x4e => Ok((input, Instructions::V128And)),
+      0x4f => Ok((input, Instructions::V128Andnot)),
+      0x50 => Ok((input, Instructions::V128Or)),
+      0x51 => Ok((input, Instructions::V128Xor)),
+      0x52 => Ok((input, Instructions::V128Bitselect)),
+      0x53 => Ok((input, Instructions::V128AnyTrue)),
+      0x54 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::V128Load8Lane { align, memory, offset, lane }))
+      },
+      0x58 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::V128Store8Lane { align, memory, offset, lane }))
+      },
+      0x55 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::V128Load16Lane { align, memory, offset, lane }))
+      },
+      0x59 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::V128Store16Lane { align, memory, offset, lane }))
+      },
+      0x56 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::V128Load32Lane { align, memory, offset, lane }))
+      },
+      0x5a => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::V128Store32Lane { align, memory, offset, lane }))
+      },
+      0x57 => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::V128Load64Lane { align, memory, offset, lane }))
+      },
+      0x5b => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        let (input, lane) = le_u8(input)?;
+        Ok((input, Instructions::V128Store64Lane { align, memory, offset, lane }))
+      },
+      0x5c => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load32Zero { align, memory, offset }))
+      },
+      0x5d => {
+        let (input, (align, memory, offset)) = parse_memarg(input)?;
+        Ok((input, Instructions::V128Load64Zero { align, memory, offset }))
+      },
+      0x5e => Ok((input, Instructions::F32x4DemoteF64x2Zero)),
+      0x5f => Ok((input, Instructions::F64x2PromoteLowF32x4)),
+      0x60 => Ok((input, Instructions::I8x16Abs)),
+      0x61 => Ok((input, Instructions::I8x16Neg)),
+      0x62 => Ok((input, Instructions::I8x16Popcnt)),
+      0x63 => Ok((input, Instructions::I8x16AllTrue)),
+      0x64 => Ok((input, Instructions::I8x16Bitmask)),
+      0x65 => Ok((input, Instructions::I8x16NarrowI16x8S)),
+      0x66 => Ok((input, Instructions::I8x16NarrowI16x8U)),
+      0x67 => Ok((input, Instructions::F32x4Ceil)),
+      0x68 => Ok((input, Instructions::F32x4Floor)),
+      0x69 => Ok((input, Instructions::F32x4Trunc)),
+      0x6a => Ok((input, Instructions::F32x4Nearest)),
+      0x6b => Ok((input, Instructions::I8x16Shl)),
+      0x6c => Ok((input, Instructions::I8x16ShrS)),
+      0x6d => Ok((input, Instructions::I8x16ShrU)),
+      0x6e => Ok((input, Instructions::I8x16Add)),
+      0x6f => Ok((input, Instructions::I8x16AddSatS)),
+      0x70 => Ok((input, Instructions::I8x16AddSatU)),
+      0x71 => Ok((input, Instructions::I8x16Sub)),
+      0x72 => Ok((input, Instructions::I8x16SubSatS)),
+      0x73 => Ok((input, Instructions::I8x16SubSatU)),
+      0x74 => Ok((input, Instructions::F64x2Ceil)),
+      0x75 => Ok((input, Instructions::F64x2Floor)),
+      0x76 => Ok((input, Instructions::I8x16MinS)),
+      0x77 => Ok((input, Instructions::I8x16MinU)),
+      0x78 => Ok((input, Instructions::I8x16MaxS)),
+      0x79 => Ok((input, Instructions::I8x16MaxU)),
+      0x7a => Ok((input, Instructions::F64x2Trunc)),
+      0x7b => Ok((input, Instructions::I8x16AvgrU)),
+      0x7c => Ok((input, Instructions::I16x8ExtaddPairwiseI8x16S)),
+      0x7d => Ok((input, Instructions::I16x8ExtaddPairwiseI8x16U)),
+      0x7e => Ok((input, Instructions::I32x4ExtaddPairwiseI16x8S)),
+      0x7f => Ok((input, Instructions::I32x4ExtaddPairwiseI16x8U)),
+      0x80 => Ok((input, Instructions::I16x8Abs)),
+      0x81 => Ok((input, Instructions::I16x8Neg)),
+      0x82 => Ok((input, Instructions::I16x8Q15mulrSatS)),
+      0x83 => Ok((input, Instructions::I16x8AllTrue)),
+      0x84 => Ok((input, Instructions::I16x8Bitmask)),
+      0x85 => Ok((input, Instructions::I16x8NarrowI32x4S)),
+      0x86 => Ok((input, Instructions::I16x8NarrowI32x4U)),
+      0x87 => Ok((input, Instructions::I16x8ExtendLowI8x16S)),
+      0x88 => Ok((input, Instructions::I16x8ExtendHighI8x16S)),
+      0x89 => Ok((input, Instructions::I16x8ExtendLowI8x16U)),
+      0x8a => Ok((input, Instructions::I16x8ExtendHighI8x16U)),
+      0x8b => Ok((input, Instructions::I16x8Shl)),
+      0x8c => Ok((input, Instructions::I16x8ShrS)),
+      0x8d => Ok((input, Instructions::I16x8ShrU)),
+      0x8e => Ok((input, Instructions::I16x8Add)),
+      0x8f => Ok((input, Instructions::I16x8AddSatS)),
+      0x90 => Ok((input, Instructions::I16x8AddSatU)),
+      0x91 => Ok((input, Instructions::I16x8Sub)),
+      0x92 => Ok((input, Instructions::I16x8SubSatS)),
+      0x93 => Ok((input, Instructions::I16x8SubSatU)),
+      0x94 => Ok((input, Instructions::F64x2Nearest)),
+      0x95 => Ok((input, Instructions::I16x8Mul)),
+      0x96 => Ok((input, Instructions::I16x8MinS)),
+      0x97 => Ok((input, Instructions::I16x8MinU)),
+      0x98 => Ok((input, Instructions::I16x8MaxS)),
+      0x99 => Ok((input, Instructions::I16x8MaxU)),
+      0x9b => Ok((input, Instructions::I16x8AvgrU)),
+      0x9c => Ok((input, Instructions::I16x8ExtmulLowI8x16S)),
+      0x9d => Ok((input, Instructions::I16x8ExtmulHighI8x16S)),
+      0x9e => Ok((input, Instructions::I16x8ExtmulLowI8x16U)),
+      0x9f => Ok((input, Instructions::I16x8ExtmulHighI8x16U)),
+      0xa0 => Ok((input, Instructions::I32x4Abs)),
+      0xa1 => Ok((input, Instructions::I32x4Neg)),
+      0xa3 => Ok((input, Instructions::I32x4AllTrue)),
+      0xa4 => Ok((input, Instructions::I32x4Bitmask)),
+      0xa7 => Ok((input, Instructions::I32x4ExtendLowI16x8S)),
+      0xa8 => Ok((input, Instructions::I32x4ExtendHighI16x8S)),
+      0xa9 => Ok((input, Instructions::I32x4ExtendLowI16x8U)),
+      0xaa => Ok((input, Instructions::I32x4ExtendHighI16x8U)),
+      0xab => Ok((input, Instructions::I32x4Shl)),
+      0xac => Ok((input, Instructions::I32x4ShrS)),
+      0xad => Ok((input, Instructions::I32x4ShrU)),
+      0xae => Ok((input, Instructions::I32x4Add)),
+      0xb1 => Ok((input, Instructions::I32x4Sub)),
+      0xb5 => Ok((input, Instructions::I32x4Mul)),
+      0xb6 => Ok((input, Instructions::I32x4MinS)),
+      0xb7 => Ok((input, Instructions::I32x4MinU)),
+      0xb8 => Ok((input, Instructions::I32x4MaxS)),
+      0xb9 => Ok((input, Instructions::I32x4MaxU)),
+      0xba => Ok((input, Instructions::I32x4DotI16x8S)),
+      0xbc => Ok((input, Instructions::I32x4ExtmulLowI16x8S)),
+      0xbd => Ok((input, Instructions::I32x4ExtmulHighI16x8S)),
+      0xbe => Ok((input, Instructions::I32x4ExtmulLowI16x8U)),
+      0xbf => Ok((input, Instructions::I32x4ExtmulHighI16x8U)),
+      0xc0 => Ok((input, Instructions::I64x2Abs)),
+      0xc1 => Ok((input, Instructions::I64x2Neg)),
+      0xc3 => Ok((input, Instructions::I64x2AllTrue)),
+      0xc4 => Ok((input, Instructions::I64x2Bitmask)),
+      0xc7 => Ok((input, Instructions::I64x2ExtendLowI32x4S)),
+      0xc8 => Ok((input, Instructions::I64x2ExtendHighI32x4S)),
+      0xc9 => Ok((input, Instructions::I64x2ExtendLowI32x4U)),
+      0xca => Ok((input, Instructions::I64x2ExtendHighI32x4U)),
+      0xcb => Ok((input, Instructions::I64x2Shl)),
+      0xcc => Ok((input, Instructions::I64x2ShrS)),
+      0xcd => Ok((input, Instructions::I64x2ShrU)),
+      0xce => Ok((input, Instructions::I64x2Add)),
+      0xd1 => Ok((input, Instructions::I64x2Sub)),
+      0xd5 => Ok((input, Instructions::I64x2Mul)),
+      0xd6 => Ok((input, Instructions::I64x2Eq)),
+      0xd7 => Ok((input, Instructions::I64x2Ne)),
+      0xd8 => Ok((input, Instructions::I64x2LtS)),
+      0xd9 => Ok((input, Instructions::I64x2GtS)),
+      0xda => Ok((input, Instructions::I64x2LeS)),
+      0xdb => Ok((input, Instructions::I64x2GeS)),
+      0xdc => Ok((input, Instructions::I64x2ExtmulLowI32x4S)),
+      0xdd => Ok((input, Instructions::I64x2ExtmulHighI32x4S)),
+      0xde => Ok((input, Instructions::I64x2ExtmulLowI32x4U)),
+      0xdf => Ok((input, Instructions::I64x2ExtmulHighI32x4U)),
+      0xe0 => Ok((input, Instructions::F32x4Abs)),
+      0xe1 => Ok((input, Instructions::F32x4Neg)),
+      0xe3 => Ok((input, Instructions::F32x4Sqrt)),
+      0xe4 => Ok((input, Instructions::F32x4Add)),
+      0xe5 => Ok((input, Instructions::F32x4Sub)),
+      0xe6 => Ok((input, Instructions::F32x4Mul)),
+      0xe7 => Ok((input, Instructions::F32x4Div)),
+      0xe8 => Ok((input, Instructions::F32x4Min)),
+      0xe9 => Ok((input, Instructions::F32x4Max)),
+      0xea => Ok((input, Instructions::F32x4Pmin)),
+      0xeb => Ok((input, Instructions::F32x4Pmax)),
+      0xec => Ok((input, Instructions::F64x2Abs)),
+      0xed => Ok((input, Instructions::F64x2Neg)),
+      0xef => Ok((input, Instructions::F64x2Sqrt)),
+      0xf0 => Ok((input, Instructions::F64x2Add)),
+      0xf1 => Ok((input, Instructions::F64x2Sub)),
+      0xf2 => Ok((input, Instructions::F64x2Mul)),
+      0xf3 => Ok((input, Instructions::F64x2Div)),
+      0xf4 => Ok((input, Instructions::F64x2Min)),
+      0xf5 => Ok((input, Instructions::F64x2Max)),
+      0xf6 => Ok((input, Instructions::F64x2Pmin)),
+      0xf7 => Ok((input, Instructions::F64x2Pmax)),
+      0xf8 => Ok((input, Instructions::I32x4TruncSatF32x4S)),
+      0xf9 => Ok((input, Instructions::I32x4TruncSatF32x4U)),
+      0xfa => Ok((input, Instructions::F32x4ConvertI32x4S)),
+      0xfb => Ok((input, Instructions::F32x4ConvertI32x4U)),
+      0xfc => Ok((input, Instructions::I32x4TruncSatF64x2SZero)),
+      0xfd => Ok((input, Instructions::I32x4TruncSatF64x2UZero)),
+      0xfe => Ok((input, Instructions::F64x2ConvertLowI32x4S)),
+      0xff => Ok((input, Instructions::F64x2ConvertLowI32x4U)),
+      _ => fail(input, format!("a known 0xfd-prefixed opcode (found 0xfd {:#x})", opcode)),
+    }
+  }
+
+  // 0xfe以降はスレッドのアトミック命令。fence以外はmemargを取る
+  fn parse_atomic(input: &[u8]) -> IResult<&[u8], Instructions> {
+    let (input, opcode) = leb128_u32(input)?;
+    if opcode == 0x03 {
+      let (rest, reserved) = le_u8(input)?;
+      if reserved != 0x00 {
+        return fail(input, format!("atomic.fence reserved byte 0x00 (found {:#04x})", reserved));
       }
+      return Ok((rest, Instructions::AtomicFence));
+    }
+    let start = input;
+    let (input, (align, memory, offset)) = parse_memarg(input)?;
+    match opcode {
+      0x00 => Ok((input, Instructions::MemoryAtomicNotify { align, memory, offset })),
+      0x01 => Ok((input, Instructions::MemoryAtomicWait32 { align, memory, offset })),
+      0x02 => Ok((input, Instructions::MemoryAtomicWait64 { align, memory, offset })),
+      0x10 => Ok((input, Instructions::I32AtomicLoad { align, memory, offset })),
+      0x11 => Ok((input, Instructions::I64AtomicLoad { align, memory, offset })),
+      0x12 => Ok((input, Instructions::I32AtomicLoad8U { align, memory, offset })),
+      0x13 => Ok((input, Instructions::I32AtomicLoad16U { align, memory, offset })),
+      0x14 => Ok((input, Instructions::I64AtomicLoad8U { align, memory, offset })),
+      0x15 => Ok((input, Instructions::I64AtomicLoad16U { align, memory, offset })),
+      0x16 => Ok((input, Instructions::I64AtomicLoad32U { align, memory, offset })),
+      0x17 => Ok((input, Instructions::I32AtomicStore { align, memory, offset })),
+      0x18 => Ok((input, Instructions::I64AtomicStore { align, memory, offset })),
+      0x19 => Ok((input, Instructions::I32AtomicStore8 { align, memory, offset })),
+      0x1a => Ok((input, Instructions::I32AtomicStore16 { align, memory, offset })),
+      0x1b => Ok((input, Instructions::I64AtomicStore8 { align, memory, offset })),
+      0x1c => Ok((input, Instructions::I64AtomicStore16 { align, memory, offset })),
+      0x1d => Ok((input, Instructions::I64AtomicStore32 { align, memory, offset })),
+      0x1e => Ok((input, Instructions::I32AtomicRmwAdd { align, memory, offset })),
+      0x1f => Ok((input, Instructions::I64AtomicRmwAdd { align, memory, offset })),
+      0x20 => Ok((input, Instructions::I32AtomicRmw8AddU { align, memory, offset })),
+      0x21 => Ok((input, Instructions::I32AtomicRmw16AddU { align, memory, offset })),
+      0x22 => Ok((input, Instructions::I64AtomicRmw8AddU { align, memory, offset })),
+      0x23 => Ok((input, Instructions::I64AtomicRmw16AddU { align, memory, offset })),
+      0x24 => Ok((input, Instructions::I64AtomicRmw32AddU { align, memory, offset })),
+      0x25 => Ok((input, Instructions::I32AtomicRmwSub { align, memory, offset })),
+      0x26 => Ok((input, Instructions::I64AtomicRmwSub { align, memory, offset })),
+      0x27 => Ok((input, Instructions::I32AtomicRmw8SubU { align, memory, offset })),
+      0x28 => Ok((input, Instructions::I32AtomicRmw16SubU { align, memory, offset })),
+      0x29 => Ok((input, Instructions::I64AtomicRmw8SubU { align, memory, offset })),
+      0x2a => Ok((input, Instructions::I64AtomicRmw16SubU { align, memory, offset })),
+      0x2b => Ok((input, Instructions::I64AtomicRmw32SubU { align, memory, offset })),
+      0x2c => Ok((input, Instructions::I32AtomicRmwAnd { align, memory, offset })),
+      0x2d => Ok((input, Instructions::I64AtomicRmwAnd { align, memory, offset })),
+      0x2e => Ok((input, Instructions::I32AtomicRmw8AndU { align, memory, offset })),
+      0x2f => Ok((input, Instructions::I32AtomicRmw16AndU { align, memory, offset })),
+      0x30 => Ok((input, Instructions::I64AtomicRmw8AndU { align, memory, offset })),
+      0x31 => Ok((input, Instructions::I64AtomicRmw16AndU { align, memory, offset })),
+      0x32 => Ok((input, Instructions::I64AtomicRmw32AndU { align, memory, offset })),
+      0x33 => Ok((input, Instructions::I32AtomicRmwOr { align, memory, offset })),
+      0x34 => Ok((input, Instructions::I64AtomicRmwOr { align, memory, offset })),
+      0x35 => Ok((input, Instructions::I32AtomicRmw8OrU { align, memory, offset })),
+      0x36 => Ok((input, Instructions::I32AtomicRmw16OrU { align, memory, offset })),
+      0x37 => Ok((input, Instructions::I64AtomicRmw8OrU { align, memory, offset })),
+      0x38 => Ok((input, Instructions::I64AtomicRmw16OrU { align, memory, offset })),
+      0x39 => Ok((input, Instructions::I64AtomicRmw32OrU { align, memory, offset })),
+      0x3a => Ok((input, Instructions::I32AtomicRmwXor { align, memory, offset })),
+      0x3b => Ok((input, Instructions::I64AtomicRmwXor { align, memory, offset })),
+      0x3c => Ok((input, Instructions::I32AtomicRmw8XorU { align, memory, offset })),
+      0x3d => Ok((input, Instructions::I32AtomicRmw16XorU { align, memory, offset })),
+      0x3e => Ok((input, Instructions::I64AtomicRmw8XorU { align, memory, offset })),
+      0x3f => Ok((input, Instructions::I64AtomicRmw16XorU { align, memory, offset })),
+      0x40 => Ok((input, Instructions::I64AtomicRmw32XorU { align, memory, offset })),
+      0x41 => Ok((input, Instructions::I32AtomicRmwXchg { align, memory, offset })),
+      0x42 => Ok((input, Instructions::I64AtomicRmwXchg { align, memory, offset })),
+      0x43 => Ok((input, Instructions::I32AtomicRmw8XchgU { align, memory, offset })),
+      0x44 => Ok((input, Instructions::I32AtomicRmw16XchgU { align, memory, offset })),
+      0x45 => Ok((input, Instructions::I64AtomicRmw8XchgU { align, memory, offset })),
+      0x46 => Ok((input, Instructions::I64AtomicRmw16XchgU { align, memory, offset })),
+      0x47 => Ok((input, Instructions::I64AtomicRmw32XchgU { align, memory, offset })),
+      0x48 => Ok((input, Instructions::I32AtomicRmwCmpxchg { align, memory, offset })),
+      0x49 => Ok((input, Instructions::I64AtomicRmwCmpxchg { align, memory, offset })),
+      0x4a => Ok((input, Instructions::I32AtomicRmw8CmpxchgU { align, memory, offset })),
+      0x4b => Ok((input, Instructions::I32AtomicRmw16CmpxchgU { align, memory, offset })),
+      0x4c => Ok((input, Instructions::I64AtomicRmw8CmpxchgU { align, memory, offset })),
+      0x4d => Ok((input, Instructions::I64AtomicRmw16CmpxchgU { align, memory, offset })),
+      0x4e => Ok((input, Instructions::I64AtomicRmw32CmpxchgU { align, memory, offset })),
+      _ => fail(start, format!("a known 0xfe-prefixed opcode (found 0xfe {:#x})", opcode)),
     }
   }
+
+  // 命令のバイナリ表現を追記する。Blockはブロック型だけ書き、jump_pcとelse_pcはデコード時に後続のElseとEndから計算し直す
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    if let Some(opcode) = self.simd_opcode() {
+      write_prefixed(buf, 0xfd, opcode);
+      match self {
+        Instructions::V128Const(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        Instructions::I8x16Shuffle(lanes) => buf.extend_from_slice(lanes),
+        Instructions::I8x16ExtractLaneS(lane)
+        | Instructions::I8x16ExtractLaneU(lane)
+        | Instructions::I8x16ReplaceLane(lane)
+        | Instructions::I16x8ExtractLaneS(lane)
+        | Instructions::I16x8ExtractLaneU(lane)
+        | Instructions::I16x8ReplaceLane(lane)
+        | Instructions::I32x4ExtractLane(lane)
+        | Instructions::I32x4ReplaceLane(lane)
+        | Instructions::I64x2ExtractLane(lane)
+        | Instructions::I64x2ReplaceLane(lane)
+        | Instructions::F32x4ExtractLane(lane)
+        | Instructions::F32x4ReplaceLane(lane)
+        | Instructions::F64x2ExtractLane(lane)
+        | Instructions::F64x2ReplaceLane(lane) => buf.push(*lane),
+        Instructions::V128Load8Lane { align, memory, offset, lane }
+        | Instructions::V128Store8Lane { align, memory, offset, lane }
+        | Instructions::V128Load16Lane { align, memory, offset, lane }
+        | Instructions::V128Store16Lane { align, memory, offset, lane }
+        | Instructions::V128Load32Lane { align, memory, offset, lane }
+        | Instructions::V128Store32Lane { align, memory, offset, lane }
+        | Instructions::V128Load64Lane { align, memory, offset, lane }
+        | Instructions::V128Store64Lane { align, memory, offset, lane } => {
+          write_memarg(buf, *align, *memory, *offset);
+          buf.push(*lane);
+        },
+        Instructions::V128Load { align, memory, offset }
+        | Instructions::V128Load8x8S { align, memory, offset }
+        | Instructions::V128Load8x8U { align, memory, offset }
+        | Instructions::V128Load16x4S { align, memory, offset }
+        | Instructions::V128Load16x4U { align, memory, offset }
+        | Instructions::V128Load32x2S { align, memory, offset }
+        | Instructions::V128Load32x2U { align, memory, offset }
+        | Instructions::V128Load8Splat { align, memory, offset }
+        | Instructions::V128Load16Splat { align, memory, offset }
+        | Instructions::V128Load32Splat { align, memory, offset }
+        | Instructions::V128Load64Splat { align, memory, offset }
+        | Instructions::V128Store { align, memory, offset }
+        | Instructions::V128Load32Zero { align, memory, offset }
+        | Instructions::V128Load64Zero { align, memory, offset } => write_memarg(buf, *align, *memory, *offset),
+        _ => {},
+      }
+      return;
+    }
+    if let Some(opcode) = self.atomic_opcode() {
+      write_prefixed(buf, 0xfe, opcode);
+      match self.atomic_memarg() {
+        Some((align, memory, offset)) => write_memarg(buf, align, memory, offset),
+        // atomic.fenceは予約された0x00を持つ
+        None => buf.push(0x00),
+      }
+      return;
+    }
+    match self {
+      Instructions::Block(block) => {
+        buf.push(0x02);
+        block.encode_type(buf);
+      },
+      Instructions::Loop(block) => {
+        buf.push(0x03);
+        block.encode_type(buf);
+      },
+      Instructions::If(block) => {
+        buf.push(0x04);
+        block.encode_type(buf);
+      },
+      Instructions::Throw(tag_idx) => write_prefixed(buf, 0x08, *tag_idx),
+      Instructions::Br(label_idx) => write_prefixed(buf, 0x0c, *label_idx),
+      Instructions::BrIf(label_idx) => write_prefixed(buf, 0x0d, *label_idx),
+      Instructions::BrTable(labels, default) => {
+        write_prefixed(buf, 0x0e, labels.len() as u32);
+        for label_idx in labels {
+          write_u32(buf, *label_idx);
+        }
+        write_u32(buf, *default);
+      },
+      Instructions::Call(func_idx) => write_prefixed(buf, 0x10, *func_idx),
+      Instructions::CallIndirect(type_idx, table_idx) => {
+        write_prefixed(buf, 0x11, *type_idx);
+        write_u32(buf, *table_idx);
+      },
+      Instructions::ReturnCall(func_idx) => write_prefixed(buf, 0x12, *func_idx),
+      Instructions::ReturnCallIndirect(type_idx, table_idx) => {
+        write_prefixed(buf, 0x13, *type_idx);
+        write_u32(buf, *table_idx);
+      },
+      Instructions::TryTable(block, catches) => {
+        buf.push(0x1f);
+        block.encode_type(buf);
+        write_u32(buf, catches.len() as u32);
+        for catch in catches {
+          catch.encode(buf);
+        }
+      },
+      Instructions::SelectValtype(valtypes) => {
+        write_prefixed(buf, 0x1c, valtypes.len() as u32);
+        for valtype in valtypes {
+          valtype.encode(buf);
+        }
+      },
+      Instructions::LocalGet(idx) => write_prefixed(buf, 0x20, *idx),
+      Instructions::LocalSet(idx) => write_prefixed(buf, 0x21, *idx),
+      Instructions::LocalTee(idx) => write_prefixed(buf, 0x22, *idx),
+      Instructions::GlobalGet(idx) => write_prefixed(buf, 0x23, *idx),
+      Instructions::GlobalSet(idx) => write_prefixed(buf, 0x24, *idx),
+      Instructions::TableGet(table_idx) => write_prefixed(buf, 0x25, *table_idx),
+      Instructions::TableSet(table_idx) => write_prefixed(buf, 0x26, *table_idx),
+      Instructions::I32Load { align, memory, offset } => {
+        buf.push(0x28);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Load { align, memory, offset } => {
+        buf.push(0x29);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::F32Load { align, memory, offset } => {
+        buf.push(0x2a);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::F64Load { align, memory, offset } => {
+        buf.push(0x2b);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I32Load8S { align, memory, offset } => {
+        buf.push(0x2c);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I32Load8U { align, memory, offset } => {
+        buf.push(0x2d);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I32Load16S { align, memory, offset } => {
+        buf.push(0x2e);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I32Load16U { align, memory, offset } => {
+        buf.push(0x2f);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Load8S { align, memory, offset } => {
+        buf.push(0x30);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Load8U { align, memory, offset } => {
+        buf.push(0x31);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Load16S { align, memory, offset } => {
+        buf.push(0x32);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Load16U { align, memory, offset } => {
+        buf.push(0x33);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Load32S { align, memory, offset } => {
+        buf.push(0x34);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Load32U { align, memory, offset } => {
+        buf.push(0x35);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I32Store { align, memory, offset } => {
+        buf.push(0x36);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Store { align, memory, offset } => {
+        buf.push(0x37);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::F32Store { align, memory, offset } => {
+        buf.push(0x38);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::F64Store { align, memory, offset } => {
+        buf.push(0x39);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I32Store8 { align, memory, offset } => {
+        buf.push(0x3a);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I32Store16 { align, memory, offset } => {
+        buf.push(0x3b);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Store8 { align, memory, offset } => {
+        buf.push(0x3c);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Store16 { align, memory, offset } => {
+        buf.push(0x3d);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::I64Store32 { align, memory, offset } => {
+        buf.push(0x3e);
+        write_memarg(buf, *align, *memory, *offset);
+      },
+      Instructions::MemorySize(memory) => write_prefixed(buf, 0x3f, *memory),
+      Instructions::MemoryGrow(memory) => write_prefixed(buf, 0x40, *memory),
+      Instructions::I32Const(v) => {
+        buf.push(0x41);
+        write_i32(buf, *v);
+      },
+      Instructions::I64Const(v) => {
+        buf.push(0x42);
+        write_i64(buf, *v);
+      },
+      // NaNのペイロードを保つためビット列をそのまま書く
+      Instructions::F32Const(v) => {
+        buf.push(0x43);
+        buf.extend_from_slice(&v.to_bits().to_le_bytes());
+      },
+      Instructions::F64Const(v) => {
+        buf.push(0x44);
+        buf.extend_from_slice(&v.to_bits().to_le_bytes());
+      },
+      Instructions::RefNull(reftype) => {
+        buf.push(0xd0);
+        reftype.encode(buf);
+      },
+      Instructions::RefFunc(func_idx) => write_prefixed(buf, 0xd2, *func_idx),
+      Instructions::I32TruncSatF32S => write_prefixed(buf, 0xfc, 0x00),
+      Instructions::I32TruncSatF32U => write_prefixed(buf, 0xfc, 0x01),
+      Instructions::I32TruncSatF64S => write_prefixed(buf, 0xfc, 0x02),
+      Instructions::I32TruncSatF64U => write_prefixed(buf, 0xfc, 0x03),
+      Instructions::I64TruncSatF32S => write_prefixed(buf, 0xfc, 0x04),
+      Instructions::I64TruncSatF32U => write_prefixed(buf, 0xfc, 0x05),
+      Instructions::I64TruncSatF64S => write_prefixed(buf, 0xfc, 0x06),
+      Instructions::I64TruncSatF64U => write_prefixed(buf, 0xfc, 0x07),
+      Instructions::MemoryInit(data_idx, memory) => {
+        write_prefixed(buf, 0xfc, 0x08);
+        write_u32(buf, *data_idx);
+        write_u32(buf, *memory);
+      },
+      Instructions::DataDrop(data_idx) => {
+        write_prefixed(buf, 0xfc, 0x09);
+        write_u32(buf, *data_idx);
+      },
+      Instructions::MemoryCopy(dst, src) => {
+        write_prefixed(buf, 0xfc, 0x0a);
+        write_u32(buf, *dst);
+        write_u32(buf, *src);
+      },
+      Instructions::MemoryFill(memory) => {
+        write_prefixed(buf, 0xfc, 0x0b);
+        write_u32(buf, *memory);
+      },
+      Instructions::TableInit(elem_idx, table_idx) => {
+        write_prefixed(buf, 0xfc, 0x0c);
+        write_u32(buf, *elem_idx);
+        write_u32(buf, *table_idx);
+      },
+      Instructions::ElemDrop(elem_idx) => {
+        write_prefixed(buf, 0xfc, 0x0d);
+        write_u32(buf, *elem_idx);
+      },
+      Instructions::TableCopy(dst, src) => {
+        write_prefixed(buf, 0xfc, 0x0e);
+        write_u32(buf, *dst);
+        write_u32(buf, *src);
+      },
+      Instructions::TableGrow(table_idx) => {
+        write_prefixed(buf, 0xfc, 0x0f);
+        write_u32(buf, *table_idx);
+      },
+      Instructions::TableSize(table_idx) => {
+        write_prefixed(buf, 0xfc, 0x10);
+        write_u32(buf, *table_idx);
+      },
+      Instructions::TableFill(table_idx) => {
+        write_prefixed(buf, 0xfc, 0x11);
+        write_u32(buf, *table_idx);
+      },
+      Instructions::Unreachable => buf.push(0x00),
+      Instructions::Nop => buf.push(0x01),
+      Instructions::Else => buf.push(0x05),
+      Instructions::ThrowRef => buf.push(0x0a),
+      Instructions::End => buf.push(0x0b),
+      Instructions::Return => buf.push(0x0f),
+      Instructions::Drop => buf.push(0x1a),
+      Instructions::Select => buf.push(0x1b),
+      Instructions::I32Eqz => buf.push(0x45),
+      Instructions::I32Eq => buf.push(0x46),
+      Instructions::I32Ne => buf.push(0x47),
+      Instructions::I32LtS => buf.push(0x48),
+      Instructions::I32LtU => buf.push(0x49),
+      Instructions::I32GtS => buf.push(0x4a),
+      Instructions::I32GtU => buf.push(0x4b),
+      Instructions::I32LeS => buf.push(0x4c),
+      Instructions::I32LeU => buf.push(0x4d),
+      Instructions::I32GeS => buf.push(0x4e),
+      Instructions::I32GeU => buf.push(0x4f),
+      Instructions::I64Eqz => buf.push(0x50),
+      Instructions::I64Eq => buf.push(0x51),
+      Instructions::I64Ne => buf.push(0x52),
+      Instructions::I64LtS => buf.push(0x53),
+      Instructions::I64LtU => buf.push(0x54),
+      Instructions::I64GtS => buf.push(0x55),
+      Instructions::I64GtU => buf.push(0x56),
+      Instructions::I64LeS => buf.push(0x57),
+      Instructions::I64LeU => buf.push(0x58),
+      Instructions::I64GeS => buf.push(0x59),
+      Instructions::I64GeU => buf.push(0x5a),
+      Instructions::F32Eq => buf.push(0x5b),
+      Instructions::F32Ne => buf.push(0x5c),
+      Instructions::F32Lt => buf.push(0x5d),
+      Instructions::F32Gt => buf.push(0x5e),
+      Instructions::F32Le => buf.push(0x5f),
+      Instructions::F32Ge => buf.push(0x60),
+      Instructions::F64Eq => buf.push(0x61),
+      Instructions::F64Ne => buf.push(0x62),
+      Instructions::F64Lt => buf.push(0x63),
+      Instructions::F64Gt => buf.push(0x64),
+      Instructions::F64Le => buf.push(0x65),
+      Instructions::F64Ge => buf.push(0x66),
+      Instructions::I32Clz => buf.push(0x67),
+      Instructions::I32Ctz => buf.push(0x68),
+      Instructions::I32Popcnt => buf.push(0x69),
+      Instructions::I32Add => buf.push(0x6a),
+      Instructions::I32Sub => buf.push(0x6b),
+      Instructions::I32Mul => buf.push(0x6c),
+      Instructions::I32DivS => buf.push(0x6d),
+      Instructions::I32DivU => buf.push(0x6e),
+      Instructions::I32RemS => buf.push(0x6f),
+      Instructions::I32RemU => buf.push(0x70),
+      Instructions::I32And => buf.push(0x71),
+      Instructions::I32Or => buf.push(0x72),
+      Instructions::I32Xor => buf.push(0x73),
+      Instructions::I32Shl => buf.push(0x74),
+      Instructions::I32ShrS => buf.push(0x75),
+      Instructions::I32ShrU => buf.push(0x76),
+      Instructions::I32Rotl => buf.push(0x77),
+      Instructions::I32Rotr => buf.push(0x78),
+      Instructions::I64Clz => buf.push(0x79),
+      Instructions::I64Ctz => buf.push(0x7a),
+      Instructions::I64Popcnt => buf.push(0x7b),
+      Instructions::I64Add => buf.push(0x7c),
+      Instructions::I64Sub => buf.push(0x7d),
+      Instructions::I64Mul => buf.push(0x7e),
+      Instructions::I64DivS => buf.push(0x7f),
+      Instructions::I64DivU => buf.push(0x80),
+      Instructions::I64RemS => buf.push(0x81),
+      Instructions::I64RemU => buf.push(0x82),
+      Instructions::I64And => buf.push(0x83),
+      Instructions::I64Or => buf.push(0x84),
+      Instructions::I64Xor => buf.push(0x85),
+      Instructions::I64Shl => buf.push(0x86),
+      Instructions::I64ShrS => buf.push(0x87),
+      Instructions::I64ShrU => buf.push(0x88),
+      Instructions::I64Rotl => buf.push(0x89),
+      Instructions::I64Rotr => buf.push(0x8a),
+      Instructions::F32Abs => buf.push(0x8b),
+      Instructions::F32Neg => buf.push(0x8c),
+      Instructions::F32Ceil => buf.push(0x8d),
+      Instructions::F32Floor => buf.push(0x8e),
+      Instructions::F32Trunc => buf.push(0x8f),
+      Instructions::F32Nearest => buf.push(0x90),
+      Instructions::F32Sqrt => buf.push(0x91),
+      Instructions::F32Add => buf.push(0x92),
+      Instructions::F32Sub => buf.push(0x93),
+      Instructions::F32Mul => buf.push(0x94),
+      Instructions::F32Div => buf.push(0x95),
+      Instructions::F32Min => buf.push(0x96),
+      Instructions::F32Max => buf.push(0x97),
+      Instructions::F32Copysign => buf.push(0x98),
+      Instructions::F64Abs => buf.push(0x99),
+      Instructions::F64Neg => buf.push(0x9a),
+      Instructions::F64Ceil => buf.push(0x9b),
+      Instructions::F64Floor => buf.push(0x9c),
+      Instructions::F64Trunc => buf.push(0x9d),
+      Instructions::F64Nearest => buf.push(0x9e),
+      Instructions::F64Sqrt => buf.push(0x9f),
+      Instructions::F64Add => buf.push(0xa0),
+      Instructions::F64Sub => buf.push(0xa1),
+      Instructions::F64Mul => buf.push(0xa2),
+      Instructions::F64Div => buf.push(0xa3),
+      Instructions::F64Min => buf.push(0xa4),
+      Instructions::F64Max => buf.push(0xa5),
+      Instructions::F64Copysign => buf.push(0xa6),
+      Instructions::I32WrapI64 => buf.push(0xa7),
+      Instructions::I32TruncF32S => buf.push(0xa8),
+      Instructions::I32TruncF32U => buf.push(0xa9),
+      Instructions::I32TruncF64S => buf.push(0xaa),
+      Instructions::I32TruncF64U => buf.push(0xab),
+      Instructions::I64ExtendI32S => buf.push(0xac),
+      Instructions::I64ExtendI32U => buf.push(0xad),
+      Instructions::I64TruncF32S => buf.push(0xae),
+      Instructions::I64TruncF32U => buf.push(0xaf),
+      Instructions::I64TruncF64S => buf.push(0xb0),
+      Instructions::I64TruncF64U => buf.push(0xb1),
+      Instructions::F32ConvertI32S => buf.push(0xb2),
+      Instructions::F32ConvertI32U => buf.push(0xb3),
+      Instructions::F32ConvertI64S => buf.push(0xb4),
+      Instructions::F32ConvertI64U => buf.push(0xb5),
+      Instructions::F32DemoteF64 => buf.push(0xb6),
+      Instructions::F64ConvertI32S => buf.push(0xb7),
+      Instructions::F64ConvertI32U => buf.push(0xb8),
+      Instructions::F64ConvertI64S => buf.push(0xb9),
+      Instructions::F64ConvertI64U => buf.push(0xba),
+      Instructions::F64PromoteF32 => buf.push(0xbb),
+      Instructions::I32ReinterpretF32 => buf.push(0xbc),
+      Instructions::I64ReinterpretF64 => buf.push(0xbd),
+      Instructions::F32ReinterpretI32 => buf.push(0xbe),
+      Instructions::F64ReinterpretI64 => buf.push(0xbf),
+      Instructions::I32Extend8S => buf.push(0xc0),
+      Instructions::I32Extend16S => buf.push(0xc1),
+      Instructions::I64Extend8S => buf.push(0xc2),
+      Instructions::I64Extend16S => buf.push(0xc3),
+      Instructions::I64Extend32S => buf.push(0xc4),
+      Instructions::RefIsNull => buf.push(0xd1),
+      _ => unreachable!("{:?} is encoded with its prefix above", self),
+    }
+  }
+
+  // SIMD命令なら0xfdに続くサブオペコードを返す
+  pub fn simd_opcode(&self) -> Option<u32> {
+    let opcode = match self {
+      Instructions::V128Load { .. } => 0,
+      Instructions::V128Load8x8S { .. } => 1,
+      Instructions::V128Load8x8U { .. } => 2,
+      Instructions::V128Load16x4S { .. } => 3,
+      Instructions::V128Load16x4U { .. } => 4,
+      Instructions::V128Load32x2S { .. } => 5,
+      Instructions::V128Load32x2U { .. } => 6,
+      Instructions::V128Load8Splat { .. } => 7,
+      Instructions::V128Load16Splat { .. } => 8,
+      Instructions::V128Load32Splat { .. } => 9,
+      Instructions::V128Load64Splat { .. } => 10,
+      Instructions::V128Store { .. } => 11,
+      Instructions::V128Const(_) => 12,
+      Instructions::I8x16Shuffle(_) => 13,
+      Instructions::I8x16Swizzle => 14,
+      Instructions::I8x16Splat => 15,
+      Instructions::I16x8Splat => 16,
+      Instructions::I32x4Splat => 17,
+      Instructions::I64x2Splat => 18,
+      Instructions::F32x4Splat => 19,
+      Instructions::F64x2Splat => 20,
+      Instructions::I8x16ExtractLaneS(_) => 21,
+      Instructions::I8x16ExtractLaneU(_) => 22,
+      Instructions::I8x16ReplaceLane(_) => 23,
+      Instructions::I16x8ExtractLaneS(_) => 24,
+      Instructions::I16x8ExtractLaneU(_) => 25,
+      Instructions::I16x8ReplaceLane(_) => 26,
+      Instructions::I32x4ExtractLane(_) => 27,
+      Instructions::I32x4ReplaceLane(_) => 28,
+      Instructions::I64x2ExtractLane(_) => 29,
+      Instructions::I64x2ReplaceLane(_) => 30,
+      Instructions::F32x4ExtractLane(_) => 31,
+      Instructions::F32x4ReplaceLane(_) => 32,
+      Instructions::F64x2ExtractLane(_) => 33,
+      Instructions::F64x2ReplaceLane(_) => 34,
+      Instructions::I8x16Eq => 35,
+      Instructions::I8x16Ne => 36,
+      Instructions::I8x16LtS => 37,
+      Instructions::I8x16LtU => 38,
+      Instructions::I8x16GtS => 39,
+      Instructions::I8x16GtU => 40,
+      Instructions::I8x16LeS => 41,
+      Instructions::I8x16LeU => 42,
+      Instructions::I8x16GeS => 43,
+      Instructions::I8x16GeU => 44,
+      Instructions::I16x8Eq => 45,
+      Instructions::I16x8Ne => 46,
+      Instructions::I16x8LtS => 47,
+      Instructions::I16x8LtU => 48,
+      Instructions::I16x8GtS => 49,
+      Instructions::I16x8GtU => 50,
+      Instructions::I16x8LeS => 51,
+      Instructions::I16x8LeU => 52,
+      Instructions::I16x8GeS => 53,
+      Instructions::I16x8GeU => 54,
+      Instructions::I32x4Eq => 55,
+      Instructions::I32x4Ne => 56,
+      Instructions::I32x4LtS => 57,
+      Instructions::I32x4LtU => 58,
+      Instructions::I32x4GtS => 59,
+      Instructions::I32x4GtU => 60,
+      Instructions::I32x4LeS => 61,
+      Instructions::I32x4LeU => 62,
+      Instructions::I32x4GeS => 63,
+      Instructions::I32x4GeU => 64,
+      Instructions::F32x4Eq => 65,
+      Instructions::F32x4Ne => 66,
+      Instructions::F32x4Lt => 67,
+      Instructions::F32x4Gt => 68,
+      Instructions::F32x4Le => 69,
+      Instructions::F32x4Ge => 70,
+      Instructions::F64x2Eq => 71,
+      Instructions::F64x2Ne => 72,
+      Instructions::F64x2Lt => 73,
+      Instructions::F64x2Gt => 74,
+      Instructions::F64x2Le => 75,
+      Instructions::F64x2Ge => 76,
+      Instructions::V128Not => 77,
+      Instructions::V128And => 78,
+      Instructions::V128Andnot => 79,
+      Instructions::V128Or => 80,
+      Instructions::V128Xor => 81,
+      Instructions::V128Bitselect => 82,
+      Instructions::V128AnyTrue => 83,
+      Instructions::V128Load8Lane { .. } => 84,
+      Instructions::V128Store8Lane { .. } => 88,
+      Instructions::V128Load16Lane { .. } => 85,
+      Instructions::V128Store16Lane { .. } => 89,
+      Instructions::V128Load32Lane { .. } => 86,
+      Instructions::V128Store32Lane { .. } => 90,
+      Instructions::V128Load64Lane { .. } => 87,
+      Instructions::V128Store64Lane { .. } => 91,
+      Instructions::V128Load32Zero { .. } => 92,
+      Instructions::V128Load64Zero { .. } => 93,
+      Instructions::F32x4DemoteF64x2Zero => 94,
+      Instructions::F64x2PromoteLowF32x4 => 95,
+      Instructions::I8x16Abs => 96,
+      Instructions::I8x16Neg => 97,
+      Instructions::I8x16Popcnt => 98,
+      Instructions::I8x16AllTrue => 99,
+      Instructions::I8x16Bitmask => 100,
+      Instructions::I8x16NarrowI16x8S => 101,
+      Instructions::I8x16NarrowI16x8U => 102,
+      Instructions::F32x4Ceil => 103,
+      Instructions::F32x4Floor => 104,
+      Instructions::F32x4Trunc => 105,
+      Instructions::F32x4Nearest => 106,
+      Instructions::I8x16Shl => 107,
+      Instructions::I8x16ShrS => 108,
+      Instructions::I8x16ShrU => 109,
+      Instructions::I8x16Add => 110,
+      Instructions::I8x16AddSatS => 111,
+      Instructions::I8x16AddSatU => 112,
+      Instructions::I8x16Sub => 113,
+      Instructions::I8x16SubSatS => 114,
+      Instructions::I8x16SubSatU => 115,
+      Instructions::F64x2Ceil => 116,
+      Instructions::F64x2Floor => 117,
+      Instructions::I8x16MinS => 118,
+      Instructions::I8x16MinU => 119,
+      Instructions::I8x16MaxS => 120,
+      Instructions::I8x16MaxU => 121,
+      Instructions::F64x2Trunc => 122,
+      Instructions::I8x16AvgrU => 123,
+      Instructions::I16x8ExtaddPairwiseI8x16S => 124,
+      Instructions::I16x8ExtaddPairwiseI8x16U => 125,
+      Instructions::I32x4ExtaddPairwiseI16x8S => 126,
+      Instructions::I32x4ExtaddPairwiseI16x8U => 127,
+      Instructions::I16x8Abs => 128,
+      Instructions::I16x8Neg => 129,
+      Instructions::I16x8Q15mulrSatS => 130,
+      Instructions::I16x8AllTrue => 131,
+      Instructions::I16x8Bitmask => 132,
+      Instructions::I16x8NarrowI32x4S => 133,
+      Instructions::I16x8NarrowI32x4U => 134,
+      Instructions::I16x8ExtendLowI8x16S => 135,
+      Instructions::I16x8ExtendHighI8x16S => 136,
+      Instructions::I16x8ExtendLowI8x16U => 137,
+      Instructions::I16x8ExtendHighI8x16U => 138,
+      Instructions::I16x8Shl => 139,
+      Instructions::I16x8ShrS => 140,
+      Instructions::I16x8ShrU => 141,
+      Instructions::I16x8Add => 142,
+      Instructions::I16x8AddSatS => 143,
+      Instructions::I16x8AddSatU => 144,
+      Instructions::I16x8Sub => 145,
+      Instructions::I16x8SubSatS => 146,
+      Instructions::I16x8SubSatU => 147,
+      Instructions::F64x2Nearest => 148,
+      Instructions::I16x8Mul => 149,
+      Instructions::I16x8MinS => 150,
+      Instructions::I16x8MinU => 151,
+      Instructions::I16x8MaxS => 152,
+      Instructions::I16x8MaxU => 153,
+      Instructions::I16x8AvgrU => 155,
+      Instructions::I16x8ExtmulLowI8x16S => 156,
+      Instructions::I16x8ExtmulHighI8x16S => 157,
+      Instructions::I16x8ExtmulLowI8x16U => 158,
+      Instructions::I16x8ExtmulHighI8x16U => 159,
+      Instructions::I32x4Abs => 160,
+      Instructions::I32x4Neg => 161,
+      Instructions::I32x4AllTrue => 163,
+      Instructions::I32x4Bitmask => 164,
+      Instructions::I32x4ExtendLowI16x8S => 167,
+      Instructions::I32x4ExtendHighI16x8S => 168,
+      Instructions::I32x4ExtendLowI16x8U => 169,
+      Instructions::I32x4ExtendHighI16x8U => 170,
+      Instructions::I32x4Shl => 171,
+      Instructions::I32x4ShrS => 172,
+      Instructions::I32x4ShrU => 173,
+      Instructions::I32x4Add => 174,
+      Instructions::I32x4Sub => 177,
+      Instructions::I32x4Mul => 181,
+      Instructions::I32x4MinS => 182,
+      Instructions::I32x4MinU => 183,
+      Instructions::I32x4MaxS => 184,
+      Instructions::I32x4MaxU => 185,
+      Instructions::I32x4DotI16x8S => 186,
+      Instructions::I32x4ExtmulLowI16x8S => 188,
+      Instructions::I32x4ExtmulHighI16x8S => 189,
+      Instructions::I32x4ExtmulLowI16x8U => 190,
+      Instructions::I32x4ExtmulHighI16x8U => 191,
+      Instructions::I64x2Abs => 192,
+      Instructions::I64x2Neg => 193,
+      Instructions::I64x2AllTrue => 195,
+      Instructions::I64x2Bitmask => 196,
+      Instructions::I64x2ExtendLowI32x4S => 199,
+      Instructions::I64x2ExtendHighI32x4S => 200,
+      Instructions::I64x2ExtendLowI32x4U => 201,
+      Instructions::I64x2ExtendHighI32x4U => 202,
+      Instructions::I64x2Shl => 203,
+      Instructions::I64x2ShrS => 204,
+      Instructions::I64x2ShrU => 205,
+      Instructions::I64x2Add => 206,
+      Instructions::I64x2Sub => 209,
+      Instructions::I64x2Mul => 213,
+      Instructions::I64x2Eq => 214,
+      Instructions::I64x2Ne => 215,
+      Instructions::I64x2LtS => 216,
+      Instructions::I64x2GtS => 217,
+      Instructions::I64x2LeS => 218,
+      Instructions::I64x2GeS => 219,
+      Instructions::I64x2ExtmulLowI32x4S => 220,
+      Instructions::I64x2ExtmulHighI32x4S => 221,
+      Instructions::I64x2ExtmulLowI32x4U => 222,
+      Instructions::I64x2ExtmulHighI32x4U => 223,
+      Instructions::F32x4Abs => 224,
+      Instructions::F32x4Neg => 225,
+      Instructions::F32x4Sqrt => 227,
+      Instructions::F32x4Add => 228,
+      Instructions::F32x4Sub => 229,
+      Instructions::F32x4Mul => 230,
+      Instructions::F32x4Div => 231,
+      Instructions::F32x4Min => 232,
+      Instructions::F32x4Max => 233,
+      Instructions::F32x4Pmin => 234,
+      Instructions::F32x4Pmax => 235,
+      Instructions::F64x2Abs => 236,
+      Instructions::F64x2Neg => 237,
+      Instructions::F64x2Sqrt => 239,
+      Instructions::F64x2Add => 240,
+      Instructions::F64x2Sub => 241,
+      Instructions::F64x2Mul => 242,
+      Instructions::F64x2Div => 243,
+      Instructions::F64x2Min => 244,
+      Instructions::F64x2Max => 245,
+      Instructions::F64x2Pmin => 246,
+      Instructions::F64x2Pmax => 247,
+      Instructions::I32x4TruncSatF32x4S => 248,
+      Instructions::I32x4TruncSatF32x4U => 249,
+      Instructions::F32x4ConvertI32x4S => 250,
+      Instructions::F32x4ConvertI32x4U => 251,
+      Instructions::I32x4TruncSatF64x2SZero => 252,
+      Instructions::I32x4TruncSatF64x2UZero => 253,
+      Instructions::F64x2ConvertLowI32x4S => 254,
+      Instructions::F64x2ConvertLowI32x4U => 255,
+      _ => return None,
+    };
+    Some(opcode)
+  }
+
+  // アトミック命令なら0xfeに続くサブオペコードを返す
+  pub fn atomic_opcode(&self) -> Option<u32> {
+    let opcode = match self {
+      Instructions::MemoryAtomicNotify { .. } => 0x00,
+      Instructions::MemoryAtomicWait32 { .. } => 0x01,
+      Instructions::MemoryAtomicWait64 { .. } => 0x02,
+      Instructions::I32AtomicLoad { .. } => 0x10,
+      Instructions::I64AtomicLoad { .. } => 0x11,
+      Instructions::I32AtomicLoad8U { .. } => 0x12,
+      Instructions::I32AtomicLoad16U { .. } => 0x13,
+      Instructions::I64AtomicLoad8U { .. } => 0x14,
+      Instructions::I64AtomicLoad16U { .. } => 0x15,
+      Instructions::I64AtomicLoad32U { .. } => 0x16,
+      Instructions::I32AtomicStore { .. } => 0x17,
+      Instructions::I64AtomicStore { .. } => 0x18,
+      Instructions::I32AtomicStore8 { .. } => 0x19,
+      Instructions::I32AtomicStore16 { .. } => 0x1a,
+      Instructions::I64AtomicStore8 { .. } => 0x1b,
+      Instructions::I64AtomicStore16 { .. } => 0x1c,
+      Instructions::I64AtomicStore32 { .. } => 0x1d,
+      Instructions::I32AtomicRmwAdd { .. } => 0x1e,
+      Instructions::I64AtomicRmwAdd { .. } => 0x1f,
+      Instructions::I32AtomicRmw8AddU { .. } => 0x20,
+      Instructions::I32AtomicRmw16AddU { .. } => 0x21,
+      Instructions::I64AtomicRmw8AddU { .. } => 0x22,
+      Instructions::I64AtomicRmw16AddU { .. } => 0x23,
+      Instructions::I64AtomicRmw32AddU { .. } => 0x24,
+      Instructions::I32AtomicRmwSub { .. } => 0x25,
+      Instructions::I64AtomicRmwSub { .. } => 0x26,
+      Instructions::I32AtomicRmw8SubU { .. } => 0x27,
+      Instructions::I32AtomicRmw16SubU { .. } => 0x28,
+      Instructions::I64AtomicRmw8SubU { .. } => 0x29,
+      Instructions::I64AtomicRmw16SubU { .. } => 0x2a,
+      Instructions::I64AtomicRmw32SubU { .. } => 0x2b,
+      Instructions::I32AtomicRmwAnd { .. } => 0x2c,
+      Instructions::I64AtomicRmwAnd { .. } => 0x2d,
+      Instructions::I32AtomicRmw8AndU { .. } => 0x2e,
+      Instructions::I32AtomicRmw16AndU { .. } => 0x2f,
+      Instructions::I64AtomicRmw8AndU { .. } => 0x30,
+      Instructions::I64AtomicRmw16AndU { .. } => 0x31,
+      Instructions::I64AtomicRmw32AndU { .. } => 0x32,
+      Instructions::I32AtomicRmwOr { .. } => 0x33,
+      Instructions::I64AtomicRmwOr { .. } => 0x34,
+      Instructions::I32AtomicRmw8OrU { .. } => 0x35,
+      Instructions::I32AtomicRmw16OrU { .. } => 0x36,
+      Instructions::I64AtomicRmw8OrU { .. } => 0x37,
+      Instructions::I64AtomicRmw16OrU { .. } => 0x38,
+      Instructions::I64AtomicRmw32OrU { .. } => 0x39,
+      Instructions::I32AtomicRmwXor { .. } => 0x3a,
+      Instructions::I64AtomicRmwXor { .. } => 0x3b,
+      Instructions::I32AtomicRmw8XorU { .. } => 0x3c,
+      Instructions::I32AtomicRmw16XorU { .. } => 0x3d,
+      Instructions::I64AtomicRmw8XorU { .. } => 0x3e,
+      Instructions::I64AtomicRmw16XorU { .. } => 0x3f,
+      Instructions::I64AtomicRmw32XorU { .. } => 0x40,
+      Instructions::I32AtomicRmwXchg { .. } => 0x41,
+      Instructions::I64AtomicRmwXchg { .. } => 0x42,
+      Instructions::I32AtomicRmw8XchgU { .. } => 0x43,
+      Instructions::I32AtomicRmw16XchgU { .. } => 0x44,
+      Instructions::I64AtomicRmw8XchgU { .. } => 0x45,
+      Instructions::I64AtomicRmw16XchgU { .. } => 0x46,
+      Instructions::I64AtomicRmw32XchgU { .. } => 0x47,
+      Instructions::I32AtomicRmwCmpxchg { .. } => 0x48,
+      Instructions::I64AtomicRmwCmpxchg { .. } => 0x49,
+      Instructions::I32AtomicRmw8CmpxchgU { .. } => 0x4a,
+      Instructions::I32AtomicRmw16CmpxchgU { .. } => 0x4b,
+      Instructions::I64AtomicRmw8CmpxchgU { .. } => 0x4c,
+      Instructions::I64AtomicRmw16CmpxchgU { .. } => 0x4d,
+      Instructions::I64AtomicRmw32CmpxchgU { .. } => 0x4e,
+      Instructions::AtomicFence => 0x03,
+      _ => return None,
+    };
+    Some(opcode)
+  }
+
+  // アトミック命令が読み書きする値の型とバイト数。0x10以降はi32、i64、i32の8/16bit、i64の8/16/32bitの7通りが繰り返す
+  pub fn atomic_access(&self) -> Option<(ValueType, u32)> {
+    match self.atomic_opcode()? {
+      0x00 | 0x01 => Some((ValueType::I32, 4)),
+      0x02 => Some((ValueType::I64, 8)),
+      opcode @ 0x10..=0x4e => Some(match (opcode - 0x10) % 7 {
+        0 => (ValueType::I32, 4),
+        1 => (ValueType::I64, 8),
+        2 => (ValueType::I32, 1),
+        3 => (ValueType::I32, 2),
+        4 => (ValueType::I64, 1),
+        5 => (ValueType::I64, 2),
+        _ => (ValueType::I64, 4),
+      }),
+      _ => None,
+    }
+  }
+
+  // アトミック命令のmemargを(align, memidx, offset)で返す。fenceはmemargを持たない
+  pub fn atomic_memarg(&self) -> Option<(u32, u32, u64)> {
+    match self {
+      Instructions::MemoryAtomicNotify { align, memory, offset }
+      | Instructions::MemoryAtomicWait32 { align, memory, offset }
+      | Instructions::MemoryAtomicWait64 { align, memory, offset }
+      | Instructions::I32AtomicLoad { align, memory, offset }
+      | Instructions::I64AtomicLoad { align, memory, offset }
+      | Instructions::I32AtomicLoad8U { align, memory, offset }
+      | Instructions::I32AtomicLoad16U { align, memory, offset }
+      | Instructions::I64AtomicLoad8U { align, memory, offset }
+      | Instructions::I64AtomicLoad16U { align, memory, offset }
+      | Instructions::I64AtomicLoad32U { align, memory, offset }
+      | Instructions::I32AtomicStore { align, memory, offset }
+      | Instructions::I64AtomicStore { align, memory, offset }
+      | Instructions::I32AtomicStore8 { align, memory, offset }
+      | Instructions::I32AtomicStore16 { align, memory, offset }
+      | Instructions::I64AtomicStore8 { align, memory, offset }
+      | Instructions::I64AtomicStore16 { align, memory, offset }
+      | Instructions::I64AtomicStore32 { align, memory, offset }
+      | Instructions::I32AtomicRmwAdd { align, memory, offset }
+      | Instructions::I64AtomicRmwAdd { align, memory, offset }
+      | Instructions::I32AtomicRmw8AddU { align, memory, offset }
+      | Instructions::I32AtomicRmw16AddU { align, memory, offset }
+      | Instructions::I64AtomicRmw8AddU { align, memory, offset }
+      | Instructions::I64AtomicRmw16AddU { align, memory, offset }
+      | Instructions::I64AtomicRmw32AddU { align, memory, offset }
+      | Instructions::I32AtomicRmwSub { align, memory, offset }
+      | Instructions::I64AtomicRmwSub { align, memory, offset }
+      | Instructions::I32AtomicRmw8SubU { align, memory, offset }
+      | Instructions::I32AtomicRmw16SubU { align, memory, offset }
+      | Instructions::I64AtomicRmw8SubU { align, memory, offset }
+      | Instructions::I64AtomicRmw16SubU { align, memory, offset }
+      | Instructions::I64AtomicRmw32SubU { align, memory, offset }
+      | Instructions::I32AtomicRmwAnd { align, memory, offset }
+      | Instructions::I64AtomicRmwAnd { align, memory, offset }
+      | Instructions::I32AtomicRmw8AndU { align, memory, offset }
+      | Instructions::I32AtomicRmw16AndU { align, memory, offset }
+      | Instructions::I64AtomicRmw8AndU { align, memory, offset }
+      | Instructions::I64AtomicRmw16AndU { align, memory, offset }
+      | Instructions::I64AtomicRmw32AndU { align, memory, offset }
+      | Instructions::I32AtomicRmwOr { align, memory, offset }
+      | Instructions::I64AtomicRmwOr { align, memory, offset }
+      | Instructions::I32AtomicRmw8OrU { align, memory, offset }
+      | Instructions::I32AtomicRmw16OrU { align, memory, offset }
+      | Instructions::I64AtomicRmw8OrU { align, memory, offset }
+      | Instructions::I64AtomicRmw16OrU { align, memory, offset }
+      | Instructions::I64AtomicRmw32OrU { align, memory, offset }
+      | Instructions::I32AtomicRmwXor { align, memory, offset }
+      | Instructions::I64AtomicRmwXor { align, memory, offset }
+      | Instructions::I32AtomicRmw8XorU { align, memory, offset }
+      | Instructions::I32AtomicRmw16XorU { align, memory, offset }
+      | Instructions::I64AtomicRmw8XorU { align, memory, offset }
+      | Instructions::I64AtomicRmw16XorU { align, memory, offset }
+      | Instructions::I64AtomicRmw32XorU { align, memory, offset }
+      | Instructions::I32AtomicRmwXchg { align, memory, offset }
+      | Instructions::I64AtomicRmwXchg { align, memory, offset }
+      | Instructions::I32AtomicRmw8XchgU { align, memory, offset }
+      | Instructions::I32AtomicRmw16XchgU { align, memory, offset }
+      | Instructions::I64AtomicRmw8XchgU { align, memory, offset }
+      | Instructions::I64AtomicRmw16XchgU { align, memory, offset }
+      | Instructions::I64AtomicRmw32XchgU { align, memory, offset }
+      | Instructions::I32AtomicRmwCmpxchg { align, memory, offset }
+      | Instructions::I64AtomicRmwCmpxchg { align, memory, offset }
+      | Instructions::I32AtomicRmw8CmpxchgU { align, memory, offset }
+      | Instructions::I32AtomicRmw16CmpxchgU { align, memory, offset }
+      | Instructions::I64AtomicRmw8CmpxchgU { align, memory, offset }
+      | Instructions::I64AtomicRmw16CmpxchgU { align, memory, offset }
+      | Instructions::I64AtomicRmw32CmpxchgU { align, memory, offset } => Some((*align, *memory, *offset)),
+      _ => None,
+    }
+  }
+
+  // テキスト形式での命令名
+  pub fn name(&self) -> &'static str {
+    match self {
+      Instructions::Unreachable => "unreachable",
+      Instructions::Nop => "nop",
+      Instructions::Block(..) => "block",
+      Instructions::Loop(..) => "loop",
+      Instructions::If(..) => "if",
+      Instructions::Else => "else",
+      Instructions::End => "end",
+      Instructions::Br(..) => "br",
+      Instructions::BrIf(..) => "br_if",
+      Instructions::BrTable(..) => "br_table",
+      Instructions::Return => "return",
+      Instructions::Call(..) => "call",
+      Instructions::CallIndirect(..) => "call_indirect",
+      Instructions::ReturnCall(..) => "return_call",
+      Instructions::ReturnCallIndirect(..) => "return_call_indirect",
+      Instructions::Throw(..) => "throw",
+      Instructions::ThrowRef => "throw_ref",
+      Instructions::TryTable(..) => "try_table",
+      Instructions::Drop => "drop",
+      Instructions::Select => "select",
+      Instructions::SelectValtype(..) => "select",
+      Instructions::I32Load { .. } => "i32.load",
+      Instructions::I64Load { .. } => "i64.load",
+      Instructions::F32Load { .. } => "f32.load",
+      Instructions::F64Load { .. } => "f64.load",
+      Instructions::I32Load8S { .. } => "i32.load8_s",
+      Instructions::I32Load8U { .. } => "i32.load8_u",
+      Instructions::I32Load16S { .. } => "i32.load16_s",
+      Instructions::I32Load16U { .. } => "i32.load16_u",
+      Instructions::I64Load8S { .. } => "i64.load8_s",
+      Instructions::I64Load8U { .. } => "i64.load8_u",
+      Instructions::I64Load16S { .. } => "i64.load16_s",
+      Instructions::I64Load16U { .. } => "i64.load16_u",
+      Instructions::I64Load32S { .. } => "i64.load32_s",
+      Instructions::I64Load32U { .. } => "i64.load32_u",
+      Instructions::I32Store { .. } => "i32.store",
+      Instructions::I64Store { .. } => "i64.store",
+      Instructions::F32Store { .. } => "f32.store",
+      Instructions::F64Store { .. } => "f64.store",
+      Instructions::I32Store8 { .. } => "i32.store8",
+      Instructions::I32Store16 { .. } => "i32.store16",
+      Instructions::I64Store8 { .. } => "i64.store8",
+      Instructions::I64Store16 { .. } => "i64.store16",
+      Instructions::I64Store32 { .. } => "i64.store32",
+      Instructions::MemorySize(..) => "memory.size",
+      Instructions::MemoryGrow(..) => "memory.grow",
+      Instructions::MemoryInit(..) => "memory.init",
+      Instructions::DataDrop(..) => "data.drop",
+      Instructions::MemoryCopy(..) => "memory.copy",
+      Instructions::MemoryFill(..) => "memory.fill",
+      Instructions::I32Const(..) => "i32.const",
+      Instructions::I64Const(..) => "i64.const",
+      Instructions::F32Const(..) => "f32.const",
+      Instructions::F64Const(..) => "f64.const",
+      Instructions::I32Eqz => "i32.eqz",
+      Instructions::I32Eq => "i32.eq",
+      Instructions::I32Ne => "i32.ne",
+      Instructions::I32LtS => "i32.lt_s",
+      Instructions::I32LtU => "i32.lt_u",
+      Instructions::I32GtS => "i32.gt_s",
+      Instructions::I32GtU => "i32.gt_u",
+      Instructions::I32LeS => "i32.le_s",
+      Instructions::I32LeU => "i32.le_u",
+      Instructions::I32GeS => "i32.ge_s",
+      Instructions::I32GeU => "i32.ge_u",
+      Instructions::I64Eqz => "i64.eqz",
+      Instructions::I64Eq => "i64.eq",
+      Instructions::I64Ne => "i64.ne",
+      Instructions::I64LtS => "i64.lt_s",
+      Instructions::I64LtU => "i64.lt_u",
+      Instructions::I64GtS => "i64.gt_s",
+      Instructions::I64GtU => "i64.gt_u",
+      Instructions::I64LeS => "i64.le_s",
+      Instructions::I64LeU => "i64.le_u",
+      Instructions::I64GeS => "i64.ge_s",
+      Instructions::I64GeU => "i64.ge_u",
+      Instructions::F32Eq => "f32.eq",
+      Instructions::F32Ne => "f32.ne",
+      Instructions::F32Lt => "f32.lt",
+      Instructions::F32Gt => "f32.gt",
+      Instructions::F32Le => "f32.le",
+      Instructions::F32Ge => "f32.ge",
+      Instructions::F64Eq => "f64.eq",
+      Instructions::F64Ne => "f64.ne",
+      Instructions::F64Lt => "f64.lt",
+      Instructions::F64Gt => "f64.gt",
+      Instructions::F64Le => "f64.le",
+      Instructions::F64Ge => "f64.ge",
+      Instructions::I32Clz => "i32.clz",
+      Instructions::I32Ctz => "i32.ctz",
+      Instructions::I32Popcnt => "i32.popcnt",
+      Instructions::I32Add => "i32.add",
+      Instructions::I32Sub => "i32.sub",
+      Instructions::I32Mul => "i32.mul",
+      Instructions::I32DivS => "i32.div_s",
+      Instructions::I32DivU => "i32.div_u",
+      Instructions::I32RemS => "i32.rem_s",
+      Instructions::I32RemU => "i32.rem_u",
+      Instructions::I32And => "i32.and",
+      Instructions::I32Or => "i32.or",
+      Instructions::I32Xor => "i32.xor",
+      Instructions::I32Shl => "i32.shl",
+      Instructions::I32ShrS => "i32.shr_s",
+      Instructions::I32ShrU => "i32.shr_u",
+      Instructions::I32Rotl => "i32.rotl",
+      Instructions::I32Rotr => "i32.rotr",
+      Instructions::I64Clz => "i64.clz",
+      Instructions::I64Ctz => "i64.ctz",
+      Instructions::I64Popcnt => "i64.popcnt",
+      Instructions::I64Add => "i64.add",
+      Instructions::I64Sub => "i64.sub",
+      Instructions::I64Mul => "i64.mul",
+      Instructions::I64DivS => "i64.div_s",
+      Instructions::I64DivU => "i64.div_u",
+      Instructions::I64RemS => "i64.rem_s",
+      Instructions::I64RemU => "i64.rem_u",
+      Instructions::I64And => "i64.and",
+      Instructions::I64Or => "i64.or",
+      Instructions::I64Xor => "i64.xor",
+      Instructions::I64Shl => "i64.shl",
+      Instructions::I64ShrS => "i64.shr_s",
+      Instructions::I64ShrU => "i64.shr_u",
+      Instructions::I64Rotl => "i64.rotl",
+      Instructions::I64Rotr => "i64.rotr",
+      Instructions::F32Abs => "f32.abs",
+      Instructions::F32Neg => "f32.neg",
+      Instructions::F32Ceil => "f32.ceil",
+      Instructions::F32Floor => "f32.floor",
+      Instructions::F32Trunc => "f32.trunc",
+      Instructions::F32Nearest => "f32.nearest",
+      Instructions::F32Sqrt => "f32.sqrt",
+      Instructions::F32Add => "f32.add",
+      Instructions::F32Sub => "f32.sub",
+      Instructions::F32Mul => "f32.mul",
+      Instructions::F32Div => "f32.div",
+      Instructions::F32Min => "f32.min",
+      Instructions::F32Max => "f32.max",
+      Instructions::F32Copysign => "f32.copysign",
+      Instructions::F64Abs => "f64.abs",
+      Instructions::F64Neg => "f64.neg",
+      Instructions::F64Ceil => "f64.ceil",
+      Instructions::F64Floor => "f64.floor",
+      Instructions::F64Trunc => "f64.trunc",
+      Instructions::F64Nearest => "f64.nearest",
+      Instructions::F64Sqrt => "f64.sqrt",
+      Instructions::F64Add => "f64.add",
+      Instructions::F64Sub => "f64.sub",
+      Instructions::F64Mul => "f64.mul",
+      Instructions::F64Div => "f64.div",
+      Instructions::F64Min => "f64.min",
+      Instructions::F64Max => "f64.max",
+      Instructions::F64Copysign => "f64.copysign",
+      Instructions::I32WrapI64 => "i32.wrap_i64",
+      Instructions::I32TruncF32S => "i32.trunc_f32_s",
+      Instructions::I32TruncF32U => "i32.trunc_f32_u",
+      Instructions::I32TruncF64S => "i32.trunc_f64_s",
+      Instructions::I32TruncF64U => "i32.trunc_f64_u",
+      Instructions::I64ExtendI32S => "i64.extend_i32_s",
+      Instructions::I64ExtendI32U => "i64.extend_i32_u",
+      Instructions::I64TruncF32S => "i64.trunc_f32_s",
+      Instructions::I64TruncF32U => "i64.trunc_f32_u",
+      Instructions::I64TruncF64S => "i64.trunc_f64_s",
+      Instructions::I64TruncF64U => "i64.trunc_f64_u",
+      Instructions::F32ConvertI32S => "f32.convert_i32_s",
+      Instructions::F32ConvertI32U => "f32.convert_i32_u",
+      Instructions::F32ConvertI64S => "f32.convert_i64_s",
+      Instructions::F32ConvertI64U => "f32.convert_i64_u",
+      Instructions::F32DemoteF64 => "f32.demote_f64",
+      Instructions::F64ConvertI32S => "f64.convert_i32_s",
+      Instructions::F64ConvertI32U => "f64.convert_i32_u",
+      Instructions::F64ConvertI64S => "f64.convert_i64_s",
+      Instructions::F64ConvertI64U => "f64.convert_i64_u",
+      Instructions::F64PromoteF32 => "f64.promote_f32",
+      Instructions::I32ReinterpretF32 => "i32.reinterpret_f32",
+      Instructions::I64ReinterpretF64 => "i64.reinterpret_f64",
+      Instructions::F32ReinterpretI32 => "f32.reinterpret_i32",
+      Instructions::F64ReinterpretI64 => "f64.reinterpret_i64",
+      Instructions::I32Extend8S => "i32.extend8_s",
+      Instructions::I32Extend16S => "i32.extend16_s",
+      Instructions::I64Extend8S => "i64.extend8_s",
+      Instructions::I64Extend16S => "i64.extend16_s",
+      Instructions::I64Extend32S => "i64.extend32_s",
+      Instructions::I32TruncSatF32S => "i32.trunc_sat_f32_s",
+      Instructions::I32TruncSatF32U => "i32.trunc_sat_f32_u",
+      Instructions::I32TruncSatF64S => "i32.trunc_sat_f64_s",
+      Instructions::I32TruncSatF64U => "i32.trunc_sat_f64_u",
+      Instructions::I64TruncSatF32S => "i64.trunc_sat_f32_s",
+      Instructions::I64TruncSatF32U => "i64.trunc_sat_f32_u",
+      Instructions::I64TruncSatF64S => "i64.trunc_sat_f64_s",
+      Instructions::I64TruncSatF64U => "i64.trunc_sat_f64_u",
+      Instructions::LocalGet(..) => "local.get",
+      Instructions::LocalSet(..) => "local.set",
+      Instructions::LocalTee(..) => "local.tee",
+      Instructions::GlobalGet(..) => "global.get",
+      Instructions::GlobalSet(..) => "global.set",
+      Instructions::TableGet(..) => "table.get",
+      Instructions::TableSet(..) => "table.set",
+      Instructions::TableInit(..) => "table.init",
+      Instructions::ElemDrop(..) => "elem.drop",
+      Instructions::TableCopy(..) => "table.copy",
+      Instructions::TableGrow(..) => "table.grow",
+      Instructions::TableSize(..) => "table.size",
+      Instructions::TableFill(..) => "table.fill",
+      Instructions::RefNull(..) => "ref.null",
+      Instructions::RefIsNull => "ref.is_null",
+      Instructions::RefFunc(..) => "ref.func",
+      Instructions::V128Load { .. } => "v128.load",
+      Instructions::V128Load8x8S { .. } => "v128.load8x8_s",
+      Instructions::V128Load8x8U { .. } => "v128.load8x8_u",
+      Instructions::V128Load16x4S { .. } => "v128.load16x4_s",
+      Instructions::V128Load16x4U { .. } => "v128.load16x4_u",
+      Instructions::V128Load32x2S { .. } => "v128.load32x2_s",
+      Instructions::V128Load32x2U { .. } => "v128.load32x2_u",
+      Instructions::V128Load8Splat { .. } => "v128.load8_splat",
+      Instructions::V128Load16Splat { .. } => "v128.load16_splat",
+      Instructions::V128Load32Splat { .. } => "v128.load32_splat",
+      Instructions::V128Load64Splat { .. } => "v128.load64_splat",
+      Instructions::V128Store { .. } => "v128.store",
+      Instructions::V128Const(..) => "v128.const",
+      Instructions::I8x16Shuffle(..) => "i8x16.shuffle",
+      Instructions::I8x16Swizzle => "i8x16.swizzle",
+      Instructions::I8x16Splat => "i8x16.splat",
+      Instructions::I16x8Splat => "i16x8.splat",
+      Instructions::I32x4Splat => "i32x4.splat",
+      Instructions::I64x2Splat => "i64x2.splat",
+      Instructions::F32x4Splat => "f32x4.splat",
+      Instructions::F64x2Splat => "f64x2.splat",
+      Instructions::I8x16ExtractLaneS(..) => "i8x16.extract_lane_s",
+      Instructions::I8x16ExtractLaneU(..) => "i8x16.extract_lane_u",
+      Instructions::I8x16ReplaceLane(..) => "i8x16.replace_lane",
+      Instructions::I16x8ExtractLaneS(..) => "i16x8.extract_lane_s",
+      Instructions::I16x8ExtractLaneU(..) => "i16x8.extract_lane_u",
+      Instructions::I16x8ReplaceLane(..) => "i16x8.replace_lane",
+      Instructions::I32x4ExtractLane(..) => "i32x4.extract_lane",
+      Instructions::I32x4ReplaceLane(..) => "i32x4.replace_lane",
+      Instructions::I64x2ExtractLane(..) => "i64x2.extract_lane",
+      Instructions::I64x2ReplaceLane(..) => "i64x2.replace_lane",
+      Instructions::F32x4ExtractLane(..) => "f32x4.extract_lane",
+      Instructions::F32x4ReplaceLane(..) => "f32x4.replace_lane",
+      Instructions::F64x2ExtractLane(..) => "f64x2.extract_lane",
+      Instructions::F64x2ReplaceLane(..) => "f64x2.replace_lane",
+      Instructions::I8x16Eq => "i8x16.eq",
+      Instructions::I8x16Ne => "i8x16.ne",
+      Instructions::I8x16LtS => "i8x16.lt_s",
+      Instructions::I8x16LtU => "i8x16.lt_u",
+      Instructions::I8x16GtS => "i8x16.gt_s",
+      Instructions::I8x16GtU => "i8x16.gt_u",
+      Instructions::I8x16LeS => "i8x16.le_s",
+      Instructions::I8x16LeU => "i8x16.le_u",
+      Instructions::I8x16GeS => "i8x16.ge_s",
+      Instructions::I8x16GeU => "i8x16.ge_u",
+      Instructions::I16x8Eq => "i16x8.eq",
+      Instructions::I16x8Ne => "i16x8.ne",
+      Instructions::I16x8LtS => "i16x8.lt_s",
+      Instructions::I16x8LtU => "i16x8.lt_u",
+      Instructions::I16x8GtS => "i16x8.gt_s",
+      Instructions::I16x8GtU => "i16x8.gt_u",
+      Instructions::I16x8LeS => "i16x8.le_s",
+      Instructions::I16x8LeU => "i16x8.le_u",
+      Instructions::I16x8GeS => "i16x8.ge_s",
+      Instructions::I16x8GeU => "i16x8.ge_u",
+      Instructions::I32x4Eq => "i32x4.eq",
+      Instructions::I32x4Ne => "i32x4.ne",
+      Instructions::I32x4LtS => "i32x4.lt_s",
+      Instructions::I32x4LtU => "i32x4.lt_u",
+      Instructions::I32x4GtS => "i32x4.gt_s",
+      Instructions::I32x4GtU => "i32x4.gt_u",
+      Instructions::I32x4LeS => "i32x4.le_s",
+      Instructions::I32x4LeU => "i32x4.le_u",
+      Instructions::I32x4GeS => "i32x4.ge_s",
+      Instructions::I32x4GeU => "i32x4.ge_u",
+      Instructions::F32x4Eq => "f32x4.eq",
+      Instructions::F32x4Ne => "f32x4.ne",
+      Instructions::F32x4Lt => "f32x4.lt",
+      Instructions::F32x4Gt => "f32x4.gt",
+      Instructions::F32x4Le => "f32x4.le",
+      Instructions::F32x4Ge => "f32x4.ge",
+      Instructions::F64x2Eq => "f64x2.eq",
+      Instructions::F64x2Ne => "f64x2.ne",
+      Instructions::F64x2Lt => "f64x2.lt",
+      Instructions::F64x2Gt => "f64x2.gt",
+      Instructions::F64x2Le => "f64x2.le",
+      Instructions::F64x2Ge => "f64x2.ge",
+      Instructions::V128Not => "v128.not",
+      Instructions::V128And => "v128.and",
+      Instructions::V128Andnot => "v128.andnot",
+      Instructions::V128Or => "v128.or",
+      Instructions::V128Xor => "v128.xor",
+      Instructions::V128Bitselect => "v128.bitselect",
+      Instructions::V128AnyTrue => "v128.any_true",
+      Instructions::V128Load8Lane { .. } => "v128.load8_lane",
+      Instructions::V128Store8Lane { .. } => "v128.store8_lane",
+      Instructions::V128Load16Lane { .. } => "v128.load16_lane",
+      Instructions::V128Store16Lane { .. } => "v128.store16_lane",
+      Instructions::V128Load32Lane { .. } => "v128.load32_lane",
+      Instructions::V128Store32Lane { .. } => "v128.store32_lane",
+      Instructions::V128Load64Lane { .. } => "v128.load64_lane",
+      Instructions::V128Store64Lane { .. } => "v128.store64_lane",
+      Instructions::V128Load32Zero { .. } => "v128.load32_zero",
+      Instructions::V128Load64Zero { .. } => "v128.load64_zero",
+      Instructions::F32x4DemoteF64x2Zero => "f32x4.demote_f64x2_zero",
+      Instructions::F64x2PromoteLowF32x4 => "f64x2.promote_low_f32x4",
+      Instructions::I8x16Abs => "i8x16.abs",
+      Instructions::I8x16Neg => "i8x16.neg",
+      Instructions::I8x16Popcnt => "i8x16.popcnt",
+      Instructions::I8x16AllTrue => "i8x16.all_true",
+      Instructions::I8x16Bitmask => "i8x16.bitmask",
+      Instructions::I8x16NarrowI16x8S => "i8x16.narrow_i16x8_s",
+      Instructions::I8x16NarrowI16x8U => "i8x16.narrow_i16x8_u",
+      Instructions::F32x4Ceil => "f32x4.ceil",
+      Instructions::F32x4Floor => "f32x4.floor",
+      Instructions::F32x4Trunc => "f32x4.trunc",
+      Instructions::F32x4Nearest => "f32x4.nearest",
+      Instructions::I8x16Shl => "i8x16.shl",
+      Instructions::I8x16ShrS => "i8x16.shr_s",
+      Instructions::I8x16ShrU => "i8x16.shr_u",
+      Instructions::I8x16Add => "i8x16.add",
+      Instructions::I8x16AddSatS => "i8x16.add_sat_s",
+      Instructions::I8x16AddSatU => "i8x16.add_sat_u",
+      Instructions::I8x16Sub => "i8x16.sub",
+      Instructions::I8x16SubSatS => "i8x16.sub_sat_s",
+      Instructions::I8x16SubSatU => "i8x16.sub_sat_u",
+      Instructions::F64x2Ceil => "f64x2.ceil",
+      Instructions::F64x2Floor => "f64x2.floor",
+      Instructions::I8x16MinS => "i8x16.min_s",
+      Instructions::I8x16MinU => "i8x16.min_u",
+      Instructions::I8x16MaxS => "i8x16.max_s",
+      Instructions::I8x16MaxU => "i8x16.max_u",
+      Instructions::F64x2Trunc => "f64x2.trunc",
+      Instructions::I8x16AvgrU => "i8x16.avgr_u",
+      Instructions::I16x8ExtaddPairwiseI8x16S => "i16x8.extadd_pairwise_i8x16_s",
+      Instructions::I16x8ExtaddPairwiseI8x16U => "i16x8.extadd_pairwise_i8x16_u",
+      Instructions::I32x4ExtaddPairwiseI16x8S => "i32x4.extadd_pairwise_i16x8_s",
+      Instructions::I32x4ExtaddPairwiseI16x8U => "i32x4.extadd_pairwise_i16x8_u",
+      Instructions::I16x8Abs => "i16x8.abs",
+      Instructions::I16x8Neg => "i16x8.neg",
+      Instructions::I16x8Q15mulrSatS => "i16x8.q15mulr_sat_s",
+      Instructions::I16x8AllTrue => "i16x8.all_true",
+      Instructions::I16x8Bitmask => "i16x8.bitmask",
+      Instructions::I16x8NarrowI32x4S => "i16x8.narrow_i32x4_s",
+      Instructions::I16x8NarrowI32x4U => "i16x8.narrow_i32x4_u",
+      Instructions::I16x8ExtendLowI8x16S => "i16x8.extend_low_i8x16_s",
+      Instructions::I16x8ExtendHighI8x16S => "i16x8.extend_high_i8x16_s",
+      Instructions::I16x8ExtendLowI8x16U => "i16x8.extend_low_i8x16_u",
+      Instructions::I16x8ExtendHighI8x16U => "i16x8.extend_high_i8x16_u",
+      Instructions::I16x8Shl => "i16x8.shl",
+      Instructions::I16x8ShrS => "i16x8.shr_s",
+      Instructions::I16x8ShrU => "i16x8.shr_u",
+      Instructions::I16x8Add => "i16x8.add",
+      Instructions::I16x8AddSatS => "i16x8.add_sat_s",
+      Instructions::I16x8AddSatU => "i16x8.add_sat_u",
+      Instructions::I16x8Sub => "i16x8.sub",
+      Instructions::I16x8SubSatS => "i16x8.sub_sat_s",
+      Instructions::I16x8SubSatU => "i16x8.sub_sat_u",
+      Instructions::F64x2Nearest => "f64x2.nearest",
+      Instructions::I16x8Mul => "i16x8.mul",
+      Instructions::I16x8MinS => "i16x8.min_s",
+      Instructions::I16x8MinU => "i16x8.min_u",
+      Instructions::I16x8MaxS => "i16x8.max_s",
+      Instructions::I16x8MaxU => "i16x8.max_u",
+      Instructions::I16x8AvgrU => "i16x8.avgr_u",
+      Instructions::I16x8ExtmulLowI8x16S => "i16x8.extmul_low_i8x16_s",
+      Instructions::I16x8ExtmulHighI8x16S => "i16x8.extmul_high_i8x16_s",
+      Instructions::I16x8ExtmulLowI8x16U => "i16x8.extmul_low_i8x16_u",
+      Instructions::I16x8ExtmulHighI8x16U => "i16x8.extmul_high_i8x16_u",
+      Instructions::I32x4Abs => "i32x4.abs",
+      Instructions::I32x4Neg => "i32x4.neg",
+      Instructions::I32x4AllTrue => "i32x4.all_true",
+      Instructions::I32x4Bitmask => "i32x4.bitmask",
+      Instructions::I32x4ExtendLowI16x8S => "i32x4.extend_low_i16x8_s",
+      Instructions::I32x4ExtendHighI16x8S => "i32x4.extend_high_i16x8_s",
+      Instructions::I32x4ExtendLowI16x8U => "i32x4.extend_low_i16x8_u",
+      Instructions::I32x4ExtendHighI16x8U => "i32x4.extend_high_i16x8_u",
+      Instructions::I32x4Shl => "i32x4.shl",
+      Instructions::I32x4ShrS => "i32x4.shr_s",
+      Instructions::I32x4ShrU => "i32x4.shr_u",
+      Instructions::I32x4Add => "i32x4.add",
+      Instructions::I32x4Sub => "i32x4.sub",
+      Instructions::I32x4Mul => "i32x4.mul",
+      Instructions::I32x4MinS => "i32x4.min_s",
+      Instructions::I32x4MinU => "i32x4.min_u",
+      Instructions::I32x4MaxS => "i32x4.max_s",
+      Instructions::I32x4MaxU => "i32x4.max_u",
+      Instructions::I32x4DotI16x8S => "i32x4.dot_i16x8_s",
+      Instructions::I32x4ExtmulLowI16x8S => "i32x4.extmul_low_i16x8_s",
+      Instructions::I32x4ExtmulHighI16x8S => "i32x4.extmul_high_i16x8_s",
+      Instructions::I32x4ExtmulLowI16x8U => "i32x4.extmul_low_i16x8_u",
+      Instructions::I32x4ExtmulHighI16x8U => "i32x4.extmul_high_i16x8_u",
+      Instructions::I64x2Abs => "i64x2.abs",
+      Instructions::I64x2Neg => "i64x2.neg",
+      Instructions::I64x2AllTrue => "i64x2.all_true",
+      Instructions::I64x2Bitmask => "i64x2.bitmask",
+      Instructions::I64x2ExtendLowI32x4S => "i64x2.extend_low_i32x4_s",
+      Instructions::I64x2ExtendHighI32x4S => "i64x2.extend_high_i32x4_s",
+      Instructions::I64x2ExtendLowI32x4U => "i64x2.extend_low_i32x4_u",
+      Instructions::I64x2ExtendHighI32x4U => "i64x2.extend_high_i32x4_u",
+      Instructions::I64x2Shl => "i64x2.shl",
+      Instructions::I64x2ShrS => "i64x2.shr_s",
+      Instructions::I64x2ShrU => "i64x2.shr_u",
+      Instructions::I64x2Add => "i64x2.add",
+      Instructions::I64x2Sub => "i64x2.sub",
+      Instructions::I64x2Mul => "i64x2.mul",
+      Instructions::I64x2Eq => "i64x2.eq",
+      Instructions::I64x2Ne => "i64x2.ne",
+      Instructions::I64x2LtS => "i64x2.lt_s",
+      Instructions::I64x2GtS => "i64x2.gt_s",
+      Instructions::I64x2LeS => "i64x2.le_s",
+      Instructions::I64x2GeS => "i64x2.ge_s",
+      Instructions::I64x2ExtmulLowI32x4S => "i64x2.extmul_low_i32x4_s",
+      Instructions::I64x2ExtmulHighI32x4S => "i64x2.extmul_high_i32x4_s",
+      Instructions::I64x2ExtmulLowI32x4U => "i64x2.extmul_low_i32x4_u",
+      Instructions::I64x2ExtmulHighI32x4U => "i64x2.extmul_high_i32x4_u",
+      Instructions::F32x4Abs => "f32x4.abs",
+      Instructions::F32x4Neg => "f32x4.neg",
+      Instructions::F32x4Sqrt => "f32x4.sqrt",
+      Instructions::F32x4Add => "f32x4.add",
+      Instructions::F32x4Sub => "f32x4.sub",
+      Instructions::F32x4Mul => "f32x4.mul",
+      Instructions::F32x4Div => "f32x4.div",
+      Instructions::F32x4Min => "f32x4.min",
+      Instructions::F32x4Max => "f32x4.max",
+      Instructions::F32x4Pmin => "f32x4.pmin",
+      Instructions::F32x4Pmax => "f32x4.pmax",
+      Instructions::F64x2Abs => "f64x2.abs",
+      Instructions::F64x2Neg => "f64x2.neg",
+      Instructions::F64x2Sqrt => "f64x2.sqrt",
+      Instructions::F64x2Add => "f64x2.add",
+      Instructions::F64x2Sub => "f64x2.sub",
+      Instructions::F64x2Mul => "f64x2.mul",
+      Instructions::F64x2Div => "f64x2.div",
+      Instructions::F64x2Min => "f64x2.min",
+      Instructions::F64x2Max => "f64x2.max",
+      Instructions::F64x2Pmin => "f64x2.pmin",
+      Instructions::F64x2Pmax => "f64x2.pmax",
+      Instructions::I32x4TruncSatF32x4S => "i32x4.trunc_sat_f32x4_s",
+      Instructions::I32x4TruncSatF32x4U => "i32x4.trunc_sat_f32x4_u",
+      Instructions::F32x4ConvertI32x4S => "f32x4.convert_i32x4_s",
+      Instructions::F32x4ConvertI32x4U => "f32x4.convert_i32x4_u",
+      Instructions::I32x4TruncSatF64x2SZero => "i32x4.trunc_sat_f64x2_s_zero",
+      Instructions::I32x4TruncSatF64x2UZero => "i32x4.trunc_sat_f64x2_u_zero",
+      Instructions::F64x2ConvertLowI32x4S => "f64x2.convert_low_i32x4_s",
+      Instructions::F64x2ConvertLowI32x4U => "f64x2.convert_low_i32x4_u",
+      Instructions::MemoryAtomicNotify { .. } => "memory.atomic.notify",
+      Instructions::MemoryAtomicWait32 { .. } => "memory.atomic.wait32",
+      Instructions::MemoryAtomicWait64 { .. } => "memory.atomic.wait64",
+      Instructions::AtomicFence => "atomic.fence",
+      Instructions::I32AtomicLoad { .. } => "i32.atomic.load",
+      Instructions::I64AtomicLoad { .. } => "i64.atomic.load",
+      Instructions::I32AtomicLoad8U { .. } => "i32.atomic.load8_u",
+      Instructions::I32AtomicLoad16U { .. } => "i32.atomic.load16_u",
+      Instructions::I64AtomicLoad8U { .. } => "i64.atomic.load8_u",
+      Instructions::I64AtomicLoad16U { .. } => "i64.atomic.load16_u",
+      Instructions::I64AtomicLoad32U { .. } => "i64.atomic.load32_u",
+      Instructions::I32AtomicStore { .. } => "i32.atomic.store",
+      Instructions::I64AtomicStore { .. } => "i64.atomic.store",
+      Instructions::I32AtomicStore8 { .. } => "i32.atomic.store8",
+      Instructions::I32AtomicStore16 { .. } => "i32.atomic.store16",
+      Instructions::I64AtomicStore8 { .. } => "i64.atomic.store8",
+      Instructions::I64AtomicStore16 { .. } => "i64.atomic.store16",
+      Instructions::I64AtomicStore32 { .. } => "i64.atomic.store32",
+      Instructions::I32AtomicRmwAdd { .. } => "i32.atomic.rmw.add",
+      Instructions::I64AtomicRmwAdd { .. } => "i64.atomic.rmw.add",
+      Instructions::I32AtomicRmw8AddU { .. } => "i32.atomic.rmw8.add_u",
+      Instructions::I32AtomicRmw16AddU { .. } => "i32.atomic.rmw16.add_u",
+      Instructions::I64AtomicRmw8AddU { .. } => "i64.atomic.rmw8.add_u",
+      Instructions::I64AtomicRmw16AddU { .. } => "i64.atomic.rmw16.add_u",
+      Instructions::I64AtomicRmw32AddU { .. } => "i64.atomic.rmw32.add_u",
+      Instructions::I32AtomicRmwSub { .. } => "i32.atomic.rmw.sub",
+      Instructions::I64AtomicRmwSub { .. } => "i64.atomic.rmw.sub",
+      Instructions::I32AtomicRmw8SubU { .. } => "i32.atomic.rmw8.sub_u",
+      Instructions::I32AtomicRmw16SubU { .. } => "i32.atomic.rmw16.sub_u",
+      Instructions::I64AtomicRmw8SubU { .. } => "i64.atomic.rmw8.sub_u",
+      Instructions::I64AtomicRmw16SubU { .. } => "i64.atomic.rmw16.sub_u",
+      Instructions::I64AtomicRmw32SubU { .. } => "i64.atomic.rmw32.sub_u",
+      Instructions::I32AtomicRmwAnd { .. } => "i32.atomic.rmw.and",
+      Instructions::I64AtomicRmwAnd { .. } => "i64.atomic.rmw.and",
+      Instructions::I32AtomicRmw8AndU { .. } => "i32.atomic.rmw8.and_u",
+      Instructions::I32AtomicRmw16AndU { .. } => "i32.atomic.rmw16.and_u",
+      Instructions::I64AtomicRmw8AndU { .. } => "i64.atomic.rmw8.and_u",
+      Instructions::I64AtomicRmw16AndU { .. } => "i64.atomic.rmw16.and_u",
+      Instructions::I64AtomicRmw32AndU { .. } => "i64.atomic.rmw32.and_u",
+      Instructions::I32AtomicRmwOr { .. } => "i32.atomic.rmw.or",
+      Instructions::I64AtomicRmwOr { .. } => "i64.atomic.rmw.or",
+      Instructions::I32AtomicRmw8OrU { .. } => "i32.atomic.rmw8.or_u",
+      Instructions::I32AtomicRmw16OrU { .. } => "i32.atomic.rmw16.or_u",
+      Instructions::I64AtomicRmw8OrU { .. } => "i64.atomic.rmw8.or_u",
+      Instructions::I64AtomicRmw16OrU { .. } => "i64.atomic.rmw16.or_u",
+      Instructions::I64AtomicRmw32OrU { .. } => "i64.atomic.rmw32.or_u",
+      Instructions::I32AtomicRmwXor { .. } => "i32.atomic.rmw.xor",
+      Instructions::I64AtomicRmwXor { .. } => "i64.atomic.rmw.xor",
+      Instructions::I32AtomicRmw8XorU { .. } => "i32.atomic.rmw8.xor_u",
+      Instructions::I32AtomicRmw16XorU { .. } => "i32.atomic.rmw16.xor_u",
+      Instructions::I64AtomicRmw8XorU { .. } => "i64.atomic.rmw8.xor_u",
+      Instructions::I64AtomicRmw16XorU { .. } => "i64.atomic.rmw16.xor_u",
+      Instructions::I64AtomicRmw32XorU { .. } => "i64.atomic.rmw32.xor_u",
+      Instructions::I32AtomicRmwXchg { .. } => "i32.atomic.rmw.xchg",
+      Instructions::I64AtomicRmwXchg { .. } => "i64.atomic.rmw.xchg",
+      Instructions::I32AtomicRmw8XchgU { .. } => "i32.atomic.rmw8.xchg_u",
+      Instructions::I32AtomicRmw16XchgU { .. } => "i32.atomic.rmw16.xchg_u",
+      Instructions::I64AtomicRmw8XchgU { .. } => "i64.atomic.rmw8.xchg_u",
+      Instructions::I64AtomicRmw16XchgU { .. } => "i64.atomic.rmw16.xchg_u",
+      Instructions::I64AtomicRmw32XchgU { .. } => "i64.atomic.rmw32.xchg_u",
+      Instructions::I32AtomicRmwCmpxchg { .. } => "i32.atomic.rmw.cmpxchg",
+      Instructions::I64AtomicRmwCmpxchg { .. } => "i64.atomic.rmw.cmpxchg",
+      Instructions::I32AtomicRmw8CmpxchgU { .. } => "i32.atomic.rmw8.cmpxchg_u",
+      Instructions::I32AtomicRmw16CmpxchgU { .. } => "i32.atomic.rmw16.cmpxchg_u",
+      Instructions::I64AtomicRmw8CmpxchgU { .. } => "i64.atomic.rmw8.cmpxchg_u",
+      Instructions::I64AtomicRmw16CmpxchgU { .. } => "i64.atomic.rmw16.cmpxchg_u",
+      Instructions::I64AtomicRmw32CmpxchgU { .. } => "i64.atomic.rmw32.cmpxchg_u",
+    }
+  }
+
 }
 
 impl Block {
   pub fn parse(input: &[u8], is_loop: bool) -> IResult<&[u8], Block> {
-    let (input, block_type) = le_u8(input)?;
-    let block_type = match block_type {
-      0x40 => BlockType::Void,
-      _ => BlockType::Value(ValueType::parse(block_type)),
+    // blocktypeは0x40、値型、またはs33の型インデックスのいずれか
+    let (input, block_type) = match input.first() {
+      Some(0x40) => (&input[1..], BlockType::Void),
+      Some(byte) if byte & 0xc0 == 0x40 => {
+        let (input, value_type) = ValueType::parse(input)?;
+        (input, BlockType::Value(value_type))
+      },
+      _ => {
+        let (rest, type_idx) = leb128_i64(input)?;
+        if !(0..=u32::MAX as i64).contains(&type_idx) {
+          return fail(input, format!("a block type (found type index {})", type_idx));
+        }
+        (rest, BlockType::TypeIndex(type_idx as u32))
+      },
     };
-    Ok((input, Block { block_type , jump_pc: 0, is_loop }))
+    Ok((input, Block { block_type, jump_pc: 0, else_pc: None, is_loop }))
+  }
+
+  // 書き出すのはblocktypeだけで、jump_pcとelse_pcはデコード時に求め直す
+  pub fn encode_type(&self, buf: &mut Vec<u8>) {
+    match &self.block_type {
+      BlockType::Void => buf.push(0x40),
+      BlockType::Value(value_type) => value_type.encode(buf),
+      BlockType::TypeIndex(type_idx) => write_i64(buf, (*type_idx).into()),
+    }
   }
 }
+
+impl Catch {
+  pub fn parse(input: &[u8]) -> IResult<&[u8], Catch> {
+    let (rest, kind) = le_u8(input)?;
+    match kind {
+      0x00 => {
+        let (rest, tag) = leb128_u32(rest)?;
+        let (rest, label) = leb128_u32(rest)?;
+        Ok((rest, Catch::Catch { tag, label }))
+      },
+      0x01 => {
+        let (rest, tag) = leb128_u32(rest)?;
+        let (rest, label) = leb128_u32(rest)?;
+        Ok((rest, Catch::CatchRef { tag, label }))
+      },
+      0x02 => {
+        let (rest, label) = leb128_u32(rest)?;
+        Ok((rest, Catch::CatchAll { label }))
+      },
+      0x03 => {
+        let (rest, label) = leb128_u32(rest)?;
+        Ok((rest, Catch::CatchAllRef { label }))
+      },
+      _ => fail(input, format!("a catch clause kind (found {:#04x})", kind)),
+    }
+  }
+
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    match self {
+      Catch::Catch { tag, label } => {
+        write_prefixed(buf, 0x00, *tag);
+        write_u32(buf, *label);
+      },
+      Catch::CatchRef { tag, label } => {
+        write_prefixed(buf, 0x01, *tag);
+        write_u32(buf, *label);
+      },
+      Catch::CatchAll { label } => write_prefixed(buf, 0x02, *label),
+      Catch::CatchAllRef { label } => write_prefixed(buf, 0x03, *label),
+    }
+  }
+
+  pub fn label(&self) -> u32 {
+    match self {
+      Catch::Catch { label, .. } | Catch::CatchRef { label, .. } | Catch::CatchAll { label } | Catch::CatchAllRef { label } => *label,
+    }
+  }
+
+  // tagの例外をこの節で捕まえられるか
+  pub fn catches(&self, tag: usize) -> bool {
+    match self {
+      Catch::Catch { tag: t, .. } | Catch::CatchRef { tag: t, .. } => *t as usize == tag,
+      Catch::CatchAll { .. } | Catch::CatchAllRef { .. } => true,
+    }
+  }
+}
+
+// alignのビット6が立っていればメモリインデックスが続く (multi-memory)。返り値は(align, memidx, offset)
+// offsetはmemory64のために64bitで読み、32bitのメモリに収まるかは検査で確かめる
+fn parse_memarg(input: &[u8]) -> IResult<&[u8], (u32, u32, u64)> {
+  let (rest, flags) = leb128_u32(input)?;
+  if flags >= 0x80 {
+    return fail(input, format!("a memarg alignment below 2^7 (found {})", flags));
+  }
+  let (rest, memory) = if flags & 0x40 != 0 { leb128_u32(rest)? } else { (rest, 0) };
+  let (rest, offset) = leb128_u64(rest)?;
+  Ok((rest, (flags & !0x40, memory, offset)))
+}
+
+// 1バイトのオペコード (または0xfc等の接頭辞) に続けてLEB128の即値を書く
+fn write_prefixed(buf: &mut Vec<u8>, opcode: u8, immediate: u32) {
+  buf.push(opcode);
+  write_u32(buf, immediate);
+}
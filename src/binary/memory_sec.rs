@@ -1,13 +1,17 @@
-use core::panic;
-
-use nom::{bytes::complete::take, IResult};
-use nom_leb128::leb128_u32;
+use nom::number::complete::le_u8;
+use nom_leb128::{leb128_u32, leb128_u64};
 
+use super::encode::write_u64;
+use super::error::{fail, with_item, IResult};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MemorySec {
-  pub min: u32,
-  pub max: Option<u32>,
+  pub min: u64,
+  pub max: Option<u64>,
+  // memory64のメモリはアドレスがi64になる
+  pub memory64: bool,
+  // スレッド間で共有するメモリ。最大値が必須になる
+  pub shared: bool,
 }
 
 impl MemorySec {
@@ -15,25 +19,47 @@ impl MemorySec {
     let (mut input, memory_count) = leb128_u32(input)?;
     let mut memories = Vec::new();
 
-    for _ in 0..memory_count {
-      let flags: &[u8];
-      let min: u32;
-      let max: u32;
-
-      (input, flags) = take(1usize)(input)?;
-      match flags[0] {
-        0x00 => {
-          (input, min) = leb128_u32(input)?;
-          memories.push(MemorySec{min, max: None});
-        }
-        0x01 => {
-          (input, min) = leb128_u32(input)?;
-          (input, max) = leb128_u32(input)?;
-          memories.push(MemorySec{min, max: Some(max)})
-        }
-        _ => panic!("unknown limits flags")
-      }
+    for i in 0..memory_count {
+      let memory: MemorySec;
+      (input, memory) = with_item(i, MemorySec::parse_single(input))?;
+      memories.push(memory);
     }
     Ok((input, memories))
   }
-}
\ No newline at end of file
+
+  pub(crate) fn parse_single(input: &[u8]) -> IResult<&[u8], MemorySec> {
+    // ビット0は最大値の有無、ビット1は共有メモリ、ビット2はmemory64
+    let (rest, flags) = le_u8(input)?;
+    if flags & !0x07 != 0 {
+      return fail(input, format!("limits flags 0x00 to 0x07 (found {:#04x})", flags));
+    }
+    let shared = flags & 0x02 != 0;
+    let memory64 = flags & 0x04 != 0;
+    let (rest, min) = parse_limit(rest, memory64)?;
+    let (rest, max) = if flags & 0x01 != 0 {
+      let (rest, max) = parse_limit(rest, memory64)?;
+      (rest, Some(max))
+    } else {
+      (rest, None)
+    };
+    Ok((rest, MemorySec { min, max, memory64, shared }))
+  }
+
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    let flags = self.max.is_some() as u8 | (self.shared as u8) << 1 | (self.memory64 as u8) << 2;
+    buf.push(flags);
+    write_u64(buf, self.min);
+    if let Some(max) = self.max {
+      write_u64(buf, max);
+    }
+  }
+}
+
+fn parse_limit(input: &[u8], memory64: bool) -> IResult<&[u8], u64> {
+  if memory64 {
+    leb128_u64(input)
+  } else {
+    let (rest, limit) = leb128_u32(input)?;
+    Ok((rest, limit as u64))
+  }
+}
@@ -1,13 +1,21 @@
 pub mod wasm;
 pub mod section;
+pub mod error;
+pub mod encode;
+pub mod print;
+pub mod custom_sec;
+pub mod name_sec;
 pub mod type_sec;
 pub mod import_sec;
 pub mod func_sec;
 pub mod table_sec;
 pub mod memory_sec;
+pub mod tag_sec;
 pub mod global_sec;
 pub mod export_sec;
+pub mod elem_sec;
 pub mod code_sec;
 pub mod data_sec;
 pub mod instructions;
-pub mod value_type;
\ No newline at end of file
+pub mod value_type;
+pub mod validate;
\ No newline at end of file
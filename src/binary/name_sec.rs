@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use nom::{bytes::complete::take, number::complete::le_u8};
+use nom_leb128::leb128_u32;
+
+use super::error::{fail, IResult};
+
+pub type NameMap = BTreeMap<u32, String>;
+pub type IndirectNameMap = BTreeMap<u32, NameMap>;
+
+// "name"カスタムセクションの中身
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NameSec {
+  pub module: Option<String>,
+  pub funcs: NameMap,
+  pub locals: IndirectNameMap,
+  pub labels: IndirectNameMap,
+  pub globals: NameMap,
+  pub data: NameMap,
+}
+
+impl NameSec {
+  pub fn parse(input: &[u8]) -> IResult<&[u8], NameSec> {
+    let mut names = NameSec::default();
+    let mut input = input;
+
+    while !input.is_empty() {
+      let (rest, id) = le_u8(input)?;
+      let (rest, size) = leb128_u32(rest)?;
+      let (rest, content) = take(size)(rest)?;
+      match id {
+        0 => {
+          let (_, module) = parse_name(content)?;
+          names.module = Some(module);
+        },
+        1 => { (_, names.funcs) = parse_name_map(content)?; },
+        2 => { (_, names.locals) = parse_indirect_name_map(content)?; },
+        3 => { (_, names.labels) = parse_indirect_name_map(content)?; },
+        7 => { (_, names.globals) = parse_name_map(content)?; },
+        9 => { (_, names.data) = parse_name_map(content)?; },
+        // type, table, memory, elem等のサブセクションは今のところ使わない
+        _ => {},
+      }
+      input = rest;
+    }
+
+    Ok((input, names))
+  }
+
+  pub fn func_name(&self, func_idx: u32) -> Option<&String> {
+    self.funcs.get(&func_idx)
+  }
+
+  pub fn local_name(&self, func_idx: u32, local_idx: u32) -> Option<&String> {
+    self.locals.get(&func_idx).and_then(|locals| locals.get(&local_idx))
+  }
+}
+
+pub fn parse_name(input: &[u8]) -> IResult<&[u8], String> {
+  let (rest, len) = leb128_u32(input)?;
+  let (rest, name) = take(len)(rest)?;
+  match String::from_utf8(name.to_vec()) {
+    Ok(name) => Ok((rest, name)),
+    Err(_) => fail(input, "a name in valid UTF-8 encoding"),
+  }
+}
+
+fn parse_name_map(input: &[u8]) -> IResult<&[u8], NameMap> {
+  let (mut input, count) = leb128_u32(input)?;
+  let mut map = NameMap::new();
+  for _ in 0..count {
+    let idx: u32;
+    let name: String;
+    (input, idx) = leb128_u32(input)?;
+    (input, name) = parse_name(input)?;
+    map.insert(idx, name);
+  }
+  Ok((input, map))
+}
+
+fn parse_indirect_name_map(input: &[u8]) -> IResult<&[u8], IndirectNameMap> {
+  let (mut input, count) = leb128_u32(input)?;
+  let mut map = IndirectNameMap::new();
+  for _ in 0..count {
+    let idx: u32;
+    let names: NameMap;
+    (input, idx) = leb128_u32(input)?;
+    (input, names) = parse_name_map(input)?;
+    map.insert(idx, names);
+  }
+  Ok((input, map))
+}
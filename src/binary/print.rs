@@ -0,0 +1,586 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use nom_leb128::leb128_u32;
+
+use super::data_sec::DataMode;
+use super::elem_sec::{ElemInit, ElemMode};
+use super::error::ParseError;
+use super::export_sec::ExportDesc;
+use super::import_sec::ImportDesc;
+use super::instructions::{BlockType, Catch, Instructions};
+use super::memory_sec::MemorySec;
+use super::section::section_name;
+use super::table_sec::{RefType, TableSec};
+use super::value_type::ValueType;
+use super::wasm::{check_magic_and_version, parse_section_id_and_content, to_parse_error, Wasm};
+
+// objdump -h形式のセクション一覧の一行
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionHeader {
+  pub id: u8,
+  // idとサイズを除いたセクションの中身の、モジュール先頭からのオフセット
+  pub start: usize,
+  pub end: usize,
+  // ベクタのセクションでは要素数、startセクションでは関数インデックス
+  pub count: Option<u32>,
+  pub custom_name: Option<String>,
+}
+
+// 中身をデコードせずにセクションを並べるので、Wasm::newが途中で弾くモジュールにも使える
+pub fn section_headers(all_data: &[u8]) -> Result<Vec<SectionHeader>, ParseError> {
+  let mut data = check_magic_and_version(all_data)?;
+  let mut headers = Vec::new();
+
+  while !data.is_empty() {
+    let (rest, (id, content)) = match parse_section_id_and_content(data) {
+      Ok(parsed) => parsed,
+      Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => return Err(to_parse_error(all_data, e)),
+      Err(nom::Err::Incomplete(_)) => return Err(ParseError::new(all_data.len(), "more bytes (unexpected end)")),
+    };
+    let start = content.as_ptr() as usize - all_data.as_ptr() as usize;
+    let (count, custom_name) = match id {
+      0 => (None, super::name_sec::parse_name(content).ok().map(|(_, name)| name)),
+      1..=13 => (leb128_u32::<_, ()>(content).ok().map(|(_, count)| count), None),
+      _ => (None, None),
+    };
+    headers.push(SectionHeader { id, start, end: start + content.len(), count, custom_name });
+    data = rest;
+  }
+
+  Ok(headers)
+}
+
+impl fmt::Display for SectionHeader {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self.id {
+      9 => "Elem".to_string(),
+      12 => "DataCount".to_string(),
+      id => section_name(id).map_or("Unknown".to_string(), capitalize),
+    };
+    write!(f, "{:>9} start={:#010x} end={:#010x} (size={:#010x})", name, self.start, self.end, self.end - self.start)?;
+    match (self.id, self.count, &self.custom_name) {
+      (0, _, Some(custom_name)) => write!(f, " {:?}", custom_name),
+      (8, Some(func_idx), _) => write!(f, " start: {}", func_idx),
+      (_, Some(count), _) => write!(f, " count: {}", count),
+      _ => Ok(()),
+    }
+  }
+}
+
+fn capitalize(name: &str) -> String {
+  let mut chars = name.chars();
+  match chars.next() {
+    Some(c) => c.to_ascii_uppercase().to_string() + chars.as_str(),
+    None => String::new(),
+  }
+}
+
+impl Wasm {
+  // wasm2watの出力と同じ形でテキスト形式にする
+  // nameセクションの関数名は識別子として正しく重複しなければ使い、それ以外はインデックスで参照して(;n;)を添える
+  pub fn to_wat(&self) -> String {
+    let mut printer = Printer { wasm: self, func_ids: HashMap::new(), out: String::new() };
+    printer.collect_func_ids();
+    printer.module();
+    printer.out
+  }
+}
+
+struct Printer<'a> {
+  wasm: &'a Wasm,
+  func_ids: HashMap<u32, String>,
+  out: String,
+}
+
+impl Printer<'_> {
+  fn collect_func_ids(&mut self) {
+    let Some(names) = &self.wasm.name_section else { return };
+    let mut seen = HashSet::new();
+    let mut duplicated = HashSet::new();
+    for name in names.funcs.values() {
+      if !seen.insert(name) {
+        duplicated.insert(name);
+      }
+    }
+    for (idx, name) in &names.funcs {
+      if is_id(name) && !duplicated.contains(name) {
+        self.func_ids.insert(*idx, format!("${}", name));
+      }
+    }
+  }
+
+  fn line(&mut self, depth: usize, text: &str) {
+    self.out.push_str(&"  ".repeat(depth));
+    self.out.push_str(text);
+    self.out.push('\n');
+  }
+
+  fn module(&mut self) {
+    let wasm = self.wasm;
+    match wasm.name_section.as_ref().and_then(|names| names.module.as_ref()) {
+      Some(name) if is_id(name) => self.line(0, &format!("(module ${}", name)),
+      _ => self.line(0, "(module"),
+    }
+
+    for (i, func_type) in wasm.type_section.iter().flatten().enumerate() {
+      let sig = signature(&func_type.param_types, &func_type.return_types);
+      self.line(1, &format!("(type (;{};) (func{}))", i, sig));
+    }
+
+    // import分も含めた各インデックス空間での番号
+    let (mut funcs, mut tables, mut memories, mut globals, mut tags) = (0, 0, 0, 0, 0);
+    for import in wasm.import_section.iter().flatten() {
+      let desc = match &import.desc {
+        ImportDesc::Func(type_idx) => {
+          funcs += 1;
+          format!("(func {} (type {}){})", self.func_def_id(funcs - 1), type_idx, self.type_signature(*type_idx))
+        },
+        ImportDesc::Table(table) => { tables += 1; format!("(table (;{};) {})", tables - 1, table_type(table)) },
+        ImportDesc::Memory(memory) => { memories += 1; format!("(memory (;{};) {})", memories - 1, memory_type(memory)) },
+        ImportDesc::Global { valtype, mutability } => {
+          globals += 1;
+          format!("(global (;{};) {})", globals - 1, global_type(valtype, *mutability))
+        },
+        ImportDesc::Tag(type_idx) => { tags += 1; format!("(tag (;{};) (type {}))", tags - 1, type_idx) },
+      };
+      self.line(1, &format!("(import {} {} {})", string(import.module.as_bytes()), string(import.field.as_bytes()), desc));
+    }
+
+    let codes = wasm.code_section.iter().flatten();
+    for (i, (func, code)) in wasm.function_section.iter().flatten().zip(codes).enumerate() {
+      let func_idx = funcs + i as u32;
+      self.line(1, &format!("(func {} (type {}){}", self.func_def_id(func_idx), func.type_idx, self.type_signature(func.type_idx)));
+      let locals: Vec<&str> = code.locals.iter()
+        .flat_map(|local| std::iter::repeat_n(value_type(&local.value_type), local.count as usize))
+        .collect();
+      if !locals.is_empty() {
+        self.line(2, &format!("(local {})", locals.join(" ")));
+      }
+      self.body(&code.instrs);
+      self.out.pop();
+      self.out.push_str(")\n");
+    }
+
+    for (i, table) in wasm.table_section.iter().flatten().enumerate() {
+      self.line(1, &format!("(table (;{};) {})", tables as usize + i, table_type(table)));
+    }
+    for (i, memory) in wasm.memory_section.iter().flatten().enumerate() {
+      self.line(1, &format!("(memory (;{};) {})", memories as usize + i, memory_type(memory)));
+    }
+    for (i, tag) in wasm.tag_section.iter().flatten().enumerate() {
+      self.line(1, &format!("(tag (;{};) (type {}))", tags as usize + i, tag.type_idx));
+    }
+    for (i, global) in wasm.global_section.iter().flatten().enumerate() {
+      let global_type = global_type(&global.valtype, global.mutability);
+      self.line(1, &format!("(global (;{};) {} {})", globals as usize + i, global_type, self.const_expr(&global.init)));
+    }
+
+    for export in wasm.export_section.iter().flatten() {
+      let desc = match export.desc {
+        ExportDesc::Func => format!("func {}", self.func_ref(export.idx)),
+        ExportDesc::Table => format!("table {}", export.idx),
+        ExportDesc::Mem => format!("memory {}", export.idx),
+        ExportDesc::Global => format!("global {}", export.idx),
+        ExportDesc::Tag => format!("tag {}", export.idx),
+      };
+      self.line(1, &format!("(export {} ({}))", string(export.name.as_bytes()), desc));
+    }
+    if let Some(func_idx) = wasm.start_section {
+      self.line(1, &format!("(start {})", self.func_ref(func_idx)));
+    }
+
+    for (i, elem) in wasm.element_section.iter().flatten().enumerate() {
+      let mut text = format!("(elem (;{};)", i);
+      match &elem.mode {
+        ElemMode::Passive => {},
+        ElemMode::Active { table_idx, offset } => {
+          if *table_idx != 0 {
+            text.push_str(&format!(" (table {})", table_idx));
+          }
+          text.push_str(&format!(" {}", self.offset_expr(offset)));
+        },
+        ElemMode::Declarative => text.push_str(" declare"),
+      }
+      match &elem.init {
+        ElemInit::FuncIndices(func_idxs) => {
+          text.push_str(" func");
+          for func_idx in func_idxs {
+            text.push_str(&format!(" {}", self.func_ref(*func_idx)));
+          }
+        },
+        ElemInit::Exprs(exprs) => {
+          text.push_str(&format!(" {}", ref_type(&elem.reftype)));
+          for expr in exprs {
+            text.push_str(&format!(" {}", self.item_expr(expr)));
+          }
+        },
+      }
+      text.push(')');
+      self.line(1, &text);
+    }
+
+    for (i, data) in wasm.data_section.iter().flatten().enumerate() {
+      let mut text = format!("(data (;{};)", i);
+      if let DataMode::Active { memory_index, offset } = &data.mode {
+        if *memory_index != 0 {
+          text.push_str(&format!(" (memory {})", memory_index));
+        }
+        text.push_str(&format!(" {}", self.offset_expr(offset)));
+      }
+      text.push_str(&format!(" {})", string(&data.init)));
+      self.line(1, &text);
+    }
+
+    for custom in &wasm.custom_sections {
+      self.line(1, &format!("(; custom section {} ({} bytes) ;)", string(custom.name.as_bytes()), custom.data.len()));
+    }
+
+    self.out.pop();
+    self.out.push_str(")\n");
+  }
+
+  // 関数本体。ブロックの中は1段ずつ字下げし、ラベルの深さを注記する
+  fn body(&mut self, instrs: &[Instructions]) {
+    let mut depth = 0;
+    for instr in instrs {
+      match instr {
+        Instructions::End => {
+          depth = usize::max(depth, 1) - 1;
+          self.line(depth + 2, "end");
+        },
+        Instructions::Else => self.line(depth + 1, "else"),
+        Instructions::Block(_) | Instructions::Loop(_) | Instructions::If(_) | Instructions::TryTable(_, _) => {
+          depth += 1;
+          self.line(depth + 1, &format!("{} (; label = @{} ;)", self.instr(instr), depth));
+        },
+        _ => self.line(depth + 2, &self.instr(instr)),
+      }
+    }
+  }
+
+  fn instr(&self, instr: &Instructions) -> String {
+    let name = instr.name();
+    let immediates = match instr {
+      Instructions::Block(block) | Instructions::Loop(block) | Instructions::If(block) => block_type(&block.block_type),
+      Instructions::TryTable(block, catches) => {
+        let mut text = block_type(&block.block_type);
+        for catch in catches {
+          text.push_str(&match catch {
+            Catch::Catch { tag, label } => format!(" (catch {} {})", tag, label),
+            Catch::CatchRef { tag, label } => format!(" (catch_ref {} {})", tag, label),
+            Catch::CatchAll { label } => format!(" (catch_all {})", label),
+            Catch::CatchAllRef { label } => format!(" (catch_all_ref {})", label),
+          });
+        }
+        text
+      },
+      Instructions::Br(idx)
+      | Instructions::BrIf(idx)
+      | Instructions::Throw(idx)
+      | Instructions::LocalGet(idx)
+      | Instructions::LocalSet(idx)
+      | Instructions::LocalTee(idx)
+      | Instructions::GlobalGet(idx)
+      | Instructions::GlobalSet(idx)
+      | Instructions::TableGet(idx)
+      | Instructions::TableSet(idx)
+      | Instructions::TableGrow(idx)
+      | Instructions::TableSize(idx)
+      | Instructions::TableFill(idx)
+      | Instructions::DataDrop(idx)
+      | Instructions::ElemDrop(idx) => format!(" {}", idx),
+      Instructions::BrTable(labels, default) => {
+        labels.iter().chain([default]).map(|label| format!(" {}", label)).collect()
+      },
+      Instructions::Call(func_idx) | Instructions::ReturnCall(func_idx) | Instructions::RefFunc(func_idx) => {
+        format!(" {}", self.func_ref(*func_idx))
+      },
+      Instructions::CallIndirect(type_idx, table_idx) | Instructions::ReturnCallIndirect(type_idx, table_idx) => {
+        match table_idx {
+          0 => format!(" (type {})", type_idx),
+          _ => format!(" {} (type {})", table_idx, type_idx),
+        }
+      },
+      Instructions::SelectValtype(valtypes) => {
+        valtypes.iter().map(|valtype| format!(" (result {})", value_type(valtype))).collect()
+      },
+      Instructions::MemorySize(memory)
+      | Instructions::MemoryGrow(memory)
+      | Instructions::MemoryFill(memory) => memory_idx(*memory),
+      Instructions::MemoryInit(data_idx, memory) => format!("{} {}", memory_idx(*memory), data_idx),
+      Instructions::MemoryCopy(dst, src) if *dst == 0 && *src == 0 => String::new(),
+      Instructions::MemoryCopy(dst, src)
+      | Instructions::TableCopy(dst, src) => format!(" {} {}", dst, src),
+      Instructions::TableInit(elem_idx, table_idx) => format!(" {} {}", table_idx, elem_idx),
+      Instructions::I32Const(v) => format!(" {}", v),
+      Instructions::I64Const(v) => format!(" {}", v),
+      Instructions::F32Const(v) => format!(" {}", f32_text(*v)),
+      Instructions::F64Const(v) => format!(" {}", f64_text(*v)),
+      Instructions::RefNull(reftype) => format!(" {}", heap_type(reftype)),
+      Instructions::V128Const(v) => {
+        let lanes: Vec<String> = (0..4).map(|i| format!("{:#010x}", (v >> (32 * i)) as u32)).collect();
+        format!(" i32x4 {}", lanes.join(" "))
+      },
+      Instructions::I8x16Shuffle(lanes) => lanes.iter().map(|lane| format!(" {}", lane)).collect(),
+      Instructions::I8x16ExtractLaneS(lane)
+      | Instructions::I8x16ExtractLaneU(lane)
+      | Instructions::I8x16ReplaceLane(lane)
+      | Instructions::I16x8ExtractLaneS(lane)
+      | Instructions::I16x8ExtractLaneU(lane)
+      | Instructions::I16x8ReplaceLane(lane)
+      | Instructions::I32x4ExtractLane(lane)
+      | Instructions::I32x4ReplaceLane(lane)
+      | Instructions::I64x2ExtractLane(lane)
+      | Instructions::I64x2ReplaceLane(lane)
+      | Instructions::F32x4ExtractLane(lane)
+      | Instructions::F32x4ReplaceLane(lane)
+      | Instructions::F64x2ExtractLane(lane)
+      | Instructions::F64x2ReplaceLane(lane) => format!(" {}", lane),
+      Instructions::V128Load8Lane { align, memory, offset, lane }
+      | Instructions::V128Store8Lane { align, memory, offset, lane }
+      | Instructions::V128Load16Lane { align, memory, offset, lane }
+      | Instructions::V128Store16Lane { align, memory, offset, lane }
+      | Instructions::V128Load32Lane { align, memory, offset, lane }
+      | Instructions::V128Store32Lane { align, memory, offset, lane }
+      | Instructions::V128Load64Lane { align, memory, offset, lane }
+      | Instructions::V128Store64Lane { align, memory, offset, lane } => {
+        format!("{} {}", memarg(instr, *align, *memory, *offset), lane)
+      },
+      Instructions::I32Load { align, memory, offset }
+      | Instructions::I64Load { align, memory, offset }
+      | Instructions::F32Load { align, memory, offset }
+      | Instructions::F64Load { align, memory, offset }
+      | Instructions::I32Load8S { align, memory, offset }
+      | Instructions::I32Load8U { align, memory, offset }
+      | Instructions::I32Load16S { align, memory, offset }
+      | Instructions::I32Load16U { align, memory, offset }
+      | Instructions::I64Load8S { align, memory, offset }
+      | Instructions::I64Load8U { align, memory, offset }
+      | Instructions::I64Load16S { align, memory, offset }
+      | Instructions::I64Load16U { align, memory, offset }
+      | Instructions::I64Load32S { align, memory, offset }
+      | Instructions::I64Load32U { align, memory, offset }
+      | Instructions::I32Store { align, memory, offset }
+      | Instructions::I64Store { align, memory, offset }
+      | Instructions::F32Store { align, memory, offset }
+      | Instructions::F64Store { align, memory, offset }
+      | Instructions::I32Store8 { align, memory, offset }
+      | Instructions::I32Store16 { align, memory, offset }
+      | Instructions::I64Store8 { align, memory, offset }
+      | Instructions::I64Store16 { align, memory, offset }
+      | Instructions::I64Store32 { align, memory, offset }
+      | Instructions::V128Load { align, memory, offset }
+      | Instructions::V128Load8x8S { align, memory, offset }
+      | Instructions::V128Load8x8U { align, memory, offset }
+      | Instructions::V128Load16x4S { align, memory, offset }
+      | Instructions::V128Load16x4U { align, memory, offset }
+      | Instructions::V128Load32x2S { align, memory, offset }
+      | Instructions::V128Load32x2U { align, memory, offset }
+      | Instructions::V128Load8Splat { align, memory, offset }
+      | Instructions::V128Load16Splat { align, memory, offset }
+      | Instructions::V128Load32Splat { align, memory, offset }
+      | Instructions::V128Load64Splat { align, memory, offset }
+      | Instructions::V128Store { align, memory, offset }
+      | Instructions::V128Load32Zero { align, memory, offset }
+      | Instructions::V128Load64Zero { align, memory, offset } => memarg(instr, *align, *memory, *offset),
+      _ => match instr.atomic_memarg() {
+        Some((align, memory, offset)) => memarg(instr, align, memory, offset),
+        None => String::new(),
+      },
+    };
+    format!("{}{}", name, immediates)
+  }
+
+  // 定数式は末尾のendを除いて折り畳み形式で並べる
+  fn folded(&self, expr: &[Instructions]) -> Vec<String> {
+    expr.iter()
+      .filter(|instr| **instr != Instructions::End)
+      .map(|instr| format!("({})", self.instr(instr)))
+      .collect()
+  }
+
+  fn const_expr(&self, expr: &[Instructions]) -> String {
+    self.folded(expr).join(" ")
+  }
+
+  fn offset_expr(&self, expr: &[Instructions]) -> String {
+    match self.folded(expr).as_slice() {
+      [single] => single.clone(),
+      instrs => format!("(offset {})", instrs.join(" ")),
+    }
+  }
+
+  fn item_expr(&self, expr: &[Instructions]) -> String {
+    match self.folded(expr).as_slice() {
+      [single] => single.clone(),
+      instrs => format!("(item {})", instrs.join(" ")),
+    }
+  }
+
+  fn func_def_id(&self, func_idx: u32) -> String {
+    match self.func_ids.get(&func_idx) {
+      Some(id) => id.clone(),
+      None => format!("(;{};)", func_idx),
+    }
+  }
+
+  fn func_ref(&self, func_idx: u32) -> String {
+    match self.func_ids.get(&func_idx) {
+      Some(id) => id.clone(),
+      None => func_idx.to_string(),
+    }
+  }
+
+  fn type_signature(&self, type_idx: u32) -> String {
+    self.wasm.type_section.as_ref()
+      .and_then(|types| types.get(type_idx as usize))
+      .map_or(String::new(), |func_type| signature(&func_type.param_types, &func_type.return_types))
+  }
+}
+
+fn signature(params: &[ValueType], results: &[ValueType]) -> String {
+  let mut text = String::new();
+  if !params.is_empty() {
+    text.push_str(&format!(" (param {})", params.iter().map(value_type).collect::<Vec<_>>().join(" ")));
+  }
+  if !results.is_empty() {
+    text.push_str(&format!(" (result {})", results.iter().map(value_type).collect::<Vec<_>>().join(" ")));
+  }
+  text
+}
+
+fn block_type(block_type: &BlockType) -> String {
+  match block_type {
+    BlockType::Void => String::new(),
+    BlockType::Value(valtype) => format!(" (result {})", value_type(valtype)),
+    BlockType::TypeIndex(type_idx) => format!(" (type {})", type_idx),
+  }
+}
+
+fn memory_idx(memory: u32) -> String {
+  match memory {
+    0 => String::new(),
+    _ => format!(" {}", memory),
+  }
+}
+
+// offsetとalignは既定値(0と自然なアラインメント)なら省く
+fn memarg(instr: &Instructions, align: u32, memory: u32, offset: u64) -> String {
+  let mut text = memory_idx(memory);
+  if offset != 0 {
+    text.push_str(&format!(" offset={}", offset));
+  }
+  match 1u64.checked_shl(align) {
+    Some(bytes) if bytes == natural_alignment(instr) => {},
+    Some(bytes) => text.push_str(&format!(" align={}", bytes)),
+    None => text.push_str(&format!(" (; align=2**{} ;)", align)),
+  }
+  text
+}
+
+// アクセスするバイト数。命令名のload8x8やstore16のような幅から求め、幅がなければ型の大きさになる
+fn natural_alignment(instr: &Instructions) -> u64 {
+  if let Some((_, bytes)) = instr.atomic_access() {
+    return bytes as u64;
+  }
+  let (ty, op) = instr.name().split_once('.').unwrap_or_default();
+  let widths: Vec<u64> = op.split(|c: char| !c.is_ascii_digit()).filter_map(|n| n.parse().ok()).collect();
+  match widths.as_slice() {
+    [bits, lanes, ..] if op.contains('x') => bits * lanes / 8,
+    [bits, ..] => bits / 8,
+    [] => match ty {
+      "i32" | "f32" => 4,
+      "i64" | "f64" => 8,
+      _ => 16,
+    },
+  }
+}
+
+fn limits(min: u64, max: Option<u64>) -> String {
+  match max {
+    Some(max) => format!("{} {}", min, max),
+    None => min.to_string(),
+  }
+}
+
+fn table_type(table: &TableSec) -> String {
+  format!("{} {}", limits(table.min.into(), table.max.map(u64::from)), ref_type(&table.reftype))
+}
+
+fn memory_type(memory: &MemorySec) -> String {
+  let index_type = if memory.memory64 { "i64 " } else { "" };
+  let shared = if memory.shared { " shared" } else { "" };
+  format!("{}{}{}", index_type, limits(memory.min, memory.max), shared)
+}
+
+fn global_type(valtype: &ValueType, mutability: bool) -> String {
+  match mutability {
+    true => format!("(mut {})", value_type(valtype)),
+    false => value_type(valtype).to_string(),
+  }
+}
+
+fn value_type(valtype: &ValueType) -> &'static str {
+  match valtype {
+    ValueType::I32 => "i32",
+    ValueType::I64 => "i64",
+    ValueType::F32 => "f32",
+    ValueType::F64 => "f64",
+    ValueType::V128 => "v128",
+    ValueType::FuncRef => "funcref",
+    ValueType::ExternRef => "externref",
+    ValueType::ExnRef => "exnref",
+  }
+}
+
+fn ref_type(reftype: &RefType) -> &'static str {
+  value_type(&ValueType::from(reftype))
+}
+
+fn heap_type(reftype: &RefType) -> &'static str {
+  match reftype {
+    RefType::FuncRef => "func",
+    RefType::ExternRef => "extern",
+    RefType::ExnRef => "exn",
+  }
+}
+
+// NaNのペイロードや符号付きゼロもそのまま読み戻せる表記にする
+fn f32_text(v: f32) -> String {
+  float_text(v.is_nan(), v.is_sign_negative(), (v.to_bits() & 0x7f_ffff) as u64, 0x40_0000, v)
+}
+
+fn f64_text(v: f64) -> String {
+  float_text(v.is_nan(), v.is_sign_negative(), v.to_bits() & 0xf_ffff_ffff_ffff, 0x8_0000_0000_0000, v)
+}
+
+fn float_text(is_nan: bool, negative: bool, payload: u64, canonical: u64, v: impl fmt::Display) -> String {
+  let sign = if negative { "-" } else { "" };
+  if is_nan && payload == canonical {
+    format!("{}nan", sign)
+  } else if is_nan {
+    format!("{}nan:{:#x}", sign, payload)
+  } else {
+    // Displayはinfと最短で元の値に戻る10進表記を出す
+    v.to_string()
+  }
+}
+
+fn string(bytes: &[u8]) -> String {
+  let mut text = String::from("\"");
+  for byte in bytes {
+    match byte {
+      b'"' | b'\\' => text.push_str(&format!("\\{}", *byte as char)),
+      0x20..=0x7e => text.push(*byte as char),
+      _ => text.push_str(&format!("\\{:02x}", byte)),
+    }
+  }
+  text.push('"');
+  text
+}
+
+// $nameとして書ける識別子か
+fn is_id(name: &str) -> bool {
+  !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c))
+}
@@ -1,7 +1,13 @@
+use nom_leb128::leb128_u32;
+
+use super::custom_sec::CustomSec;
+use super::error::{DecodeError, IResult};
 use super::data_sec::Data;
+use super::elem_sec::Elem;
 use super::global_sec::GlobalVar;
 use super::memory_sec::MemorySec;
 use super::table_sec::TableSec;
+use super::tag_sec::Tag;
 use super::type_sec::FuncType;
 use super::import_sec::Import;
 use super::func_sec::Func;
@@ -11,7 +17,7 @@ use super::code_sec::Code;
 
 #[derive(Debug)]
 pub enum Section {
-  CustomSection,
+  CustomSection(CustomSec),
   TypeSection(Vec<FuncType>),
   ImportSection(Vec<Import>),
   FunctionSection(Vec<Func>),
@@ -19,95 +25,68 @@ pub enum Section {
   MemorySection(Vec<MemorySec>),
   GlobalSection(Vec<GlobalVar>),
   ExportSection(Vec<ExportFunc>),
-  StartSection,
-  ElementSection,
+  StartSection(u32),
+  ElementSection(Vec<Elem>),
   CodeSection(Vec<Code>),
   DataSection(Vec<Data>),
-  DataCountSection,
+  DataCountSection(u32),
+  TagSection(Vec<Tag>),
 }
 
 impl Section {
-  pub fn match_section(section_id: u8, section_data: &[u8]) -> Section{
-    match section_id {
-      0 => {
-        Section::CustomSection
-      },
-      1 => {
-        let func_types = match FuncType::parse(section_data) {
-          Ok((_, func_types)) => func_types,
-          Err(e) => panic!("Error: {:#x?}", e),
-        };
-        
-        Section::TypeSection(func_types)
-      },
-      2 => {
-        let imports = match Import::parse(section_data) {
-          Ok((_, imports)) => imports,
-          Err(e) => panic!("Error: {:#x?}", e),
-        };
-        Section::ImportSection(imports)
-      },
-      3 => {
-        let funcs = match Func::parse(section_data) {
-          Ok((_, funcs)) => funcs,
-          Err(e) => panic!("Error: {:#x?}", e),
-        };
-        Section::FunctionSection(funcs)
-      },
-      4 => {
-        let tables = match TableSec::parse(section_data) {
-          Ok((_, tables)) => tables,
-          Err(e) => panic!("Error: {:#x?}", e),
-        };
-        Section::TableSection(tables)
-      },
-      5 => {
-        let memories = match MemorySec::parse(section_data) {
-          Ok((_, memories)) => memories,
-          Err(e) => panic!("Error: {:#x?}", e),
-        };
-        Section::MemorySection(memories)
-      },
-      6 => {
-        let globals = match GlobalVar::parse(section_data) {
-          Ok((_, globals)) => globals,
-          Err(e) => panic!("Error: {:#x?}", e),
-        };
-        Section::GlobalSection(globals)
-      },
-      7 => {
-        let export_funcs = match ExportFunc::parse(section_data) {
-          Ok((_, export_funcs)) => export_funcs,
-          Err(e) => panic!("Error: {:#x?}", e),
-        };
+  pub fn match_section(section_id: u8, section_data: &[u8]) -> Result<Section, DecodeError<&[u8]>> {
+    let section = match section_id {
+      0 => Section::CustomSection(parse_all(CustomSec::parse, section_data)?),
+      1 => Section::TypeSection(parse_all(FuncType::parse, section_data)?),
+      2 => Section::ImportSection(parse_all(Import::parse, section_data)?),
+      3 => Section::FunctionSection(parse_all(Func::parse, section_data)?),
+      4 => Section::TableSection(parse_all(TableSec::parse, section_data)?),
+      5 => Section::MemorySection(parse_all(MemorySec::parse, section_data)?),
+      6 => Section::GlobalSection(parse_all(GlobalVar::parse, section_data)?),
+      7 => Section::ExportSection(parse_all(ExportFunc::parse, section_data)?),
+      8 => Section::StartSection(parse_all(leb128_u32, section_data)?),
+      9 => Section::ElementSection(parse_all(Elem::parse, section_data)?),
+      10 => Section::CodeSection(parse_all(Code::parse, section_data)?),
+      11 => Section::DataSection(parse_all(Data::parse, section_data)?),
+      12 => Section::DataCountSection(parse_all(leb128_u32, section_data)?),
+      13 => Section::TagSection(parse_all(Tag::parse, section_data)?),
+      _ => return Err(DecodeError::new(section_data, format!("a known section id (found {})", section_id))),
+    };
+    Ok(section)
+  }
+}
 
-        Section::ExportSection(export_funcs)
-      },
-      8 => {
-        Section::StartSection
-      },
-      9 => {
-        Section::ElementSection
-      },
-      10 => {
-        let codes = match Code::parse(section_data){
-          Ok((_, codes)) => codes,
-          Err(e) => panic!("Error: {:#x?}", e),
-        };
-        Section::CodeSection(codes)
-      },
-      11 => {
-        let data = match Data::parse(section_data) {
-          Ok((_, data)) => data,
-          Err(e) => panic!("Error: {:#x?}", e)
-        };
-        Section::DataSection(data)
-      },
-      12 => {
-        Section::DataCountSection
-      },
-      _ => panic!("Unknown section id: {}", section_id),
-    }
+pub fn section_name(section_id: u8) -> Option<&'static str> {
+  match section_id {
+    0 => Some("custom"),
+    1 => Some("type"),
+    2 => Some("import"),
+    3 => Some("function"),
+    4 => Some("table"),
+    5 => Some("memory"),
+    6 => Some("global"),
+    7 => Some("export"),
+    8 => Some("start"),
+    9 => Some("element"),
+    10 => Some("code"),
+    11 => Some("data"),
+    12 => Some("datacount"),
+    13 => Some("tag"),
+    _ => None,
+  }
+}
+
+// セクションの中身を余さず読み切れなければサイズの不一致として扱う
+fn parse_all<'a, O>(
+  parser: impl Fn(&'a [u8]) -> IResult<&'a [u8], O>,
+  section_data: &'a [u8],
+) -> Result<O, DecodeError<&'a [u8]>> {
+  match parser(section_data) {
+    Ok((rest, _)) if !rest.is_empty() => Err(DecodeError::new(rest, "end of section (section size mismatch)")),
+    Ok((_, parsed)) => Ok(parsed),
+    Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(e),
+    // completeなパーサしか使っていないが念のためセクション末尾を指す
+    Err(nom::Err::Incomplete(_)) => Err(DecodeError::new(&section_data[section_data.len()..], "more bytes (unexpected end)")),
   }
 }
 
@@ -119,7 +98,7 @@ mod tests {
   fn test_match_type_section() {
     let section_id = 1;
     let section_data = vec![0x01, 0x60, 0x01, 0x7f, 0x00];
-    let section = Section::match_section(section_id, &section_data);
+    let section = Section::match_section(section_id, &section_data).unwrap();
     match section {
       Section::TypeSection(func_types) => {
         assert_eq!(func_types.len(), 1);
@@ -134,7 +113,7 @@ mod tests {
   fn test_match_code_section() {
     let section_id = 10;
     let section_data = vec![0x01, 0x09, 0x01, 0x01, 0x7e, 0x42, 0x01, 0x42, 0x02, 0x7c, 0x0b];
-    let section = Section::match_section(section_id, &section_data);
+    let section = Section::match_section(section_id, &section_data).unwrap();
     match section {
       Section::CodeSection(codes) => {
         assert_eq!(codes.len(), 1);
@@ -144,4 +123,20 @@ mod tests {
       _ => panic!("Invalid section: {:?}", section),
     }
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_match_section_reports_position() {
+    // 2つ目の型がfunc型の0x60で始まっていない
+    let section_data = vec![0x02, 0x60, 0x00, 0x00, 0x61, 0x00, 0x00];
+    let err = Section::match_section(1, &section_data).unwrap_err();
+    assert_eq!(err.item, Some(1));
+    assert_eq!(section_data.len() - err.input.len(), 4);
+  }
+
+  #[test]
+  fn test_match_section_size_mismatch() {
+    let section_data = vec![0x01, 0x60, 0x00, 0x00, 0xff];
+    let err = Section::match_section(1, &section_data).unwrap_err();
+    assert_eq!(err.input, &[0xff]);
+  }
+}
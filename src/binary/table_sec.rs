@@ -1,5 +1,10 @@
-use nom::{bytes::complete::take, IResult};
+use nom::number::complete::le_u8;
 use nom_leb128::leb128_u32;
+use serde::{Deserialize, Serialize};
+
+use super::encode::write_u32;
+use super::error::{fail, with_item, IResult};
+use super::value_type::ValueType;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct TableSec {
@@ -8,47 +13,71 @@ pub struct TableSec {
   pub reftype: RefType,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RefType {
   FuncRef,
   ExternRef,
+  ExnRef,
 }
 
 impl TableSec {
   pub fn parse(input: &[u8]) -> IResult<&[u8], Vec<TableSec>> {
     let mut tables = Vec::new();
     let (mut input, count) = leb128_u32(input)?;
-    for _ in 0..count {
-      let (rest, reftype) = RefType::parse(input)?;
-      let (rest, flag) = take(1usize)(rest)?;
-      match flag[0] {
-        0x70 => {
-          let (rest, min) = leb128_u32(rest)?;
-          tables.push(TableSec{min, max: None, reftype});
-          input = rest;
-        }
-        0x01 => {
-          let (rest, min) = leb128_u32(rest)?;
-          let (rest, max) = leb128_u32(rest)?;
-          tables.push(TableSec{min, max: Some(max), reftype});
-          input = rest;
-        }
-        _ => panic!("unknown limits flags")
-      }
-      
+    for i in 0..count {
+      let table: TableSec;
+      (input, table) = with_item(i, TableSec::parse_single(input))?;
+      tables.push(table);
     }
 
     Ok((input, tables))
   }
+
+  pub(crate) fn parse_single(input: &[u8]) -> IResult<&[u8], TableSec> {
+    let (input, reftype) = RefType::parse(input)?;
+    let (rest, flag) = le_u8(input)?;
+    match flag {
+      0x00 => {
+        let (rest, min) = leb128_u32(rest)?;
+        Ok((rest, TableSec{min, max: None, reftype}))
+      }
+      0x01 => {
+        let (rest, min) = leb128_u32(rest)?;
+        let (rest, max) = leb128_u32(rest)?;
+        Ok((rest, TableSec{min, max: Some(max), reftype}))
+      }
+      _ => fail(input, format!("limits flags 0x00 or 0x01 (found {:#04x})", flag)),
+    }
+  }
+
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    self.reftype.encode(buf);
+    match self.max {
+      Some(max) => {
+        buf.push(0x01);
+        write_u32(buf, self.min);
+        write_u32(buf, max);
+      },
+      None => {
+        buf.push(0x00);
+        write_u32(buf, self.min);
+      },
+    }
+  }
 }
 
 impl RefType {
   pub fn parse(input: &[u8]) -> IResult<&[u8], RefType> {
-    let (input, reftype) = take(1usize)(input)?;
-    match reftype[0] {
-      0x70 => Ok((input, RefType::FuncRef)),
-      0x6F => Ok((input, RefType::ExternRef)),
-      _ => panic!("unknown reference type"),
+    let (rest, reftype) = le_u8(input)?;
+    match reftype {
+      0x70 => Ok((rest, RefType::FuncRef)),
+      0x6F => Ok((rest, RefType::ExternRef)),
+      0x69 => Ok((rest, RefType::ExnRef)),
+      _ => fail(input, format!("a reference type (found {:#04x})", reftype)),
     }
   }
-}
\ No newline at end of file
+
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    ValueType::from(self).encode(buf);
+  }
+}
@@ -0,0 +1,40 @@
+use nom::number::complete::le_u8;
+use nom_leb128::leb128_u32;
+
+use super::encode::write_u32;
+use super::error::{fail, with_item, IResult};
+
+// 例外のタグ。型は引数がペイロードで、返り値は空でなければならない
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+  pub type_idx: u32,
+}
+
+impl Tag {
+  pub fn parse(input: &[u8]) -> IResult<&[u8], Vec<Tag>> {
+    let (mut input, tag_count) = leb128_u32(input)?;
+    let mut tags = Vec::new();
+
+    for i in 0..tag_count {
+      let tag: Tag;
+      (input, tag) = with_item(i, Tag::parse_single(input))?;
+      tags.push(tag);
+    }
+    Ok((input, tags))
+  }
+
+  pub(crate) fn parse_single(input: &[u8]) -> IResult<&[u8], Tag> {
+    // 属性は0x00 (exception) しか定義されていない
+    let (rest, attribute) = le_u8(input)?;
+    if attribute != 0x00 {
+      return fail(input, format!("tag attribute 0x00 (found {:#04x})", attribute));
+    }
+    let (rest, type_idx) = leb128_u32(rest)?;
+    Ok((rest, Tag { type_idx }))
+  }
+
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    buf.push(0x00);
+    write_u32(buf, self.type_idx);
+  }
+}
@@ -1,12 +1,12 @@
-use nom::{
-  bytes::complete::{tag, take, take_till},
-  IResult
-};
+use nom::number::complete::le_u8;
 use nom_leb128::leb128_u32;
+use serde::{Deserialize, Serialize};
 
+use super::encode::write_u32;
+use super::error::{fail, with_item, IResult};
 use super::value_type::ValueType;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct FuncType {
   pub param_types: Vec<ValueType>,
   pub return_types: Vec<ValueType>,
@@ -16,24 +16,33 @@ impl FuncType {
   pub fn parse(input: &[u8]) -> IResult<&[u8], Vec<FuncType>> {
     let (mut input,func_type_count) = leb128_u32(input)?;
     let mut func_types: Vec<FuncType> = Vec::new();
-    for _ in 0..func_type_count {
-      let param_count: u32;
-      let params: &[u8];
-      let return_count: u32;
-      let returns: &[u8];
+    for i in 0..func_type_count {
+      let func_type: FuncType;
+      (input, func_type) = with_item(i, FuncType::parse_single(input))?;
+      func_types.push(func_type);
+    }
+    Ok((input, func_types))
+  }
 
-      (input, _) = take_till(|c| c == 0x60)(input)?;
-      (input, _) = tag([0x60])(input)?;
-      (input, param_count) = leb128_u32(input)?;
-      (input, params) = take(param_count)(input)?;
-      (input, return_count) = leb128_u32(input)?;
-      (input, returns) = take(return_count)(input)?;
+  fn parse_single(input: &[u8]) -> IResult<&[u8], FuncType> {
+    let (rest, form) = le_u8(input)?;
+    if form != 0x60 {
+      return fail(input, format!("function type 0x60 (found {:#04x})", form));
+    }
+    let (input, param_count) = leb128_u32(rest)?;
+    let (input, param_types) = ValueType::parse_vec(input, param_count)?;
+    let (input, return_count) = leb128_u32(input)?;
+    let (input, return_types) = ValueType::parse_vec(input, return_count)?;
+    Ok((input, FuncType { param_types, return_types }))
+  }
 
-      let param_types = params.iter().map(|&x| ValueType::parse(x)).collect();
-      let return_types = returns.iter().map(|&x| ValueType::parse(x)).collect();
-      
-      func_types.push(FuncType { param_types, return_types });
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    buf.push(0x60);
+    for types in [&self.param_types, &self.return_types] {
+      write_u32(buf, types.len() as u32);
+      for value_type in types {
+        value_type.encode(buf);
+      }
     }
-    Ok((input, func_types))
   }
-}
\ No newline at end of file
+}
@@ -0,0 +1,1203 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use super::data_sec::DataMode;
+use super::elem_sec::{ElemInit, ElemMode};
+use super::export_sec::ExportDesc;
+use super::import_sec::ImportDesc;
+use super::instructions::{Block, BlockType, Catch, Instructions};
+use super::table_sec::RefType;
+use super::type_sec::FuncType;
+use super::value_type::ValueType;
+use super::wasm::Wasm;
+
+const MAX_PAGES: u64 = 65536;
+const MAX_PAGES64: u64 = 1 << 48;
+// 引数を含めた1関数あたりのローカル変数の上限。他のエンジンと同じ値にしている
+const MAX_LOCALS: u32 = 50000;
+
+// validateのエラー。関数本体のエラーではfunc_idxとinstr_offsetが問題の命令を指す
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+  pub func_idx: Option<u32>,
+  pub instr_offset: Option<usize>,
+  pub message: String,
+}
+
+impl ValidationError {
+  fn new(message: impl Into<String>) -> ValidationError {
+    ValidationError { func_idx: None, instr_offset: None, message: message.into() }
+  }
+}
+
+impl fmt::Display for ValidationError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "invalid module")?;
+    if let Some(func_idx) = self.func_idx {
+      write!(f, " in func {}", func_idx)?;
+    }
+    if let Some(offset) = self.instr_offset {
+      write!(f, " at instruction {}", offset)?;
+    }
+    write!(f, ": {}", self.message)
+  }
+}
+
+impl std::error::Error for ValidationError {}
+
+type Result<T> = std::result::Result<T, ValidationError>;
+
+// 検査中のオペランドの型。Unknownはunreachableの後に現れる何にでもなれる型
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+  I32,
+  I64,
+  F32,
+  F64,
+  V128,
+  FuncRef,
+  ExternRef,
+  ExnRef,
+  Unknown,
+}
+
+impl From<&ValueType> for Operand {
+  fn from(value_type: &ValueType) -> Operand {
+    match value_type {
+      ValueType::I32 => Operand::I32,
+      ValueType::I64 => Operand::I64,
+      ValueType::F32 => Operand::F32,
+      ValueType::F64 => Operand::F64,
+      ValueType::V128 => Operand::V128,
+      ValueType::FuncRef => Operand::FuncRef,
+      ValueType::ExternRef => Operand::ExternRef,
+      ValueType::ExnRef => Operand::ExnRef,
+    }
+  }
+}
+
+impl From<&RefType> for Operand {
+  fn from(reftype: &RefType) -> Operand {
+    match reftype {
+      RefType::FuncRef => Operand::FuncRef,
+      RefType::ExternRef => Operand::ExternRef,
+      RefType::ExnRef => Operand::ExnRef,
+    }
+  }
+}
+
+impl Operand {
+  // 型注釈なしのselectに使える数値型とベクタ型
+  fn is_num(&self) -> bool {
+    matches!(self, Operand::I32 | Operand::I64 | Operand::F32 | Operand::F64 | Operand::V128 | Operand::Unknown)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FrameKind {
+  Func,
+  Block,
+  Loop,
+  If,
+  Else,
+}
+
+#[derive(Debug)]
+struct CtrlFrame {
+  kind: FrameKind,
+  start_types: Vec<Operand>,
+  end_types: Vec<Operand>,
+  height: usize,
+  unreachable: bool,
+}
+
+impl CtrlFrame {
+  // brの飛び先が受け取る値。loopは先頭に戻るので引数側になる
+  fn label_types(&self) -> &[Operand] {
+    if self.kind == FrameKind::Loop { &self.start_types } else { &self.end_types }
+  }
+}
+
+#[derive(Debug, Clone)]
+struct GlobalType {
+  valtype: Operand,
+  mutable: bool,
+  imported: bool,
+}
+
+// 各インデックス空間。importされたものが先頭に並ぶ
+struct Context<'a> {
+  types: &'a [FuncType],
+  funcs: Vec<u32>,
+  tables: Vec<RefType>,
+  // 各メモリのアドレスの型
+  memories: Vec<Operand>,
+  globals: Vec<GlobalType>,
+  // memory.initとdata.dropはデータカウントセクションがないと使えない
+  data_count: Option<u32>,
+  elems: Vec<RefType>,
+  refs: HashSet<u32>,
+  // タグの型インデックス
+  tags: Vec<u32>,
+}
+
+// 仕様の検証アルゴリズムに沿って検査し、最初に見つかったエラーを返す
+pub fn validate(wasm: &Wasm) -> Result<()> {
+  let ctx = Context::new(wasm)?;
+
+  // importしたテーブルとメモリの型も定義したものと同じ制約を満たす
+  let imports = wasm.import_section.iter().flatten();
+  let imported_tables = imports.clone().filter_map(|import| match &import.desc {
+    ImportDesc::Table(table) => Some(table),
+    _ => None,
+  });
+  for table in imported_tables.chain(wasm.table_section.iter().flatten()) {
+    check_limits(table.min.into(), table.max.map(u64::from), u32::MAX.into())?;
+  }
+  let imported_memories = imports.filter_map(|import| match &import.desc {
+    ImportDesc::Memory(memory) => Some(memory),
+    _ => None,
+  });
+  for memory in imported_memories.chain(wasm.memory_section.iter().flatten()) {
+    check_limits(memory.min, memory.max, if memory.memory64 { MAX_PAGES64 } else { MAX_PAGES })?;
+    if memory.shared && memory.max.is_none() {
+      return Err(ValidationError::new("shared memory must have maximum"));
+    }
+  }
+
+  // globalの初期化式から参照できるのはimportしたglobalだけ
+  if let Some(globals) = &wasm.global_section {
+    for global in globals {
+      ctx.check_const_expr(&global.init, Operand::from(&global.valtype))?;
+    }
+  }
+
+  if let Some(elems) = &wasm.element_section {
+    for elem in elems {
+      if let ElemMode::Active { table_idx, offset } = &elem.mode {
+        match ctx.tables.get(*table_idx as usize) {
+          Some(reftype) if *reftype == elem.reftype => {},
+          Some(_) => return Err(ValidationError::new("type mismatch")),
+          None => return Err(ValidationError::new(format!("unknown table {}", table_idx))),
+        }
+        ctx.check_const_expr(offset, Operand::I32)?;
+      }
+      match &elem.init {
+        ElemInit::FuncIndices(funcs) => {
+          for func_idx in funcs {
+            ctx.func_type(*func_idx)?;
+          }
+        },
+        ElemInit::Exprs(exprs) => {
+          for expr in exprs {
+            ctx.check_const_expr(expr, Operand::from(&elem.reftype))?;
+          }
+        },
+      }
+    }
+  }
+
+  if let Some(data) = &wasm.data_section {
+    for segment in data {
+      if let DataMode::Active { memory_index, offset } = &segment.mode {
+        let Some(addr) = ctx.memories.get(*memory_index as usize) else {
+          return Err(ValidationError::new(format!("unknown memory {}", memory_index)));
+        };
+        ctx.check_const_expr(offset, *addr)?;
+      }
+    }
+  }
+
+  if let Some(func_idx) = wasm.start_section {
+    let func_type = ctx.func_type(func_idx)?;
+    if !func_type.param_types.is_empty() || !func_type.return_types.is_empty() {
+      return Err(ValidationError::new("start function must have type [] -> []"));
+    }
+  }
+
+  if let Some(exports) = &wasm.export_section {
+    let mut names = HashSet::new();
+    for export in exports {
+      if !names.insert(export.name.as_str()) {
+        return Err(ValidationError::new(format!("duplicate export name \"{}\"", export.name)));
+      }
+      let idx = export.idx as usize;
+      let (kind, count) = match export.desc {
+        ExportDesc::Func => ("function", ctx.funcs.len()),
+        ExportDesc::Table => ("table", ctx.tables.len()),
+        ExportDesc::Mem => ("memory", ctx.memories.len()),
+        ExportDesc::Global => ("global", ctx.globals.len()),
+        ExportDesc::Tag => ("tag", ctx.tags.len()),
+      };
+      if idx >= count {
+        return Err(ValidationError::new(format!("unknown {} {}", kind, idx)));
+      }
+    }
+  }
+
+  let imported_funcs = ctx.funcs.len() - wasm.function_section.as_ref().map_or(0, |funcs| funcs.len());
+  if let Some(codes) = &wasm.code_section {
+    for (i, code) in codes.iter().enumerate() {
+      let func_idx = (imported_funcs + i) as u32;
+      let func_type = ctx.func_type(func_idx)?;
+      // 宣言の数を展開する前に合計を確かめ、巨大なcountで確保に失敗しないようにする
+      let local_count = code.locals.iter()
+        .try_fold(func_type.param_types.len() as u32, |sum, local| sum.checked_add(local.count))
+        .filter(|&count| count <= MAX_LOCALS);
+      if local_count.is_none() {
+        return Err(ValidationError { func_idx: Some(func_idx), ..ValidationError::new("too many locals") });
+      }
+      let mut locals: Vec<Operand> = func_type.param_types.iter().map(Operand::from).collect();
+      for local in &code.locals {
+        locals.extend(local.to_value_type_vec().iter().map(Operand::from));
+      }
+      let mut validator = FuncValidator::new(&ctx, locals, func_type);
+      validator.validate(&code.instrs).map_err(|e| ValidationError { func_idx: Some(func_idx), ..e })?;
+    }
+  }
+
+  Ok(())
+}
+
+fn check_limits(min: u64, max: Option<u64>, bound: u64) -> Result<()> {
+  if min > bound || max.is_some_and(|max| max > bound) {
+    return Err(ValidationError::new(format!("size must be at most {}", bound)));
+  }
+  if max.is_some_and(|max| min > max) {
+    return Err(ValidationError::new("size minimum must not be greater than maximum"));
+  }
+  Ok(())
+}
+
+impl<'a> Context<'a> {
+  fn new(wasm: &'a Wasm) -> Result<Context<'a>> {
+    let types = wasm.type_section.as_deref().unwrap_or(&[]);
+    let mut ctx = Context {
+      types,
+      funcs: Vec::new(),
+      tables: Vec::new(),
+      memories: Vec::new(),
+      globals: Vec::new(),
+      data_count: wasm.data_count_section,
+      elems: wasm.element_section.as_ref().map_or(Vec::new(), |elems| elems.iter().map(|elem| elem.reftype.clone()).collect()),
+      refs: HashSet::new(),
+      tags: Vec::new(),
+    };
+
+    if let Some(imports) = &wasm.import_section {
+      for import in imports {
+        match &import.desc {
+          ImportDesc::Func(type_idx) => ctx.funcs.push(*type_idx),
+          ImportDesc::Table(table) => ctx.tables.push(table.reftype.clone()),
+          ImportDesc::Memory(memory) => ctx.memories.push(if memory.memory64 { Operand::I64 } else { Operand::I32 }),
+          ImportDesc::Global { valtype, mutability } => ctx.globals.push(GlobalType {
+            valtype: Operand::from(valtype),
+            mutable: *mutability,
+            imported: true,
+          }),
+          ImportDesc::Tag(type_idx) => ctx.tags.push(*type_idx),
+        }
+      }
+    }
+    if let Some(funcs) = &wasm.function_section {
+      ctx.funcs.extend(funcs.iter().map(|func| func.type_idx));
+    }
+    for type_idx in &ctx.funcs {
+      if *type_idx as usize >= types.len() {
+        return Err(ValidationError::new(format!("unknown type {}", type_idx)));
+      }
+    }
+    if let Some(tags) = &wasm.tag_section {
+      ctx.tags.extend(tags.iter().map(|tag| tag.type_idx));
+    }
+    for type_idx in &ctx.tags {
+      match types.get(*type_idx as usize) {
+        Some(func_type) if !func_type.return_types.is_empty() => {
+          return Err(ValidationError::new("non-empty tag result type"));
+        },
+        Some(_) => {},
+        None => return Err(ValidationError::new(format!("unknown type {}", type_idx))),
+      }
+    }
+    if let Some(tables) = &wasm.table_section {
+      ctx.tables.extend(tables.iter().map(|table| table.reftype.clone()));
+    }
+    if let Some(memories) = &wasm.memory_section {
+      ctx.memories.extend(memories.iter().map(|memory| if memory.memory64 { Operand::I64 } else { Operand::I32 }));
+    }
+    if let Some(globals) = &wasm.global_section {
+      ctx.globals.extend(globals.iter().map(|global| GlobalType {
+        valtype: Operand::from(&global.valtype),
+        mutable: global.mutability,
+        imported: false,
+      }));
+    }
+
+    // 関数本体のref.funcで使えるのは本体以外で参照されている関数だけ
+    if let Some(elems) = &wasm.element_section {
+      for elem in elems {
+        match &elem.init {
+          ElemInit::FuncIndices(funcs) => ctx.refs.extend(funcs),
+          ElemInit::Exprs(exprs) => ctx.refs.extend(exprs.iter().flat_map(|expr| ref_funcs(expr))),
+        }
+      }
+    }
+    if let Some(globals) = &wasm.global_section {
+      ctx.refs.extend(globals.iter().flat_map(|global| ref_funcs(&global.init)));
+    }
+    if let Some(exports) = &wasm.export_section {
+      ctx.refs.extend(exports.iter().filter(|e| e.desc == ExportDesc::Func).map(|e| e.idx));
+    }
+
+    Ok(ctx)
+  }
+
+  fn func_type(&self, func_idx: u32) -> Result<&'a FuncType> {
+    match self.funcs.get(func_idx as usize) {
+      Some(type_idx) => Ok(&self.types[*type_idx as usize]),
+      None => Err(ValidationError::new(format!("unknown function {}", func_idx))),
+    }
+  }
+
+  // call_indirectのtableはfuncrefでなければならない
+  fn indirect_type(&self, type_idx: u32, table_idx: u32) -> Result<&'a FuncType> {
+    match self.tables.get(table_idx as usize) {
+      Some(RefType::FuncRef) => {},
+      Some(_) => return Err(ValidationError::new("type mismatch")),
+      None => return Err(ValidationError::new(format!("unknown table {}", table_idx))),
+    }
+    match self.types.get(type_idx as usize) {
+      Some(func_type) => Ok(func_type),
+      None => Err(ValidationError::new(format!("unknown type {}", type_idx))),
+    }
+  }
+
+  // タグの引数が例外のペイロードになる
+  fn tag_type(&self, tag_idx: u32) -> Result<Vec<Operand>> {
+    match self.tags.get(tag_idx as usize) {
+      Some(type_idx) => Ok(self.types[*type_idx as usize].param_types.iter().map(Operand::from).collect()),
+      None => Err(ValidationError::new(format!("unknown tag {}", tag_idx))),
+    }
+  }
+
+  fn table_type(&self, table_idx: u32) -> Result<Operand> {
+    match self.tables.get(table_idx as usize) {
+      Some(reftype) => Ok(Operand::from(reftype)),
+      None => Err(ValidationError::new(format!("unknown table {}", table_idx))),
+    }
+  }
+
+  fn elem_type(&self, elem_idx: u32) -> Result<Operand> {
+    match self.elems.get(elem_idx as usize) {
+      Some(reftype) => Ok(Operand::from(reftype)),
+      None => Err(ValidationError::new(format!("unknown elem segment {}", elem_idx))),
+    }
+  }
+
+  fn block_type(&self, block: &Block) -> Result<(Vec<Operand>, Vec<Operand>)> {
+    match &block.block_type {
+      BlockType::Void => Ok((vec![], vec![])),
+      BlockType::Value(value_type) => Ok((vec![], vec![Operand::from(value_type)])),
+      BlockType::TypeIndex(type_idx) => match self.types.get(*type_idx as usize) {
+        Some(func_type) => Ok((
+          func_type.param_types.iter().map(Operand::from).collect(),
+          func_type.return_types.iter().map(Operand::from).collect(),
+        )),
+        None => Err(ValidationError::new(format!("unknown type {}", type_idx))),
+      },
+    }
+  }
+
+  fn check_const_expr(&self, expr: &[Instructions], expected: Operand) -> Result<()> {
+    let mut stack = Vec::new();
+    for instr in expr {
+      let operand = match instr {
+        Instructions::I32Const(_) => Operand::I32,
+        Instructions::I64Const(_) => Operand::I64,
+        Instructions::F32Const(_) => Operand::F32,
+        Instructions::F64Const(_) => Operand::F64,
+        Instructions::V128Const(_) => Operand::V128,
+        Instructions::RefNull(reftype) => Operand::from(reftype),
+        Instructions::RefFunc(func_idx) => {
+          self.func_type(*func_idx)?;
+          Operand::FuncRef
+        },
+        Instructions::GlobalGet(global_idx) => match self.globals.get(*global_idx as usize) {
+          Some(global) if global.imported && !global.mutable => global.valtype,
+          Some(_) => return Err(ValidationError::new("constant expression required")),
+          None => return Err(ValidationError::new(format!("unknown global {}", global_idx))),
+        },
+        // extended-constで使える算術命令
+        Instructions::I32Add | Instructions::I32Sub | Instructions::I32Mul => const_binop(&mut stack, Operand::I32)?,
+        Instructions::I64Add | Instructions::I64Sub | Instructions::I64Mul => const_binop(&mut stack, Operand::I64)?,
+        Instructions::End => break,
+        _ => return Err(ValidationError::new("constant expression required")),
+      };
+      stack.push(operand);
+    }
+    match stack.as_slice() {
+      [operand] if *operand == expected || *operand == Operand::Unknown => Ok(()),
+      _ => Err(ValidationError::new("type mismatch")),
+    }
+  }
+}
+
+fn const_binop(stack: &mut Vec<Operand>, operand: Operand) -> Result<Operand> {
+  match (stack.pop(), stack.pop()) {
+    (Some(rhs), Some(lhs)) if rhs == operand && lhs == operand => Ok(operand),
+    _ => Err(ValidationError::new("type mismatch")),
+  }
+}
+
+fn ref_funcs(expr: &[Instructions]) -> impl Iterator<Item = u32> + '_ {
+  expr.iter().filter_map(|instr| match instr {
+    Instructions::RefFunc(func_idx) => Some(*func_idx),
+    _ => None,
+  })
+}
+
+fn check_ref_match(expected: Operand, actual: Operand) -> Result<()> {
+  if expected != actual && expected != Operand::Unknown && actual != Operand::Unknown {
+    return Err(ValidationError::new("type mismatch"));
+  }
+  Ok(())
+}
+
+// 仕様書のAppendixにある検査アルゴリズムをそのまま実装している
+struct FuncValidator<'a> {
+  ctx: &'a Context<'a>,
+  locals: Vec<Operand>,
+  return_types: Vec<Operand>,
+  vals: Vec<Operand>,
+  ctrls: Vec<CtrlFrame>,
+}
+
+impl<'a> FuncValidator<'a> {
+  fn new(ctx: &'a Context<'a>, locals: Vec<Operand>, func_type: &FuncType) -> FuncValidator<'a> {
+    let return_types: Vec<Operand> = func_type.return_types.iter().map(Operand::from).collect();
+    let mut validator = FuncValidator { ctx, locals, return_types: return_types.clone(), vals: Vec::new(), ctrls: Vec::new() };
+    validator.push_ctrl(FrameKind::Func, vec![], return_types);
+    validator
+  }
+
+  fn validate(&mut self, instrs: &[Instructions]) -> Result<()> {
+    for (pc, instr) in instrs.iter().enumerate() {
+      self.validate_instr(instr).map_err(|e| ValidationError { instr_offset: Some(pc), ..e })?;
+    }
+    // Code::parseで取り除かれた関数末尾のendの分
+    let end = self.pop_ctrl().map_err(|e| ValidationError { instr_offset: Some(instrs.len()), ..e })?;
+    if end.kind != FrameKind::Func {
+      return Err(ValidationError { instr_offset: Some(instrs.len()), ..ValidationError::new("unclosed block") });
+    }
+    Ok(())
+  }
+
+  fn push_val(&mut self, operand: Operand) {
+    self.vals.push(operand);
+  }
+
+  fn pop_val(&mut self) -> Result<Operand> {
+    let frame = self.ctrls.last().unwrap();
+    if self.vals.len() == frame.height {
+      if frame.unreachable {
+        return Ok(Operand::Unknown);
+      }
+      return Err(ValidationError::new("type mismatch"));
+    }
+    Ok(self.vals.pop().unwrap())
+  }
+
+  fn pop_expect(&mut self, expected: Operand) -> Result<Operand> {
+    let actual = self.pop_val()?;
+    if actual != expected && actual != Operand::Unknown && expected != Operand::Unknown {
+      return Err(ValidationError::new("type mismatch"));
+    }
+    Ok(if actual == Operand::Unknown { expected } else { actual })
+  }
+
+  fn push_vals(&mut self, operands: &[Operand]) {
+    self.vals.extend_from_slice(operands);
+  }
+
+  fn pop_vals(&mut self, operands: &[Operand]) -> Result<Vec<Operand>> {
+    let mut popped = Vec::new();
+    for operand in operands.iter().rev() {
+      popped.push(self.pop_expect(*operand)?);
+    }
+    popped.reverse();
+    Ok(popped)
+  }
+
+  fn push_ctrl(&mut self, kind: FrameKind, start_types: Vec<Operand>, end_types: Vec<Operand>) {
+    let height = self.vals.len();
+    self.push_vals(&start_types);
+    self.ctrls.push(CtrlFrame { kind, start_types, end_types, height, unreachable: false });
+  }
+
+  fn pop_ctrl(&mut self) -> Result<CtrlFrame> {
+    let Some(frame) = self.ctrls.last() else {
+      return Err(ValidationError::new("unexpected end"));
+    };
+    let end_types = frame.end_types.clone();
+    let height = frame.height;
+    self.pop_vals(&end_types)?;
+    if self.vals.len() != height {
+      return Err(ValidationError::new("type mismatch"));
+    }
+    Ok(self.ctrls.pop().unwrap())
+  }
+
+  fn set_unreachable(&mut self) {
+    let frame = self.ctrls.last_mut().unwrap();
+    self.vals.truncate(frame.height);
+    frame.unreachable = true;
+  }
+
+  fn label_types(&self, label_idx: u32) -> Result<Vec<Operand>> {
+    let depth = label_idx as usize;
+    if depth >= self.ctrls.len() {
+      return Err(ValidationError::new(format!("unknown label {}", label_idx)));
+    }
+    Ok(self.ctrls[self.ctrls.len() - 1 - depth].label_types().to_vec())
+  }
+
+  // catch節が飛び先のラベルに渡す値はペイロードと、_refならexnref
+  fn check_catch(&self, catch: &Catch) -> Result<()> {
+    let mut vals = match catch {
+      Catch::Catch { tag, .. } | Catch::CatchRef { tag, .. } => self.ctx.tag_type(*tag)?,
+      Catch::CatchAll { .. } | Catch::CatchAllRef { .. } => vec![],
+    };
+    if matches!(catch, Catch::CatchRef { .. } | Catch::CatchAllRef { .. }) {
+      vals.push(Operand::ExnRef);
+    }
+    if self.label_types(catch.label())? != vals {
+      return Err(ValidationError::new("type mismatch"));
+    }
+    Ok(())
+  }
+
+  fn local(&self, local_idx: u32) -> Result<Operand> {
+    match self.locals.get(local_idx as usize) {
+      Some(operand) => Ok(*operand),
+      None => Err(ValidationError::new(format!("unknown local {}", local_idx))),
+    }
+  }
+
+  fn global(&self, global_idx: u32) -> Result<GlobalType> {
+    match self.ctx.globals.get(global_idx as usize) {
+      Some(global) => Ok(global.clone()),
+      None => Err(ValidationError::new(format!("unknown global {}", global_idx))),
+    }
+  }
+
+  // メモリのアドレスの型を返す
+  fn check_memory(&self, memory_idx: u32) -> Result<Operand> {
+    match self.ctx.memories.get(memory_idx as usize) {
+      Some(addr) => Ok(*addr),
+      None => Err(ValidationError::new(format!("unknown memory {}", memory_idx))),
+    }
+  }
+
+  // alignは2の冪の指数で与えられる。32bitのメモリではoffsetも32bitに収まらなければならない
+  fn check_memarg(&self, memory_idx: u32, align: u32, offset: u64, natural: u32) -> Result<Operand> {
+    let addr = self.check_memory(memory_idx)?;
+    if align > natural {
+      return Err(ValidationError::new("alignment must not be larger than natural"));
+    }
+    if addr == Operand::I32 && offset > u32::MAX as u64 {
+      return Err(ValidationError::new("offset out of range"));
+    }
+    Ok(addr)
+  }
+
+  fn check_data(&self, data_idx: u32) -> Result<()> {
+    let Some(data_count) = self.ctx.data_count else {
+      return Err(ValidationError::new("data count section required"));
+    };
+    if data_idx >= data_count {
+      return Err(ValidationError::new(format!("unknown data segment {}", data_idx)));
+    }
+    Ok(())
+  }
+
+  fn load(&mut self, memory_idx: u32, align: u32, offset: u64, natural: u32, result: Operand) -> Result<()> {
+    let addr = self.check_memarg(memory_idx, align, offset, natural)?;
+    self.pop_expect(addr)?;
+    self.push_val(result);
+    Ok(())
+  }
+
+  fn store(&mut self, memory_idx: u32, align: u32, offset: u64, natural: u32, operand: Operand) -> Result<()> {
+    let addr = self.check_memarg(memory_idx, align, offset, natural)?;
+    self.pop_expect(operand)?;
+    self.pop_expect(addr)?;
+    Ok(())
+  }
+
+  fn load_lane(&mut self, memory_idx: u32, align: u32, offset: u64, natural: u32, lane: u8, lanes: u8) -> Result<()> {
+    let addr = self.check_memarg(memory_idx, align, offset, natural)?;
+    if lane >= lanes {
+      return Err(ValidationError::new("invalid lane index"));
+    }
+    self.pop_vals(&[addr, Operand::V128])?;
+    self.push_val(Operand::V128);
+    Ok(())
+  }
+
+  fn store_lane(&mut self, memory_idx: u32, align: u32, offset: u64, natural: u32, lane: u8, lanes: u8) -> Result<()> {
+    let addr = self.check_memarg(memory_idx, align, offset, natural)?;
+    if lane >= lanes {
+      return Err(ValidationError::new("invalid lane index"));
+    }
+    self.pop_vals(&[addr, Operand::V128])?;
+    Ok(())
+  }
+
+  // アトミック命令はアラインメントが自然なアラインメントと一致していなければならない
+  fn atomic(&mut self, instr: &Instructions) -> Result<()> {
+    let (Some(opcode), Some((align, memory_idx, offset)), Some((value_type, size))) =
+      (instr.atomic_opcode(), instr.atomic_memarg(), instr.atomic_access()) else {
+      return Err(ValidationError::new(format!("unsupported instruction {:?}", instr)));
+    };
+    let natural = size.trailing_zeros();
+    let addr = self.check_memarg(memory_idx, align, offset, natural)?;
+    if align != natural {
+      return Err(ValidationError::new("atomic alignment must be natural"));
+    }
+    let operand = Operand::from(&value_type);
+    match opcode {
+      // notify
+      0x00 => {
+        self.pop_vals(&[addr, Operand::I32])?;
+        self.push_val(Operand::I32);
+      },
+      // wait32, wait64
+      0x01 | 0x02 => {
+        self.pop_vals(&[addr, operand, Operand::I64])?;
+        self.push_val(Operand::I32);
+      },
+      0x10..=0x16 => {
+        self.pop_expect(addr)?;
+        self.push_val(operand);
+      },
+      0x17..=0x1d => {
+        self.pop_vals(&[addr, operand])?;
+      },
+      // rmw.cmpxchgは期待する値と置き換える値を取る
+      0x48..=0x4e => {
+        self.pop_vals(&[addr, operand, operand])?;
+        self.push_val(operand);
+      },
+      _ => {
+        self.pop_vals(&[addr, operand])?;
+        self.push_val(operand);
+      },
+    }
+    Ok(())
+  }
+
+  fn extract_lane(&mut self, lane: u8, lanes: u8, result: Operand) -> Result<()> {
+    if lane >= lanes {
+      return Err(ValidationError::new("invalid lane index"));
+    }
+    self.pop_expect(Operand::V128)?;
+    self.push_val(result);
+    Ok(())
+  }
+
+  fn replace_lane(&mut self, lane: u8, lanes: u8, operand: Operand) -> Result<()> {
+    if lane >= lanes {
+      return Err(ValidationError::new("invalid lane index"));
+    }
+    self.pop_vals(&[Operand::V128, operand])?;
+    self.push_val(Operand::V128);
+    Ok(())
+  }
+
+  // ベクタのシフト量はi32で与えられる
+  fn shiftop(&mut self) -> Result<()> {
+    self.pop_vals(&[Operand::V128, Operand::I32])?;
+    self.push_val(Operand::V128);
+    Ok(())
+  }
+
+  fn unop(&mut self, operand: Operand) -> Result<()> {
+    self.pop_expect(operand)?;
+    self.push_val(operand);
+    Ok(())
+  }
+
+  fn binop(&mut self, operand: Operand) -> Result<()> {
+    self.pop_expect(operand)?;
+    self.pop_expect(operand)?;
+    self.push_val(operand);
+    Ok(())
+  }
+
+  fn testop(&mut self, operand: Operand) -> Result<()> {
+    self.pop_expect(operand)?;
+    self.push_val(Operand::I32);
+    Ok(())
+  }
+
+  fn relop(&mut self, operand: Operand) -> Result<()> {
+    self.pop_expect(operand)?;
+    self.pop_expect(operand)?;
+    self.push_val(Operand::I32);
+    Ok(())
+  }
+
+  fn cvtop(&mut self, from: Operand, to: Operand) -> Result<()> {
+    self.pop_expect(from)?;
+    self.push_val(to);
+    Ok(())
+  }
+
+  fn call(&mut self, func_type: &FuncType) -> Result<()> {
+    let params: Vec<Operand> = func_type.param_types.iter().map(Operand::from).collect();
+    let results: Vec<Operand> = func_type.return_types.iter().map(Operand::from).collect();
+    self.pop_vals(&params)?;
+    self.push_vals(&results);
+    Ok(())
+  }
+
+  // 呼び出し先の返り値がそのまま自分の返り値になるので型が一致しなければならない
+  fn return_call(&mut self, func_type: &FuncType) -> Result<()> {
+    let results: Vec<Operand> = func_type.return_types.iter().map(Operand::from).collect();
+    if results != self.return_types {
+      return Err(ValidationError::new("type mismatch"));
+    }
+    let params: Vec<Operand> = func_type.param_types.iter().map(Operand::from).collect();
+    self.pop_vals(&params)?;
+    self.set_unreachable();
+    Ok(())
+  }
+
+  fn validate_instr(&mut self, instr: &Instructions) -> Result<()> {
+    use Operand::{F32, F64, I32, I64, V128};
+
+    match instr {
+      Instructions::Unreachable => self.set_unreachable(),
+      Instructions::Nop => {},
+      Instructions::Block(block) => {
+        let (start, end) = self.ctx.block_type(block)?;
+        self.pop_vals(&start)?;
+        self.push_ctrl(FrameKind::Block, start, end);
+      },
+      Instructions::Loop(block) => {
+        let (start, end) = self.ctx.block_type(block)?;
+        self.pop_vals(&start)?;
+        self.push_ctrl(FrameKind::Loop, start, end);
+      },
+      Instructions::If(block) => {
+        let (start, end) = self.ctx.block_type(block)?;
+        self.pop_expect(I32)?;
+        self.pop_vals(&start)?;
+        self.push_ctrl(FrameKind::If, start, end);
+      },
+      Instructions::Else => {
+        let frame = self.pop_ctrl()?;
+        if frame.kind != FrameKind::If {
+          return Err(ValidationError::new("else without matching if"));
+        }
+        self.push_ctrl(FrameKind::Else, frame.start_types, frame.end_types);
+      },
+      Instructions::End => {
+        let frame = self.pop_ctrl()?;
+        if frame.kind == FrameKind::Func {
+          return Err(ValidationError::new("unexpected end"));
+        }
+        // elseの無いifは条件が偽のとき引数をそのまま結果として返す
+        if frame.kind == FrameKind::If && frame.start_types != frame.end_types {
+          return Err(ValidationError::new("type mismatch"));
+        }
+        self.push_vals(&frame.end_types);
+      },
+      Instructions::Br(label_idx) => {
+        let types = self.label_types(*label_idx)?;
+        self.pop_vals(&types)?;
+        self.set_unreachable();
+      },
+      Instructions::BrIf(label_idx) => {
+        self.pop_expect(I32)?;
+        let types = self.label_types(*label_idx)?;
+        let vals = self.pop_vals(&types)?;
+        self.push_vals(&vals);
+      },
+      Instructions::BrTable(labels, default) => {
+        self.pop_expect(I32)?;
+        let default_types = self.label_types(*default)?;
+        for label_idx in labels {
+          let types = self.label_types(*label_idx)?;
+          if types.len() != default_types.len() {
+            return Err(ValidationError::new("type mismatch"));
+          }
+          let vals = self.pop_vals(&types)?;
+          self.push_vals(&vals);
+        }
+        self.pop_vals(&default_types)?;
+        self.set_unreachable();
+      },
+      Instructions::Return => {
+        let types = self.return_types.clone();
+        self.pop_vals(&types)?;
+        self.set_unreachable();
+      },
+      Instructions::Call(func_idx) => {
+        let func_type = self.ctx.func_type(*func_idx)?;
+        self.call(func_type)?;
+      },
+      Instructions::CallIndirect(type_idx, table_idx) => {
+        let func_type = self.ctx.indirect_type(*type_idx, *table_idx)?;
+        self.pop_expect(I32)?;
+        self.call(func_type)?;
+      },
+      Instructions::ReturnCall(func_idx) => {
+        let func_type = self.ctx.func_type(*func_idx)?;
+        self.return_call(func_type)?;
+      },
+      Instructions::ReturnCallIndirect(type_idx, table_idx) => {
+        let func_type = self.ctx.indirect_type(*type_idx, *table_idx)?;
+        self.pop_expect(I32)?;
+        self.return_call(func_type)?;
+      },
+      Instructions::Throw(tag_idx) => {
+        let payload = self.ctx.tag_type(*tag_idx)?;
+        self.pop_vals(&payload)?;
+        self.set_unreachable();
+      },
+      Instructions::ThrowRef => {
+        self.pop_expect(Operand::ExnRef)?;
+        self.set_unreachable();
+      },
+      Instructions::TryTable(block, catches) => {
+        let (start, end) = self.ctx.block_type(block)?;
+        // catch節のラベルはtry_table自身のラベルを積む前の文脈で引く
+        for catch in catches {
+          self.check_catch(catch)?;
+        }
+        self.pop_vals(&start)?;
+        self.push_ctrl(FrameKind::Block, start, end);
+      },
+      Instructions::Drop => {
+        self.pop_val()?;
+      },
+      Instructions::Select => {
+        self.pop_expect(I32)?;
+        let t1 = self.pop_val()?;
+        let t2 = self.pop_val()?;
+        if !t1.is_num() || !t2.is_num() {
+          return Err(ValidationError::new("type mismatch"));
+        }
+        if t1 != t2 && t1 != Operand::Unknown && t2 != Operand::Unknown {
+          return Err(ValidationError::new("type mismatch"));
+        }
+        self.push_val(if t1 == Operand::Unknown { t2 } else { t1 });
+      },
+      Instructions::SelectValtype(valtypes) => {
+        let [valtype] = valtypes.as_slice() else {
+          return Err(ValidationError::new("invalid result arity"));
+        };
+        let operand = Operand::from(valtype);
+        self.pop_expect(I32)?;
+        self.pop_expect(operand)?;
+        self.pop_expect(operand)?;
+        self.push_val(operand);
+      },
+      Instructions::I32Load { align, memory, offset } => self.load(*memory, *align, *offset, 2, I32)?,
+      Instructions::I64Load { align, memory, offset } => self.load(*memory, *align, *offset, 3, I64)?,
+      Instructions::F32Load { align, memory, offset } => self.load(*memory, *align, *offset, 2, F32)?,
+      Instructions::F64Load { align, memory, offset } => self.load(*memory, *align, *offset, 3, F64)?,
+      Instructions::I32Load8S { align, memory, offset } | Instructions::I32Load8U { align, memory, offset } => self.load(*memory, *align, *offset, 0, I32)?,
+      Instructions::I32Load16S { align, memory, offset } | Instructions::I32Load16U { align, memory, offset } => self.load(*memory, *align, *offset, 1, I32)?,
+      Instructions::I64Load8S { align, memory, offset } | Instructions::I64Load8U { align, memory, offset } => self.load(*memory, *align, *offset, 0, I64)?,
+      Instructions::I64Load16S { align, memory, offset } | Instructions::I64Load16U { align, memory, offset } => self.load(*memory, *align, *offset, 1, I64)?,
+      Instructions::I64Load32S { align, memory, offset } | Instructions::I64Load32U { align, memory, offset } => self.load(*memory, *align, *offset, 2, I64)?,
+      Instructions::I32Store { align, memory, offset } => self.store(*memory, *align, *offset, 2, I32)?,
+      Instructions::I64Store { align, memory, offset } => self.store(*memory, *align, *offset, 3, I64)?,
+      Instructions::F32Store { align, memory, offset } => self.store(*memory, *align, *offset, 2, F32)?,
+      Instructions::F64Store { align, memory, offset } => self.store(*memory, *align, *offset, 3, F64)?,
+      Instructions::I32Store8 { align, memory, offset } => self.store(*memory, *align, *offset, 0, I32)?,
+      Instructions::I32Store16 { align, memory, offset } => self.store(*memory, *align, *offset, 1, I32)?,
+      Instructions::I64Store8 { align, memory, offset } => self.store(*memory, *align, *offset, 0, I64)?,
+      Instructions::I64Store16 { align, memory, offset } => self.store(*memory, *align, *offset, 1, I64)?,
+      Instructions::I64Store32 { align, memory, offset } => self.store(*memory, *align, *offset, 2, I64)?,
+      Instructions::MemorySize(memory_idx) => {
+        let addr = self.check_memory(*memory_idx)?;
+        self.push_val(addr);
+      },
+      Instructions::MemoryGrow(memory_idx) => {
+        let addr = self.check_memory(*memory_idx)?;
+        self.unop(addr)?;
+      },
+      Instructions::MemoryInit(data_idx, memory_idx) => {
+        let addr = self.check_memory(*memory_idx)?;
+        self.check_data(*data_idx)?;
+        self.pop_vals(&[addr, I32, I32])?;
+      },
+      Instructions::DataDrop(data_idx) => self.check_data(*data_idx)?,
+      Instructions::MemoryCopy(dst_idx, src_idx) => {
+        let dst = self.check_memory(*dst_idx)?;
+        let src = self.check_memory(*src_idx)?;
+        // 長さは両方が64bitのメモリのときだけi64になる
+        let len = if dst == I32 || src == I32 { I32 } else if dst == I64 && src == I64 { I64 } else { Operand::Unknown };
+        self.pop_vals(&[dst, src, len])?;
+      },
+      Instructions::MemoryFill(memory_idx) => {
+        let addr = self.check_memory(*memory_idx)?;
+        self.pop_vals(&[addr, I32, addr])?;
+      },
+      Instructions::I32Const(_) => self.push_val(I32),
+      Instructions::I64Const(_) => self.push_val(I64),
+      Instructions::F32Const(_) => self.push_val(F32),
+      Instructions::F64Const(_) => self.push_val(F64),
+
+      Instructions::I32Eqz => self.testop(I32)?,
+      Instructions::I32Eq | Instructions::I32Ne | Instructions::I32LtS | Instructions::I32LtU
+      | Instructions::I32GtS | Instructions::I32GtU | Instructions::I32LeS | Instructions::I32LeU
+      | Instructions::I32GeS | Instructions::I32GeU => self.relop(I32)?,
+      Instructions::I64Eqz => self.testop(I64)?,
+      Instructions::I64Eq | Instructions::I64Ne | Instructions::I64LtS | Instructions::I64LtU
+      | Instructions::I64GtS | Instructions::I64GtU | Instructions::I64LeS | Instructions::I64LeU
+      | Instructions::I64GeS | Instructions::I64GeU => self.relop(I64)?,
+      Instructions::F32Eq | Instructions::F32Ne | Instructions::F32Lt | Instructions::F32Gt
+      | Instructions::F32Le | Instructions::F32Ge => self.relop(F32)?,
+      Instructions::F64Eq | Instructions::F64Ne | Instructions::F64Lt | Instructions::F64Gt
+      | Instructions::F64Le | Instructions::F64Ge => self.relop(F64)?,
+
+      Instructions::I32Clz | Instructions::I32Ctz | Instructions::I32Popcnt
+      | Instructions::I32Extend8S | Instructions::I32Extend16S => self.unop(I32)?,
+      Instructions::I32Add | Instructions::I32Sub | Instructions::I32Mul | Instructions::I32DivS
+      | Instructions::I32DivU | Instructions::I32RemS | Instructions::I32RemU | Instructions::I32And
+      | Instructions::I32Or | Instructions::I32Xor | Instructions::I32Shl | Instructions::I32ShrS
+      | Instructions::I32ShrU | Instructions::I32Rotl | Instructions::I32Rotr => self.binop(I32)?,
+      Instructions::I64Clz | Instructions::I64Ctz | Instructions::I64Popcnt
+      | Instructions::I64Extend8S | Instructions::I64Extend16S | Instructions::I64Extend32S => self.unop(I64)?,
+      Instructions::I64Add | Instructions::I64Sub | Instructions::I64Mul | Instructions::I64DivS
+      | Instructions::I64DivU | Instructions::I64RemS | Instructions::I64RemU | Instructions::I64And
+      | Instructions::I64Or | Instructions::I64Xor | Instructions::I64Shl | Instructions::I64ShrS
+      | Instructions::I64ShrU | Instructions::I64Rotl | Instructions::I64Rotr => self.binop(I64)?,
+      Instructions::F32Abs | Instructions::F32Neg | Instructions::F32Ceil | Instructions::F32Floor
+      | Instructions::F32Trunc | Instructions::F32Nearest | Instructions::F32Sqrt => self.unop(F32)?,
+      Instructions::F32Add | Instructions::F32Sub | Instructions::F32Mul | Instructions::F32Div
+      | Instructions::F32Min | Instructions::F32Max | Instructions::F32Copysign => self.binop(F32)?,
+      Instructions::F64Abs | Instructions::F64Neg | Instructions::F64Ceil | Instructions::F64Floor
+      | Instructions::F64Trunc | Instructions::F64Nearest | Instructions::F64Sqrt => self.unop(F64)?,
+      Instructions::F64Add | Instructions::F64Sub | Instructions::F64Mul | Instructions::F64Div
+      | Instructions::F64Min | Instructions::F64Max | Instructions::F64Copysign => self.binop(F64)?,
+
+      Instructions::I32WrapI64 => self.cvtop(I64, I32)?,
+      Instructions::I32TruncF32S | Instructions::I32TruncF32U => self.cvtop(F32, I32)?,
+      Instructions::I32TruncF64S | Instructions::I32TruncF64U => self.cvtop(F64, I32)?,
+      Instructions::I64ExtendI32S | Instructions::I64ExtendI32U => self.cvtop(I32, I64)?,
+      Instructions::I64TruncF32S | Instructions::I64TruncF32U => self.cvtop(F32, I64)?,
+      Instructions::I64TruncF64S | Instructions::I64TruncF64U => self.cvtop(F64, I64)?,
+      Instructions::I32TruncSatF32S | Instructions::I32TruncSatF32U => self.cvtop(F32, I32)?,
+      Instructions::I32TruncSatF64S | Instructions::I32TruncSatF64U => self.cvtop(F64, I32)?,
+      Instructions::I64TruncSatF32S | Instructions::I64TruncSatF32U => self.cvtop(F32, I64)?,
+      Instructions::I64TruncSatF64S | Instructions::I64TruncSatF64U => self.cvtop(F64, I64)?,
+      Instructions::F32ConvertI32S | Instructions::F32ConvertI32U => self.cvtop(I32, F32)?,
+      Instructions::F32ConvertI64S | Instructions::F32ConvertI64U => self.cvtop(I64, F32)?,
+      Instructions::F32DemoteF64 => self.cvtop(F64, F32)?,
+      Instructions::F64ConvertI32S | Instructions::F64ConvertI32U => self.cvtop(I32, F64)?,
+      Instructions::F64ConvertI64S | Instructions::F64ConvertI64U => self.cvtop(I64, F64)?,
+      Instructions::F64PromoteF32 => self.cvtop(F32, F64)?,
+      Instructions::I32ReinterpretF32 => self.cvtop(F32, I32)?,
+      Instructions::I64ReinterpretF64 => self.cvtop(F64, I64)?,
+      Instructions::F32ReinterpretI32 => self.cvtop(I32, F32)?,
+      Instructions::F64ReinterpretI64 => self.cvtop(I64, F64)?,
+
+      Instructions::LocalGet(local_idx) => {
+        let operand = self.local(*local_idx)?;
+        self.push_val(operand);
+      },
+      Instructions::LocalSet(local_idx) => {
+        let operand = self.local(*local_idx)?;
+        self.pop_expect(operand)?;
+      },
+      Instructions::LocalTee(local_idx) => {
+        let operand = self.local(*local_idx)?;
+        self.unop(operand)?;
+      },
+      Instructions::GlobalGet(global_idx) => {
+        let global = self.global(*global_idx)?;
+        self.push_val(global.valtype);
+      },
+      Instructions::GlobalSet(global_idx) => {
+        let global = self.global(*global_idx)?;
+        if !global.mutable {
+          return Err(ValidationError::new("global is immutable"));
+        }
+        self.pop_expect(global.valtype)?;
+      },
+      Instructions::TableGet(table_idx) => {
+        let reftype = self.ctx.table_type(*table_idx)?;
+        self.pop_expect(I32)?;
+        self.push_val(reftype);
+      },
+      Instructions::TableSet(table_idx) => {
+        let reftype = self.ctx.table_type(*table_idx)?;
+        self.pop_vals(&[I32, reftype])?;
+      },
+      Instructions::TableInit(elem_idx, table_idx) => {
+        let table_type = self.ctx.table_type(*table_idx)?;
+        let elem_type = self.ctx.elem_type(*elem_idx)?;
+        check_ref_match(table_type, elem_type)?;
+        self.pop_vals(&[I32, I32, I32])?;
+      },
+      Instructions::ElemDrop(elem_idx) => {
+        self.ctx.elem_type(*elem_idx)?;
+      },
+      Instructions::TableCopy(dst, src) => {
+        let dst_type = self.ctx.table_type(*dst)?;
+        let src_type = self.ctx.table_type(*src)?;
+        check_ref_match(dst_type, src_type)?;
+        self.pop_vals(&[I32, I32, I32])?;
+      },
+      Instructions::TableGrow(table_idx) => {
+        let reftype = self.ctx.table_type(*table_idx)?;
+        self.pop_vals(&[reftype, I32])?;
+        self.push_val(I32);
+      },
+      Instructions::TableSize(table_idx) => {
+        self.ctx.table_type(*table_idx)?;
+        self.push_val(I32);
+      },
+      Instructions::TableFill(table_idx) => {
+        let reftype = self.ctx.table_type(*table_idx)?;
+        self.pop_vals(&[I32, reftype, I32])?;
+      },
+      Instructions::RefNull(reftype) => self.push_val(Operand::from(reftype)),
+      Instructions::RefIsNull => {
+        let operand = self.pop_val()?;
+        if operand.is_num() && operand != Operand::Unknown {
+          return Err(ValidationError::new("type mismatch"));
+        }
+        self.push_val(I32);
+      },
+      Instructions::RefFunc(func_idx) => {
+        self.ctx.func_type(*func_idx)?;
+        if !self.ctx.refs.contains(func_idx) {
+          return Err(ValidationError::new("undeclared function reference"));
+        }
+        self.push_val(Operand::FuncRef);
+      },
+
+      Instructions::V128Load { align, memory, offset } => self.load(*memory, *align, *offset, 4, V128)?,
+      Instructions::V128Load8x8S { align, memory, offset } => self.load(*memory, *align, *offset, 3, V128)?,
+      Instructions::V128Load8x8U { align, memory, offset } => self.load(*memory, *align, *offset, 3, V128)?,
+      Instructions::V128Load16x4S { align, memory, offset } => self.load(*memory, *align, *offset, 3, V128)?,
+      Instructions::V128Load16x4U { align, memory, offset } => self.load(*memory, *align, *offset, 3, V128)?,
+      Instructions::V128Load32x2S { align, memory, offset } => self.load(*memory, *align, *offset, 3, V128)?,
+      Instructions::V128Load32x2U { align, memory, offset } => self.load(*memory, *align, *offset, 3, V128)?,
+      Instructions::V128Load8Splat { align, memory, offset } => self.load(*memory, *align, *offset, 0, V128)?,
+      Instructions::V128Load16Splat { align, memory, offset } => self.load(*memory, *align, *offset, 1, V128)?,
+      Instructions::V128Load32Splat { align, memory, offset } => self.load(*memory, *align, *offset, 2, V128)?,
+      Instructions::V128Load64Splat { align, memory, offset } => self.load(*memory, *align, *offset, 3, V128)?,
+      Instructions::V128Store { align, memory, offset } => self.store(*memory, *align, *offset, 4, V128)?,
+      Instructions::V128Const(_) => self.push_val(V128),
+      Instructions::I8x16Shuffle(lanes) => {
+        if lanes.iter().any(|lane| *lane >= 32) {
+          return Err(ValidationError::new("invalid lane index"));
+        }
+        self.binop(V128)?;
+      },
+      Instructions::I8x16Splat => self.cvtop(I32, V128)?,
+      Instructions::I16x8Splat => self.cvtop(I32, V128)?,
+      Instructions::I32x4Splat => self.cvtop(I32, V128)?,
+      Instructions::I64x2Splat => self.cvtop(I64, V128)?,
+      Instructions::F32x4Splat => self.cvtop(F32, V128)?,
+      Instructions::F64x2Splat => self.cvtop(F64, V128)?,
+      Instructions::I8x16ExtractLaneS(lane) => self.extract_lane(*lane, 16, I32)?,
+      Instructions::I8x16ExtractLaneU(lane) => self.extract_lane(*lane, 16, I32)?,
+      Instructions::I8x16ReplaceLane(lane) => self.replace_lane(*lane, 16, I32)?,
+      Instructions::I16x8ExtractLaneS(lane) => self.extract_lane(*lane, 8, I32)?,
+      Instructions::I16x8ExtractLaneU(lane) => self.extract_lane(*lane, 8, I32)?,
+      Instructions::I16x8ReplaceLane(lane) => self.replace_lane(*lane, 8, I32)?,
+      Instructions::I32x4ExtractLane(lane) => self.extract_lane(*lane, 4, I32)?,
+      Instructions::I32x4ReplaceLane(lane) => self.replace_lane(*lane, 4, I32)?,
+      Instructions::I64x2ExtractLane(lane) => self.extract_lane(*lane, 2, I64)?,
+      Instructions::I64x2ReplaceLane(lane) => self.replace_lane(*lane, 2, I64)?,
+      Instructions::F32x4ExtractLane(lane) => self.extract_lane(*lane, 4, F32)?,
+      Instructions::F32x4ReplaceLane(lane) => self.replace_lane(*lane, 4, F32)?,
+      Instructions::F64x2ExtractLane(lane) => self.extract_lane(*lane, 2, F64)?,
+      Instructions::F64x2ReplaceLane(lane) => self.replace_lane(*lane, 2, F64)?,
+      Instructions::V128Load8Lane { align, memory, offset, lane } => self.load_lane(*memory, *align, *offset, 0, *lane, 16)?,
+      Instructions::V128Store8Lane { align, memory, offset, lane } => self.store_lane(*memory, *align, *offset, 0, *lane, 16)?,
+      Instructions::V128Load16Lane { align, memory, offset, lane } => self.load_lane(*memory, *align, *offset, 1, *lane, 8)?,
+      Instructions::V128Store16Lane { align, memory, offset, lane } => self.store_lane(*memory, *align, *offset, 1, *lane, 8)?,
+      Instructions::V128Load32Lane { align, memory, offset, lane } => self.load_lane(*memory, *align, *offset, 2, *lane, 4)?,
+      Instructions::V128Store32Lane { align, memory, offset, lane } => self.store_lane(*memory, *align, *offset, 2, *lane, 4)?,
+      Instructions::V128Load64Lane { align, memory, offset, lane } => self.load_lane(*memory, *align, *offset, 3, *lane, 2)?,
+      Instructions::V128Store64Lane { align, memory, offset, lane } => self.store_lane(*memory, *align, *offset, 3, *lane, 2)?,
+      Instructions::V128Load32Zero { align, memory, offset } => self.load(*memory, *align, *offset, 2, V128)?,
+      Instructions::V128Load64Zero { align, memory, offset } => self.load(*memory, *align, *offset, 3, V128)?,
+      Instructions::V128Not | Instructions::F32x4DemoteF64x2Zero | Instructions::F64x2PromoteLowF32x4
+      | Instructions::I8x16Abs | Instructions::I8x16Neg | Instructions::I8x16Popcnt | Instructions::F32x4Ceil
+      | Instructions::F32x4Floor | Instructions::F32x4Trunc | Instructions::F32x4Nearest
+      | Instructions::F64x2Ceil | Instructions::F64x2Floor | Instructions::F64x2Trunc
+      | Instructions::I16x8ExtaddPairwiseI8x16S | Instructions::I16x8ExtaddPairwiseI8x16U
+      | Instructions::I32x4ExtaddPairwiseI16x8S | Instructions::I32x4ExtaddPairwiseI16x8U
+      | Instructions::I16x8Abs | Instructions::I16x8Neg | Instructions::I16x8ExtendLowI8x16S
+      | Instructions::I16x8ExtendHighI8x16S | Instructions::I16x8ExtendLowI8x16U
+      | Instructions::I16x8ExtendHighI8x16U | Instructions::F64x2Nearest | Instructions::I32x4Abs
+      | Instructions::I32x4Neg | Instructions::I32x4ExtendLowI16x8S | Instructions::I32x4ExtendHighI16x8S
+      | Instructions::I32x4ExtendLowI16x8U | Instructions::I32x4ExtendHighI16x8U | Instructions::I64x2Abs
+      | Instructions::I64x2Neg | Instructions::I64x2ExtendLowI32x4S | Instructions::I64x2ExtendHighI32x4S
+      | Instructions::I64x2ExtendLowI32x4U | Instructions::I64x2ExtendHighI32x4U | Instructions::F32x4Abs
+      | Instructions::F32x4Neg | Instructions::F32x4Sqrt | Instructions::F64x2Abs | Instructions::F64x2Neg
+      | Instructions::F64x2Sqrt | Instructions::I32x4TruncSatF32x4S | Instructions::I32x4TruncSatF32x4U
+      | Instructions::F32x4ConvertI32x4S | Instructions::F32x4ConvertI32x4U
+      | Instructions::I32x4TruncSatF64x2SZero | Instructions::I32x4TruncSatF64x2UZero
+      | Instructions::F64x2ConvertLowI32x4S | Instructions::F64x2ConvertLowI32x4U => self.unop(V128)?,
+      Instructions::I8x16Swizzle | Instructions::I8x16Eq | Instructions::I8x16Ne | Instructions::I8x16LtS
+      | Instructions::I8x16LtU | Instructions::I8x16GtS | Instructions::I8x16GtU | Instructions::I8x16LeS
+      | Instructions::I8x16LeU | Instructions::I8x16GeS | Instructions::I8x16GeU | Instructions::I16x8Eq
+      | Instructions::I16x8Ne | Instructions::I16x8LtS | Instructions::I16x8LtU | Instructions::I16x8GtS
+      | Instructions::I16x8GtU | Instructions::I16x8LeS | Instructions::I16x8LeU | Instructions::I16x8GeS
+      | Instructions::I16x8GeU | Instructions::I32x4Eq | Instructions::I32x4Ne | Instructions::I32x4LtS
+      | Instructions::I32x4LtU | Instructions::I32x4GtS | Instructions::I32x4GtU | Instructions::I32x4LeS
+      | Instructions::I32x4LeU | Instructions::I32x4GeS | Instructions::I32x4GeU | Instructions::F32x4Eq
+      | Instructions::F32x4Ne | Instructions::F32x4Lt | Instructions::F32x4Gt | Instructions::F32x4Le
+      | Instructions::F32x4Ge | Instructions::F64x2Eq | Instructions::F64x2Ne | Instructions::F64x2Lt
+      | Instructions::F64x2Gt | Instructions::F64x2Le | Instructions::F64x2Ge | Instructions::V128And
+      | Instructions::V128Andnot | Instructions::V128Or | Instructions::V128Xor
+      | Instructions::I8x16NarrowI16x8S | Instructions::I8x16NarrowI16x8U | Instructions::I8x16Add
+      | Instructions::I8x16AddSatS | Instructions::I8x16AddSatU | Instructions::I8x16Sub
+      | Instructions::I8x16SubSatS | Instructions::I8x16SubSatU | Instructions::I8x16MinS
+      | Instructions::I8x16MinU | Instructions::I8x16MaxS | Instructions::I8x16MaxU | Instructions::I8x16AvgrU
+      | Instructions::I16x8Q15mulrSatS | Instructions::I16x8NarrowI32x4S | Instructions::I16x8NarrowI32x4U
+      | Instructions::I16x8Add | Instructions::I16x8AddSatS | Instructions::I16x8AddSatU
+      | Instructions::I16x8Sub | Instructions::I16x8SubSatS | Instructions::I16x8SubSatU
+      | Instructions::I16x8Mul | Instructions::I16x8MinS | Instructions::I16x8MinU | Instructions::I16x8MaxS
+      | Instructions::I16x8MaxU | Instructions::I16x8AvgrU | Instructions::I16x8ExtmulLowI8x16S
+      | Instructions::I16x8ExtmulHighI8x16S | Instructions::I16x8ExtmulLowI8x16U
+      | Instructions::I16x8ExtmulHighI8x16U | Instructions::I32x4Add | Instructions::I32x4Sub
+      | Instructions::I32x4Mul | Instructions::I32x4MinS | Instructions::I32x4MinU | Instructions::I32x4MaxS
+      | Instructions::I32x4MaxU | Instructions::I32x4DotI16x8S | Instructions::I32x4ExtmulLowI16x8S
+      | Instructions::I32x4ExtmulHighI16x8S | Instructions::I32x4ExtmulLowI16x8U
+      | Instructions::I32x4ExtmulHighI16x8U | Instructions::I64x2Add | Instructions::I64x2Sub
+      | Instructions::I64x2Mul | Instructions::I64x2Eq | Instructions::I64x2Ne | Instructions::I64x2LtS
+      | Instructions::I64x2GtS | Instructions::I64x2LeS | Instructions::I64x2GeS
+      | Instructions::I64x2ExtmulLowI32x4S | Instructions::I64x2ExtmulHighI32x4S
+      | Instructions::I64x2ExtmulLowI32x4U | Instructions::I64x2ExtmulHighI32x4U | Instructions::F32x4Add
+      | Instructions::F32x4Sub | Instructions::F32x4Mul | Instructions::F32x4Div | Instructions::F32x4Min
+      | Instructions::F32x4Max | Instructions::F32x4Pmin | Instructions::F32x4Pmax | Instructions::F64x2Add
+      | Instructions::F64x2Sub | Instructions::F64x2Mul | Instructions::F64x2Div | Instructions::F64x2Min
+      | Instructions::F64x2Max | Instructions::F64x2Pmin | Instructions::F64x2Pmax => self.binop(V128)?,
+      Instructions::V128Bitselect => {
+        self.pop_vals(&[V128, V128, V128])?;
+        self.push_val(V128);
+      },
+      Instructions::V128AnyTrue | Instructions::I8x16AllTrue | Instructions::I8x16Bitmask
+      | Instructions::I16x8AllTrue | Instructions::I16x8Bitmask | Instructions::I32x4AllTrue
+      | Instructions::I32x4Bitmask | Instructions::I64x2AllTrue | Instructions::I64x2Bitmask => self.testop(V128)?,
+      Instructions::I8x16Shl | Instructions::I8x16ShrS | Instructions::I8x16ShrU | Instructions::I16x8Shl
+      | Instructions::I16x8ShrS | Instructions::I16x8ShrU | Instructions::I32x4Shl | Instructions::I32x4ShrS
+      | Instructions::I32x4ShrU | Instructions::I64x2Shl | Instructions::I64x2ShrS | Instructions::I64x2ShrU => self.shiftop()?,
+      Instructions::AtomicFence => {},
+      _ => self.atomic(instr)?,
+    }
+    Ok(())
+  }
+}
@@ -1,7 +1,9 @@
-use nom::IResult;
+use nom::number::complete::le_u8;
 use serde::{Deserialize, Serialize};
 
 use crate::exec::value::Value;
+use super::table_sec::RefType;
+use super::error::{fail, IResult};
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum ValueType {
@@ -9,26 +11,48 @@ pub enum ValueType {
   I64,
   F32,
   F64,
+  V128,
+  FuncRef,
+  ExternRef,
+  ExnRef,
 }
 
 impl ValueType {
-  pub fn parse(input: u8) -> ValueType {
-    match input {
-      0x7F => ValueType::I32,
-      0x7E => ValueType::I64,
-      0x7D => ValueType::F32,
-      0x7C => ValueType::F64,
-      _ => panic!("Unknown value type: {:#x?}", input),
+  pub fn parse(input: &[u8]) -> IResult<&[u8], ValueType> {
+    let (rest, byte) = le_u8(input)?;
+    match byte {
+      0x7F => Ok((rest, ValueType::I32)),
+      0x7E => Ok((rest, ValueType::I64)),
+      0x7D => Ok((rest, ValueType::F32)),
+      0x7C => Ok((rest, ValueType::F64)),
+      0x7B => Ok((rest, ValueType::V128)),
+      0x70 => Ok((rest, ValueType::FuncRef)),
+      0x6F => Ok((rest, ValueType::ExternRef)),
+      0x69 => Ok((rest, ValueType::ExnRef)),
+      _ => fail(input, format!("a value type (found {:#04x})", byte)),
     }
   }
 
+  pub fn encode(&self, buf: &mut Vec<u8>) {
+    buf.push(match self {
+      ValueType::I32 => 0x7F,
+      ValueType::I64 => 0x7E,
+      ValueType::F32 => 0x7D,
+      ValueType::F64 => 0x7C,
+      ValueType::V128 => 0x7B,
+      ValueType::FuncRef => 0x70,
+      ValueType::ExternRef => 0x6F,
+      ValueType::ExnRef => 0x69,
+    });
+  }
+
   pub fn parse_vec(input: &[u8], count: u32) -> IResult<&[u8], Vec<ValueType>> {
     let mut res = Vec::new();
     let mut input = input;
     for _ in 0..count {
-      let value_type = ValueType::parse(input[0]);
+      let value_type: ValueType;
+      (input, value_type) = ValueType::parse(input)?;
       res.push(value_type);
-      input = &input[1..];
     }
     Ok((input, res))
   }
@@ -39,6 +63,20 @@ impl ValueType {
       ValueType::I64 => Value::I64(0),
       ValueType::F32 => Value::F32(0.0),
       ValueType::F64 => Value::F64(0.0),
+      ValueType::V128 => Value::V128(0),
+      ValueType::FuncRef => Value::FuncRef(None),
+      ValueType::ExternRef => Value::ExternRef(None),
+      ValueType::ExnRef => Value::ExnRef(None),
     }
   }
-}
\ No newline at end of file
+}
+
+impl From<&RefType> for ValueType {
+  fn from(reftype: &RefType) -> ValueType {
+    match reftype {
+      RefType::FuncRef => ValueType::FuncRef,
+      RefType::ExternRef => ValueType::ExternRef,
+      RefType::ExnRef => ValueType::ExnRef,
+    }
+  }
+}
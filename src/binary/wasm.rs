@@ -1,13 +1,19 @@
 use std::io::Read;
-use nom::bytes::complete::{tag, take};
-use nom::IResult;
+use nom::bytes::complete::take;
+use nom::number::complete::le_u8;
 use nom_leb128::leb128_u32;
 
+use super::custom_sec::CustomSec;
 use super::data_sec::Data;
+use super::elem_sec::Elem;
+use super::encode::{write_bytes, write_u32};
+use super::error::{DecodeError, IResult, ParseError};
 use super::global_sec::GlobalVar;
 use super::memory_sec::MemorySec;
-use super::section::Section;
+use super::name_sec::NameSec;
+use super::section::{section_name, Section};
 use super::table_sec::TableSec;
+use super::tag_sec::Tag;
 use super::type_sec::FuncType;
 use super::import_sec::Import;
 use super::func_sec::Func;
@@ -24,20 +30,27 @@ pub struct Wasm {
   pub memory_section: Option<Vec<MemorySec>>,
   pub global_section: Option<Vec<GlobalVar>>,
   pub export_section: Option<Vec<ExportFunc>>,
+  pub start_section: Option<u32>,
+  pub element_section: Option<Vec<Elem>>,
   pub code_section: Option<Vec<Code>>,
-  pub data_section: Option<Vec<Data>>
+  pub data_section: Option<Vec<Data>>,
+  pub data_count_section: Option<u32>,
+  pub tag_section: Option<Vec<Tag>>,
+  pub custom_sections: Vec<CustomSec>,
+  pub name_section: Option<NameSec>,
 }
 
 const MAGIC: &[u8; 4] = &[0x00, 0x61, 0x73, 0x6d];
 const VERSION: &[u8; 4] = &[0x01, 0x00, 0x00, 0x00];
 
 impl Wasm {
-  pub fn new<T: Read>(mut reader: T) -> Wasm {
+  pub fn new<T: Read>(mut reader: T) -> Result<Wasm, ParseError> {
 
     let mut all_data: Vec<u8> = Vec::new();
-    reader.read_to_end(&mut all_data).unwrap();
+    reader.read_to_end(&mut all_data)
+      .map_err(|e| ParseError::new(0, format!("a readable module ({})", e)))?;
 
-    let mut data = check_magic_and_version(&all_data);
+    let mut data = check_magic_and_version(&all_data)?;
 
     let mut wasm = Wasm{
       type_section: None,
@@ -47,22 +60,42 @@ impl Wasm {
       memory_section: None,
       global_section: None,
       export_section: None,
+      start_section: None,
+      element_section: None,
       code_section: None,
       data_section: None,
+      data_count_section: None,
+      tag_section: None,
+      custom_sections: Vec::new(),
+      name_section: None,
     };
 
-    loop {
+    while !data.is_empty() {
       let section_id: u8;
-      let section_size: u32;
       let section_data: &[u8];
 
-      (data, (section_id, section_size, section_data)) = parse_section_id_and_content(data).unwrap();
-      if section_id == 0 && section_size == 0 {
-          break;
-      }
+      (data, (section_id, section_data)) = match parse_section_id_and_content(data) {
+        Ok(parsed) => parsed,
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => return Err(to_parse_error(&all_data, e)),
+        Err(nom::Err::Incomplete(_)) => return Err(ParseError::new(all_data.len(), "more bytes (unexpected end)")),
+      };
 
-      let section = Section::match_section(section_id, section_data);
+      let section = Section::match_section(section_id, section_data)
+        .map_err(|e| {
+          let e = match section_name(section_id) {
+            Some(name) => e.in_section(name),
+            None => e,
+          };
+          to_parse_error(&all_data, e)
+        })?;
       match section {
+        Section::CustomSection(custom) => {
+          // nameセクションが壊れていてもモジュール自体は有効なので無視する
+          if custom.name == "name" {
+            wasm.name_section = NameSec::parse(&custom.data).ok().map(|(_, names)| names);
+          }
+          wasm.custom_sections.push(custom);
+        },
         Section::TypeSection(func_types) => {
             wasm.type_section = Some(func_types);
         },
@@ -84,40 +117,151 @@ impl Wasm {
         Section::ExportSection(export_funcs) => {
             wasm.export_section = Some(export_funcs);
         },
+        Section::StartSection(func_idx) => {
+            wasm.start_section = Some(func_idx);
+        },
+        Section::ElementSection(elems) => {
+            wasm.element_section = Some(elems);
+        },
         Section::CodeSection(codes) => {
             wasm.code_section = Some(codes);
         },
         Section::DataSection(data) => {
           wasm.data_section = Some(data);
         },
-        _ => {},
+        Section::DataCountSection(count) => {
+          wasm.data_count_section = Some(count);
+        },
+        Section::TagSection(tags) => {
+          wasm.tag_section = Some(tags);
+        },
+      }
+    }
+
+    let func_count = wasm.function_section.as_ref().map_or(0, |funcs| funcs.len());
+    let code_count = wasm.code_section.as_ref().map_or(0, |codes| codes.len());
+    if func_count != code_count {
+      return Err(ParseError::new(
+        all_data.len(),
+        format!("as many code entries as functions ({} functions, {} code entries)", func_count, code_count),
+      ));
+    }
+    if let Some(data_count) = wasm.data_count_section {
+      let data_len = wasm.data_section.as_ref().map_or(0, |data| data.len());
+      if data_count as usize != data_len {
+        return Err(ParseError::new(
+          all_data.len(),
+          format!("as many data segments as the data count ({} data count, {} segments)", data_count, data_len),
+        ));
       }
     }
-    wasm
+    Ok(wasm)
+  }
+}
+
+impl Wasm {
+  // バイナリ形式に書き戻す。既知のセクションは標準の順に並べ (tagセクションはmemoryとglobalの間)
+  // nameを含むカスタムセクションは元の位置を覚えていないので最後に書く
+  pub fn encode(&self) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(VERSION);
+
+    if let Some(types) = &self.type_section {
+      write_section(&mut buf, 1, &encode_vec(types, FuncType::encode));
+    }
+    if let Some(imports) = &self.import_section {
+      write_section(&mut buf, 2, &encode_vec(imports, Import::encode));
+    }
+    if let Some(funcs) = &self.function_section {
+      write_section(&mut buf, 3, &encode_vec(funcs, |func, buf| write_u32(buf, func.type_idx)));
+    }
+    if let Some(tables) = &self.table_section {
+      write_section(&mut buf, 4, &encode_vec(tables, TableSec::encode));
+    }
+    if let Some(memories) = &self.memory_section {
+      write_section(&mut buf, 5, &encode_vec(memories, MemorySec::encode));
+    }
+    if let Some(tags) = &self.tag_section {
+      write_section(&mut buf, 13, &encode_vec(tags, Tag::encode));
+    }
+    if let Some(globals) = &self.global_section {
+      write_section(&mut buf, 6, &encode_vec(globals, GlobalVar::encode));
+    }
+    if let Some(exports) = &self.export_section {
+      write_section(&mut buf, 7, &encode_vec(exports, ExportFunc::encode));
+    }
+    if let Some(func_idx) = self.start_section {
+      let mut content = Vec::new();
+      write_u32(&mut content, func_idx);
+      write_section(&mut buf, 8, &content);
+    }
+    if let Some(elems) = &self.element_section {
+      write_section(&mut buf, 9, &encode_vec(elems, Elem::encode));
+    }
+    if let Some(count) = self.data_count_section {
+      let mut content = Vec::new();
+      write_u32(&mut content, count);
+      write_section(&mut buf, 12, &content);
+    }
+    if let Some(codes) = &self.code_section {
+      write_section(&mut buf, 10, &encode_vec(codes, Code::encode));
+    }
+    if let Some(data) = &self.data_section {
+      write_section(&mut buf, 11, &encode_vec(data, Data::encode));
+    }
+    for custom in &self.custom_sections {
+      let mut content = Vec::new();
+      custom.encode(&mut content);
+      write_section(&mut buf, 0, &content);
+    }
+    buf
   }
 }
 
-fn check_magic_and_version(data: &[u8]) -> &[u8] {
-  let data = match tag::<_, _, ()>(MAGIC)(data) {
-    Ok((data, _)) => data,
-    Err(e) => panic!("Unexpected magic number: {:#x?}", e),
-  };
-  
-  (match tag::<_, _, ()>(VERSION)(data) {
-    Ok((data, _)) => data,
-    Err(e) => panic!("Unexpected version number: {:#x?}", e),
-  }) as _
+fn encode_vec<T>(items: &[T], encode: impl Fn(&T, &mut Vec<u8>)) -> Vec<u8> {
+  let mut content = Vec::new();
+  write_u32(&mut content, items.len() as u32);
+  for item in items {
+    encode(item, &mut content);
+  }
+  content
+}
+
+fn write_section(buf: &mut Vec<u8>, section_id: u8, content: &[u8]) {
+  buf.push(section_id);
+  write_bytes(buf, content);
 }
 
+pub(crate) fn check_magic_and_version(data: &[u8]) -> Result<&[u8], ParseError> {
+  if data.len() < 4 || &data[..4] != MAGIC {
+    return Err(ParseError::new(0, "magic number \\0asm"));
+  }
+  if data.len() < 8 || &data[4..8] != VERSION {
+    return Err(ParseError::new(4, "version 1"));
+  }
+  Ok(&data[8..])
+}
+
+// DecodeErrorが指している残りの入力からモジュール先頭からのオフセットを求める
+pub(crate) fn to_parse_error(all_data: &[u8], e: DecodeError<&[u8]>) -> ParseError {
+  let offset = e.input.as_ptr() as usize - all_data.as_ptr() as usize;
+  ParseError {
+    section: e.section.map(|s| s.to_string()),
+    item: e.item,
+    offset,
+    expected: e.expected,
+  }
+}
 
-fn parse_section_id_and_content(data: &[u8]) -> IResult<&[u8], (u8, u32, &[u8])> {
-  if data.is_empty() {
-    return Ok((data, (0, 0, &[])));
+pub(crate) fn parse_section_id_and_content(data: &[u8]) -> IResult<&[u8], (u8, &[u8])> {
+  let (data, section_id) = le_u8(data)?;
+  let (rest, section_size) = leb128_u32(data)?;
+  if rest.len() < section_size as usize {
+    return Err(nom::Err::Error(DecodeError::new(data, format!("a section size within the module (found {} bytes)", section_size))));
   }
-  let (data, section_id) = take(1u8)(data)?;
-  let (data, section_size) = leb128_u32(data)?;
-  let (data, section_data) = take(section_size)(data)?;
-  Ok((data, (section_id[0], section_size, section_data)))
+  let (rest, section_data) = take(section_size)(rest)?;
+  Ok((rest, (section_id, section_data)))
 }
 
 // セクションパース部分nomで書き直せそうだが、気合で書いて愛着があるので一旦このまま
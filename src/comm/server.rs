@@ -2,6 +2,7 @@ use async_ucx::ucp::*;
 use std::mem::MaybeUninit;
 
 use crate::exec::exec_machine::ExecMachine;
+use crate::exec::linker::Linker;
 use crate::exec::wasi::WasiSnapshotPreview1;
 
 pub async fn server_start() -> anyhow::Result<()> {
@@ -37,11 +38,13 @@ pub async fn server_start() -> anyhow::Result<()> {
           // println!("len: {}, buf: {:?}", len, buf);
           ep.tag_send(tag, &[i]).await.unwrap();
           let mut machine = ExecMachine::deserialize(&buf).await.unwrap();
+          machine.snapshot_on_trap = true;
           let mut wasi = WasiSnapshotPreview1::new();
-          match machine.exec(&mut wasi).await {
+          let mut linker = Linker::with_wasi();
+          match machine.exec(&mut wasi, &mut linker).await {
             std::result::Result::Ok(_) => { println!("return {:?}", machine.value_stack.last()); },
             Err(e) => {
-              println!("ExecuteError: {:?}", e.message);
+              println!("ExecuteError: {}", e);
               println!("VM: {:#?}", e.vm);
             },
           }
@@ -0,0 +1,99 @@
+use crate::binary::instructions::Instructions;
+use crate::binary::value_type::ValueType;
+use super::op::OpError;
+use super::store::MemoryInst;
+use super::trap::Trap;
+use super::value::Value;
+
+// 0xfe命令のうちwait以外を実行する。waitは眠る必要があるのでExecMachineが扱う
+// 共有メモリへのアクセスは毎回ロックを取るので、fenceは何もしなくてよい
+pub fn exec_atomic(instr: &Instructions, stack: &mut Vec<Value>, memories: &mut [MemoryInst]) -> Result<(), OpError> {
+  let Some(opcode) = instr.atomic_opcode() else {
+    return Err(OpError::InvalidType(format!("not an atomic instruction: {:?}", instr)));
+  };
+  let (Some((_, memory_idx, offset)), Some((value_type, size))) = (instr.atomic_memarg(), instr.atomic_access()) else {
+    return Ok(());
+  };
+  let memory = memories.get_mut(memory_idx as usize)
+    .ok_or(OpError::InvalidType(format!("memory {} not found", memory_idx)))?;
+
+  match opcode {
+    // notify
+    0x00 => {
+      let count = pop_bits(stack)? as u32;
+      let addr = effective_addr(memory, offset, pop_bits(stack)?, size)?;
+      // 共有していないメモリには待っているスレッドがいない
+      let woken = memory.shared.as_ref().map_or(0, |shared| shared.notify(addr, count));
+      stack.push(Value::I32(woken as i32));
+    },
+    0x10..=0x16 => {
+      let addr = effective_addr(memory, offset, pop_bits(stack)?, size)?;
+      let bytes = memory.load(addr, 0, size).map_err(|_| Trap::MemoryOutOfBounds)?;
+      let mut value = [0u8; 8];
+      value[..size as usize].copy_from_slice(&bytes);
+      stack.push(to_value(&value_type, u64::from_le_bytes(value)));
+    },
+    0x17..=0x1d => {
+      let value = pop_bits(stack)?;
+      let addr = effective_addr(memory, offset, pop_bits(stack)?, size)?;
+      memory.store(addr, 0, size, &value.to_le_bytes()).map_err(|_| Trap::MemoryOutOfBounds)?;
+    },
+    // cmpxchgは期待する値もアクセス幅に切り詰めてから比べる
+    0x48..=0x4e => {
+      let replacement = pop_bits(stack)?;
+      let expected = pop_bits(stack)? & mask(size);
+      let addr = effective_addr(memory, offset, pop_bits(stack)?, size)?;
+      let old = memory.rmw(addr, size, |old| if old == expected { replacement } else { old })
+        .map_err(|_| Trap::MemoryOutOfBounds)?;
+      stack.push(to_value(&value_type, old));
+    },
+    // add、sub、and、or、xor、xchgの順に7命令ずつ並んでいる
+    0x1e..=0x47 => {
+      let value = pop_bits(stack)?;
+      let addr = effective_addr(memory, offset, pop_bits(stack)?, size)?;
+      let op: fn(u64, u64) -> u64 = match (opcode - 0x1e) / 7 {
+        0 => u64::wrapping_add,
+        1 => u64::wrapping_sub,
+        2 => |old, v| old & v,
+        3 => |old, v| old | v,
+        4 => |old, v| old ^ v,
+        _ => |_, v| v,
+      };
+      let old = memory.rmw(addr, size, |old| op(old, value)).map_err(|_| Trap::MemoryOutOfBounds)?;
+      stack.push(to_value(&value_type, old));
+    },
+    _ => return Err(OpError::InvalidType(format!("{:?} must be executed by the machine", instr))),
+  }
+  Ok(())
+}
+
+// アトミック命令の実効アドレス。範囲外のトラップがアライメント違反より先になる
+pub fn effective_addr(memory: &MemoryInst, offset: u64, addr: u64, size: u32) -> Result<u64, Trap> {
+  let addr = offset.checked_add(addr)
+    .filter(|addr| addr.checked_add(size as u64).is_some_and(|end| end <= memory.len() as u64))
+    .ok_or(Trap::MemoryOutOfBounds)?;
+  if addr % size as u64 != 0 {
+    return Err(Trap::UnalignedAtomic);
+  }
+  Ok(addr)
+}
+
+// アトミック命令の値とアドレスはビット列として扱う。i32は符号なしに広げる
+fn pop_bits(stack: &mut Vec<Value>) -> Result<u64, OpError> {
+  match stack.pop() {
+    Some(Value::I32(v)) => Ok(v as u32 as u64),
+    Some(Value::I64(v)) => Ok(v as u64),
+    v => Err(OpError::InvalidType(format!("expected i32 or i64, found {:?}", v))),
+  }
+}
+
+fn mask(size: u32) -> u64 {
+  if size == 8 { u64::MAX } else { (1 << (size * 8)) - 1 }
+}
+
+fn to_value(value_type: &ValueType, bits: u64) -> Value {
+  match value_type {
+    ValueType::I64 => Value::I64(bits as i64),
+    _ => Value::I32(bits as u32 as i32),
+  }
+}
@@ -1,24 +1,34 @@
 use serde::{Deserialize, Serialize};
-use crate::binary::instructions::{Block, BlockType};
-
-use super::value::Value;
+use crate::binary::instructions::{Block, Catch};
+use crate::binary::value_type::ValueType;
 
 #[derive(Debug, Clone, PartialEq , Serialize, Deserialize)]
 pub struct BlockFrame {
-  pub value_stack_evac: Vec<Value>,
-  pub return_type: BlockType,
+  // ブロックに入った時点の値スタックの高さ。引数は取り除いた後の値
+  pub sp: usize,
+  pub param_types: Vec<ValueType>,
+  pub return_types: Vec<ValueType>,
   pub jump_pc: usize,
   pub is_loop: bool,
+  // try_tableのcatch節。他のブロックでは空
+  pub catches: Vec<Catch>,
 }
 
 
 impl BlockFrame {
-  pub fn new(value_stack_evac: Vec<Value>, block: Block, is_loop: bool) -> BlockFrame {
+  pub fn new(sp: usize, block: &Block, param_types: Vec<ValueType>, return_types: Vec<ValueType>) -> BlockFrame {
     BlockFrame {
-      value_stack_evac,
-      return_type: block.block_type,
+      sp,
+      param_types,
+      return_types,
       jump_pc: block.jump_pc,
-      is_loop,
+      is_loop: block.is_loop,
+      catches: Vec::new(),
     }
   }
+
+  // brでこのラベルに飛ぶときに持っていく値の型。loopは先頭に戻るので引数になる
+  pub fn label_types(&self) -> &[ValueType] {
+    if self.is_loop { &self.param_types } else { &self.return_types }
+  }
 }
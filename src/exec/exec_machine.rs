@@ -3,14 +3,18 @@ use core::panic;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
+use crate::binary::validate::validate;
 use crate::binary::value_type::ValueType;
 use crate::binary::wasm::Wasm;
-use crate::binary::instructions::{BlockType, Instructions};
+use crate::binary::instructions::{Block, BlockType, Catch, Instructions};
+use super::atomic::{effective_addr, exec_atomic};
 use super::block_frame::BlockFrame;
-use super::store::Store;
+use super::store::{ExnInst, MemoryInst, Store};
+use super::op::OpError;
+use super::trap::{Trap, TrapError};
 use super::value::Value;
 use super::func_instance::{FuncInstance, InternalFunc};
-use super::import::init_import;
+use super::linker::Linker;
 use super::wasi::WasiSnapshotPreview1;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,13 +22,16 @@ pub struct ExecMachine {
   pub value_stack: Vec<Value>,
   pub call_stack: Vec<FuncInstance>,
   pub store: Store,
+  // init_without_start で遅延させたstart関数
+  pub start: Option<usize>,
+  // trueのときトラップ時点のVMをTrapErrorに複製して持たせる
+  // 共有メモリを持つVMは複製しても中身が他のスレッドと共有されたままなので、スナップショットは取らない
+  #[serde(skip)]
+  pub snapshot_on_trap: bool,
 }
 
-#[derive(Debug)]
-pub struct TrapError {
-  pub message: String,
-  pub vm: ExecMachine,
-}
+// 呼び出しの深さの上限。無限再帰はこれを超えたところでトラップする
+const MAX_CALL_DEPTH: usize = 10000;
 
 impl Default for ExecMachine {
     fn default() -> Self {
@@ -38,15 +45,20 @@ impl ExecMachine {
       value_stack: Vec::new(),
       call_stack: Vec::new(),
       store: Store::default(),
+      start: None,
+      snapshot_on_trap: false,
     }
   }
 
-  pub fn init(wasm: Wasm, entry_point:&str, locals: Vec<Value>) -> ExecMachine {
-    let mut vm = ExecMachine::new();
-    let func_instances = FuncInstance::new(&wasm);
-    vm.store = Store::new(func_instances.clone(), &wasm);
-    vm.call_stack.push(vm.store.call_func_by_name(entry_point, locals));
-    vm
+  // インスタンス化してstart関数を実行し、次のexecでentry_pointが呼ばれるように積む
+  pub async fn init(wasm: Wasm, entry_point:&str, locals: Vec<Value>, wasi: &mut WasiSnapshotPreview1, linker: &mut Linker) -> Result<ExecMachine> {
+    let mut vm = ExecMachine::init_without_start(wasm, linker)?;
+    if let Err(e) = vm.run_start(wasi, linker).await {
+      return Err(anyhow::anyhow!("start function trapped: {}", e));
+    }
+    let entry = vm.store.call_func_by_name(entry_point, locals);
+    vm.push_call(entry).map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(vm)
   }
 
   pub async fn deserialize(vm: &[u8]) -> Result<ExecMachine> {
@@ -56,122 +68,161 @@ impl ExecMachine {
     }
   }
 
-  pub async fn exec(&mut self, wasi: &mut WasiSnapshotPreview1) -> Result<&ExecMachine, TrapError> {
-    let mut import = init_import();
+  // 積まれた呼び出しを実行する。importした関数はlinkerのホスト関数を呼ぶ
+  pub async fn exec(&mut self, wasi: &mut WasiSnapshotPreview1, linker: &mut Linker) -> Result<&ExecMachine, TrapError> {
     while let Some(func) = self.call_stack.pop() {
       match func {
         FuncInstance::External(ext) => {
-          match import.get_mut(&ext.env_name) {
-            Some(h) => {
-              match h.get_mut(&ext.name) {
-                Some(func) => {
-                  let ret = func(wasi, &mut self.store, ext.params);
-                  self.value_stack.push(ret.unwrap().unwrap());
-                },
-                None => {
-                  return Err(TrapError{
-                    message: "unknown func name".to_string(),
-                    vm: self.clone()
-                  })
-                }
-              }
-            }
-            None => {
-              return Err(TrapError{
-                message: "unknown env name".to_string(),
-                vm: self.clone()
-              })
+          let result = linker.call(&ext.env_name, &ext.name, wasi, &mut self.store, ext.params.clone())
+            .map_err(|e| Trap::HostError(e.to_string()));
+          match result {
+            Ok(ret) => self.value_stack.extend(ret),
+            Err(trap) => {
+              let mut e = TrapError::new(trap);
+              e.backtrace = self.backtrace(FuncInstance::External(ext).display_name());
+              return Err(self.attach_snapshot(e));
             }
           }
         },
-        FuncInstance::Internal(func) => {self.run(func).await?;},
+        FuncInstance::Internal(func) => {
+          let func_idx = func.func_idx as usize;
+          let pc = func.frame.pc;
+          if let Err(mut e) = self.run(func).await {
+            e.func_idx = Some(func_idx);
+            e.pc = Some(pc);
+            let trapped = match self.store.get_func(func_idx) {
+              Some(f) => f.display_name(),
+              None => format!("func {}", func_idx),
+            };
+            e.backtrace = self.backtrace(trapped);
+            return Err(self.attach_snapshot(e));
+          }
+        },
       }
     }
     Ok(self)
   }
 
-  pub fn init_without_start(wasm: Wasm) -> ExecMachine {
+  // トラップした関数から呼び出し元へ遡って関数名を並べる
+  fn backtrace(&self, trapped: String) -> Vec<String> {
+    let mut frames = vec![trapped];
+    for caller in self.call_stack.iter().rev() {
+      frames.push(caller.display_name());
+    }
+    frames
+  }
+
+  // VMの複製は重いので、明示的に有効にされたときだけ取る
+  fn attach_snapshot(&self, mut e: TrapError) -> TrapError {
+    if self.snapshot_on_trap && !self.store.has_shared_memory() {
+      e.vm = Some(Box::new(self.clone()));
+    }
+    e
+  }
+
+  // start関数を実行せずにインスタンス化する。start関数はrun_startを呼ぶまでstartに残る
+  // importがlinkerに型の合う形で定義されていなければ失敗する
+  pub fn init_without_start(wasm: Wasm, linker: &Linker) -> Result<ExecMachine> {
+    validate(&wasm)?;
+    linker.resolve(&wasm)?;
     let mut vm = ExecMachine::new();
     let func_instances = FuncInstance::new(&wasm);
-    vm.store = Store::new(func_instances.clone(), &wasm);
-    vm
+    vm.store = Store::new(func_instances.clone(), &wasm, linker)?;
+    if let Some(func_idx) = wasm.start_section {
+      let func_idx = func_idx as usize;
+      if func_idx >= vm.store.funcs.len() {
+        return Err(anyhow::anyhow!("unknown function {}", func_idx));
+      }
+      vm.start = Some(func_idx);
+    }
+    Ok(vm)
   }
 
-  pub async fn invoke(&mut self,wasi: &mut WasiSnapshotPreview1, entry_point: String, locals: Vec<Value>) -> Result<&ExecMachine, TrapError> {
-    self.call_stack.push(self.store.call_func_by_name(&entry_point, locals));
-    self.exec(wasi).await
+  // 同じインスタンスの別スレッドとしてentry_pointを呼ぶVMを作る
+  // 共有メモリはこのVMと共有し、それ以外のストアの中身は複製する。それぞれ別のtokioタスクでexecできる
+  pub fn new_thread(&self, entry_point: &str, locals: Vec<Value>) -> Result<ExecMachine> {
+    let mut vm = ExecMachine::new();
+    vm.store = self.store.clone();
+    let entry = vm.store.call_func_by_name(entry_point, locals);
+    vm.push_call(entry).map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(vm)
   }
 
-  pub async fn run(&mut self, mut func: InternalFunc)  -> Result<&ExecMachine, TrapError> {
-    let Some(instr) = func.instrs.get(func.pc) else {
+  // 遅延させたstart関数を最後まで実行する。積まれていた呼び出しはそのまま残す
+  pub async fn run_start(&mut self, wasi: &mut WasiSnapshotPreview1, linker: &mut Linker) -> Result<&ExecMachine, TrapError> {
+    let Some(func_idx) = self.start.take() else {
       return Ok(self);
     };
+    let pending = std::mem::take(&mut self.call_stack);
+    let Some(start) = self.store.call_func(func_idx, vec![]) else {
+      return Err(TrapError::new(Trap::Internal(format!("start function {} not found", func_idx))));
+    };
+    self.push_call(start)?;
+    self.exec(wasi, linker).await?;
+    self.call_stack = pending;
+    Ok(self)
+  }
+
+  pub async fn invoke(&mut self,wasi: &mut WasiSnapshotPreview1, linker: &mut Linker, entry_point: String, locals: Vec<Value>) -> Result<&ExecMachine, TrapError> {
+    let entry = self.store.call_func_by_name(&entry_point, locals);
+    self.push_call(entry)?;
+    self.exec(wasi, linker).await
+  }
+
+  pub async fn run(&mut self, mut func: InternalFunc)  -> Result<&ExecMachine, TrapError> {
+    let Some(instr) = func.instrs.get(func.frame.pc) else {
+      return self.return_func(&func);
+    };
 
     println!("call_stack: {}", self.call_stack.len());
     // if self.call_stack.len() == 6 {
 
-    println!("instr: {:?}, pc: {}, stack: {:?}, locals: {:?}", instr, func.pc, self.value_stack, func.locals);
+    println!("instr: {:?}, pc: {}, stack: {:?}, locals: {:?}", instr, func.frame.pc, self.value_stack, func.frame.locals);
     println!("label_stack: {:#?}", func.label_stack);
     // }
     match instr {
       Instructions::Nop => {},
       Instructions::Unreachable => {
-        return Err(TrapError {
-          message: "Unreachable".to_string(),
-          vm: self.clone(),
-        });
-      },
-      Instructions::Block(block) => {
-        let frame = BlockFrame::new(self.value_stack.clone(), block.clone(), false);
-        self.value_stack.clear();
-        func.label_stack.push(frame);
+        return Err(TrapError::new(Trap::Unreachable));
       },
-      Instructions::Loop(block) => {
-        let frame = BlockFrame::new(self.value_stack.clone(), block.clone(), true);
-        self.value_stack.clear();
-        func.label_stack.push(frame);
+      Instructions::Block(block) | Instructions::Loop(block) => {
+        self.enter_block(&mut func.label_stack, block)?;
       },
       Instructions::If(block) => {
         let val = self.value_stack.pop().unwrap();
         match val {
           Value::I32(v) => {
             if v != 0 {
-              let frame = BlockFrame::new(self.value_stack.clone(), block.clone(), false);
-              self.value_stack.clear();
-              func.label_stack.push(frame);
+              self.enter_block(&mut func.label_stack, block)?;
+            } else if let Some(else_pc) = block.else_pc {
+              self.enter_block(&mut func.label_stack, block)?;
+              func.frame.pc = else_pc;
             } else {
-              let frame = BlockFrame::new(self.value_stack.clone(), block.clone(), false);
-              self.value_stack.clear();
-              func.label_stack.push(frame);
-              func.pc = block.jump_pc;
+              // elseが無ければ引数がそのまま結果になるのでラベルは積まずにendを飛び越す
+              func.frame.pc = block.jump_pc;
             }
           },
           _ => {
-            return Err(TrapError {
-              message: "If: invalid value type".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("If: invalid value type".to_string())));
           }
         }
       },
       Instructions::Else => {
-        loop {
-          func.pc += 1;
-          let instr = func.instrs.get(func.pc).unwrap();
-          if instr == &Instructions::End {
-            func.label_stack.pop();
-            break;
+        // then節の終わり。対応するendの次へ進む
+        let frame = match func.label_stack.pop() {
+          Some(f) => f,
+          None => {
+            return Err(TrapError::new(Trap::Internal("Else: label stack underflow".to_string())));
           }
-        }
+        };
+        func.frame.pc = frame.jump_pc;
+        self.end_block(frame)?;
       },
       Instructions::End => {
         let frame = match func.label_stack.pop() {
           Some(f) => f,
           None => {
-            return Err(TrapError {
-              message: "End: label stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("End: label stack underflow".to_string())));
           }
         };
         
@@ -179,6 +230,10 @@ impl ExecMachine {
       },
       Instructions::Br(idx) => {
         let idx = *idx as usize;
+        // 一番外側のラベルは関数本体そのもの
+        if idx == func.label_stack.len() {
+          return self.return_func(&func);
+        }
         self.pop_labels(&mut func, idx)?;
       },
       Instructions::BrIf(idx) => {
@@ -186,14 +241,14 @@ impl ExecMachine {
           Some(Value::I32(val)) => {
             if val != 0 {
               let idx = *idx as usize;
+              if idx == func.label_stack.len() {
+                return self.return_func(&func);
+              }
               self.pop_labels(&mut func, idx)?;
             }
           },
           _ => {
-            return Err(TrapError {
-              message: "BrIf: invalid value type".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("BrIf: invalid value type".to_string())));
           }
         }
       },
@@ -201,64 +256,77 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(Value::I32(v)) => v,
           _ => {
-            return Err(TrapError {
-              message: "BrTable: invalid value type".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("BrTable: invalid value type".to_string())));
           }
         };
 
-        let idx = if val as usize >= labelidxs.len() {
+        let idx = if val as u32 as usize >= labelidxs.len() {
           *default
         } else {
-          labelidxs[val as usize]
+          labelidxs[val as u32 as usize]
         };
-        
-        self.pop_labels(&mut func, idx as usize)?;
+        let idx = idx as usize;
+        if idx == func.label_stack.len() {
+          return self.return_func(&func);
+        }
+        self.pop_labels(&mut func, idx)?;
       },
       Instructions::Return => {
-        return Ok(self);
+        return self.return_func(&func);
       },
       Instructions::Call(idx) => {
         // self.serialize_vm();
-        let callee = self.store.get_func(*idx as usize);
-        let mut args = Vec::new();
-        for pty in callee.param_types().iter() {
-          match self.value_stack.pop() {
-            Some(v) => {
-              if v.eq_for_value_type(pty) {
-                args.insert(0, v);
-              } else {
-                return Err(TrapError {
-                  message: "Call: invalid value type".to_string(),
-                  vm: self.clone(),
-                });
-              }
-            }
-            None => {
-              return Err(TrapError {
-                message: "Call: value stack underflow".to_string(),
-                vm: self.clone(),
-              });
-            }
+        let idx = *idx as usize;
+        return self.call(func, idx);
+      }
+      Instructions::CallIndirect(type_idx, table_idx) => {
+        let func_addr = self.indirect_func(*type_idx, *table_idx)?;
+        return self.call(func, func_addr);
+      }
+      Instructions::ReturnCall(idx) => {
+        let idx = *idx as usize;
+        return self.return_call(func, idx);
+      }
+      Instructions::ReturnCallIndirect(type_idx, table_idx) => {
+        let func_addr = self.indirect_func(*type_idx, *table_idx)?;
+        return self.return_call(func, func_addr);
+      }
+      Instructions::Throw(tag_idx) => {
+        let tag = *tag_idx as usize;
+        let Some(tag_type) = self.store.tags.get(tag) else {
+          return Err(TrapError::new(Trap::Internal(format!("Throw: tag {} not found", tag))));
+        };
+        let fields = self.pop_values(&tag_type.param_types.clone(), "Throw")?;
+        if self.store.needs_exn_gc() {
+          self.collect_exns(&func, &fields);
+        }
+        let exn_addr = self.store.alloc_exn(ExnInst { tag, fields });
+        return self.throw_exn(func, exn_addr);
+      }
+      Instructions::ThrowRef => {
+        match self.value_stack.pop() {
+          Some(Value::ExnRef(Some(exn_addr))) => return self.throw_exn(func, exn_addr),
+          Some(Value::ExnRef(None)) => {
+            return Err(TrapError::new(Trap::NullExceptionReference));
+          }
+          _ => {
+            return Err(TrapError::new(Trap::Internal("ThrowRef: invalid value type".to_string())));
           }
         }
-        let called_func = self.store.call_func(*idx as usize, args);
-        func.pc += 1;
-        self.call_stack.push(FuncInstance::Internal(func));
-        self.call_stack.push(called_func);
-        return Ok(self);
       }
+      Instructions::TryTable(block, catches) => {
+        self.enter_block(&mut func.label_stack, block)?;
+        if let Some(frame) = func.label_stack.last_mut() {
+          frame.catches = catches.clone();
+        }
+      },
       Instructions::Drop => {
         self.value_stack.pop();
       },
       Instructions::Select => {
-        let (Some(c), Some(a), Some(b)) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop()) 
+        let (Some(c), Some(b), Some(a)) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "Select: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("Select: value stack underflow".to_string())))
         };
         if c.eq_for_value_type(&ValueType::I32) {
           if c != Value::I32(0) {
@@ -267,19 +335,13 @@ impl ExecMachine {
             self.value_stack.push(b);
           }
         } else {
-          return Err(TrapError {
-            message: "Select: invalid value type".to_string(),
-            vm: self.clone(),
-          });
+          return Err(TrapError::new(Trap::Internal("Select: invalid value type".to_string())));
         }
       },
       Instructions::SelectValtype(_) => {
-        let (Some(c), Some(a), Some(b)) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop()) 
+        let (Some(c), Some(b), Some(a)) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "Select: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("Select: value stack underflow".to_string())))
         };
         if c.eq_for_value_type(&ValueType::I32) {
           if c != Value::I32(0) {
@@ -288,460 +350,364 @@ impl ExecMachine {
             self.value_stack.push(b);
           }
         } else {
-          return Err(TrapError {
-            message: "Select: invalid value type".to_string(),
-            vm: self.clone(),
-          });
+          return Err(TrapError::new(Trap::Internal("Select: invalid value type".to_string())));
         }
       }
-      Instructions::I32Load { align: _, offset } => {
+      Instructions::I32Load { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I32Load: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Load: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i32>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: i32 = i32::from_le_bytes([value[0], value[1], value[2], value[3]]);
         self.value_stack.push(Value::I32(value));
       },
-      Instructions::I64Load { align: _, offset } => {
+      Instructions::I64Load { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I64Load: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I64Load: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i64>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: i64 = i64::from_le_bytes([value[0], value[1], value[2], value[3], value[4], value[5], value[6], value[7]]);
         self.value_stack.push(Value::I64(value));
       },
-      Instructions::F32Load { align: _, offset } => {
+      Instructions::F32Load { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "F32Load: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("F32Load: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<f32>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: f32 = f32::from_le_bytes([value[0], value[1], value[2], value[3]]);
         self.value_stack.push(Value::F32(value));
       },
-      Instructions::F64Load { align: _, offset } => {
+      Instructions::F64Load { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "F64Load: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("F64Load: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<f64>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: f64 = f64::from_le_bytes([value[0], value[1], value[2], value[3], value[4], value[5], value[6], value[7]]);
         self.value_stack.push(Value::F64(value));
       },
-      Instructions::I32Load8S { align: _, offset } => {
+      Instructions::I32Load8S { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I32Load8S: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Load8S: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i8>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: i8 = i8::from_le_bytes([value[0]]);
         self.value_stack.push(Value::I32(value as i32));
       },
-      Instructions::I32Load8U { align: _, offset } => {
+      Instructions::I32Load8U { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I32Load8U: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Load8U: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<u8>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: u8 = u8::from_le_bytes([value[0]]);
         self.value_stack.push(Value::I32(value as i32));
       },
-      Instructions::I32Load16S { align: _, offset } => {
+      Instructions::I32Load16S { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I32Load16S: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Load16S: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i16>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: i16 = i16::from_le_bytes([value[0], value[1]]);
         self.value_stack.push(Value::I32(value as i32));
       },
-      Instructions::I32Load16U { align: _, offset } => {
+      Instructions::I32Load16U { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I32Load16U: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Load16U: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<u16>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: u16 = u16::from_le_bytes([value[0], value[1]]);
         self.value_stack.push(Value::I32(value as i32));
       },
-      Instructions::I64Load8S { align: _, offset } => {
+      Instructions::I64Load8S { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I64Load8S: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I64Load8S: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i8>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: i8 = i8::from_le_bytes([value[0]]);
         self.value_stack.push(Value::I64(value as i64));
       },
-      Instructions::I64Load8U { align: _, offset } => {
+      Instructions::I64Load8U { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I64Load8U: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I64Load8U: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<u8>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: u8 = u8::from_le_bytes([value[0]]);
         self.value_stack.push(Value::I64(value as i64));
       },
-      Instructions::I64Load16S { align: _, offset } => {
+      Instructions::I64Load16S { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I64Load16S: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I64Load16S: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i16>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: i16 = i16::from_le_bytes([value[0], value[1]]);
         self.value_stack.push(Value::I64(value as i64));
       },
-      Instructions::I64Load16U { align: _, offset } => {
+      Instructions::I64Load16U { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I64Load16U: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I64Load16U: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<u16>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: u16 = u16::from_le_bytes([value[0], value[1]]);
         self.value_stack.push(Value::I64(value as i64));
       },
-      Instructions::I64Load32S { align: _, offset } => {
+      Instructions::I64Load32S { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I64Load32S: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I64Load32S: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i32>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: i32 = i32::from_le_bytes([value[0], value[1], value[2], value[3]]);
         self.value_stack.push(Value::I64(value as i64));
       },
-      Instructions::I64Load32U { align: _, offset } => {
+      Instructions::I64Load32U { align: _, memory, offset } => {
         let Some(addr) = self.value_stack.pop() 
         else { 
-          return Err(TrapError {
-            message: "I64Load32U: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I64Load32U: value stack underflow".to_string())))
         };
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<u32>();
-        let value = self.store.memories[0].load(*offset, addr, size as u32).unwrap();
+        let value = self.memory(*memory)?.load(*offset, addr, size as u32)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
         let value: u32 = u32::from_le_bytes([value[0], value[1], value[2], value[3]]);
         self.value_stack.push(Value::I64(value as i64));
       },
-      Instructions::I32Store { align: _, offset } => {
+      Instructions::I32Store { align: _, memory, offset } => {
         let (Some(value), Some(addr)) = (self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "I32Store: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Store: value stack underflow".to_string())))
         };
 
-        let addr = Into::<i32>::into(addr) as usize;
-        let offset = (*offset) as usize;
-        let at = addr + offset; // 2
-        let end = at + std::mem::size_of::<i32>();
+        let addr = to_addr(addr)?;
+        let size = std::mem::size_of::<i32>();
 
-        let memory = self
-                        .store
-                        .memories
-                        .get_mut(0)
-                        .unwrap();
+        let memory = self.memory(*memory)?;
         let value: i32 = value.into();
-        memory.memory[at..end].copy_from_slice(&value.to_le_bytes());
+        memory.store(*offset, addr, size as u32, &value.to_le_bytes())
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
       },
-      Instructions::I64Store { align: _, offset } => {
+      Instructions::I64Store { align: _, memory, offset } => {
         let (Some(value), Some(addr)) = (self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "I32Store: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Store: value stack underflow".to_string())))
         };
 
-        let addr = Into::<i32>::into(addr) as usize;
-        let offset = (*offset) as usize;
-        let at = addr + offset; // 2
-        let end = at + std::mem::size_of::<i64>();
+        let addr = to_addr(addr)?;
+        let size = std::mem::size_of::<i64>();
 
-        let memory = self
-                        .store
-                        .memories
-                        .get_mut(0)
-                        .unwrap();
+        let memory = self.memory(*memory)?;
         let value: i64 = value.into();
-        memory.memory[at..end].copy_from_slice(&value.to_le_bytes());
+        memory.store(*offset, addr, size as u32, &value.to_le_bytes())
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
       },
-      Instructions::F32Store { align: _, offset } => {
+      Instructions::F32Store { align: _, memory, offset } => {
         let (Some(value), Some(addr)) = (self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "I32Store: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Store: value stack underflow".to_string())))
         };
 
-        let addr = Into::<i32>::into(addr) as usize;
-        let offset = (*offset) as usize;
-        let at = addr + offset; // 2
-        let end = at + std::mem::size_of::<f32>();
+        let addr = to_addr(addr)?;
+        let size = std::mem::size_of::<f32>();
 
-        let memory = self
-                        .store
-                        .memories
-                        .get_mut(0)
-                        .unwrap();
+        let memory = self.memory(*memory)?;
         let value: f32 = value.into();
-        memory.memory[at..end].copy_from_slice(&value.to_le_bytes());
+        memory.store(*offset, addr, size as u32, &value.to_le_bytes())
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
       },
-      Instructions::F64Store { align: _, offset } => {
+      Instructions::F64Store { align: _, memory, offset } => {
         let (Some(value), Some(addr)) = (self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "I32Store: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Store: value stack underflow".to_string())))
         };
 
-        let addr = Into::<i32>::into(addr) as usize;
-        let offset = (*offset) as usize;
-        let at = addr + offset; // 2
-        let end = at + std::mem::size_of::<f64>();
+        let addr = to_addr(addr)?;
+        let size = std::mem::size_of::<f64>();
 
-        let memory = self
-                        .store
-                        .memories
-                        .get_mut(0)
-                        .unwrap();
+        let memory = self.memory(*memory)?;
         let value: f64 = value.into();
-        memory.memory[at..end].copy_from_slice(&value.to_le_bytes());
+        memory.store(*offset, addr, size as u32, &value.to_le_bytes())
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
       },
-      Instructions::I32Store8 { align: _, offset } => {
+      Instructions::I32Store8 { align: _, memory, offset } => {
         let (Some(value), Some(addr)) = (self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "I32Store: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Store: value stack underflow".to_string())))
         };
         let value: i32 = value.into();
         let value = value.to_le_bytes().to_vec();
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i8>();
 
-        let memory = self
-                        .store
-                        .memories
-                        .get_mut(0)
-                        .unwrap();
+        let memory = self.memory(*memory)?;
         match memory.store(*offset, addr, size as u32, &value) {
           Ok(_) => {},
-          Err(e) => {
-            return Err(TrapError {
-              message: format!("I32Store8: {}", e),
-              vm: self.clone(),
-            });
+          Err(_) => {
+            return Err(TrapError::new(Trap::MemoryOutOfBounds));
           }
         }
       },
-      Instructions::I32Store16 { align: _, offset } => {
+      Instructions::I32Store16 { align: _, memory, offset } => {
         let (Some(value), Some(addr)) = (self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "I32Store: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Store: value stack underflow".to_string())))
         };
         let value: i32 = value.into();
         let value = value.to_le_bytes().to_vec();
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i16>();
 
-        let memory = self
-                        .store
-                        .memories
-                        .get_mut(0)
-                        .unwrap();
+        let memory = self.memory(*memory)?;
         match memory.store(*offset, addr, size as u32, &value) {
           Ok(_) => {},
-          Err(e) => {
-            return Err(TrapError {
-              message: format!("I32Store16: {}", e),
-              vm: self.clone(),
-            });
+          Err(_) => {
+            return Err(TrapError::new(Trap::MemoryOutOfBounds));
           }
         }
       },
-      Instructions::I64Store8 { align: _, offset } => {
+      Instructions::I64Store8 { align: _, memory, offset } => {
         let (Some(value), Some(addr)) = (self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "I32Store: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Store: value stack underflow".to_string())))
         };
         let value: i64 = value.into();
         let value = value.to_le_bytes().to_vec();
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i8>();
 
-        let memory = self
-                        .store
-                        .memories
-                        .get_mut(0)
-                        .unwrap();
+        let memory = self.memory(*memory)?;
         match memory.store(*offset, addr, size as u32, &value) {
           Ok(_) => {},
-          Err(e) => {
-            return Err(TrapError {
-              message: format!("I64Store8: {}", e),
-              vm: self.clone(),
-            });
+          Err(_) => {
+            return Err(TrapError::new(Trap::MemoryOutOfBounds));
           }
         }
       },
-      Instructions::I64Store16 { align: _, offset } => {
+      Instructions::I64Store16 { align: _, memory, offset } => {
         let (Some(value), Some(addr)) = (self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "I32Store: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Store: value stack underflow".to_string())))
         };
         let value: i64 = value.into();
         let value = value.to_le_bytes().to_vec();
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i16>();
 
-        let memory = self
-                        .store
-                        .memories
-                        .get_mut(0)
-                        .unwrap();
+        let memory = self.memory(*memory)?;
         match memory.store(*offset, addr, size as u32, &value) {
           Ok(_) => {},
-          Err(e) => {
-            return Err(TrapError {
-              message: format!("I64Store16: {}", e),
-              vm: self.clone(),
-            });
+          Err(_) => {
+            return Err(TrapError::new(Trap::MemoryOutOfBounds));
           }
         }
       },
-      Instructions::I64Store32 { align: _, offset } => {
+      Instructions::I64Store32 { align: _, memory, offset } => {
         let (Some(value), Some(addr)) = (self.value_stack.pop(), self.value_stack.pop()) 
         else { 
-          return Err(TrapError {
-            message: "I32Store: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
+          return Err(TrapError::new(Trap::Internal("I32Store: value stack underflow".to_string())))
         };
         let value: i64 = value.into();
         let value = value.to_le_bytes().to_vec();
-        let addr = Into::<i32>::into(addr) as u32;
+        let addr = to_addr(addr)?;
         let size = std::mem::size_of::<i32>();
 
-        let memory = self
-                        .store
-                        .memories
-                        .get_mut(0)
-                        .unwrap();
+        let memory = self.memory(*memory)?;
         match memory.store(*offset, addr, size as u32, &value) {
           Ok(_) => {},
-          Err(e) => {
-            return Err(TrapError {
-              message: format!("I64Store32: {}", e),
-              vm: self.clone(),
-            });
+          Err(_) => {
+            return Err(TrapError::new(Trap::MemoryOutOfBounds));
           }
         }
       },
-      Instructions::MemorySize => {
-        let size = self.store.memories[0].size();
+      Instructions::MemorySize(memory) => {
+        let size = self.memory(*memory)?.size();
         self.value_stack.push(size);
       },
-      Instructions::MemoryCopy => {
-        let (Some(dst), Some(src), Some(len)) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop()) 
-        else { 
-          return Err(TrapError {
-            message: "MemoryCopy: value stack underflow".to_string(), 
-            vm: self.clone() 
-          })
-        };
-        match (dst, src, len) {
-          (Value::I32(dst), Value::I32(src), Value::I32(len)) => {
-            self.store.memories[0].copy(dst as usize, src as usize, len as usize).unwrap();
-          },
-          _ => {
-            return Err(TrapError {
-              message: "MemoryCopy: invalid value type".to_string(),
-              vm: self.clone(),
-            });
-          }
-        }
-      }
+      Instructions::MemoryGrow(memory) => {
+        let Some(n) = self.value_stack.pop()
+        else {
+          return Err(TrapError::new(Trap::Internal("MemoryGrow: value stack underflow".to_string())))
+        };
+        let n = to_addr(n)?;
+        let prev = self.memory(*memory)?.grow(n);
+        self.value_stack.push(prev);
+      },
+      // アドレスと長さはメモリがmemory64ならi64、そうでなければi32で積まれている
+      Instructions::MemoryInit(data_idx, memory) => {
+        let (Some(Value::I32(n)), Some(Value::I32(src)), Some(dst)) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop())
+        else {
+          return Err(TrapError::new(Trap::Internal("MemoryInit: invalid operands".to_string())))
+        };
+        self.store.memory_init(*data_idx as usize, *memory as usize, to_addr(dst)?, src as u32 as u64, n as u32 as u64)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
+      },
+      Instructions::DataDrop(data_idx) => {
+        self.store.data_drop(*data_idx as usize);
+      },
+      Instructions::MemoryCopy(dst_idx, src_idx) => {
+        let (Some(n), Some(src), Some(dst)) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop())
+        else {
+          return Err(TrapError::new(Trap::Internal("MemoryCopy: value stack underflow".to_string())))
+        };
+        self.store.memory_copy(*dst_idx as usize, *src_idx as usize, to_addr(dst)?, to_addr(src)?, to_addr(n)?)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
+      },
+      Instructions::MemoryFill(memory) => {
+        let (Some(n), Some(Value::I32(val)), Some(dst)) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop())
+        else {
+          return Err(TrapError::new(Trap::Internal("MemoryFill: invalid operands".to_string())))
+        };
+        self.memory(*memory)?.fill(to_addr(dst)?, to_addr(n)?, val as u8)
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
+      },
       Instructions::I32Const(val) => {
         self.value_stack.push(Value::I32(*val));
       },
@@ -760,10 +726,7 @@ impl ExecMachine {
         let ret = match crate::exec::op::exec_itestop(instr, val) {
           Ok(v) => v,
           Err(e) => {
-            return Err(TrapError {
-              message: format!("I32Eqz: {}", e),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal(format!("I32Eqz: {}", e))));
           }
         };
         self.value_stack.push(ret);
@@ -791,19 +754,13 @@ impl ExecMachine {
         let (b, a) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(b), Some(a)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "I32Eq: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I32Eq: value stack underflow".to_string())));
           }
         };
         let ret = match crate::exec::op::exec_irelop(instr, b, a) {
           Ok(v) => v,
           Err(e) => {
-            return Err(TrapError {
-              message: format!("I32Eq: {}", e),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal(format!("I32Eq: {}", e))));
           }
         };
         self.value_stack.push(ret);
@@ -823,19 +780,13 @@ impl ExecMachine {
         let (b, a) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(b), Some(a)) => (b, a),
           _ => {
-            return Err(TrapError {
-              message: "F32Eq: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Eq: value stack underflow".to_string())));
           }
         };
         let ret = match crate::exec::op::exec_frelop(instr, b, a) {
           Ok(v) => v,
           Err(e) => {
-            return Err(TrapError {
-              message: format!("F32Eq: {}", e),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal(format!("F32Eq: {}", e))));
           }
         };
         self.value_stack.push(ret);
@@ -849,19 +800,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I32Ctz: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I32Ctz: value stack underflow".to_string())));
           }
         };
         let ret = match crate::exec::op::exec_iuop(instr, val) {
           Ok(v) => v,
           Err(e) => {
-            return Err(TrapError {
-              message: format!("I32Ctz: {}", e),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal(format!("I32Ctz: {}", e))));
           }
         };
         self.value_stack.push(ret);
@@ -899,19 +844,16 @@ impl ExecMachine {
         let (b, a) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(b), Some(a)) => (b, a),
           _ => {
-            return Err(TrapError {
-              message: "I32Add: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I32Add: value stack underflow".to_string())));
           }
         };
         let ret = match crate::exec::op::exec_ibinop(instr, a, b) {
           Ok(v) => v,
-          Err(e) => {
-            return Err(TrapError {
-              message: format!("I32Add: {}", e),
-              vm: self.clone(),
-            });
+          Err(OpError::Trap(trap)) => {
+            return Err(TrapError::new(trap));
+          }
+          Err(OpError::InvalidType(e)) => {
+            return Err(TrapError::new(Trap::Internal(format!("I32Add: {}", e))));
           }
         };
         self.value_stack.push(ret);
@@ -920,19 +862,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32Abs: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Abs: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F32(v) => Value::F32(v.abs()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Abs".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Abs".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -941,19 +877,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32Neg: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Neg: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F32(v) => Value::F32(-v),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Neg".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Neg".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -962,19 +892,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32Ceil: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Ceil: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F32(v) => Value::F32(v.ceil()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Ceil".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Ceil".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -983,19 +907,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32Floor: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Floor: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F32(v) => Value::F32(v.floor()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Floor".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Floor".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1004,19 +922,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32Trunc: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Trunc: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F32(v) => Value::F32(v.trunc()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Trunc".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Trunc".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1025,19 +937,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32Nearest: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Nearest: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F32(v) => Value::F32(v.round()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Nearest".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Nearest".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1046,19 +952,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32Sqrt: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Sqrt: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F32(v) => Value::F32(v.sqrt()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Sqrt".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Sqrt".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1067,19 +967,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F32Add: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Add: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F32(a), Value::F32(b)) => Value::F32(a + b),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Add".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Add".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1088,19 +982,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F32Sub: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Sub: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F32(a), Value::F32(b)) => Value::F32(b - a),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Sub".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Sub".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1109,19 +997,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F32Mul: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Mul: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F32(a), Value::F32(b)) => Value::F32(a * b),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Mul".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Mul".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1130,20 +1012,14 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F32Div: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Div: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F32(0.0), Value::F32(_)) => Value::F32(0.0),
           (Value::F32(a), Value::F32(b)) => Value::F32(b / a),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Div".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Div".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1152,19 +1028,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F32Min: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Min: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F32(a), Value::F32(b)) => Value::F32(a.min(b)),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Min".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Min".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1173,19 +1043,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F32Max: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Max: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F32(a), Value::F32(b)) => Value::F32(a.max(b)),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Max".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Max".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1194,19 +1058,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F32Copysign: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32Copysign: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F32(a), Value::F32(b)) => Value::F32(a.copysign(b)),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32Copysign".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32Copysign".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1215,19 +1073,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64Abs: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Abs: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F64(v) => Value::F64(v.abs()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Abs".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Abs".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1236,19 +1088,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64Neg: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Neg: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F64(v) => Value::F64(-v),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Neg".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Neg".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1257,19 +1103,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64Ceil: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Ceil: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F64(v) => Value::F64(v.ceil()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Ceil".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Ceil".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1278,19 +1118,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64Floor: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Floor: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F64(v) => Value::F64(v.floor()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Floor".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Floor".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1299,19 +1133,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64Trunc: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Trunc: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F64(v) => Value::F64(v.trunc()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Trunc".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Trunc".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1320,19 +1148,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64Nearest: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Nearest: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F64(v) => Value::F64(v.round()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Nearest".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Nearest".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1341,19 +1163,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64Sqrt: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Sqrt: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F64(v) => Value::F64(v.sqrt()),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Sqrt".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Sqrt".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1362,19 +1178,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F64Add: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Add: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F64(a), Value::F64(b)) => Value::F64(a + b),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Add".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Add".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1383,19 +1193,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F64Sub: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Sub: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F64(a), Value::F64(b)) => Value::F64(b - a),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Sub".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Sub".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1404,19 +1208,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F64Mul: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Mul: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F64(a), Value::F64(b)) => Value::F64(a * b),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Mul".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Mul".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1425,20 +1223,14 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F64Div: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Div: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F64(0.0), Value::F64(_)) => Value::F64(0.0),
           (Value::F64(a), Value::F64(b)) => Value::F64(b / a),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Div".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Div".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1447,19 +1239,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F64Min: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Min: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F64(a), Value::F64(b)) => Value::F64(a.min(b)),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Min".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Min".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1468,19 +1254,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F64Max: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Max: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F64(a), Value::F64(b)) => Value::F64(a.max(b)),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Max".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Max".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1489,19 +1269,13 @@ impl ExecMachine {
         let (a, b) = match (self.value_stack.pop(), self.value_stack.pop()) {
           (Some(a), Some(b)) => (a, b),
           _ => {
-            return Err(TrapError {
-              message: "F64Copysign: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64Copysign: value stack underflow".to_string())));
           }
         };
         let ret = match (a, b) {
           (Value::F64(a), Value::F64(b)) => Value::F64(a.copysign(b)),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64Copysign".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64Copysign".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1510,103 +1284,60 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I32WrapI64: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I32WrapI64: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::I64(v) => Value::I32(v as i32),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I32WrapI64".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for I32WrapI64".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::I32TruncF32S => {
+      Instructions::I32TruncF32S
+      | Instructions::I32TruncF32U
+      | Instructions::I32TruncF64S
+      | Instructions::I32TruncF64U
+      | Instructions::I64TruncF32S
+      | Instructions::I64TruncF32U
+      | Instructions::I64TruncF64S
+      | Instructions::I64TruncF64U => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I32TruncF32S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal(format!("{:?}: value stack underflow", instr))));
           }
         };
-        let ret = match val {
-          Value::F32(v) => Value::I32(v as i32),
-          _ => {
-            return Err(TrapError {
-              message: "Invalid type for I32TruncF32S".to_string(),
-              vm: self.clone(),
-            });
-          }
-        };
-        self.value_stack.push(ret);
-      },
-      Instructions::I32TruncF32U => {
-        let val = match self.value_stack.pop() {
-          Some(v) => v,
-          None => {
-            return Err(TrapError {
-              message: "I32TruncF32U: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+        let ret = match crate::exec::op::exec_trunc(instr, val) {
+          Ok(v) => v,
+          Err(OpError::Trap(trap)) => {
+            return Err(TrapError::new(trap));
           }
-        };
-        let ret = match val {
-          Value::F32(v) => Value::I32(v as i32),
-          _ => {
-            return Err(TrapError {
-              message: "Invalid type for I32TruncF32U".to_string(),
-              vm: self.clone(),
-            });
+          Err(OpError::InvalidType(e)) => {
+            return Err(TrapError::new(Trap::Internal(format!("{:?}: {}", instr, e))));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::I32TruncF64S => {
+      Instructions::I32TruncSatF32S
+      | Instructions::I32TruncSatF32U
+      | Instructions::I32TruncSatF64S
+      | Instructions::I32TruncSatF64U
+      | Instructions::I64TruncSatF32S
+      | Instructions::I64TruncSatF32U
+      | Instructions::I64TruncSatF64S
+      | Instructions::I64TruncSatF64U => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I32TruncF64S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
-          }
-        };
-        let ret = match val {
-          Value::F64(v) => Value::I32(v as i32),
-          _ => {
-            return Err(TrapError {
-              message: "Invalid type for I32TruncF64S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal(format!("{:?}: value stack underflow", instr))));
           }
         };
-        self.value_stack.push(ret);
-      },
-      Instructions::I32TruncF64U => {
-        let val = match self.value_stack.pop() {
-          Some(v) => v,
-          None => {
-            return Err(TrapError {
-              message: "I32TruncF64U: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
-          }
-        };
-        let ret = match val {
-          Value::F64(v) => Value::I32(v as i32),
-          _ => {
-            return Err(TrapError {
-              message: "Invalid type for I32TruncF64U".to_string(),
-              vm: self.clone(),
-            });
+        let ret = match crate::exec::op::exec_trunc_sat(instr, val) {
+          Ok(v) => v,
+          Err(e) => {
+            return Err(TrapError::new(Trap::Internal(format!("{:?}: {}", instr, e))));
           }
         };
         self.value_stack.push(ret);
@@ -1615,19 +1346,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I64ExtendI32S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I64ExtendI32S: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::I32(v) => Value::I64(v as i64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I64ExtendI32S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for I64ExtendI32S".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1636,271 +1361,164 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I64ExtendI32U: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
-          }
-        };
-        let ret = match val {
-          Value::I32(v) => Value::I64(v as i64),
-          _ => {
-            return Err(TrapError {
-              message: "Invalid type for I64ExtendI32U".to_string(),
-              vm: self.clone(),
-            });
-          }
-        };
-        self.value_stack.push(ret);
-      },
-      Instructions::I64TruncF32S => {
-        let val = match self.value_stack.pop() {
-          Some(v) => v,
-          None => {
-            return Err(TrapError {
-              message: "I64TruncF32S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I64ExtendI32U: value stack underflow".to_string())));
           }
         };
         let ret = match val {
-          Value::F32(v) => Value::I64(v as i64),
+          Value::I32(v) => Value::I64(v as u32 as i64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I64TruncF32S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for I64ExtendI32U".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::I64TruncF32U => {
-        let val = match self.value_stack.pop() {
-          Some(v) => v,
-          None => {
-            return Err(TrapError {
-              message: "I64TruncF32U: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
-          }
-        };
-        let ret = match val {
-          Value::F32(v) => Value::I64(v as i64),
-          _ => {
-            return Err(TrapError {
-              message: "Invalid type for I64TruncF32U".to_string(),
-              vm: self.clone(),
-            });
-          }
-        };
-        self.value_stack.push(ret);
-      },
-      Instructions::I64TruncF64S => {
+      Instructions::F32ConvertI32S => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I64TruncF64S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32ConvertI32S: value stack underflow".to_string())));
           }
         };
         let ret = match val {
-          Value::F64(v) => Value::I64(v as i64),
+          Value::I32(v) => Value::F32(v as f32),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I64TruncF64S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32ConvertI32S".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::I64TruncF64U => {
+      Instructions::F32ConvertI32U => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I64TruncF64U: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32ConvertI32U: value stack underflow".to_string())));
           }
         };
         let ret = match val {
-          Value::F64(v) => Value::I64(v as i64),
+          Value::I32(v) => Value::F32(v as u32 as f32),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I64TruncF64U".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32ConvertI32U".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::F32ConvertI32S => {
+      Instructions::F32ConvertI64S => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32ConvertI32S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32ConvertI64S: value stack underflow".to_string())));
           }
         };
         let ret = match val {
-          Value::I32(v) => Value::F32(v as f32),
+          Value::I64(v) => Value::F32(v as f32),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32ConvertI32S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32ConvertI64S".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::F32ConvertI32U => {
+      Instructions::F32ConvertI64U => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32ConvertI32U: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32ConvertI64U: value stack underflow".to_string())));
           }
         };
         let ret = match val {
-          Value::I32(v) => Value::F32(v as f32),
+          Value::I64(v) => Value::F32(v as u64 as f32),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32ConvertI32U".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32ConvertI64U".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::F32ConvertI64S => {
+      Instructions::F64ConvertI32S => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32ConvertI64S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64ConvertI32S: value stack underflow".to_string())));
           }
         };
         let ret = match val {
-          Value::I64(v) => Value::F32(v as f32),
+          Value::I32(v) => Value::F64(v as f64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32ConvertI64S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64ConvertI32S".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::F32ConvertI64U => {
+      Instructions::F64ConvertI32U => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32ConvertI64U: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64ConvertI32U: value stack underflow".to_string())));
           }
         };
         let ret = match val {
-          Value::I64(v) => Value::F32(v as f32),
+          Value::I32(v) => Value::F64(v as u32 as f64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32ConvertI64U".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64ConvertI32U".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::F64ConvertI32S => {
+      Instructions::F64ConvertI64S => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64ConvertI32S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64ConvertI64S: value stack underflow".to_string())));
           }
         };
         let ret = match val {
-          Value::I32(v) => Value::F64(v as f64),
+          Value::I64(v) => Value::F64(v as f64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64ConvertI32S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64ConvertI64S".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::F64ConvertI32U => {
+      Instructions::F64ConvertI64U => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64ConvertI32U: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64ConvertI64U: value stack underflow".to_string())));
           }
         };
         let ret = match val {
-          Value::I32(v) => Value::F64(v as f64),
+          Value::I64(v) => Value::F64(v as u64 as f64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64ConvertI32U".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64ConvertI64U".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::F64ConvertI64S => {
+      Instructions::F32DemoteF64 => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64ConvertI64S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32DemoteF64: value stack underflow".to_string())));
           }
         };
+        // asは最近接偶数への丸めで、範囲外は無限大になる
         let ret = match val {
-          Value::I64(v) => Value::F64(v as f64),
+          Value::F64(v) => Value::F32(v as f32),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64ConvertI64S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32DemoteF64".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
-      Instructions::F64ConvertI64U => {
+      Instructions::F64PromoteF32 => {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64ConvertI64U: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64PromoteF32: value stack underflow".to_string())));
           }
         };
         let ret = match val {
-          Value::I64(v) => Value::F64(v as f64),
+          Value::F32(v) => Value::F64(v as f64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64ConvertI64U".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64PromoteF32".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1909,19 +1527,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I32ReinterpretF32: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I32ReinterpretF32: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F32(v) => Value::I32(v.to_bits() as i32),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I32ReinterpretF32".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for I32ReinterpretF32".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1930,19 +1542,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I64ReinterpretF64: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I64ReinterpretF64: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::F64(v) => Value::I64(v.to_bits() as i64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I64ReinterpretF64".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for I64ReinterpretF64".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1951,19 +1557,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F32ReinterpretI32: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F32ReinterpretI32: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::I32(v) => Value::F32(f32::from_bits(v as u32)),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F32ReinterpretI32".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F32ReinterpretI32".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1972,19 +1572,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "F64ReinterpretI64: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("F64ReinterpretI64: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::I64(v) => Value::F64(f64::from_bits(v as u64)),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for F64ReinterpretI64".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for F64ReinterpretI64".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -1993,19 +1587,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I32Extend8S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I32Extend8S: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::I32(v) => Value::I32((v as i8) as i32),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I32Extend8S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for I32Extend8S".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -2014,19 +1602,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I32Extend16S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I32Extend16S: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::I32(v) => Value::I32((v as i16) as i32),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I32Extend16S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for I32Extend16S".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -2035,19 +1617,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I64Extend8S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I64Extend8S: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::I64(v) => Value::I64((v as i8) as i64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I64Extend8S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for I64Extend8S".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -2056,19 +1632,13 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I64Extend16S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I64Extend16S: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::I64(v) => Value::I64((v as i16) as i64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I64Extend16S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for I64Extend16S".to_string())));
           }
         };
         self.value_stack.push(ret);
@@ -2077,33 +1647,23 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "I64Extend32S: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("I64Extend32S: value stack underflow".to_string())));
           }
         };
         let ret = match val {
           Value::I64(v) => Value::I64((v as i32) as i64),
           _ => {
-            return Err(TrapError {
-              message: "Invalid type for I64Extend32S".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("Invalid type for I64Extend32S".to_string())));
           }
         };
         self.value_stack.push(ret);
       },
       Instructions::LocalGet(idx) => {
         self.validate_local(&func, idx)?;
-        let val = match func.locals.get(*idx as usize) {
+        let val = match func.frame.locals.get(*idx as usize) {
           Some(v) => v.clone(),
           None => {
-            let message = format!("LocalGet: local {} not found", idx);
-            return Err(TrapError {
-              message,
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal(format!("LocalGet: local {} not found", idx))));
           }
         };
         self.value_stack.push(val);
@@ -2113,47 +1673,32 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "LocalSet: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("LocalSet: value stack underflow".to_string())));
           }
         };
-        if !Value::match_value(&val, &func.locals[*idx as usize]) {
-          return Err(TrapError {
-            message: "LocalSet: invalid value type".to_string(),
-            vm: self.clone(),
-          });
+        if !Value::match_value(&val, &func.frame.locals[*idx as usize]) {
+          return Err(TrapError::new(Trap::Internal("LocalSet: invalid value type".to_string())));
         }
-        func.locals[*idx as usize] = val;
+        func.frame.locals[*idx as usize] = val;
       },
       Instructions::LocalTee(idx) => {
         self.validate_local(&func, idx)?;
         let val = match self.value_stack.last() {
           Some(v) => v.clone(),
           None => {
-            return Err(TrapError {
-              message: "LocalTee: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("LocalTee: value stack underflow".to_string())));
           }
         };
-        if !Value::match_value(&val, &func.locals[*idx as usize]) {
-          return Err(TrapError {
-            message: "LocalTee: invalid value type".to_string(),
-            vm: self.clone(),
-          });
+        if !Value::match_value(&val, &func.frame.locals[*idx as usize]) {
+          return Err(TrapError::new(Trap::Internal("LocalTee: invalid value type".to_string())));
         }
-        func.locals[*idx as usize] = val;
+        func.frame.locals[*idx as usize] = val;
       },
       Instructions::GlobalGet(idx) => {
         let val = match self.store.globals.get(*idx as usize) {
           Some(v) => v.value.clone(),
           None => {
-            return Err(TrapError {
-              message: "GlobalGet: global not found".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("GlobalGet: global not found".to_string())));
           }
         };
         self.value_stack.push(val);
@@ -2162,74 +1707,366 @@ impl ExecMachine {
         let val = match self.value_stack.pop() {
           Some(v) => v,
           None => {
-            return Err(TrapError {
-              message: "GlobalSet: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("GlobalSet: value stack underflow".to_string())));
           }
         };
         
         let global = match self.store.globals.get_mut(*idx as usize) {
           Some(g) => g,
           None => {
-            return Err(TrapError {
-              message: "GlobalSet: global not found".to_string(),
-              vm: self.clone(),
-            });
+            return Err(TrapError::new(Trap::Internal("GlobalSet: global not found".to_string())));
           }
         };
 
         if !global.mutability {
-          return Err(TrapError {
-            message: "GlobalSet: global is immutable".to_string(),
-            vm: self.clone(),
-          });
+          return Err(TrapError::new(Trap::Internal("GlobalSet: global is immutable".to_string())));
         };
 
         if !Value::match_value(&val, &global.value) {
-          return Err(TrapError {
-            message: "GlobalSet: invalid value type".to_string(),
-            vm: self.clone(),
-          });
+          return Err(TrapError::new(Trap::Internal("GlobalSet: invalid value type".to_string())));
         }
         global.value = val;
       }
+      Instructions::TableGet(table_idx) => {
+        let Some(Value::I32(index)) = self.value_stack.pop()
+        else {
+          return Err(TrapError::new(Trap::Internal("TableGet: invalid operands".to_string())))
+        };
+        let table = &self.store.tables[*table_idx as usize];
+        let Some(value) = table.elems.get(index as u32 as usize) else {
+          return Err(TrapError::new(Trap::TableOutOfBounds));
+        };
+        self.value_stack.push(value.clone());
+      },
+      Instructions::TableSet(table_idx) => {
+        let (Some(value), Some(Value::I32(index))) = (self.value_stack.pop(), self.value_stack.pop())
+        else {
+          return Err(TrapError::new(Trap::Internal("TableSet: invalid operands".to_string())))
+        };
+        let table = &mut self.store.tables[*table_idx as usize];
+        let Some(elem) = table.elems.get_mut(index as u32 as usize) else {
+          return Err(TrapError::new(Trap::TableOutOfBounds));
+        };
+        *elem = value;
+      },
+      Instructions::TableInit(elem_idx, table_idx) => {
+        let (Some(Value::I32(n)), Some(Value::I32(src)), Some(Value::I32(dst))) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop())
+        else {
+          return Err(TrapError::new(Trap::Internal("TableInit: invalid operands".to_string())))
+        };
+        self.store.table_init(*elem_idx as usize, *table_idx as usize, dst as u32 as usize, src as u32 as usize, n as u32 as usize)
+          .map_err(|_| TrapError::new(Trap::TableOutOfBounds))?;
+      },
+      Instructions::ElemDrop(elem_idx) => {
+        self.store.elem_drop(*elem_idx as usize);
+      },
+      Instructions::TableCopy(dst_idx, src_idx) => {
+        let (Some(Value::I32(n)), Some(Value::I32(src)), Some(Value::I32(dst))) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop())
+        else {
+          return Err(TrapError::new(Trap::Internal("TableCopy: invalid operands".to_string())))
+        };
+        self.store.table_copy(*dst_idx as usize, *src_idx as usize, dst as u32 as usize, src as u32 as usize, n as u32 as usize)
+          .map_err(|_| TrapError::new(Trap::TableOutOfBounds))?;
+      },
+      Instructions::TableGrow(table_idx) => {
+        let (Some(Value::I32(n)), Some(init)) = (self.value_stack.pop(), self.value_stack.pop())
+        else {
+          return Err(TrapError::new(Trap::Internal("TableGrow: invalid operands".to_string())))
+        };
+        let size = self.store.tables[*table_idx as usize].grow(n as u32, init);
+        self.value_stack.push(size);
+      },
+      Instructions::TableSize(table_idx) => {
+        let size = self.store.tables[*table_idx as usize].size();
+        self.value_stack.push(size);
+      },
+      Instructions::TableFill(table_idx) => {
+        let (Some(Value::I32(n)), Some(value), Some(Value::I32(index))) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop())
+        else {
+          return Err(TrapError::new(Trap::Internal("TableFill: invalid operands".to_string())))
+        };
+        self.store.tables[*table_idx as usize].fill(index as u32 as usize, n as u32 as usize, value)
+          .map_err(|_| TrapError::new(Trap::TableOutOfBounds))?;
+      },
+      Instructions::RefNull(reftype) => {
+        self.value_stack.push(Value::null(reftype));
+      },
+      Instructions::RefIsNull => {
+        let Some(value) = self.value_stack.pop()
+        else {
+          return Err(TrapError::new(Trap::Internal("RefIsNull: value stack underflow".to_string())))
+        };
+        self.value_stack.push(Value::I32(value.is_null() as i32));
+      },
+      Instructions::RefFunc(func_idx) => {
+        self.value_stack.push(Value::FuncRef(Some(*func_idx as usize)));
+      },
+      Instructions::MemoryAtomicWait32 { memory, offset, .. } | Instructions::MemoryAtomicWait64 { memory, offset, .. } => {
+        let (Some(Value::I64(timeout)), Some(expected), Some(addr)) = (self.value_stack.pop(), self.value_stack.pop(), self.value_stack.pop()) else {
+          return Err(TrapError::new(Trap::Internal("MemoryAtomicWait: invalid value type".to_string())));
+        };
+        let expected = match expected {
+          Value::I32(v) => v.to_le_bytes().to_vec(),
+          Value::I64(v) => v.to_le_bytes().to_vec(),
+          _ => {
+            return Err(TrapError::new(Trap::Internal("MemoryAtomicWait: invalid value type".to_string())));
+          }
+        };
+        let memory = self.memory(*memory)?;
+        let Some(shared) = memory.shared.clone() else {
+          return Err(TrapError::new(Trap::ExpectedSharedMemory));
+        };
+        let addr = effective_addr(memory, *offset, to_addr(addr)?, expected.len() as u32)?;
+        // 他のスレッドのnotifyを待つ間はこのタスクだけが眠る
+        let result = shared.wait(addr, &expected, timeout).await
+          .map_err(|_| TrapError::new(Trap::MemoryOutOfBounds))?;
+        self.value_stack.push(Value::I32(result));
+      },
+      _ if instr.atomic_opcode().is_some() => {
+        match exec_atomic(instr, &mut self.value_stack, &mut self.store.memories) {
+          Ok(()) => {},
+          Err(OpError::Trap(trap)) => {
+            return Err(TrapError::new(trap));
+          }
+          Err(OpError::InvalidType(e)) => {
+            return Err(TrapError::new(Trap::Internal(format!("{:?}: {}", instr, e))));
+          }
+        }
+      },
+      _ if instr.simd_opcode().is_some() => {
+        match crate::exec::simd::exec_simd(instr, &mut self.value_stack, &mut self.store.memories) {
+          Ok(()) => {},
+          Err(OpError::Trap(trap)) => {
+            return Err(TrapError::new(trap));
+          }
+          Err(OpError::InvalidType(e)) => {
+            return Err(TrapError::new(Trap::Internal(format!("{:?}: {}", instr, e))));
+          }
+        }
+      },
       _ => panic!("Unknown instruction: {:?}", instr),
     }
 
-    func.pc += 1;
+    func.frame.pc += 1;
     self.call_stack.push(FuncInstance::Internal(func));
     Ok(self)
   }
 
-  pub fn end_block(&mut self, frame: BlockFrame) -> Result<(), TrapError> {
-    let ret = match frame.return_type {
-      BlockType::Void => None,
-      BlockType::Value(t) => {
-        match self.value_stack.pop() {
-          Some(v) => {
-            if v.eq_for_value_type(&t) {
-              Some(v)
-            } else {
-              return Err(TrapError {
-                message: "End: invalid return type".to_string(),
-                vm: self.clone(),
-              });
-            }
-          }
-          None => {
-            return Err(TrapError {
-              message: "End: value stack underflow".to_string(),
-              vm: self.clone(),
-            });
+  fn call(&mut self, mut func: InternalFunc, func_idx: usize) -> Result<&ExecMachine, TrapError> {
+    let args = self.pop_args(func_idx)?;
+    let Some(called_func) = self.store.call_func(func_idx, args) else {
+      return Err(TrapError::new(Trap::UndefinedElement));
+    };
+    func.frame.pc += 1;
+    self.call_stack.push(FuncInstance::Internal(func));
+    self.push_call(called_func)?;
+    Ok(self)
+  }
+
+  // 末尾呼び出しは呼び出し元のフレームを捨てて置き換えるのでcall_stackが伸びない
+  fn return_call(&mut self, func: InternalFunc, func_idx: usize) -> Result<&ExecMachine, TrapError> {
+    let args = self.pop_args(func_idx)?;
+    self.value_stack.truncate(func.frame.sp);
+    let Some(called_func) = self.store.call_func(func_idx, args) else {
+      return Err(TrapError::new(Trap::UndefinedElement));
+    };
+    self.push_call(called_func)?;
+    Ok(self)
+  }
+
+  fn pop_args(&mut self, func_idx: usize) -> Result<Vec<Value>, TrapError> {
+    let Some(callee) = self.store.get_func(func_idx) else {
+      return Err(TrapError::new(Trap::UndefinedElement));
+    };
+    let mut args = Vec::new();
+    for pty in callee.param_types().iter() {
+      match self.value_stack.pop() {
+        Some(v) => {
+          if v.eq_for_value_type(pty) {
+            args.insert(0, v);
+          } else {
+            return Err(TrapError::new(Trap::Internal("Call: invalid value type".to_string())));
           }
         }
+        None => {
+          return Err(TrapError::new(Trap::Internal("Call: value stack underflow".to_string())));
+        }
+      }
+    }
+    Ok(args)
+  }
+
+  // テーブルから呼び出す関数を引き、型が一致するか確かめる
+  fn indirect_func(&mut self, type_idx: u32, table_idx: u32) -> Result<usize, TrapError> {
+    let elem_idx = match self.value_stack.pop() {
+      Some(Value::I32(v)) => v as u32 as usize,
+      _ => {
+        return Err(TrapError::new(Trap::Internal("CallIndirect: invalid value type".to_string())));
+      }
+    };
+    let func_addr = match self.store.tables.get(table_idx as usize) {
+      Some(table) => match table.elems.get(elem_idx) {
+        Some(Value::FuncRef(Some(addr))) => *addr,
+        Some(Value::FuncRef(None)) => {
+          return Err(TrapError::new(Trap::UninitializedElement));
+        }
+        Some(_) => {
+          return Err(TrapError::new(Trap::Internal("CallIndirect: table element is not a funcref".to_string())));
+        }
+        None => {
+          return Err(TrapError::new(Trap::UndefinedElement));
+        }
+      },
+      None => {
+        return Err(TrapError::new(Trap::Internal(format!("CallIndirect: table {} not found", table_idx))));
+      }
+    };
+    let expected = self.store.types.get(type_idx as usize);
+    // 範囲外のアドレスが入っていても関数が無いものとしてトラップにする
+    let Some(callee) = self.store.get_func(func_addr) else {
+      return Err(TrapError::new(Trap::UndefinedElement));
+    };
+    let actual = self.store.types.get(callee.type_idx() as usize);
+    if expected.is_none() || expected != actual {
+      return Err(TrapError::new(Trap::IndirectCallTypeMismatch));
+    }
+    Ok(func_addr)
+  }
+
+  // 例外を捕まえるtry_tableまで、関数の中のラベルとcall_stackの呼び出し元を遡る
+  fn throw_exn(&mut self, mut func: InternalFunc, exn_addr: usize) -> Result<&ExecMachine, TrapError> {
+    let tag = match self.store.exns.get(exn_addr) {
+      Some(exn) => exn.tag,
+      None => {
+        return Err(TrapError::new(Trap::Internal(format!("Throw: exception {} not found", exn_addr))));
+      }
+    };
+    // 捕まえる節がどこにもなければスタックを崩さずにトラップにする
+    let callers = self.call_stack.iter().filter_map(|f| match f {
+      FuncInstance::Internal(f) => Some(f.label_stack.iter()),
+      FuncInstance::External(_) => None,
+    });
+    let caught = func.label_stack.iter().chain(callers.flatten())
+      .any(|frame| frame.catches.iter().any(|catch| catch.catches(tag)));
+    if !caught {
+      return Err(TrapError::new(Trap::UncaughtException));
+    }
+
+    loop {
+      while let Some(frame) = func.label_stack.pop() {
+        let Some(catch) = frame.catches.iter().find(|catch| catch.catches(tag)) else {
+          continue;
+        };
+        self.value_stack.truncate(frame.sp);
+        if let Catch::Catch { .. } | Catch::CatchRef { .. } = catch {
+          self.value_stack.extend(self.store.exns[exn_addr].fields.clone());
+        }
+        if let Catch::CatchRef { .. } | Catch::CatchAllRef { .. } = catch {
+          self.value_stack.push(Value::ExnRef(Some(exn_addr)));
+        }
+        // catch節のラベルはtry_tableを取り除いた後のラベルスタックで数える
+        let label = catch.label() as usize;
+        if label == func.label_stack.len() {
+          return self.return_func(&func);
+        }
+        self.pop_labels(&mut func, label)?;
+        func.frame.pc += 1;
+        self.call_stack.push(FuncInstance::Internal(func));
+        return Ok(self);
+      }
+      // この関数では捕まらなかったので呼び出し元へ抜ける
+      self.value_stack.truncate(func.frame.sp);
+      func = match self.call_stack.pop() {
+        Some(FuncInstance::Internal(caller)) => caller,
+        _ => {
+          return Err(TrapError::new(Trap::Internal("Throw: handler not found".to_string())));
+        }
+      };
+    }
+  }
+
+  // 実行中の関数は呼び出し元と違ってcall_stackに積まれていないので別に渡す
+  // fieldsはこれから投げる例外のフィールド
+  fn collect_exns(&mut self, func: &InternalFunc, fields: &[Value]) {
+    let frames = self.call_stack.iter().flat_map(|f| match f {
+      FuncInstance::Internal(f) => f.frame.locals.iter(),
+      FuncInstance::External(f) => f.params.iter(),
+    });
+    let roots = self.value_stack.iter().chain(&func.frame.locals).chain(frames).chain(fields);
+    self.store.collect_exns(roots);
+  }
+
+  fn memory(&mut self, memory_idx: u32) -> Result<&mut MemoryInst, TrapError> {
+    match self.store.memories.get_mut(memory_idx as usize) {
+      Some(memory) => Ok(memory),
+      None => Err(TrapError::new(Trap::Internal(format!("memory {} not found", memory_idx)))),
+    }
+  }
+
+  // ブロックの型から引数と返り値の型を引く
+  fn block_signature(&self, block_type: &BlockType) -> Result<(Vec<ValueType>, Vec<ValueType>), TrapError> {
+    match block_type {
+      BlockType::Void => Ok((vec![], vec![])),
+      BlockType::Value(t) => Ok((vec![], vec![t.clone()])),
+      BlockType::TypeIndex(type_idx) => match self.store.types.get(*type_idx as usize) {
+        Some(t) => Ok((t.param_types.clone(), t.return_types.clone())),
+        None => Err(TrapError::new(Trap::Internal(format!("Block: type {} not found", type_idx)))),
       },
+    }
+  }
+
+  // 引数はスタックに積んだまま、その下の高さを覚えてラベルを積む
+  fn enter_block(&mut self, label_stack: &mut Vec<BlockFrame>, block: &Block) -> Result<(), TrapError> {
+    let (param_types, return_types) = self.block_signature(&block.block_type)?;
+    let sp = match self.value_stack.len().checked_sub(param_types.len()) {
+      Some(sp) => sp,
+      None => {
+        return Err(TrapError::new(Trap::Internal("Block: value stack underflow".to_string())));
+      }
     };
-    self.value_stack = frame.value_stack_evac;
-    if let Some(v) = ret {
-      self.value_stack.push(v);
+    label_stack.push(BlockFrame::new(sp, block, param_types, return_types));
+    Ok(())
+  }
+
+  // typesの並びの値をスタックから取り出す。返り値は積まれていた順のまま
+  fn pop_values(&mut self, types: &[ValueType], instr: &str) -> Result<Vec<Value>, TrapError> {
+    if self.value_stack.len() < types.len() {
+      return Err(TrapError::new(Trap::Internal(format!("{}: value stack underflow", instr))));
+    }
+    let values = self.value_stack.split_off(self.value_stack.len() - types.len());
+    if !values.iter().zip(types).all(|(v, t)| v.eq_for_value_type(t)) {
+      return Err(TrapError::new(Trap::Internal(format!("{}: invalid value type", instr))));
     }
+    Ok(values)
+  }
+
+  pub fn end_block(&mut self, frame: BlockFrame) -> Result<(), TrapError> {
+    let results = self.pop_values(&frame.return_types, "End")?;
+    self.value_stack.truncate(frame.sp);
+    self.value_stack.extend(results);
+    Ok(())
+  }
+
+  // 返り値だけを残して呼び出し時の高さまでスタックを戻す
+  fn return_func(&mut self, func: &InternalFunc) -> Result<&ExecMachine, TrapError> {
+    if self.value_stack.len() < func.frame.sp + func.frame.arity {
+      return Err(TrapError::new(Trap::Internal("Return: value stack underflow".to_string())));
+    }
+    let results = self.pop_values(&func.return_types, "Return")?;
+    self.value_stack.truncate(func.frame.sp);
+    self.value_stack.extend(results);
+    Ok(self)
+  }
+
+  // 呼び出し時の値スタックの高さをフレームに記録してから積む
+  fn push_call(&mut self, mut func: FuncInstance) -> Result<(), TrapError> {
+    if self.call_stack.len() >= MAX_CALL_DEPTH {
+      return Err(TrapError::new(Trap::StackExhausted));
+    }
+    if let FuncInstance::Internal(f) = &mut func {
+      f.frame.sp = self.value_stack.len();
+    }
+    self.call_stack.push(func);
     Ok(())
   }
 
@@ -2240,10 +2077,7 @@ impl ExecMachine {
             func.label_stack.pop();
         },
         None => {
-          return Err(TrapError {
-            message: "End: label stack underflow".to_string(),
-            vm: self.clone(),
-          });
+          return Err(TrapError::new(Trap::Internal("End: label stack underflow".to_string())));
         }
       }
     }
@@ -2251,18 +2085,19 @@ impl ExecMachine {
 
     match func.label_stack.pop() {
       Some(frame) => {
-        func.pc = frame.jump_pc;
+        func.frame.pc = frame.jump_pc;
         if frame.is_loop {
+          // loopの先頭に戻るときは引数だけを持ってやり直す
+          let params = self.pop_values(&frame.param_types, "Br")?;
+          self.value_stack.truncate(frame.sp);
+          self.value_stack.extend(params);
           func.label_stack.push(frame);
         } else {
           self.end_block(frame)?;
         }
       }
       None => {
-        return Err(TrapError {
-          message: "End: label stack underflow".to_string(),
-          vm: self.clone(),
-        });
+        return Err(TrapError::new(Trap::Internal("End: label stack underflow".to_string())));
       }
     }
     
@@ -2270,16 +2105,26 @@ impl ExecMachine {
   }
 
   pub fn validate_local(&self, func: &InternalFunc, idx: &u32) -> Result<(), TrapError> {
-    if func.locals.len() <= *idx as usize {
-      return Err(TrapError {
-        message: format!("LocalGet: local {} not found", idx),
-        vm: self.clone(),
-      });
+    if func.frame.locals.len() <= *idx as usize {
+      return Err(TrapError::new(Trap::Internal(format!("LocalGet: local {} not found", idx))));
     }
     Ok(())
   }
 
-  pub fn serialize_vm(&self) -> Vec<u8> {
-    bincode::serialize(&self).unwrap()
+  // VM全体をシリアライズする。他のスレッドが書き換えうる共有メモリを持つときは失敗する
+  pub fn serialize_vm(&self) -> Result<Vec<u8>> {
+    if self.store.has_shared_memory() {
+      return Err(anyhow::anyhow!("cannot serialize a machine with a shared memory"));
+    }
+    Ok(bincode::serialize(&self)?)
+  }
+}
+
+// メモリのアドレスや長さとして積まれた値を符号なしで読む。32bitのメモリではi32、memory64ではi64になる
+fn to_addr(value: Value) -> Result<u64, TrapError> {
+  match value {
+    Value::I32(v) => Ok(v as u32 as u64),
+    Value::I64(v) => Ok(v as u64),
+    _ => Err(TrapError::new(Trap::Internal(format!("invalid address type: {:?}", value)))),
   }
-}
\ No newline at end of file
+}
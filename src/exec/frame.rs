@@ -1,22 +1,25 @@
+use serde::{Deserialize, Serialize};
+
 use super::value::Value;
 
-#[derive(Debug, Clone)]
+// 関数呼び出し1回分の活性化フレーム
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Frame {
-  pub pc: isize,
+  pub pc: usize,
+  // 呼び出した時点の値スタックの高さ。引数は取り除いた後の値
   pub sp: usize,
-  pub func_idx: usize,
+  // 関数の返り値の個数
   pub arity: usize,
   pub locals: Vec<Value>,
 }
 
 impl Frame {
-  pub fn new(pc: isize, sp: usize, func_idx: usize, arity: usize, locals: Vec<Value>) -> Frame {
+  pub fn new(pc: usize, sp: usize, arity: usize, locals: Vec<Value>) -> Frame {
     Frame {
       pc,
       sp,
-      func_idx,
       arity,
       locals,
     }
   }
-}
\ No newline at end of file
+}
@@ -7,6 +7,7 @@ use crate::binary::instructions::Instructions;
 use crate::binary::value_type::ValueType;
 use crate::binary::wasm::Wasm;
 use super::block_frame::BlockFrame;
+use super::frame::Frame;
 use super::value::Value;
 
 #[derive(Debug, Clone, PartialEq , Serialize, Deserialize)]
@@ -17,11 +18,14 @@ pub enum FuncInstance {
 
 #[derive(Debug, Clone, PartialEq , Serialize, Deserialize)]
 pub struct InternalFunc {
+  pub func_idx: u32,
   pub name: Option<String>,
+  pub export_name: Option<String>,
+  pub type_idx: u32,
   pub param_types: Vec<ValueType>,
-  pub locals: Vec<Value>,
+  pub return_types: Vec<ValueType>,
   pub instrs: Vec<Instructions>,
-  pub pc: usize,
+  pub frame: Frame,
   pub label_stack: Vec<BlockFrame>,
 }
 
@@ -29,6 +33,7 @@ pub struct InternalFunc {
 pub struct ExternalFunc {
   pub env_name: String,
   pub name: String,
+  pub type_idx: u32,
   pub param_types: Vec<ValueType>,
   pub params: Vec<Value>,
   pub return_types: Vec<ValueType>,
@@ -38,11 +43,11 @@ impl FuncInstance {
   pub fn new(wasm: &Wasm) -> Vec<FuncInstance> {
     let mut func_instances: Vec<FuncInstance> = Vec::new();
 
-    match (&wasm.type_section, &wasm.function_section, &wasm.export_section, &wasm.code_section) {
-      (Some(types), Some(funcs), Some(exports), Some(codes)) => {
-        let import_func_count: usize;
+    if let (Some(types), Some(funcs), Some(codes)) = (&wasm.type_section, &wasm.function_section, &wasm.code_section) {
+        // exportセクションが無いモジュールでもstart関数やテーブルから呼ばれる関数は作る
+        let exports = wasm.export_section.as_deref().unwrap_or_default();
+        let mut import_func_count = 0;
         if let Some(inputs) = &wasm.import_section {
-          import_func_count = inputs.len();
           for input in inputs.iter() {
             if let ImportDesc::Func(type_idx) = &input.desc {
               let (param_types, return_types) = match types.get(*type_idx as usize) {
@@ -52,53 +57,61 @@ impl FuncInstance {
               func_instances.push(FuncInstance::External(ExternalFunc {
                 env_name: input.module.clone(),
                 name: input.field.clone(),
+                type_idx: *type_idx,
                 param_types,
                 params: Vec::new(),
                 return_types,
               }));
+              // 関数以外のimportは関数のインデックス空間に入らない
+              import_func_count += 1;
             }
           }
-        } else { import_func_count = 0 }
+        }
 
         for (i, (func, code)) in funcs.iter().zip(codes.iter()).enumerate() {
 
-          let param_types = match types.get(func.type_idx as usize) {
-            Some(t) => t.param_types.clone(),
+          let (param_types, return_types) = match types.get(func.type_idx as usize) {
+            Some(t) => (t.param_types.clone(), t.return_types.clone()),
             None => panic!("type_idx {} not found", func.type_idx),
           };
           let mut local_types = param_types.clone();
           let mut locals = Vec::new();
           locals.extend(param_types.iter().map(|t| t.to_init_value()));
+          // countの合計はvalidateで上限を確かめてある
           for l in code.locals.iter() {
             local_types.extend(l.to_value_type_vec());
             locals.extend(vec![l.value_type.to_init_value(); l.count as usize]);
           }
-          let _ = code.locals.iter().map(|l| local_types.extend(l.to_value_type_vec()));
 
-          let name: Option<String> = exports.iter().find_map(|e| {
+          let func_idx = (i + import_func_count) as u32;
+          let export_name: Option<String> = exports.iter().find_map(|e| {
             if !(e.desc == ExportDesc::Func) {
               return None;
             }
-            if e.func_idx == (i + import_func_count) as u32 {
+            if e.idx == func_idx {
               Some(e.name.clone())
             } else {
               None
             }
           });
-          
+          // nameセクションがあればそちらを優先し、なければexport名で代用する
+          let name = wasm.name_section.as_ref()
+            .and_then(|names| names.func_name(func_idx).cloned())
+            .or(export_name.clone());
 
           func_instances.push(FuncInstance::Internal(InternalFunc {
+            func_idx,
             name,
+            export_name,
+            type_idx: func.type_idx,
             param_types,
-            locals,
+            frame: Frame::new(0, 0, return_types.len(), locals),
+            return_types,
             instrs: code.instrs.clone(),
-            pc: 0,
             label_stack: Vec::new(),
           }));
         
       }
-    },
-      _ => {},
     }
     func_instances
   }
@@ -110,10 +123,42 @@ impl FuncInstance {
     }
   }
 
+  // トラップ時のバックトレース等で使う表示名
+  pub fn display_name(&self) -> String {
+    match self {
+      FuncInstance::Internal(i) => match &i.name {
+        Some(name) => format!("${}", name),
+        None => format!("func {}", i.func_idx),
+      },
+      FuncInstance::External(e) => format!("{}.{}", e.env_name, e.name),
+    }
+  }
+
+  pub fn export_name(&self) -> Option<&String> {
+    match self {
+      FuncInstance::Internal(i) => i.export_name.as_ref(),
+      FuncInstance::External(_) => None,
+    }
+  }
+
+  pub fn type_idx(&self) -> u32 {
+    match self {
+      FuncInstance::Internal(i) => i.type_idx,
+      FuncInstance::External(e) => e.type_idx,
+    }
+  }
+
   pub fn param_types(&self) -> Vec<ValueType> {
     match self {
       FuncInstance::Internal(i) => i.param_types.clone(),
       FuncInstance::External(e) => e.param_types.clone(),
     }
   }
+
+  pub fn return_types(&self) -> Vec<ValueType> {
+    match self {
+      FuncInstance::Internal(i) => i.return_types.clone(),
+      FuncInstance::External(e) => e.return_types.clone(),
+    }
+  }
 }
\ No newline at end of file
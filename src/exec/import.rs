@@ -1,51 +1,39 @@
 #![allow(dead_code)]
 
 use anyhow::{anyhow, Result, Ok};
-use std::{collections::HashMap, env, fs::OpenOptions, io::{Read, Seek, SeekFrom, Write}, mem::ManuallyDrop, path::Path};
-use super::{store::{MemoryInst, Store}, value::Value, wasi::WasiSnapshotPreview1};
-
-pub type ImportFunc = Box<dyn FnMut(&mut WasiSnapshotPreview1, &mut Store, Vec<Value>) -> Result<Option<Value>>>;
-pub type ImportTable = HashMap<String, HashMap<String, ImportFunc>>;
-
-// static IMPORT_FUNCS: LazyLock<Mutex<Arc<Box<ImportTable>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
-
-pub fn init_import() -> ImportTable {
-  let mut import: ImportTable = HashMap::new();
-  let mut add_hash: HashMap<String, ImportFunc> = HashMap::new();
-  add_hash.insert("add".to_owned(), Box::new(|_, _, values| ->Result<Option<Value>> {
-    match (values[0].clone(), values[1].clone()) {
-      (Value::I64(a), Value::I64(b)) => Ok(Some(Value::I64(a + b))),
-      _ => panic!("Invalid arg types in import func")
-    }
-  }));
-  import.insert("env".to_owned(), add_hash);
-
-  let mut wasi_hash: HashMap<String, ImportFunc> = HashMap::new();
-  wasi_hash.insert("fd_write".to_owned(), Box::new(fd_write));
-  wasi_hash.insert("random_get".to_owned(), Box::new(random_get));
-  wasi_hash.insert("fd_prestat_get".to_owned(), Box::new(fd_prestat_get));
-  wasi_hash.insert("fd_prestat_dir_name".to_owned(), Box::new(fd_prestat_dir_name));
-  wasi_hash.insert("fd_close".to_owned(), Box::new(fd_close));
-  wasi_hash.insert("fd_read".to_owned(), Box::new(fd_read));
-  wasi_hash.insert("environ_sizes_get".to_owned(), Box::new(environ_sizes_get));
-  wasi_hash.insert("environ_get".to_owned(), Box::new(environ_get));
-  wasi_hash.insert("path_open".to_owned(), Box::new(path_open));
-  wasi_hash.insert("fd_seek".to_owned(), Box::new(fd_seek));
-
-
-  import.insert("wasi_snapshot_preview1".to_owned(), wasi_hash);
-
-  import
+use std::{env, fs::OpenOptions, io::{Read, Seek, SeekFrom, Write}, mem::ManuallyDrop, path::Path};
+use crate::binary::{type_sec::FuncType, value_type::ValueType};
+use super::{linker::Linker, store::{MemoryInst, Store}, value::Value, wasi::WasiSnapshotPreview1};
+
+pub type ImportFunc = Box<dyn FnMut(&mut WasiSnapshotPreview1, &mut Store, Vec<Value>) -> Result<Vec<Value>> + Send>;
+
+// 対応しているWASI関数をwasi_snapshot_preview1に定義する
+pub fn define_wasi(linker: &mut Linker) -> Result<()> {
+  use ValueType::{I32, I64};
+  let errno = |param_types: &[ValueType]| FuncType { param_types: param_types.to_vec(), return_types: vec![I32] };
+  let module = "wasi_snapshot_preview1";
+  linker
+    .define_func(module, "fd_write", errno(&[I32, I32, I32, I32]), fd_write)?
+    .define_func(module, "random_get", errno(&[I32, I32]), random_get)?
+    .define_func(module, "fd_prestat_get", errno(&[I32, I32]), fd_prestat_get)?
+    .define_func(module, "fd_prestat_dir_name", errno(&[I32, I32, I32]), fd_prestat_dir_name)?
+    .define_func(module, "fd_close", errno(&[I32]), fd_close)?
+    .define_func(module, "fd_read", errno(&[I32, I32, I32, I32]), fd_read)?
+    .define_func(module, "environ_sizes_get", errno(&[I32, I32]), environ_sizes_get)?
+    .define_func(module, "environ_get", errno(&[I32, I32]), environ_get)?
+    .define_func(module, "path_open", errno(&[I32, I32, I32, I32, I32, I64, I64, I32, I32]), path_open)?
+    .define_func(module, "fd_seek", errno(&[I32, I64, I32, I32]), fd_seek)?;
+  Ok(())
 }
 
-pub fn fd_write(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Option<Value>> {
+pub fn fd_write(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Vec<Value>> {
   dbg!("fd_write");
   let args: Vec<i32> = args.into_iter().map(Into::into).collect();
 
   let fd = args[0];
-  let mut iovs = args[1] as usize;
+  let mut iovs = args[1] as u32;
   let iovs_len = args[2];
-  let rp = args[3] as usize;
+  let rp = args[3] as u32;
 
   let file = wasi
     .file_table
@@ -54,95 +42,76 @@ pub fn fd_write(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Va
     .as_mut()
     .ok_or(anyhow!("not found fd"))?;
 
-  let memory = store
-    .memories
-    .get_mut(0)
-    .ok_or(anyhow::anyhow!("not found memory"))?;
+  let memory = store.host_memory()?;
 
   let mut nwritten = 0;
 
   for _ in 0..iovs_len {
-    let start = memory_read(&memory.memory, iovs)? as usize;
+    let start = memory_read_4byte(memory, iovs)? as u32;
     iovs += 4;
 
-    let len: i32 = memory_read(&memory.memory, iovs)?;
+    let len = memory_read_4byte(memory, iovs)? as u32;
     iovs += 4;
 
-    let end = start + len as usize;
-    nwritten += file.write(&memory.memory[start..end])?;
+    nwritten += file.write(&memory.read(start.into(), len.into())?)?;
   }
 
-  memory_write(&mut memory.memory, rp, &nwritten.to_le_bytes())?;
+  memory.store(rp.into(), 0, 4, &(nwritten as u32).to_le_bytes())?;
 
-  Ok(Some(0.into()))
+  Ok(vec![0.into()])
 }
 
-fn memory_read(buf: &[u8], start: usize) -> Result<i32> {
-  let end = start + 4;
-  Ok(<i32>::from_le_bytes(buf[start..end].try_into()?))
-}
-
-fn memory_write(buf: &mut [u8], start: usize, data: &[u8]) -> Result<()> {
-  let end = start + data.len();
-  buf[start..end].copy_from_slice(data);
-  Ok(())
-}
-
-fn random_get(_wasi: &mut WasiSnapshotPreview1,store: &mut Store, args: Vec<Value>) -> Result<Option<Value>> {
+fn random_get(_wasi: &mut WasiSnapshotPreview1,store: &mut Store, args: Vec<Value>) -> Result<Vec<Value>> {
   let args: Vec<i32> = args.into_iter().map(Into::into).collect();
   let buf = args[0] as usize;
   let buf_len = args[1] as usize;
-  for i in 0..buf_len {
-      let random = rand::random();
-      store.memories[0].memory[buf + i] = random;
-  }
-  Ok(Some(Value::I32(0)))
+  let random: Vec<u8> = (0..buf_len).map(|_| rand::random()).collect();
+  store.host_memory()?.write(buf as u32 as u64, &random)?;
+  Ok(vec![Value::I32(0)])
 }
 
-fn fd_prestat_get(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Option<Value>> {
+fn fd_prestat_get(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Vec<Value>> {
   let args: Vec<i32> = args.into_iter().map(Into::into).collect();
   let fd = args[0];
   let buf = args[1] as u32;
 
   let Some(Some(path)) = wasi.file_path.get(fd as usize) else {
-      return Ok(Some(ERRNO_BADF.into()));
+      return Ok(vec![ERRNO_BADF.into()]);
   };
-  store.memories[0].store(buf, 0, 1, &[0])?;
+  store.host_memory()?.store(buf.into(), 0, 1, &[0])?;
   store
-      .memories[0]
-      .store(buf, 4, 4, &(path.len() as i32).to_le_bytes())?;
-  Ok(Some(Value::I32(0)))
+      .host_memory()?
+      .store(buf.into(), 4, 4, &(path.len() as i32).to_le_bytes())?;
+  Ok(vec![Value::I32(0)])
 }
 
 fn fd_prestat_dir_name(
   wasi: &mut WasiSnapshotPreview1,
   store: &mut Store,
   args: Vec<Value>,
-) -> Result<Option<Value>> {
+) -> Result<Vec<Value>> {
   let args: Vec<i32> = args.into_iter().map(Into::into).collect();
   let fd = args[0] as usize;
   let buf = args[1] as usize;
 
   let Some(Some(path)) = wasi.file_path.get(fd) else {
-      return Ok(Some(ERRNO_BADF.into()));
+      return Ok(vec![ERRNO_BADF.into()]);
   };
-  for i in 0..path.len() {
-      store.memories[0].memory[buf + i] = path.as_bytes()[i];
-  }
-  Ok(Some(Value::I32(0)))
+  store.host_memory()?.write(buf as u32 as u64, path.as_bytes())?;
+  Ok(vec![Value::I32(0)])
 }
 
-fn fd_close(wasi: &mut WasiSnapshotPreview1, _store: &mut Store, args: Vec<Value>) -> Result<Option<Value>> {
+fn fd_close(wasi: &mut WasiSnapshotPreview1, _store: &mut Store, args: Vec<Value>) -> Result<Vec<Value>> {
   let args: Vec<i32> = args.into_iter().map(Into::into).collect();
   let fd = args[0] as usize;
   if fd >= 3 {
       wasi.file_table[fd] = None;
       wasi.file_path[fd] = None;
   }
-  Ok(Some(Value::I32(0)))
+  Ok(vec![Value::I32(0)])
 }
 
-fn fd_read(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Option<Value>> {
+fn fd_read(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Vec<Value>> {
   let args = args.into_iter().map(Into::into).collect::<Vec<i32>>();
   let fd = args[0];
   let mut iovs = args[1] as u32;
@@ -157,23 +126,24 @@ fn fd_read(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>)
       .ok_or(anyhow!("Not found fd"))?;
 
   let mut nread = 0;
-  let memory = &mut store.memories[0];
+  let memory = store.host_memory()?;
 
   for _ in 0..iovs_len {
       let start = memory_read_4byte(memory, iovs)? as usize;
       iovs += 4;
       let len = memory_read_4byte(memory, iovs)? as usize;
       iovs += 4;
-      let end = start + len;
-      nread += file
-          .read(&mut memory.memory[start..end])?;
+      let mut buf = vec![0; len];
+      let n = file.read(&mut buf)?;
+      memory.write(start as u32 as u64, &buf[..n])?;
+      nread += n;
   }
-  memory.store(rp as u32, 0, 4, &nread.to_le_bytes())?;
+  memory.store(rp as u32 as u64, 0, 4, &nread.to_le_bytes())?;
 
-  Ok(Some(Value::I32(0)))
+  Ok(vec![Value::I32(0)])
 }
 
-fn environ_sizes_get(_wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Option<Value>> {
+fn environ_sizes_get(_wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Vec<Value>> {
   let args: Vec<i32> = args.into_iter().map(Into::into).collect();
   let environc_offset = args[0] as u32;
   let environ_buf_size_offset = args[1] as u32;
@@ -183,39 +153,39 @@ fn environ_sizes_get(_wasi: &mut WasiSnapshotPreview1, store: &mut Store, args:
       environc += 1;
       environ_buf_size += (env.0.len() + 1 + env.1.len() + 1) as i32;
   }
-  let memory = &mut store.memories[0];
-  memory.store(environc_offset, 0, 4, &environc.to_le_bytes())?;
+  let memory = store.host_memory()?;
+  memory.store(environc_offset.into(), 0, 4, &environc.to_le_bytes())?;
   memory.store(
-      environ_buf_size_offset,
+      environ_buf_size_offset.into(),
       0,
       4,
       &environ_buf_size.to_le_bytes(),
   )?;
-  Ok(Some(Value::I32(0)))
+  Ok(vec![Value::I32(0)])
 }
 
-fn environ_get(_wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Option<Value>> {
+fn environ_get(_wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Vec<Value>> {
   let args: Vec<i32> = args.into_iter().map(Into::into).collect();
   let mut environ_offset = args[0] as u32;
   let mut environ_buf_offset = args[1];
   for (key, value) in env::vars() {
       store
-          .memories[0]
-          .store(environ_offset, 0, 4, &environ_buf_offset.to_le_bytes())?;
+          .host_memory()?
+          .store(environ_offset.into(), 0, 4, &environ_buf_offset.to_le_bytes())?;
       environ_offset += 4;
       let text = format!("{key}={value}\0");
-      store.memories[0].store(
-          environ_buf_offset as u32,
+      store.host_memory()?.store(
+          environ_buf_offset as u32 as u64,
           0,
           text.len() as u32,
           text.as_bytes(),
       )?;
       environ_buf_offset += text.len() as i32;
   }
-  Ok(Some(Value::I32(0)))
+  Ok(vec![Value::I32(0)])
 }
 
-fn path_open(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Option<Value>> {
+fn path_open(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Vec<Value>> {
   let fd: i32 = args[0].clone().into();
   // let dirflags = args[1].;
   let path_offset = i32::from(args[2].clone()) as u32;
@@ -227,12 +197,12 @@ fn path_open(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value
   let opened_fd_offset = i32::from(args[8].clone()) as u32;
 
   let Some(Some(path)) = wasi.file_path.get(fd as usize) else {
-      return Ok(Some(ERRNO_INVAL.into()));
+      return Ok(vec![ERRNO_INVAL.into()]);
   };
 
   let file_path = store
-      .memories[0]
-      .load(path_offset, 0, path_len as u32)?
+      .host_memory()?
+      .load(path_offset.into(), 0, path_len as u32)?
       .iter()
       .map(|b| *b as char)
       .collect::<String>();
@@ -258,38 +228,38 @@ fn path_open(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value
   wasi.file_path
     .push(Some(resolved_path.to_str().unwrap().to_string()));
   store
-    .memories[0]
-    .store(opened_fd_offset, 0, 4, &opened_fd.to_le_bytes())?;
+    .host_memory()?
+    .store(opened_fd_offset.into(), 0, 4, &opened_fd.to_le_bytes())?;
 
-  Ok(Some(Value::I32(0)))
+  Ok(vec![Value::I32(0)])
 }
 
-fn fd_seek(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Option<Value>> {
+fn fd_seek(wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Vec<Value>> {
   let fd: i32 = args[0].clone().into();
   let offset = args[1].clone().into();
   let whence = args[2].clone().into();
   let new_offset_offset: i32 = args[3].clone().into();
 
   let Some(Some(file)) = wasi.file_table.get_mut(fd as usize) else {
-    return Ok(Some(ERRNO_BADF.into()));
+    return Ok(vec![ERRNO_BADF.into()]);
   };
 
   let new_offset = match whence {
     0 => file.seek(SeekFrom::Start(offset as u64)),
     1 => file.seek(SeekFrom::Current(offset)),
     2 => file.seek(SeekFrom::End(offset)),
-    _ => return Ok(Some(ERRNO_INVAL.into())),
+    _ => return Ok(vec![ERRNO_INVAL.into()]),
   }?;
 
   store
-    .memories[0]
-    .store(new_offset_offset as u32, 0, 8, &new_offset.to_le_bytes())?;
+    .host_memory()?
+    .store(new_offset_offset as u32 as u64, 0, 8, &new_offset.to_le_bytes())?;
 
-  Ok(Some(Value::I32(0)))
+  Ok(vec![Value::I32(0)])
 }
 
 fn memory_read_4byte(memory: &MemoryInst, addr: u32) -> Result<i32> {
-  let bytes = memory.load(addr, 0, 4)?;
+  let bytes = memory.load(addr.into(), 0, 4)?;
   Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
 }
 
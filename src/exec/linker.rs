@@ -0,0 +1,198 @@
+use std::{collections::HashMap, fmt};
+
+use anyhow::{anyhow, Result};
+use crate::binary::{import_sec::ImportDesc, memory_sec::MemorySec, table_sec::TableSec, type_sec::FuncType, value_type::ValueType, wasm::Wasm};
+use super::{import::{define_wasi, ImportFunc}, store::{ExternType, GlobalValue, MemoryInst, Store, TableInst}, value::Value, wasi::WasiSnapshotPreview1};
+
+type Name = (String, String);
+
+// importを解決するホスト関数、メモリ、テーブル、グローバル、タグ。importのモジュール名とフィールド名で引く
+#[derive(Default)]
+pub struct Linker {
+  funcs: HashMap<Name, HostFunc>,
+  // インスタンス化のたびにストアへ複製される。共有メモリは複製しても同じ中身を指す
+  memories: HashMap<Name, MemoryInst>,
+  tables: HashMap<Name, TableInst>,
+  globals: HashMap<Name, GlobalValue>,
+  tags: HashMap<Name, FuncType>,
+}
+
+impl fmt::Debug for Linker {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fn names<V>(map: &HashMap<Name, V>) -> Vec<String> {
+      let mut names: Vec<String> = map.keys().map(|(module, name)| format!("{}.{}", module, name)).collect();
+      names.sort();
+      names
+    }
+    f.debug_struct("Linker")
+      .field("funcs", &names(&self.funcs))
+      .field("memories", &names(&self.memories))
+      .field("tables", &names(&self.tables))
+      .field("globals", &names(&self.globals))
+      .field("tags", &names(&self.tags))
+      .finish()
+  }
+}
+
+struct HostFunc {
+  ty: FuncType,
+  func: ImportFunc,
+}
+
+impl Linker {
+  pub fn new() -> Linker {
+    Linker::default()
+  }
+
+  // wasi_snapshot_preview1のWASI関数を定義したLinker
+  pub fn with_wasi() -> Linker {
+    let mut linker = Linker::new();
+    define_wasi(&mut linker).expect("WASI functions are defined once");
+    linker
+  }
+
+  // module.nameのimportが解決されるホスト関数を定義する。戻り値は結果の型ごとに一つずつ並べて返す
+  pub fn define_func<F>(&mut self, module: &str, name: &str, ty: FuncType, func: F) -> Result<&mut Linker>
+  where
+    F: FnMut(&mut WasiSnapshotPreview1, &mut Store, Vec<Value>) -> Result<Vec<Value>> + Send + 'static,
+  {
+    let key = self.new_name(module, name)?;
+    self.funcs.insert(key, HostFunc { ty, func: Box::new(func) });
+    Ok(self)
+  }
+
+  // 共有メモリ以外はインスタンスごとに複製される
+  pub fn define_memory(&mut self, module: &str, name: &str, memory: MemoryInst) -> Result<&mut Linker> {
+    let key = self.new_name(module, name)?;
+    self.memories.insert(key, memory);
+    Ok(self)
+  }
+
+  pub fn define_table(&mut self, module: &str, name: &str, table: TableInst) -> Result<&mut Linker> {
+    let key = self.new_name(module, name)?;
+    self.tables.insert(key, table);
+    Ok(self)
+  }
+
+  // env.__memory_baseなど。インスタンスごとに複製されるので、mutableなglobalへの書き込みはホストからは見えない
+  pub fn define_global(&mut self, module: &str, name: &str, global: GlobalValue) -> Result<&mut Linker> {
+    let key = self.new_name(module, name)?;
+    self.globals.insert(key, global);
+    Ok(self)
+  }
+
+  pub fn define_tag(&mut self, module: &str, name: &str, ty: FuncType) -> Result<&mut Linker> {
+    let key = self.new_name(module, name)?;
+    self.tags.insert(key, ty);
+    Ok(self)
+  }
+
+  // 種類が違っても同じ名前は二度定義できない
+  fn new_name(&self, module: &str, name: &str) -> Result<Name> {
+    let key = (module.to_owned(), name.to_owned());
+    if self.funcs.contains_key(&key) || self.memories.contains_key(&key) || self.tables.contains_key(&key)
+      || self.globals.contains_key(&key) || self.tags.contains_key(&key) {
+      return Err(anyhow!("{}.{} is already defined", module, name));
+    }
+    Ok(key)
+  }
+
+  pub fn get_memory(&self, module: &str, name: &str) -> Option<&MemoryInst> {
+    self.memories.get(&(module.to_owned(), name.to_owned()))
+  }
+
+  pub fn get_table(&self, module: &str, name: &str) -> Option<&TableInst> {
+    self.tables.get(&(module.to_owned(), name.to_owned()))
+  }
+
+  pub fn get_global(&self, module: &str, name: &str) -> Option<&GlobalValue> {
+    self.globals.get(&(module.to_owned(), name.to_owned()))
+  }
+
+  // すべてのimportが定義されていて型が合うか確かめる
+  // メモリとテーブルはimportの最小値以上、最大値以内の大きさなら合う
+  pub fn resolve(&self, wasm: &Wasm) -> Result<()> {
+    let Some(ref imports) = wasm.import_section else {
+      return Ok(());
+    };
+    let types = wasm.type_section.as_deref().unwrap_or_default();
+    let func_type = |type_idx: u32| types.get(type_idx as usize).ok_or(anyhow!("unknown type {}", type_idx));
+    for import in imports {
+      let key = (import.module.clone(), import.field.clone());
+      // 見つからなければNone、型が合わなければ期待した型と実際の型を返す
+      let found = match &import.desc {
+        ImportDesc::Func(type_idx) => {
+          let ty = func_type(*type_idx)?;
+          self.funcs.get(&key).map(|host| (host.ty == *ty).then_some(()).ok_or_else(|| {
+            (signature(ty), signature(&host.ty))
+          }))
+        },
+        ImportDesc::Memory(ty) => self.memories.get(&key).map(|memory| {
+          let actual = memory.ty();
+          memory_matches(&actual, ty).then_some(()).ok_or_else(|| (format!("{:?}", ty), format!("{:?}", actual)))
+        }),
+        ImportDesc::Table(ty) => self.tables.get(&key).map(|table| {
+          let actual = table.ty();
+          table_matches(&actual, ty).then_some(()).ok_or_else(|| (format!("{:?}", ty), format!("{:?}", actual)))
+        }),
+        ImportDesc::Global { valtype, mutability } => self.globals.get(&key).map(|global| {
+          let expected = ExternType::Global { valtype: valtype.clone(), mutability: *mutability };
+          let actual = ExternType::Global { valtype: global.value.value_type(), mutability: global.mutability };
+          (expected == actual).then_some(()).ok_or_else(|| (format!("{:?}", expected), format!("{:?}", actual)))
+        }),
+        ImportDesc::Tag(type_idx) => {
+          let ty = func_type(*type_idx)?;
+          self.tags.get(&key).map(|tag| (tag == ty).then_some(()).ok_or_else(|| (signature(ty), signature(tag))))
+        },
+      };
+      match found {
+        None => return Err(anyhow!("unknown import: {}.{}", import.module, import.field)),
+        Some(Err((expected, actual))) => return Err(anyhow!(
+          "incompatible import type for {}.{}: expected {}, found {}",
+          import.module, import.field, expected, actual,
+        )),
+        Some(Ok(())) => {},
+      }
+    }
+    Ok(())
+  }
+
+  // ホスト関数を呼び、返した値が宣言した型に合うか確かめる
+  pub fn call(&mut self, module: &str, name: &str, wasi: &mut WasiSnapshotPreview1, store: &mut Store, args: Vec<Value>) -> Result<Vec<Value>> {
+    let Some(host) = self.funcs.get_mut(&(module.to_owned(), name.to_owned())) else {
+      return Err(anyhow!("unknown import: {}.{}", module, name));
+    };
+    let ret = (host.func)(wasi, store, args)?;
+    let ret_types: Vec<ValueType> = ret.iter().map(Value::value_type).collect();
+    if ret_types != host.ty.return_types {
+      return Err(anyhow!(
+        "{}.{} returned {:?}, expected {:?}",
+        module, name, ret_types, host.ty.return_types,
+      ));
+    }
+    Ok(ret)
+  }
+}
+
+// 定義されたものの大きさがimportの最小値以上で、最大値もimportの最大値以内なら合う
+fn limits_match(min: u64, max: Option<u64>, import_min: u64, import_max: Option<u64>) -> bool {
+  min >= import_min && match import_max {
+    Some(import_max) => max.is_some_and(|max| max <= import_max),
+    None => true,
+  }
+}
+
+fn memory_matches(actual: &MemorySec, import: &MemorySec) -> bool {
+  actual.memory64 == import.memory64 && actual.shared == import.shared
+    && limits_match(actual.min, actual.max, import.min, import.max)
+}
+
+fn table_matches(actual: &TableSec, import: &TableSec) -> bool {
+  actual.reftype == import.reftype
+    && limits_match(actual.min.into(), actual.max.map(u64::from), import.min.into(), import.max.map(u64::from))
+}
+
+// エラーメッセージ用の "[I32, I32] -> [I64]" 形式
+fn signature(ty: &FuncType) -> String {
+  format!("{:?} -> {:?}", ty.param_types, ty.return_types)
+}
@@ -5,5 +5,9 @@ pub mod block_frame;
 pub mod frame;
 pub mod store;
 pub mod import;
+pub mod linker;
 pub mod wasi;
-pub mod op;
\ No newline at end of file
+pub mod op;
+pub mod simd;
+pub mod atomic;
+pub mod trap;
\ No newline at end of file
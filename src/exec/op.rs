@@ -1,6 +1,19 @@
 use crate::binary::instructions::Instructions;
+use super::trap::Trap;
 use super::value::Value;
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpError {
+  InvalidType(String),
+  Trap(Trap),
+}
+
+impl From<Trap> for OpError {
+  fn from(trap: Trap) -> OpError {
+    OpError::Trap(trap)
+  }
+}
+
 pub fn exec_irelop(op: &Instructions, lhs: Value, rhs: Value) -> Result<Value, String> {
   match (lhs, rhs) {
     (Value::I32(lhs), Value::I32(rhs)) => Ok(exec_irelop_i32(op, lhs, rhs)),
@@ -109,23 +122,25 @@ fn exec_iuop_i64(op: &Instructions, v: i64) -> Value {
   }
 }
 
-pub fn exec_ibinop(op: &Instructions, lhs: Value, rhs: Value) -> Result<Value, String> {
+pub fn exec_ibinop(op: &Instructions, lhs: Value, rhs: Value) -> Result<Value, OpError> {
   match (lhs, rhs) {
-    (Value::I32(lhs), Value::I32(rhs)) => Ok(exec_ibinop_i32(op, lhs, rhs)),
-    (Value::I64(lhs), Value::I64(rhs)) => Ok(exec_ibinop_i64(op, lhs, rhs)),
-    _ => Err("Invalid type for ibinop".to_string()),
+    (Value::I32(lhs), Value::I32(rhs)) => Ok(exec_ibinop_i32(op, lhs, rhs)?),
+    (Value::I64(lhs), Value::I64(rhs)) => Ok(exec_ibinop_i64(op, lhs, rhs)?),
+    _ => Err(OpError::InvalidType("Invalid type for ibinop".to_string())),
   }
 }
 
-fn exec_ibinop_i32(op: &Instructions, lhs: i32, rhs: i32) -> Value {
-  match op {
+fn exec_ibinop_i32(op: &Instructions, lhs: i32, rhs: i32) -> Result<Value, Trap> {
+  let divisor = |rhs: i32| if rhs == 0 { Err(Trap::IntegerDivideByZero) } else { Ok(rhs) };
+  Ok(match op {
     Instructions::I32Add => Value::I32(lhs.wrapping_add(rhs)),
     Instructions::I32Sub => Value::I32(lhs.wrapping_sub(rhs)),
     Instructions::I32Mul => Value::I32(lhs.wrapping_mul(rhs)),
-    Instructions::I32DivS => Value::I32(lhs.wrapping_div(rhs)),
-    Instructions::I32DivU => Value::I32((lhs as u32).wrapping_div(rhs as u32) as i32),
-    Instructions::I32RemS => Value::I32(lhs.wrapping_rem(rhs)),
-    Instructions::I32RemU => Value::I32((lhs as u32).wrapping_rem(rhs as u32) as i32),
+    Instructions::I32DivS => Value::I32(lhs.checked_div(divisor(rhs)?).ok_or(Trap::IntegerOverflow)?),
+    Instructions::I32DivU => Value::I32(((lhs as u32) / divisor(rhs)? as u32) as i32),
+    // i32::MIN % -1 はオーバーフローせず0になる
+    Instructions::I32RemS => Value::I32(lhs.wrapping_rem(divisor(rhs)?)),
+    Instructions::I32RemU => Value::I32(((lhs as u32) % divisor(rhs)? as u32) as i32),
     Instructions::I32And => Value::I32(lhs & rhs),
     Instructions::I32Or => Value::I32(lhs | rhs),
     Instructions::I32Xor => Value::I32(lhs ^ rhs),
@@ -135,18 +150,19 @@ fn exec_ibinop_i32(op: &Instructions, lhs: i32, rhs: i32) -> Value {
     Instructions::I32Rotl => Value::I32(lhs.rotate_left(rhs as u32)),
     Instructions::I32Rotr => Value::I32(lhs.rotate_right(rhs as u32)),
     _ => unimplemented!(),
-  }
+  })
 }
 
-fn exec_ibinop_i64(op: &Instructions, lhs: i64, rhs: i64) -> Value {
-  match op {
+fn exec_ibinop_i64(op: &Instructions, lhs: i64, rhs: i64) -> Result<Value, Trap> {
+  let divisor = |rhs: i64| if rhs == 0 { Err(Trap::IntegerDivideByZero) } else { Ok(rhs) };
+  Ok(match op {
     Instructions::I64Add => Value::I64(lhs.wrapping_add(rhs)),
     Instructions::I64Sub => Value::I64(lhs.wrapping_sub(rhs)),
     Instructions::I64Mul => Value::I64(lhs.wrapping_mul(rhs)),
-    Instructions::I64DivS => Value::I64(lhs.wrapping_div(rhs)),
-    Instructions::I64DivU => Value::I64((lhs as u64).wrapping_div(rhs as u64) as i64),
-    Instructions::I64RemS => Value::I64(lhs.wrapping_rem(rhs)),
-    Instructions::I64RemU => Value::I64((lhs as u64).wrapping_rem(rhs as u64) as i64),
+    Instructions::I64DivS => Value::I64(lhs.checked_div(divisor(rhs)?).ok_or(Trap::IntegerOverflow)?),
+    Instructions::I64DivU => Value::I64(((lhs as u64) / divisor(rhs)? as u64) as i64),
+    Instructions::I64RemS => Value::I64(lhs.wrapping_rem(divisor(rhs)?)),
+    Instructions::I64RemU => Value::I64(((lhs as u64) % divisor(rhs)? as u64) as i64),
     Instructions::I64And => Value::I64(lhs & rhs),
     Instructions::I64Or => Value::I64(lhs | rhs),
     Instructions::I64Xor => Value::I64(lhs ^ rhs),
@@ -156,5 +172,49 @@ fn exec_ibinop_i64(op: &Instructions, lhs: i64, rhs: i64) -> Value {
     Instructions::I64Rotl => Value::I64(lhs.rotate_left(rhs as u32)),
     Instructions::I64Rotr => Value::I64(lhs.rotate_right(rhs as u32)),
     _ => unimplemented!(),
+  })
+}
+
+pub fn exec_trunc(op: &Instructions, v: Value) -> Result<Value, OpError> {
+  let v = match v {
+    Value::F32(v) => v as f64,
+    Value::F64(v) => v,
+    _ => return Err(OpError::InvalidType("Invalid type for trunc".to_string())),
+  };
+  // f32からf64への変換は誤差なしなので範囲検査はf64でまとめて行う
+  Ok(match op {
+    Instructions::I32TruncF32S | Instructions::I32TruncF64S => Value::I32(trunc_in_range(v, -2147483648.0, 2147483648.0)? as i32),
+    Instructions::I32TruncF32U | Instructions::I32TruncF64U => Value::I32(trunc_in_range(v, 0.0, 4294967296.0)? as u32 as i32),
+    Instructions::I64TruncF32S | Instructions::I64TruncF64S => Value::I64(trunc_in_range(v, -9223372036854775808.0, 9223372036854775808.0)? as i64),
+    Instructions::I64TruncF32U | Instructions::I64TruncF64U => Value::I64(trunc_in_range(v, 0.0, 18446744073709551616.0)? as u64 as i64),
+    _ => unimplemented!(),
+  })
+}
+
+// Rustのasによるキャストは飽和するのでそのまま使える。NaNは0になる
+pub fn exec_trunc_sat(op: &Instructions, v: Value) -> Result<Value, String> {
+  let v = match v {
+    Value::F32(v) => v as f64,
+    Value::F64(v) => v,
+    _ => return Err("Invalid type for trunc_sat".to_string()),
+  };
+  Ok(match op {
+    Instructions::I32TruncSatF32S | Instructions::I32TruncSatF64S => Value::I32(v as i32),
+    Instructions::I32TruncSatF32U | Instructions::I32TruncSatF64U => Value::I32(v as u32 as i32),
+    Instructions::I64TruncSatF32S | Instructions::I64TruncSatF64S => Value::I64(v as i64),
+    Instructions::I64TruncSatF32U | Instructions::I64TruncSatF64U => Value::I64(v as u64 as i64),
+    _ => unimplemented!(),
+  })
+}
+
+// 切り捨てた値が [min, max) に収まらなければトラップ
+fn trunc_in_range(v: f64, min: f64, max: f64) -> Result<f64, Trap> {
+  if v.is_nan() {
+    return Err(Trap::InvalidConversionToInteger);
   }
-}
\ No newline at end of file
+  let t = v.trunc();
+  if t < min || t >= max {
+    return Err(Trap::IntegerOverflow);
+  }
+  Ok(t)
+}
@@ -0,0 +1,600 @@
+use crate::binary::instructions::Instructions;
+use super::op::OpError;
+use super::store::MemoryInst;
+use super::trap::Trap;
+use super::value::Value;
+
+// v128をレーンの配列に分解する関数と、配列からv128に戻す関数を定義する
+macro_rules! lanes {
+  ($to:ident, $from:ident, $t:ty, $n:expr) => {
+    fn $to(v: u128) -> [$t; $n] {
+      let bytes = v.to_le_bytes();
+      let size = 16 / $n;
+      std::array::from_fn(|i| <$t>::from_le_bytes(bytes[i * size..(i + 1) * size].try_into().unwrap()))
+    }
+
+    fn $from(lanes: [$t; $n]) -> u128 {
+      let mut bytes = [0u8; 16];
+      let size = 16 / $n;
+      for (i, lane) in lanes.iter().enumerate() {
+        bytes[i * size..(i + 1) * size].copy_from_slice(&lane.to_le_bytes());
+      }
+      u128::from_le_bytes(bytes)
+    }
+  };
+}
+
+lanes!(i8x16, from_i8x16, i8, 16);
+lanes!(u8x16, from_u8x16, u8, 16);
+lanes!(i16x8, from_i16x8, i16, 8);
+lanes!(u16x8, from_u16x8, u16, 8);
+lanes!(i32x4, from_i32x4, i32, 4);
+lanes!(u32x4, from_u32x4, u32, 4);
+lanes!(i64x2, from_i64x2, i64, 2);
+lanes!(u64x2, from_u64x2, u64, 2);
+lanes!(f32x4, from_f32x4, f32, 4);
+lanes!(f64x2, from_f64x2, f64, 2);
+
+fn zip<T: Copy, U, const N: usize>(a: [T; N], b: [T; N], f: impl Fn(T, T) -> U) -> [U; N] {
+  std::array::from_fn(|i| f(a[i], b[i]))
+}
+
+// 比較結果をレーン幅いっぱいの1か0で埋める
+fn mask<const N: usize>(m: [bool; N]) -> u128 {
+  let width = 128 / N;
+  let lane = if width == 128 { u128::MAX } else { (1u128 << width) - 1 };
+  m.iter().enumerate().fold(0, |acc, (i, &b)| if b { acc | lane << (i * width) } else { acc })
+}
+
+fn bitmask<T: Copy, const N: usize>(lanes: [T; N], is_negative: impl Fn(T) -> bool) -> i32 {
+  lanes.iter().enumerate().fold(0, |acc, (i, &lane)| if is_negative(lane) { acc | 1 << i } else { acc })
+}
+
+// 前半をaから、後半をbから取って飽和させながら半分の幅に詰める
+fn narrow<T: Copy, U, const N: usize, const M: usize>(a: [T; N], b: [T; N], f: impl Fn(T) -> U) -> [U; M] {
+  std::array::from_fn(|i| if i < N { f(a[i]) } else { f(b[i - N]) })
+}
+
+// 下位または上位の半分のレーンを倍の幅に広げる
+fn extend<T: Copy, U, const N: usize, const M: usize>(a: [T; N], high: bool, f: impl Fn(T) -> U) -> [U; M] {
+  let base = if high { M } else { 0 };
+  std::array::from_fn(|i| f(a[base + i]))
+}
+
+// wasmのmin/maxはNaNを伝播し、-0 < +0 として扱う
+macro_rules! float_minmax {
+  ($min:ident, $max:ident, $t:ty) => {
+    fn $min(a: $t, b: $t) -> $t {
+      if a.is_nan() || b.is_nan() {
+        <$t>::NAN
+      } else if a == 0.0 && b == 0.0 {
+        if a.is_sign_negative() { a } else { b }
+      } else {
+        a.min(b)
+      }
+    }
+
+    fn $max(a: $t, b: $t) -> $t {
+      if a.is_nan() || b.is_nan() {
+        <$t>::NAN
+      } else if a == 0.0 && b == 0.0 {
+        if a.is_sign_positive() { a } else { b }
+      } else {
+        a.max(b)
+      }
+    }
+  };
+}
+
+float_minmax!(f32_min, f32_max, f32);
+float_minmax!(f64_min, f64_max, f64);
+
+fn pop(stack: &mut Vec<Value>) -> Result<Value, OpError> {
+  stack.pop().ok_or(OpError::InvalidType("value stack underflow".to_string()))
+}
+
+fn pop_v128(stack: &mut Vec<Value>) -> Result<u128, OpError> {
+  match pop(stack)? {
+    Value::V128(v) => Ok(v),
+    v => Err(OpError::InvalidType(format!("expected v128, found {:?}", v))),
+  }
+}
+
+fn pop_i32(stack: &mut Vec<Value>) -> Result<i32, OpError> {
+  match pop(stack)? {
+    Value::I32(v) => Ok(v),
+    v => Err(OpError::InvalidType(format!("expected i32, found {:?}", v))),
+  }
+}
+
+// アドレスは32bitのメモリならi32、memory64ならi64で積まれている
+fn pop_addr(stack: &mut Vec<Value>) -> Result<u64, OpError> {
+  match pop(stack)? {
+    Value::I32(v) => Ok(v as u32 as u64),
+    Value::I64(v) => Ok(v as u64),
+    v => Err(OpError::InvalidType(format!("expected an address, found {:?}", v))),
+  }
+}
+
+fn pop_i64(stack: &mut Vec<Value>) -> Result<i64, OpError> {
+  match pop(stack)? {
+    Value::I64(v) => Ok(v),
+    v => Err(OpError::InvalidType(format!("expected i64, found {:?}", v))),
+  }
+}
+
+fn pop_f32(stack: &mut Vec<Value>) -> Result<f32, OpError> {
+  match pop(stack)? {
+    Value::F32(v) => Ok(v),
+    v => Err(OpError::InvalidType(format!("expected f32, found {:?}", v))),
+  }
+}
+
+fn pop_f64(stack: &mut Vec<Value>) -> Result<f64, OpError> {
+  match pop(stack)? {
+    Value::F64(v) => Ok(v),
+    v => Err(OpError::InvalidType(format!("expected f64, found {:?}", v))),
+  }
+}
+
+fn load<const N: usize>(memory: &MemoryInst, offset: u64, addr: u64) -> Result<[u8; N], OpError> {
+  let bytes = memory.load(offset, addr, N as u32).map_err(|_| Trap::MemoryOutOfBounds)?;
+  Ok(bytes[..].try_into().unwrap())
+}
+
+// 0xfdで始まるSIMD命令を実行する。ロードとストアはmemargが指すメモリを使う
+pub fn exec_simd(instr: &Instructions, stack: &mut Vec<Value>, memories: &mut [MemoryInst]) -> Result<(), OpError> {
+  use Instructions::*;
+
+  // メモリ命令
+  match instr {
+    V128Load { memory: memory_idx, offset, .. }
+    | V128Load8x8S { memory: memory_idx, offset, .. } | V128Load8x8U { memory: memory_idx, offset, .. }
+    | V128Load16x4S { memory: memory_idx, offset, .. } | V128Load16x4U { memory: memory_idx, offset, .. }
+    | V128Load32x2S { memory: memory_idx, offset, .. } | V128Load32x2U { memory: memory_idx, offset, .. }
+    | V128Load8Splat { memory: memory_idx, offset, .. } | V128Load16Splat { memory: memory_idx, offset, .. }
+    | V128Load32Splat { memory: memory_idx, offset, .. } | V128Load64Splat { memory: memory_idx, offset, .. }
+    | V128Load32Zero { memory: memory_idx, offset, .. } | V128Load64Zero { memory: memory_idx, offset, .. } => {
+      let addr = pop_addr(stack)?;
+      let memory = memory(memories, *memory_idx)?;
+      let v = match instr {
+        V128Load { .. } => u128::from_le_bytes(load::<16>(memory, *offset, addr)?),
+        V128Load8x8S { .. } => from_i16x8(u8x8(memory, *offset, addr)?.map(|b| b as i8 as i16)),
+        V128Load8x8U { .. } => from_i16x8(u8x8(memory, *offset, addr)?.map(|b| b as i16)),
+        V128Load16x4S { .. } => {
+          let b = load::<8>(memory, *offset, addr)?;
+          from_i32x4(std::array::from_fn(|i| i16::from_le_bytes([b[2 * i], b[2 * i + 1]]) as i32))
+        },
+        V128Load16x4U { .. } => {
+          let b = load::<8>(memory, *offset, addr)?;
+          from_i32x4(std::array::from_fn(|i| u16::from_le_bytes([b[2 * i], b[2 * i + 1]]) as i32))
+        },
+        V128Load32x2S { .. } => {
+          let b = load::<8>(memory, *offset, addr)?;
+          from_i64x2(std::array::from_fn(|i| i32::from_le_bytes(b[4 * i..4 * i + 4].try_into().unwrap()) as i64))
+        },
+        V128Load32x2U { .. } => {
+          let b = load::<8>(memory, *offset, addr)?;
+          from_i64x2(std::array::from_fn(|i| u32::from_le_bytes(b[4 * i..4 * i + 4].try_into().unwrap()) as i64))
+        },
+        V128Load8Splat { .. } => from_u8x16([load::<1>(memory, *offset, addr)?[0]; 16]),
+        V128Load16Splat { .. } => from_u16x8([u16::from_le_bytes(load::<2>(memory, *offset, addr)?); 8]),
+        V128Load32Splat { .. } => from_u32x4([u32::from_le_bytes(load::<4>(memory, *offset, addr)?); 4]),
+        V128Load64Splat { .. } => from_u64x2([u64::from_le_bytes(load::<8>(memory, *offset, addr)?); 2]),
+        V128Load32Zero { .. } => u32::from_le_bytes(load::<4>(memory, *offset, addr)?) as u128,
+        V128Load64Zero { .. } => u64::from_le_bytes(load::<8>(memory, *offset, addr)?) as u128,
+        _ => unreachable!(),
+      };
+      stack.push(Value::V128(v));
+      return Ok(());
+    },
+    V128Store { memory: memory_idx, offset, .. } => {
+      let v = pop_v128(stack)?;
+      let addr = pop_addr(stack)?;
+      memory(memories, *memory_idx)?.store(*offset, addr, 16, &v.to_le_bytes())
+        .map_err(|_| Trap::MemoryOutOfBounds)?;
+      return Ok(());
+    },
+    V128Load8Lane { memory: memory_idx, offset, lane, .. } | V128Load16Lane { memory: memory_idx, offset, lane, .. }
+    | V128Load32Lane { memory: memory_idx, offset, lane, .. } | V128Load64Lane { memory: memory_idx, offset, lane, .. } => {
+      let v = pop_v128(stack)?;
+      let addr = pop_addr(stack)?;
+      let size = lane_size(instr);
+      let bytes = memory(memories, *memory_idx)?.load(*offset, addr, size as u32)
+        .map_err(|_| Trap::MemoryOutOfBounds)?;
+      let mut lanes = v.to_le_bytes();
+      let start = *lane as usize * size;
+      lanes[start..start + size].copy_from_slice(&bytes);
+      stack.push(Value::V128(u128::from_le_bytes(lanes)));
+      return Ok(());
+    },
+    V128Store8Lane { memory: memory_idx, offset, lane, .. } | V128Store16Lane { memory: memory_idx, offset, lane, .. }
+    | V128Store32Lane { memory: memory_idx, offset, lane, .. } | V128Store64Lane { memory: memory_idx, offset, lane, .. } => {
+      let v = pop_v128(stack)?;
+      let addr = pop_addr(stack)?;
+      let size = lane_size(instr);
+      let lanes = v.to_le_bytes();
+      let start = *lane as usize * size;
+      memory(memories, *memory_idx)?.store(*offset, addr, size as u32, &lanes[start..start + size])
+        .map_err(|_| Trap::MemoryOutOfBounds)?;
+      return Ok(());
+    },
+    _ => {},
+  }
+
+  // 定数、splat、レーン操作
+  let result = match instr {
+    V128Const(v) => Value::V128(*v),
+    I8x16Splat => Value::V128(from_i8x16([pop_i32(stack)? as i8; 16])),
+    I16x8Splat => Value::V128(from_i16x8([pop_i32(stack)? as i16; 8])),
+    I32x4Splat => Value::V128(from_i32x4([pop_i32(stack)?; 4])),
+    I64x2Splat => Value::V128(from_i64x2([pop_i64(stack)?; 2])),
+    F32x4Splat => Value::V128(from_f32x4([pop_f32(stack)?; 4])),
+    F64x2Splat => Value::V128(from_f64x2([pop_f64(stack)?; 2])),
+    I8x16ExtractLaneS(l) => Value::I32(i8x16(pop_v128(stack)?)[*l as usize] as i32),
+    I8x16ExtractLaneU(l) => Value::I32(u8x16(pop_v128(stack)?)[*l as usize] as i32),
+    I16x8ExtractLaneS(l) => Value::I32(i16x8(pop_v128(stack)?)[*l as usize] as i32),
+    I16x8ExtractLaneU(l) => Value::I32(u16x8(pop_v128(stack)?)[*l as usize] as i32),
+    I32x4ExtractLane(l) => Value::I32(i32x4(pop_v128(stack)?)[*l as usize]),
+    I64x2ExtractLane(l) => Value::I64(i64x2(pop_v128(stack)?)[*l as usize]),
+    F32x4ExtractLane(l) => Value::F32(f32x4(pop_v128(stack)?)[*l as usize]),
+    F64x2ExtractLane(l) => Value::F64(f64x2(pop_v128(stack)?)[*l as usize]),
+    I8x16ReplaceLane(l) => {
+      let x = pop_i32(stack)?;
+      let mut lanes = i8x16(pop_v128(stack)?);
+      lanes[*l as usize] = x as i8;
+      Value::V128(from_i8x16(lanes))
+    },
+    I16x8ReplaceLane(l) => {
+      let x = pop_i32(stack)?;
+      let mut lanes = i16x8(pop_v128(stack)?);
+      lanes[*l as usize] = x as i16;
+      Value::V128(from_i16x8(lanes))
+    },
+    I32x4ReplaceLane(l) => {
+      let x = pop_i32(stack)?;
+      let mut lanes = i32x4(pop_v128(stack)?);
+      lanes[*l as usize] = x;
+      Value::V128(from_i32x4(lanes))
+    },
+    I64x2ReplaceLane(l) => {
+      let x = pop_i64(stack)?;
+      let mut lanes = i64x2(pop_v128(stack)?);
+      lanes[*l as usize] = x;
+      Value::V128(from_i64x2(lanes))
+    },
+    F32x4ReplaceLane(l) => {
+      let x = pop_f32(stack)?;
+      let mut lanes = f32x4(pop_v128(stack)?);
+      lanes[*l as usize] = x;
+      Value::V128(from_f32x4(lanes))
+    },
+    F64x2ReplaceLane(l) => {
+      let x = pop_f64(stack)?;
+      let mut lanes = f64x2(pop_v128(stack)?);
+      lanes[*l as usize] = x;
+      Value::V128(from_f64x2(lanes))
+    },
+
+    // [v128] -> [i32]
+    V128AnyTrue => Value::I32((pop_v128(stack)? != 0) as i32),
+    I8x16AllTrue => Value::I32(i8x16(pop_v128(stack)?).iter().all(|&x| x != 0) as i32),
+    I16x8AllTrue => Value::I32(i16x8(pop_v128(stack)?).iter().all(|&x| x != 0) as i32),
+    I32x4AllTrue => Value::I32(i32x4(pop_v128(stack)?).iter().all(|&x| x != 0) as i32),
+    I64x2AllTrue => Value::I32(i64x2(pop_v128(stack)?).iter().all(|&x| x != 0) as i32),
+    I8x16Bitmask => Value::I32(bitmask(i8x16(pop_v128(stack)?), |x| x < 0)),
+    I16x8Bitmask => Value::I32(bitmask(i16x8(pop_v128(stack)?), |x| x < 0)),
+    I32x4Bitmask => Value::I32(bitmask(i32x4(pop_v128(stack)?), |x| x < 0)),
+    I64x2Bitmask => Value::I32(bitmask(i64x2(pop_v128(stack)?), |x| x < 0)),
+
+    // [v128 i32] -> [v128]。シフト量はレーン幅で剰余を取る
+    I8x16Shl | I8x16ShrS | I8x16ShrU | I16x8Shl | I16x8ShrS | I16x8ShrU
+    | I32x4Shl | I32x4ShrS | I32x4ShrU | I64x2Shl | I64x2ShrS | I64x2ShrU => {
+      let s = pop_i32(stack)? as u32;
+      let v = pop_v128(stack)?;
+      Value::V128(exec_shift(instr, v, s))
+    },
+
+    V128Bitselect => {
+      let c = pop_v128(stack)?;
+      let b = pop_v128(stack)?;
+      let a = pop_v128(stack)?;
+      Value::V128((a & c) | (b & !c))
+    },
+
+    _ => {
+      if let Some(v) = exec_unop(instr, stack)? {
+        Value::V128(v)
+      } else {
+        let b = pop_v128(stack)?;
+        let a = pop_v128(stack)?;
+        Value::V128(exec_binop(instr, a, b)?)
+      }
+    },
+  };
+  stack.push(result);
+  Ok(())
+}
+
+fn memory(memories: &mut [MemoryInst], memory_idx: u32) -> Result<&mut MemoryInst, OpError> {
+  memories.get_mut(memory_idx as usize).ok_or(OpError::InvalidType(format!("memory {} not found", memory_idx)))
+}
+
+fn u8x8(memory: &MemoryInst, offset: u64, addr: u64) -> Result<[u8; 8], OpError> {
+  load::<8>(memory, offset, addr)
+}
+
+fn lane_size(instr: &Instructions) -> usize {
+  match instr {
+    Instructions::V128Load8Lane { .. } | Instructions::V128Store8Lane { .. } => 1,
+    Instructions::V128Load16Lane { .. } | Instructions::V128Store16Lane { .. } => 2,
+    Instructions::V128Load32Lane { .. } | Instructions::V128Store32Lane { .. } => 4,
+    _ => 8,
+  }
+}
+
+fn exec_shift(instr: &Instructions, v: u128, s: u32) -> u128 {
+  use Instructions::*;
+  match instr {
+    I8x16Shl => from_i8x16(i8x16(v).map(|x| x.wrapping_shl(s % 8))),
+    I8x16ShrS => from_i8x16(i8x16(v).map(|x| x.wrapping_shr(s % 8))),
+    I8x16ShrU => from_u8x16(u8x16(v).map(|x| x.wrapping_shr(s % 8))),
+    I16x8Shl => from_i16x8(i16x8(v).map(|x| x.wrapping_shl(s % 16))),
+    I16x8ShrS => from_i16x8(i16x8(v).map(|x| x.wrapping_shr(s % 16))),
+    I16x8ShrU => from_u16x8(u16x8(v).map(|x| x.wrapping_shr(s % 16))),
+    I32x4Shl => from_i32x4(i32x4(v).map(|x| x.wrapping_shl(s % 32))),
+    I32x4ShrS => from_i32x4(i32x4(v).map(|x| x.wrapping_shr(s % 32))),
+    I32x4ShrU => from_u32x4(u32x4(v).map(|x| x.wrapping_shr(s % 32))),
+    I64x2Shl => from_i64x2(i64x2(v).map(|x| x.wrapping_shl(s % 64))),
+    I64x2ShrS => from_i64x2(i64x2(v).map(|x| x.wrapping_shr(s % 64))),
+    I64x2ShrU => from_u64x2(u64x2(v).map(|x| x.wrapping_shr(s % 64))),
+    _ => unreachable!(),
+  }
+}
+
+// [v128] -> [v128] の命令。該当しなければNoneを返してスタックには触らない
+fn exec_unop(instr: &Instructions, stack: &mut Vec<Value>) -> Result<Option<u128>, OpError> {
+  use Instructions::*;
+  let is_unop = matches!(instr,
+    V128Not | I8x16Abs | I8x16Neg | I8x16Popcnt | I16x8Abs | I16x8Neg | I32x4Abs | I32x4Neg | I64x2Abs | I64x2Neg
+    | F32x4Abs | F32x4Neg | F32x4Sqrt | F32x4Ceil | F32x4Floor | F32x4Trunc | F32x4Nearest
+    | F64x2Abs | F64x2Neg | F64x2Sqrt | F64x2Ceil | F64x2Floor | F64x2Trunc | F64x2Nearest
+    | I16x8ExtaddPairwiseI8x16S | I16x8ExtaddPairwiseI8x16U | I32x4ExtaddPairwiseI16x8S | I32x4ExtaddPairwiseI16x8U
+    | I16x8ExtendLowI8x16S | I16x8ExtendHighI8x16S | I16x8ExtendLowI8x16U | I16x8ExtendHighI8x16U
+    | I32x4ExtendLowI16x8S | I32x4ExtendHighI16x8S | I32x4ExtendLowI16x8U | I32x4ExtendHighI16x8U
+    | I64x2ExtendLowI32x4S | I64x2ExtendHighI32x4S | I64x2ExtendLowI32x4U | I64x2ExtendHighI32x4U
+    | F32x4DemoteF64x2Zero | F64x2PromoteLowF32x4
+    | I32x4TruncSatF32x4S | I32x4TruncSatF32x4U | F32x4ConvertI32x4S | F32x4ConvertI32x4U
+    | I32x4TruncSatF64x2SZero | I32x4TruncSatF64x2UZero | F64x2ConvertLowI32x4S | F64x2ConvertLowI32x4U
+  );
+  if !is_unop {
+    return Ok(None);
+  }
+  let v = pop_v128(stack)?;
+  let result = match instr {
+    V128Not => !v,
+    I8x16Abs => from_i8x16(i8x16(v).map(|x| x.wrapping_abs())),
+    I8x16Neg => from_i8x16(i8x16(v).map(|x| x.wrapping_neg())),
+    I8x16Popcnt => from_u8x16(u8x16(v).map(|x| x.count_ones() as u8)),
+    I16x8Abs => from_i16x8(i16x8(v).map(|x| x.wrapping_abs())),
+    I16x8Neg => from_i16x8(i16x8(v).map(|x| x.wrapping_neg())),
+    I32x4Abs => from_i32x4(i32x4(v).map(|x| x.wrapping_abs())),
+    I32x4Neg => from_i32x4(i32x4(v).map(|x| x.wrapping_neg())),
+    I64x2Abs => from_i64x2(i64x2(v).map(|x| x.wrapping_abs())),
+    I64x2Neg => from_i64x2(i64x2(v).map(|x| x.wrapping_neg())),
+    F32x4Abs => from_f32x4(f32x4(v).map(|x| x.abs())),
+    F32x4Neg => from_f32x4(f32x4(v).map(|x| -x)),
+    F32x4Sqrt => from_f32x4(f32x4(v).map(|x| x.sqrt())),
+    F32x4Ceil => from_f32x4(f32x4(v).map(|x| x.ceil())),
+    F32x4Floor => from_f32x4(f32x4(v).map(|x| x.floor())),
+    F32x4Trunc => from_f32x4(f32x4(v).map(|x| x.trunc())),
+    F32x4Nearest => from_f32x4(f32x4(v).map(|x| x.round_ties_even())),
+    F64x2Abs => from_f64x2(f64x2(v).map(|x| x.abs())),
+    F64x2Neg => from_f64x2(f64x2(v).map(|x| -x)),
+    F64x2Sqrt => from_f64x2(f64x2(v).map(|x| x.sqrt())),
+    F64x2Ceil => from_f64x2(f64x2(v).map(|x| x.ceil())),
+    F64x2Floor => from_f64x2(f64x2(v).map(|x| x.floor())),
+    F64x2Trunc => from_f64x2(f64x2(v).map(|x| x.trunc())),
+    F64x2Nearest => from_f64x2(f64x2(v).map(|x| x.round_ties_even())),
+    I16x8ExtaddPairwiseI8x16S => {
+      let a = i8x16(v);
+      from_i16x8(std::array::from_fn(|i| a[2 * i] as i16 + a[2 * i + 1] as i16))
+    },
+    I16x8ExtaddPairwiseI8x16U => {
+      let a = u8x16(v);
+      from_u16x8(std::array::from_fn(|i| a[2 * i] as u16 + a[2 * i + 1] as u16))
+    },
+    I32x4ExtaddPairwiseI16x8S => {
+      let a = i16x8(v);
+      from_i32x4(std::array::from_fn(|i| a[2 * i] as i32 + a[2 * i + 1] as i32))
+    },
+    I32x4ExtaddPairwiseI16x8U => {
+      let a = u16x8(v);
+      from_u32x4(std::array::from_fn(|i| a[2 * i] as u32 + a[2 * i + 1] as u32))
+    },
+    I16x8ExtendLowI8x16S => from_i16x8(extend(i8x16(v), false, |x| x as i16)),
+    I16x8ExtendHighI8x16S => from_i16x8(extend(i8x16(v), true, |x| x as i16)),
+    I16x8ExtendLowI8x16U => from_u16x8(extend(u8x16(v), false, |x| x as u16)),
+    I16x8ExtendHighI8x16U => from_u16x8(extend(u8x16(v), true, |x| x as u16)),
+    I32x4ExtendLowI16x8S => from_i32x4(extend(i16x8(v), false, |x| x as i32)),
+    I32x4ExtendHighI16x8S => from_i32x4(extend(i16x8(v), true, |x| x as i32)),
+    I32x4ExtendLowI16x8U => from_u32x4(extend(u16x8(v), false, |x| x as u32)),
+    I32x4ExtendHighI16x8U => from_u32x4(extend(u16x8(v), true, |x| x as u32)),
+    I64x2ExtendLowI32x4S => from_i64x2(extend(i32x4(v), false, |x| x as i64)),
+    I64x2ExtendHighI32x4S => from_i64x2(extend(i32x4(v), true, |x| x as i64)),
+    I64x2ExtendLowI32x4U => from_u64x2(extend(u32x4(v), false, |x| x as u64)),
+    I64x2ExtendHighI32x4U => from_u64x2(extend(u32x4(v), true, |x| x as u64)),
+    F32x4DemoteF64x2Zero => {
+      let a = f64x2(v);
+      from_f32x4([a[0] as f32, a[1] as f32, 0.0, 0.0])
+    },
+    F64x2PromoteLowF32x4 => from_f64x2(extend(f32x4(v), false, |x| x as f64)),
+    // asによるキャストは飽和し、NaNは0になる
+    I32x4TruncSatF32x4S => from_i32x4(f32x4(v).map(|x| x as i32)),
+    I32x4TruncSatF32x4U => from_u32x4(f32x4(v).map(|x| x as u32)),
+    F32x4ConvertI32x4S => from_f32x4(i32x4(v).map(|x| x as f32)),
+    F32x4ConvertI32x4U => from_f32x4(u32x4(v).map(|x| x as f32)),
+    I32x4TruncSatF64x2SZero => {
+      let a = f64x2(v);
+      from_i32x4([a[0] as i32, a[1] as i32, 0, 0])
+    },
+    I32x4TruncSatF64x2UZero => {
+      let a = f64x2(v);
+      from_u32x4([a[0] as u32, a[1] as u32, 0, 0])
+    },
+    F64x2ConvertLowI32x4S => from_f64x2(extend(i32x4(v), false, |x| x as f64)),
+    F64x2ConvertLowI32x4U => from_f64x2(extend(u32x4(v), false, |x| x as f64)),
+    _ => unreachable!(),
+  };
+  Ok(Some(result))
+}
+
+// [v128 v128] -> [v128] の命令
+fn exec_binop(instr: &Instructions, a: u128, b: u128) -> Result<u128, OpError> {
+  use Instructions::*;
+  Ok(match instr {
+    V128And => a & b,
+    V128Andnot => a & !b,
+    V128Or => a | b,
+    V128Xor => a ^ b,
+    I8x16Shuffle(lanes) => {
+      let (a, b) = (u8x16(a), u8x16(b));
+      from_u8x16(lanes.map(|l| if l < 16 { a[l as usize] } else { b[l as usize - 16] }))
+    },
+    I8x16Swizzle => {
+      let a = u8x16(a);
+      from_u8x16(u8x16(b).map(|l| if l < 16 { a[l as usize] } else { 0 }))
+    },
+
+    I8x16Eq => mask(zip(i8x16(a), i8x16(b), |x, y| x == y)),
+    I8x16Ne => mask(zip(i8x16(a), i8x16(b), |x, y| x != y)),
+    I8x16LtS => mask(zip(i8x16(a), i8x16(b), |x, y| x < y)),
+    I8x16LtU => mask(zip(u8x16(a), u8x16(b), |x, y| x < y)),
+    I8x16GtS => mask(zip(i8x16(a), i8x16(b), |x, y| x > y)),
+    I8x16GtU => mask(zip(u8x16(a), u8x16(b), |x, y| x > y)),
+    I8x16LeS => mask(zip(i8x16(a), i8x16(b), |x, y| x <= y)),
+    I8x16LeU => mask(zip(u8x16(a), u8x16(b), |x, y| x <= y)),
+    I8x16GeS => mask(zip(i8x16(a), i8x16(b), |x, y| x >= y)),
+    I8x16GeU => mask(zip(u8x16(a), u8x16(b), |x, y| x >= y)),
+    I16x8Eq => mask(zip(i16x8(a), i16x8(b), |x, y| x == y)),
+    I16x8Ne => mask(zip(i16x8(a), i16x8(b), |x, y| x != y)),
+    I16x8LtS => mask(zip(i16x8(a), i16x8(b), |x, y| x < y)),
+    I16x8LtU => mask(zip(u16x8(a), u16x8(b), |x, y| x < y)),
+    I16x8GtS => mask(zip(i16x8(a), i16x8(b), |x, y| x > y)),
+    I16x8GtU => mask(zip(u16x8(a), u16x8(b), |x, y| x > y)),
+    I16x8LeS => mask(zip(i16x8(a), i16x8(b), |x, y| x <= y)),
+    I16x8LeU => mask(zip(u16x8(a), u16x8(b), |x, y| x <= y)),
+    I16x8GeS => mask(zip(i16x8(a), i16x8(b), |x, y| x >= y)),
+    I16x8GeU => mask(zip(u16x8(a), u16x8(b), |x, y| x >= y)),
+    I32x4Eq => mask(zip(i32x4(a), i32x4(b), |x, y| x == y)),
+    I32x4Ne => mask(zip(i32x4(a), i32x4(b), |x, y| x != y)),
+    I32x4LtS => mask(zip(i32x4(a), i32x4(b), |x, y| x < y)),
+    I32x4LtU => mask(zip(u32x4(a), u32x4(b), |x, y| x < y)),
+    I32x4GtS => mask(zip(i32x4(a), i32x4(b), |x, y| x > y)),
+    I32x4GtU => mask(zip(u32x4(a), u32x4(b), |x, y| x > y)),
+    I32x4LeS => mask(zip(i32x4(a), i32x4(b), |x, y| x <= y)),
+    I32x4LeU => mask(zip(u32x4(a), u32x4(b), |x, y| x <= y)),
+    I32x4GeS => mask(zip(i32x4(a), i32x4(b), |x, y| x >= y)),
+    I32x4GeU => mask(zip(u32x4(a), u32x4(b), |x, y| x >= y)),
+    I64x2Eq => mask(zip(i64x2(a), i64x2(b), |x, y| x == y)),
+    I64x2Ne => mask(zip(i64x2(a), i64x2(b), |x, y| x != y)),
+    I64x2LtS => mask(zip(i64x2(a), i64x2(b), |x, y| x < y)),
+    I64x2GtS => mask(zip(i64x2(a), i64x2(b), |x, y| x > y)),
+    I64x2LeS => mask(zip(i64x2(a), i64x2(b), |x, y| x <= y)),
+    I64x2GeS => mask(zip(i64x2(a), i64x2(b), |x, y| x >= y)),
+    F32x4Eq => mask(zip(f32x4(a), f32x4(b), |x, y| x == y)),
+    F32x4Ne => mask(zip(f32x4(a), f32x4(b), |x, y| x != y)),
+    F32x4Lt => mask(zip(f32x4(a), f32x4(b), |x, y| x < y)),
+    F32x4Gt => mask(zip(f32x4(a), f32x4(b), |x, y| x > y)),
+    F32x4Le => mask(zip(f32x4(a), f32x4(b), |x, y| x <= y)),
+    F32x4Ge => mask(zip(f32x4(a), f32x4(b), |x, y| x >= y)),
+    F64x2Eq => mask(zip(f64x2(a), f64x2(b), |x, y| x == y)),
+    F64x2Ne => mask(zip(f64x2(a), f64x2(b), |x, y| x != y)),
+    F64x2Lt => mask(zip(f64x2(a), f64x2(b), |x, y| x < y)),
+    F64x2Gt => mask(zip(f64x2(a), f64x2(b), |x, y| x > y)),
+    F64x2Le => mask(zip(f64x2(a), f64x2(b), |x, y| x <= y)),
+    F64x2Ge => mask(zip(f64x2(a), f64x2(b), |x, y| x >= y)),
+
+    I8x16NarrowI16x8S => from_i8x16(narrow(i16x8(a), i16x8(b), |x| x.clamp(i8::MIN as i16, i8::MAX as i16) as i8)),
+    I8x16NarrowI16x8U => from_u8x16(narrow(i16x8(a), i16x8(b), |x| x.clamp(0, u8::MAX as i16) as u8)),
+    I16x8NarrowI32x4S => from_i16x8(narrow(i32x4(a), i32x4(b), |x| x.clamp(i16::MIN as i32, i16::MAX as i32) as i16)),
+    I16x8NarrowI32x4U => from_u16x8(narrow(i32x4(a), i32x4(b), |x| x.clamp(0, u16::MAX as i32) as u16)),
+
+    I8x16Add => from_i8x16(zip(i8x16(a), i8x16(b), i8::wrapping_add)),
+    I8x16AddSatS => from_i8x16(zip(i8x16(a), i8x16(b), i8::saturating_add)),
+    I8x16AddSatU => from_u8x16(zip(u8x16(a), u8x16(b), u8::saturating_add)),
+    I8x16Sub => from_i8x16(zip(i8x16(a), i8x16(b), i8::wrapping_sub)),
+    I8x16SubSatS => from_i8x16(zip(i8x16(a), i8x16(b), i8::saturating_sub)),
+    I8x16SubSatU => from_u8x16(zip(u8x16(a), u8x16(b), u8::saturating_sub)),
+    I8x16MinS => from_i8x16(zip(i8x16(a), i8x16(b), i8::min)),
+    I8x16MinU => from_u8x16(zip(u8x16(a), u8x16(b), u8::min)),
+    I8x16MaxS => from_i8x16(zip(i8x16(a), i8x16(b), i8::max)),
+    I8x16MaxU => from_u8x16(zip(u8x16(a), u8x16(b), u8::max)),
+    I8x16AvgrU => from_u8x16(zip(u8x16(a), u8x16(b), |x, y| (x as u16 + y as u16).div_ceil(2) as u8)),
+
+    I16x8Add => from_i16x8(zip(i16x8(a), i16x8(b), i16::wrapping_add)),
+    I16x8AddSatS => from_i16x8(zip(i16x8(a), i16x8(b), i16::saturating_add)),
+    I16x8AddSatU => from_u16x8(zip(u16x8(a), u16x8(b), u16::saturating_add)),
+    I16x8Sub => from_i16x8(zip(i16x8(a), i16x8(b), i16::wrapping_sub)),
+    I16x8SubSatS => from_i16x8(zip(i16x8(a), i16x8(b), i16::saturating_sub)),
+    I16x8SubSatU => from_u16x8(zip(u16x8(a), u16x8(b), u16::saturating_sub)),
+    I16x8Mul => from_i16x8(zip(i16x8(a), i16x8(b), i16::wrapping_mul)),
+    I16x8MinS => from_i16x8(zip(i16x8(a), i16x8(b), i16::min)),
+    I16x8MinU => from_u16x8(zip(u16x8(a), u16x8(b), u16::min)),
+    I16x8MaxS => from_i16x8(zip(i16x8(a), i16x8(b), i16::max)),
+    I16x8MaxU => from_u16x8(zip(u16x8(a), u16x8(b), u16::max)),
+    I16x8AvgrU => from_u16x8(zip(u16x8(a), u16x8(b), |x, y| (x as u32 + y as u32).div_ceil(2) as u16)),
+    I16x8Q15mulrSatS => from_i16x8(zip(i16x8(a), i16x8(b), |x, y| {
+      ((x as i32 * y as i32 + 0x4000) >> 15).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+    })),
+    I16x8ExtmulLowI8x16S => from_i16x8(zip(extend(i8x16(a), false, |x| x as i16), extend(i8x16(b), false, |x| x as i16), i16::wrapping_mul)),
+    I16x8ExtmulHighI8x16S => from_i16x8(zip(extend(i8x16(a), true, |x| x as i16), extend(i8x16(b), true, |x| x as i16), i16::wrapping_mul)),
+    I16x8ExtmulLowI8x16U => from_u16x8(zip(extend(u8x16(a), false, |x| x as u16), extend(u8x16(b), false, |x| x as u16), u16::wrapping_mul)),
+    I16x8ExtmulHighI8x16U => from_u16x8(zip(extend(u8x16(a), true, |x| x as u16), extend(u8x16(b), true, |x| x as u16), u16::wrapping_mul)),
+
+    I32x4Add => from_i32x4(zip(i32x4(a), i32x4(b), i32::wrapping_add)),
+    I32x4Sub => from_i32x4(zip(i32x4(a), i32x4(b), i32::wrapping_sub)),
+    I32x4Mul => from_i32x4(zip(i32x4(a), i32x4(b), i32::wrapping_mul)),
+    I32x4MinS => from_i32x4(zip(i32x4(a), i32x4(b), i32::min)),
+    I32x4MinU => from_u32x4(zip(u32x4(a), u32x4(b), u32::min)),
+    I32x4MaxS => from_i32x4(zip(i32x4(a), i32x4(b), i32::max)),
+    I32x4MaxU => from_u32x4(zip(u32x4(a), u32x4(b), u32::max)),
+    I32x4DotI16x8S => {
+      let (a, b) = (i16x8(a), i16x8(b));
+      from_i32x4(std::array::from_fn(|i| {
+        (a[2 * i] as i32 * b[2 * i] as i32).wrapping_add(a[2 * i + 1] as i32 * b[2 * i + 1] as i32)
+      }))
+    },
+    I32x4ExtmulLowI16x8S => from_i32x4(zip(extend(i16x8(a), false, |x| x as i32), extend(i16x8(b), false, |x| x as i32), i32::wrapping_mul)),
+    I32x4ExtmulHighI16x8S => from_i32x4(zip(extend(i16x8(a), true, |x| x as i32), extend(i16x8(b), true, |x| x as i32), i32::wrapping_mul)),
+    I32x4ExtmulLowI16x8U => from_u32x4(zip(extend(u16x8(a), false, |x| x as u32), extend(u16x8(b), false, |x| x as u32), u32::wrapping_mul)),
+    I32x4ExtmulHighI16x8U => from_u32x4(zip(extend(u16x8(a), true, |x| x as u32), extend(u16x8(b), true, |x| x as u32), u32::wrapping_mul)),
+
+    I64x2Add => from_i64x2(zip(i64x2(a), i64x2(b), i64::wrapping_add)),
+    I64x2Sub => from_i64x2(zip(i64x2(a), i64x2(b), i64::wrapping_sub)),
+    I64x2Mul => from_i64x2(zip(i64x2(a), i64x2(b), i64::wrapping_mul)),
+    I64x2ExtmulLowI32x4S => from_i64x2(zip(extend(i32x4(a), false, |x| x as i64), extend(i32x4(b), false, |x| x as i64), i64::wrapping_mul)),
+    I64x2ExtmulHighI32x4S => from_i64x2(zip(extend(i32x4(a), true, |x| x as i64), extend(i32x4(b), true, |x| x as i64), i64::wrapping_mul)),
+    I64x2ExtmulLowI32x4U => from_u64x2(zip(extend(u32x4(a), false, |x| x as u64), extend(u32x4(b), false, |x| x as u64), u64::wrapping_mul)),
+    I64x2ExtmulHighI32x4U => from_u64x2(zip(extend(u32x4(a), true, |x| x as u64), extend(u32x4(b), true, |x| x as u64), u64::wrapping_mul)),
+
+    F32x4Add => from_f32x4(zip(f32x4(a), f32x4(b), |x, y| x + y)),
+    F32x4Sub => from_f32x4(zip(f32x4(a), f32x4(b), |x, y| x - y)),
+    F32x4Mul => from_f32x4(zip(f32x4(a), f32x4(b), |x, y| x * y)),
+    F32x4Div => from_f32x4(zip(f32x4(a), f32x4(b), |x, y| x / y)),
+    F32x4Min => from_f32x4(zip(f32x4(a), f32x4(b), f32_min)),
+    F32x4Max => from_f32x4(zip(f32x4(a), f32x4(b), f32_max)),
+    F32x4Pmin => from_f32x4(zip(f32x4(a), f32x4(b), |x, y| if y < x { y } else { x })),
+    F32x4Pmax => from_f32x4(zip(f32x4(a), f32x4(b), |x, y| if x < y { y } else { x })),
+    F64x2Add => from_f64x2(zip(f64x2(a), f64x2(b), |x, y| x + y)),
+    F64x2Sub => from_f64x2(zip(f64x2(a), f64x2(b), |x, y| x - y)),
+    F64x2Mul => from_f64x2(zip(f64x2(a), f64x2(b), |x, y| x * y)),
+    F64x2Div => from_f64x2(zip(f64x2(a), f64x2(b), |x, y| x / y)),
+    F64x2Min => from_f64x2(zip(f64x2(a), f64x2(b), f64_min)),
+    F64x2Max => from_f64x2(zip(f64x2(a), f64x2(b), f64_max)),
+    F64x2Pmin => from_f64x2(zip(f64x2(a), f64x2(b), |x, y| if y < x { y } else { x })),
+    F64x2Pmax => from_f64x2(zip(f64x2(a), f64x2(b), |x, y| if x < y { y } else { x })),
+
+    _ => return Err(OpError::InvalidType(format!("{:?} is not a SIMD instruction", instr))),
+  })
+}
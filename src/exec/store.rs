@@ -1,23 +1,99 @@
-use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, ops::Range, sync::{Arc, Mutex, PoisonError}, time::Duration};
+
+use serde::{ser::Error, Deserialize, Serialize, Serializer};
+use tokio::sync::oneshot;
 
 use anyhow::{anyhow, Result};
-use crate::binary::{instructions::Instructions, wasm::Wasm};
-use super::{func_instance::FuncInstance, value::Value};
+use crate::binary::{data_sec::DataMode, elem_sec::{ElemInit, ElemMode}, export_sec::ExportDesc, import_sec::ImportDesc, instructions::Instructions, memory_sec::MemorySec, table_sec::{RefType, TableSec}, type_sec::FuncType, value_type::ValueType, wasm::Wasm};
+use super::{func_instance::FuncInstance, linker::Linker, value::Value};
 
 pub const PAGE_SIZE: usize = 65536; // 64Ki
+const MAX_PAGES: u64 = 65536;
+const MAX_PAGES64: u64 = 1 << 48;
+// 例外がこの数に達するまでは回収しない
+const MIN_EXN_GC_THRESHOLD: usize = 64;
 
 #[derive(Debug, Default, Clone, PartialEq , Serialize, Deserialize)]
 pub struct Store {
+  pub types: Vec<FuncType>,
   pub funcs: Vec<FuncInstance>,
-  pub tables: Vec<Table>,
+  pub tables: Vec<TableInst>,
   pub memories: Vec<MemoryInst>,
   pub globals: Vec<GlobalValue>,
+  // data.dropされたセグメントは空になる
+  pub datas: Vec<Vec<u8>>,
+  // elem.dropされたセグメントは空になる
+  pub elems: Vec<Vec<Value>>,
+  // タグの型。アドレスはモジュール内のタグインデックスと同じ
+  pub tags: Vec<FuncType>,
+  // throwで作られた例外。Value::ExnRefはここを指す
+  pub exns: Vec<ExnInst>,
+  // どこからも参照されなくなった例外のアドレス。次のthrowで使い回す
+  pub free_exns: Vec<usize>,
+  // exnsがこの数に達して空きが無ければ、次のthrowの前に参照されていない例外を回収する
+  pub exn_gc_threshold: usize,
+  // ホスト関数が読み書きするメモリ。"memory"としてexportされたものが既定になる
+  pub host_memory: usize,
+  // export名とそれが指すアドレス。exportセクションの順に並ぶ
+  pub exports: Vec<ExportInst>,
+}
+
+#[derive(Debug, Clone, PartialEq , Serialize, Deserialize)]
+pub struct ExportInst {
+  pub name: String,
+  pub value: ExternVal,
+}
+
+// exportが指すもの。Store内のアドレスで表す
+#[derive(Debug, Clone, Copy, PartialEq, Eq , Serialize, Deserialize)]
+pub enum ExternVal {
+  Func(usize),
+  Table(usize),
+  Memory(usize),
+  Global(usize),
+  Tag(usize),
+}
+
+// exportしたものの型。テーブルとメモリは現在の大きさを最小値として返す
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExternType {
+  Func(FuncType),
+  Table(TableSec),
+  Memory(MemorySec),
+  Global { valtype: ValueType, mutability: bool },
+  Tag(FuncType),
+}
+
+#[derive(Debug, Clone, PartialEq , Serialize, Deserialize)]
+pub struct ExnInst {
+  pub tag: usize,
+  pub fields: Vec<Value>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq , Serialize, Deserialize)]
 pub struct MemoryInst {
+  // 共有メモリではここは空のままで、中身はsharedにある
   pub memory: Vec<u8>,
-  pub max: Option<u32>,
+  pub max: Option<u64>,
+  // アドレスがi64のメモリ (memory64)
+  pub memory64: bool,
+  // 共有メモリ。複製しても同じ中身を指すので、スナップショットは取れない
+  #[serde(skip_deserializing, skip_serializing_if = "Option::is_none", serialize_with = "reject_shared")]
+  pub shared: Option<Arc<SharedMemory>>,
+}
+
+// 複数のExecMachineから同時に触られる共有メモリの中身。アクセスのたびにロックを取るので、各ロードとストアはアトミックになる
+#[derive(Debug, Default)]
+pub struct SharedMemory {
+  bytes: Mutex<Vec<u8>>,
+  // memory.atomic.waitで眠っているスレッド。notifyは待ち始めた順に起こす
+  waiters: Mutex<Vec<Waiter>>,
+}
+
+#[derive(Debug)]
+struct Waiter {
+  addr: u64,
+  wake: oneshot::Sender<()>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq , Serialize, Deserialize)]
@@ -26,72 +102,47 @@ pub struct GlobalValue {
   pub mutability: bool,
 }
 
+// 要素はValue::FuncRef、Value::ExternRef、Value::ExnRefのいずれかで、reftypeに揃っている
 #[derive(Debug, Clone, PartialEq , Serialize, Deserialize)]
-pub enum Table {
-  FuncRef(Vec<Option<usize>>),
-  ExternRef(Vec<Option<Value>>),
-}
-
-impl Default for Table {
-  fn default() -> Self {
-    Table::FuncRef(Vec::new())
-  }
+pub struct TableInst {
+  pub reftype: RefType,
+  pub elems: Vec<Value>,
+  pub max: Option<u32>,
 }
 
 impl Store {
-  pub fn new(funcs: Vec<FuncInstance>, wasm: &Wasm) -> Store {
-    let mut tables: Vec<Table> = Vec::new();
-    if let Some(ref table_sec) = wasm.table_section {
-      for table_s in table_sec {
-        match table_s.reftype {
-          crate::binary::table_sec::RefType::FuncRef => {
-            let mut table = Vec::new();
-            table.resize(table_s.min as usize, None);
-            tables.push(Table::FuncRef(table));
-          },
-          crate::binary::table_sec::RefType::ExternRef => {
-            let mut table = Vec::new();
-            table.resize(table_s.min as usize, None);
-            tables.push(Table::ExternRef(table));
-          },
+  // importしたテーブル、メモリ、グローバルはLinkerから複製する。importはLinker::resolveで解決済みであること
+  pub fn new(funcs: Vec<FuncInstance>, wasm: &Wasm, linker: &Linker) -> Result<Store> {
+    let mut tables = Vec::new();
+    let mut memories = Vec::new();
+    let mut globals = Vec::new();
+    // importしたテーブル、メモリ、グローバルはそれぞれのインデックス空間の先頭に置く
+    if let Some(ref imports) = wasm.import_section {
+      for import in imports {
+        let unknown = || anyhow!("unknown import: {}.{}", import.module, import.field);
+        match &import.desc {
+          ImportDesc::Table(_) => tables.push(linker.get_table(&import.module, &import.field).ok_or_else(unknown)?.clone()),
+          ImportDesc::Memory(_) => memories.push(linker.get_memory(&import.module, &import.field).ok_or_else(unknown)?.clone()),
+          ImportDesc::Global { .. } => globals.push(linker.get_global(&import.module, &import.field).ok_or_else(unknown)?.clone()),
+          ImportDesc::Func(_) | ImportDesc::Tag(_) => {},
         }
       }
     }
-    let mut memories = Vec::new();
+    if let Some(ref table_sec) = wasm.table_section {
+      for table in table_sec {
+        tables.push(TableInst::new(table)?);
+      }
+    }
     if let Some(ref memory_sec) = wasm.memory_section {
       for memory in memory_sec {
-        let min = memory.min * PAGE_SIZE as u32;
-        let memory_inst = MemoryInst {
-          memory: vec![0; min as usize],
-          max: memory.max,
-        };
-        memories.push(memory_inst);
+        memories.push(MemoryInst::from_type(memory)?);
       }
     }
 
-    if let Some(ref data) = wasm.data_section {
-      for data in data {
-        let memory = memories
-          .get_mut(data.memory_index as usize)
-          .unwrap();
-        let offset = data.offset as usize;
-        let init = &data.init;
-
-        if offset + init.len() > memory.memory.len() {
-            panic!("data is too large to fit in memory");
-        }
-        memory.memory[offset..offset + init.len()].copy_from_slice(init);
-      }     
-    }
-
-    let mut globals = Vec::new();
     if let Some(ref global_sec) = wasm.global_section {
       for global in global_sec {
-        let value = match global.init[0] {
-          Instructions::I32Const(v) => Value::I32(v),
-          Instructions::I64Const(v) => Value::I64(v),
-          _ => panic!("Invalid global init value"),
-        };
+        // 初期化式から読めるのは先に並んでいるimportしたglobalだけ
+        let value = eval_const_expr(&global.init, &globals)?;
         globals.push(GlobalValue {
           value,
           mutability: global.mutability,
@@ -99,43 +150,130 @@ impl Store {
       }
     }
 
+    let types = wasm.type_section.clone().unwrap_or_default();
+    let mut tags = Vec::new();
+    if let Some(ref imports) = wasm.import_section {
+      for import in imports {
+        if let ImportDesc::Tag(type_idx) = import.desc {
+          tags.push(types[type_idx as usize].clone());
+        }
+      }
+    }
+    if let Some(ref tag_sec) = wasm.tag_section {
+      tags.extend(tag_sec.iter().map(|tag| types[tag.type_idx as usize].clone()));
+    }
 
-    Store {
+    let mut store = Store {
+      types,
       funcs,
       tables,
       memories,
       globals,
+      datas: Vec::new(),
+      elems: Vec::new(),
+      tags,
+      exns: Vec::new(),
+      free_exns: Vec::new(),
+      exn_gc_threshold: MIN_EXN_GC_THRESHOLD,
+      host_memory: 0,
+      exports: Vec::new(),
+    };
+    if let Some(ref exports) = wasm.export_section {
+      // モジュール内のインデックスがそのままストアのアドレスになっている
+      store.exports = exports.iter().map(|export| {
+        let idx = export.idx as usize;
+        let value = match export.desc {
+          ExportDesc::Func => ExternVal::Func(idx),
+          ExportDesc::Table => ExternVal::Table(idx),
+          ExportDesc::Mem => ExternVal::Memory(idx),
+          ExportDesc::Global => ExternVal::Global(idx),
+          ExportDesc::Tag => ExternVal::Tag(idx),
+        };
+        ExportInst { name: export.name.clone(), value }
+      }).collect();
+    }
+    if let Some(ExternVal::Memory(memory_idx)) = store.export("memory") {
+      store.host_memory = memory_idx;
+    }
+
+    if let Some(ref elems) = wasm.element_section {
+      for elem in elems {
+        let refs = elem_refs(&elem.init, &store.globals, store.funcs.len())?;
+        match &elem.mode {
+          // activeとdeclarativeなセグメントはインスタンス化時にdropされる
+          ElemMode::Active { table_idx, offset } => {
+            store.init_table(*table_idx as usize, offset, &refs)?;
+            store.elems.push(Vec::new());
+          },
+          ElemMode::Declarative => store.elems.push(Vec::new()),
+          ElemMode::Passive => store.elems.push(refs),
+        }
+      }
     }
+
+    if let Some(ref data) = wasm.data_section {
+      for data in data {
+        match &data.mode {
+          // activeなセグメントはインスタンス化時に配置した後dropされる
+          DataMode::Active { memory_index, offset } => {
+            store.init_memory(*memory_index as usize, offset, &data.init)?;
+            store.datas.push(Vec::new());
+          },
+          DataMode::Passive => store.datas.push(data.init.clone()),
+        }
+      }
+    }
+
+    Ok(store)
   }
 
-  pub fn get_instr(&self, func_idx: usize, pc: usize) -> Option<&Instructions> {
-    match self.funcs.get(func_idx) {
-      Some(f) => match f {
-        FuncInstance::Internal(i) => match i.instrs.get(pc) {
-          Some(i) => Some(i),
-          None => None,
-        },
-        FuncInstance::External(_) => None,
-      },
-      None => panic!("func_idx out of range"),
+  fn init_memory(&mut self, memory_idx: usize, offset: &[Instructions], init: &[u8]) -> Result<()> {
+    // memory64のメモリではオフセットがi64になる
+    let offset = match eval_const_expr(offset, &self.globals)? {
+      Value::I32(v) => v as u32 as u64,
+      Value::I64(v) => v as u64,
+      v => return Err(anyhow!("data offset must be an integer, got {:?}", v)),
+    };
+    let memory = self
+      .memories
+      .get_mut(memory_idx)
+      .ok_or(anyhow!("unknown memory {}", memory_idx))?;
+    memory.write(offset, init).map_err(|_| anyhow!("out of bounds memory access"))
+  }
+
+  fn init_table(&mut self, table_idx: usize, offset: &[Instructions], refs: &[Value]) -> Result<()> {
+    let offset = match eval_const_expr(offset, &self.globals)? {
+      Value::I32(v) => v as u32 as usize,
+      v => return Err(anyhow!("element offset must be an i32, got {:?}", v)),
+    };
+    let table = self.tables.get_mut(table_idx).ok_or(anyhow!("unknown table {}", table_idx))?;
+    if offset + refs.len() > table.elems.len() {
+      return Err(anyhow!("out of bounds table access"));
     }
+    table.elems[offset..offset + refs.len()].clone_from_slice(refs);
+    Ok(())
   }
 
-  pub fn get_func(&self, func_idx: usize) -> &FuncInstance {
-    match self.funcs.get(func_idx) {
-      Some(f) => f,
-      None => panic!("func_idx out of range"),
+  pub fn get_instr(&self, func_idx: usize, pc: usize) -> Option<&Instructions> {
+    match self.get_func(func_idx)? {
+      FuncInstance::Internal(i) => i.instrs.get(pc),
+      FuncInstance::External(_) => None,
     }
   }
 
-  pub fn call_func(&self, func_idx:usize, args: Vec<Value>) -> FuncInstance {
-    let mut func_instance = self.get_func(func_idx).clone();
+  // テーブルから引いたアドレスは範囲外のこともあるので、呼び出し側でトラップにする
+  pub fn get_func(&self, func_idx: usize) -> Option<&FuncInstance> {
+    self.funcs.get(func_idx)
+  }
+
+  pub fn call_func(&self, func_idx:usize, args: Vec<Value>) -> Option<FuncInstance> {
+    let mut func_instance = self.get_func(func_idx)?.clone();
     match &mut func_instance {
       FuncInstance::Internal(func_instance) => {
         if args.len() == func_instance.param_types.len() {
           if args.iter().zip(&mut func_instance.param_types.iter()).all(|(a, b)| a.eq_for_value_type(b)) {
             for (i, a) in args.iter().enumerate() {
-              func_instance.locals[i] = a.clone();
+              func_instance.frame.locals[i] = a.clone();
             }
           } else {
             panic!("Invalid args type");
@@ -148,94 +286,538 @@ impl Store {
         func_instance.params = args;
       },
     }
-    func_instance
+    Some(func_instance)
   }
 
   pub fn call_func_by_name(&self, name: &str, args: Vec<Value>) -> FuncInstance {
     let func_idx = match self.funcs.iter()
-      .position(|f| f.name().map_or(false, |n| n == name)){
+      .position(|f| f.export_name().is_some_and(|n| n == name)){
       Some(idx) => idx,
       None => {
-        println!("funcs: {:?}", self.funcs);
-        panic!("function {} not found", name)
+        let exported = self.funcs.iter()
+          .filter_map(|f| f.export_name().map(|n| format!("{} ({})", n, f.display_name())))
+          .collect::<Vec<_>>()
+          .join(", ");
+        panic!("function {} not found, exported functions: [{}]", name, exported)
       },
     };
-    self.call_func(func_idx, args)
-  }
-
-  // pub fn memory_init(
-  //   &mut self,
-  //   dataidx: usize,
-  //   src: usize,
-  //   dest: usize,
-  //   size: usize,
-  // ) -> Result<()> {
-  //     if src + size > self.data[dataidx].len() {
-  //         Err("Out of data")?;
-  //     }
-  //     if dest + size > self.memory.data.len() {
-  //         Err("Out of memory")?;
-  //     }
-  //     if size != 0 {
-  //         self.memory.data[dest..dest + size]
-  //             .copy_from_slice(&self.data[dataidx][src..src + size]);
-  //     }
-  //     Ok(())
-  // }
+    self.call_func(func_idx, args).expect("exported function is in the store")
+  }
+
+  // 空いたアドレスがあれば使い回す
+  pub fn alloc_exn(&mut self, exn: ExnInst) -> usize {
+    if let Some(exn_addr) = self.free_exns.pop() {
+      self.exns[exn_addr] = exn;
+      return exn_addr;
+    }
+    self.exns.push(exn);
+    self.exns.len() - 1
+  }
+
+  pub fn needs_exn_gc(&self) -> bool {
+    self.free_exns.is_empty() && self.exns.len() >= self.exn_gc_threshold
+  }
+
+  // rootsとグローバル、テーブル、要素セグメントから辿れない例外を空きにする
+  // rootsにはスタックやローカル変数など、ストアの外にある値を渡す。ホストが取り出して持っているexnrefは辿れない
+  pub fn collect_exns<'a>(&mut self, roots: impl IntoIterator<Item = &'a Value>) {
+    let mut live = vec![false; self.exns.len()];
+    let mut pending: Vec<usize> = roots.into_iter().filter_map(exn_addr).collect();
+    let store_values = self.globals.iter().map(|global| &global.value)
+      .chain(self.tables.iter().flat_map(|table| &table.elems))
+      .chain(self.elems.iter().flatten());
+    pending.extend(store_values.filter_map(exn_addr));
+    // 例外のフィールドに入っている例外も生きている
+    while let Some(addr) = pending.pop() {
+      match live.get_mut(addr) {
+        Some(marked) if !*marked => {
+          *marked = true;
+          pending.extend(self.exns[addr].fields.iter().filter_map(exn_addr));
+        },
+        _ => {},
+      }
+    }
+    self.free_exns.clear();
+    for (addr, exn) in self.exns.iter_mut().enumerate() {
+      if !live[addr] {
+        exn.fields = Vec::new();
+        self.free_exns.push(addr);
+      }
+    }
+    let live_count = self.exns.len() - self.free_exns.len();
+    self.exn_gc_threshold = (live_count * 2).max(MIN_EXN_GC_THRESHOLD);
+  }
+
+  pub fn export(&self, name: &str) -> Option<ExternVal> {
+    self.exports.iter().find(|export| export.name == name).map(|export| export.value)
+  }
+
+  // exportセクションの順に、種類と型を並べる
+  pub fn exports(&self) -> impl Iterator<Item = (&str, ExternType)> + '_ {
+    self.exports.iter().map(|export| (export.name.as_str(), self.extern_type(export.value)))
+  }
+
+  pub fn extern_type(&self, value: ExternVal) -> ExternType {
+    match value {
+      ExternVal::Func(func_idx) => {
+        let (param_types, return_types) = match &self.funcs[func_idx] {
+          FuncInstance::Internal(f) => (f.param_types.clone(), f.return_types.clone()),
+          FuncInstance::External(f) => (f.param_types.clone(), f.return_types.clone()),
+        };
+        ExternType::Func(FuncType { param_types, return_types })
+      },
+      ExternVal::Table(table_idx) => ExternType::Table(self.tables[table_idx].ty()),
+      ExternVal::Memory(memory_idx) => ExternType::Memory(self.memories[memory_idx].ty()),
+      ExternVal::Global(global_idx) => {
+        let global = &self.globals[global_idx];
+        ExternType::Global { valtype: global.value.value_type(), mutability: global.mutability }
+      },
+      ExternVal::Tag(tag_idx) => ExternType::Tag(self.tags[tag_idx].clone()),
+    }
+  }
+
+  pub fn get_memory(&self, name: &str) -> Option<&MemoryInst> {
+    match self.export(name)? {
+      ExternVal::Memory(memory_idx) => self.memories.get(memory_idx),
+      _ => None,
+    }
+  }
+
+  // ホストが引数を渡すときなどにexportしたメモリへ書き込む
+  pub fn get_memory_mut(&mut self, name: &str) -> Option<&mut MemoryInst> {
+    match self.export(name)? {
+      ExternVal::Memory(memory_idx) => self.memories.get_mut(memory_idx),
+      _ => None,
+    }
+  }
+
+  pub fn get_table(&self, name: &str) -> Option<&TableInst> {
+    match self.export(name)? {
+      ExternVal::Table(table_idx) => self.tables.get(table_idx),
+      _ => None,
+    }
+  }
+
+  pub fn get_table_mut(&mut self, name: &str) -> Option<&mut TableInst> {
+    match self.export(name)? {
+      ExternVal::Table(table_idx) => self.tables.get_mut(table_idx),
+      _ => None,
+    }
+  }
+
+  pub fn get_global(&self, name: &str) -> Option<&GlobalValue> {
+    match self.export(name)? {
+      ExternVal::Global(global_idx) => self.globals.get(global_idx),
+      _ => None,
+    }
+  }
+
+  // exportしたmutableなglobalにホストから値を入れる。型はglobalと同じでなければならない
+  pub fn set_global(&mut self, name: &str, value: Value) -> Result<()> {
+    let Some(ExternVal::Global(global_idx)) = self.export(name) else {
+      return Err(anyhow!("no global exported as \"{}\"", name));
+    };
+    let global = self.globals.get_mut(global_idx).ok_or(anyhow!("unknown global {}", global_idx))?;
+    if !global.mutability {
+      return Err(anyhow!("global \"{}\" is immutable", name));
+    }
+    if !Value::match_value(&global.value, &value) {
+      return Err(anyhow!("type mismatch: global \"{}\" is {:?}, got {:?}", name, global.value.value_type(), value));
+    }
+    global.value = value;
+    Ok(())
+  }
+
+  pub fn has_shared_memory(&self) -> bool {
+    self.memories.iter().any(|memory| memory.shared.is_some())
+  }
+
+  // ホスト関数が読み書きするメモリ。host_memoryを参照
+  pub fn host_memory(&mut self) -> Result<&mut MemoryInst> {
+    let memory_idx = self.host_memory;
+    self.memories.get_mut(memory_idx).ok_or(anyhow!("unknown memory {}", memory_idx))
+  }
+
+  pub fn memory_init(&mut self, data_idx: usize, memory_idx: usize, dest: u64, src: u64, size: u64) -> Result<()> {
+    let data = self.datas.get(data_idx).ok_or(anyhow!("unknown data segment {}", data_idx))?;
+    let memory = self.memories.get_mut(memory_idx).ok_or(anyhow!("unknown memory {}", memory_idx))?;
+    let src = checked_range(src, size, data.len()).ok_or(anyhow!("Out of memory"))?;
+    memory.write(dest, &data[src])
+  }
+
+  pub fn memory_copy(&mut self, dst_idx: usize, src_idx: usize, dest: u64, src: u64, size: u64) -> Result<()> {
+    if dst_idx == src_idx {
+      let memory = self.memories.get_mut(dst_idx).ok_or(anyhow!("unknown memory {}", dst_idx))?;
+      return memory.copy(src, dest, size);
+    }
+    let src_memory = self.memories.get(src_idx).ok_or(anyhow!("unknown memory {}", src_idx))?;
+    let bytes = src_memory.read(src, size)?;
+    let dst_memory = self.memories.get_mut(dst_idx).ok_or(anyhow!("unknown memory {}", dst_idx))?;
+    dst_memory.write(dest, &bytes)
+  }
+
+  pub fn data_drop(&mut self, data_idx: usize) {
+    if let Some(data) = self.datas.get_mut(data_idx) {
+      *data = Vec::new();
+    }
+  }
+
+  pub fn table_init(&mut self, elem_idx: usize, table_idx: usize, dest: usize, src: usize, size: usize) -> Result<()> {
+    let elem = self.elems.get(elem_idx).ok_or(anyhow!("unknown elem segment {}", elem_idx))?;
+    let table = self.tables.get_mut(table_idx).ok_or(anyhow!("unknown table {}", table_idx))?;
+    if src + size > elem.len() || dest + size > table.elems.len() {
+      return Err(anyhow!("out of bounds table access"));
+    }
+    table.elems[dest..dest + size].clone_from_slice(&elem[src..src + size]);
+    Ok(())
+  }
+
+  pub fn elem_drop(&mut self, elem_idx: usize) {
+    if let Some(elem) = self.elems.get_mut(elem_idx) {
+      *elem = Vec::new();
+    }
+  }
+
+  pub fn table_copy(&mut self, dst_idx: usize, src_idx: usize, dest: usize, src: usize, size: usize) -> Result<()> {
+    let src_table = self.tables.get(src_idx).ok_or(anyhow!("unknown table {}", src_idx))?;
+    if src + size > src_table.elems.len() {
+      return Err(anyhow!("out of bounds table access"));
+    }
+    let refs = src_table.elems[src..src + size].to_vec();
+    let dst_table = self.tables.get_mut(dst_idx).ok_or(anyhow!("unknown table {}", dst_idx))?;
+    if dest + size > dst_table.elems.len() {
+      return Err(anyhow!("out of bounds table access"));
+    }
+    dst_table.elems[dest..dest + size].clone_from_slice(&refs);
+    Ok(())
+  }
+}
+
+// テーブルに入る関数参照は、インスタンス化された関数の範囲に収まっていなければならない
+fn exn_addr(value: &Value) -> Option<usize> {
+  match value {
+    Value::ExnRef(addr) => *addr,
+    _ => None,
+  }
+}
+
+fn elem_refs(init: &ElemInit, globals: &[GlobalValue], func_count: usize) -> Result<Vec<Value>> {
+  let refs = match init {
+    ElemInit::FuncIndices(funcs) => funcs.iter().map(|&f| Value::FuncRef(Some(f as usize))).collect(),
+    ElemInit::Exprs(exprs) => exprs.iter().map(|expr| eval_const_expr(expr, globals)).collect::<Result<Vec<_>>>()?,
+  };
+  for value in &refs {
+    if let Value::FuncRef(Some(func_idx)) = value {
+      if *func_idx >= func_count {
+        return Err(anyhow!("unknown function {}", func_idx));
+      }
+    }
+  }
+  Ok(refs)
+}
+
+// 定数式 (globalの初期値、dataやelemのオフセットと要素) を評価する
+// globalsはそれまでに定義されたもの。validateにより読めるのはimportしたものだけ
+pub fn eval_const_expr(expr: &[Instructions], globals: &[GlobalValue]) -> Result<Value> {
+  let mut stack = Vec::new();
+  for instr in expr {
+    let value = match instr {
+      Instructions::I32Const(v) => Value::I32(*v),
+      Instructions::I64Const(v) => Value::I64(*v),
+      Instructions::F32Const(v) => Value::F32(*v),
+      Instructions::F64Const(v) => Value::F64(*v),
+      Instructions::V128Const(v) => Value::V128(*v),
+      Instructions::RefNull(reftype) => Value::null(reftype),
+      Instructions::RefFunc(f) => Value::FuncRef(Some(*f as usize)),
+      Instructions::GlobalGet(idx) => match globals.get(*idx as usize) {
+        Some(global) => global.value.clone(),
+        None => return Err(anyhow!("unknown global {} in constant expression", idx)),
+      },
+      // extended-constの算術命令
+      Instructions::I32Add | Instructions::I32Sub | Instructions::I32Mul
+      | Instructions::I64Add | Instructions::I64Sub | Instructions::I64Mul => {
+        let (Some(rhs), Some(lhs)) = (stack.pop(), stack.pop()) else {
+          return Err(anyhow!("stack underflow in constant expression"));
+        };
+        match (instr, lhs, rhs) {
+          (Instructions::I32Add, Value::I32(a), Value::I32(b)) => Value::I32(a.wrapping_add(b)),
+          (Instructions::I32Sub, Value::I32(a), Value::I32(b)) => Value::I32(a.wrapping_sub(b)),
+          (Instructions::I32Mul, Value::I32(a), Value::I32(b)) => Value::I32(a.wrapping_mul(b)),
+          (Instructions::I64Add, Value::I64(a), Value::I64(b)) => Value::I64(a.wrapping_add(b)),
+          (Instructions::I64Sub, Value::I64(a), Value::I64(b)) => Value::I64(a.wrapping_sub(b)),
+          (Instructions::I64Mul, Value::I64(a), Value::I64(b)) => Value::I64(a.wrapping_mul(b)),
+          (_, lhs, rhs) => return Err(anyhow!("type mismatch in constant expression: {:?} {:?} {:?}", instr, lhs, rhs)),
+        }
+      },
+      Instructions::End => break,
+      _ => return Err(anyhow!("{:?} is not allowed in a constant expression", instr)),
+    };
+    stack.push(value);
+  }
+  match (stack.pop(), stack.is_empty()) {
+    (Some(value), true) => Ok(value),
+    _ => Err(anyhow!("constant expression must produce exactly one value: {:?}", expr)),
+  }
 }
 
 impl MemoryInst {
-  pub fn store(&mut self, offset: u32, index: u32, size: u32, value: &[u8]) -> Result<()> {
-    let addr = offset + index;
-    let addr = addr as usize;
-    let size = size as usize;
-    if addr + size > self.memory.len() {
-        return Err(anyhow!("Out of memory"));
+  pub fn new(min: u64, max: Option<u64>, memory64: bool) -> Result<MemoryInst> {
+    let size = pages_to_bytes(min).ok_or(anyhow!("memory size too large"))?;
+    let mut memory = Vec::new();
+    memory.try_reserve_exact(size).map_err(|_| anyhow!("memory size too large"))?;
+    memory.resize(size, 0);
+    Ok(MemoryInst { memory, max, memory64, shared: None })
+  }
+
+  // 共有メモリを作る。複製しても同じ中身を指す
+  pub fn new_shared(min: u64, max: u64, memory64: bool) -> Result<MemoryInst> {
+    let MemoryInst { memory, .. } = MemoryInst::new(min, Some(max), memory64)?;
+    let shared = SharedMemory { bytes: Mutex::new(memory), waiters: Mutex::new(Vec::new()) };
+    Ok(MemoryInst { memory: Vec::new(), max: Some(max), memory64, shared: Some(Arc::new(shared)) })
+  }
+
+  // 現在の大きさを最小値とした型
+  pub fn ty(&self) -> MemorySec {
+    MemorySec {
+      min: (self.len() / PAGE_SIZE) as u64,
+      max: self.max,
+      memory64: self.memory64,
+      shared: self.shared.is_some(),
     }
-    self.memory[addr..addr + size].copy_from_slice(&value[0..size]);
-    Ok(())
   }
 
-  pub fn load(&self, offset: u32, index: u32, size: u32) -> Result<&[u8]> {
-    let addr = offset + index;
-    let addr = addr as usize;
-    let size = size as usize;
-    if addr + size > self.memory.len() {
-        return Err(anyhow!("Out of memory"));
+  // 型どおりのメモリを作る。sharedなら共有メモリにする
+  pub fn from_type(memory: &MemorySec) -> Result<MemoryInst> {
+    match memory.max {
+      Some(max) if memory.shared => MemoryInst::new_shared(memory.min, max, memory.memory64),
+      _ => MemoryInst::new(memory.min, memory.max, memory.memory64),
+    }
+  }
+
+  // 共有メモリならロックを取ってから中身に触る
+  fn with_bytes<R>(&mut self, f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    match &self.shared {
+      Some(shared) => f(&mut shared.lock()),
+      None => f(&mut self.memory),
+    }
+  }
+
+  // 実効アドレスoffset + indexは桁あふれさせずに求める。32bitのメモリでは33bitの和になる
+  pub fn store(&mut self, offset: u64, index: u64, size: u32, value: &[u8]) -> Result<()> {
+    let addr = offset.checked_add(index).ok_or(anyhow!("Out of memory"))?;
+    self.write(addr, &value[0..size as usize])
+  }
+
+  // 共有メモリはロックの外へ借用を返せないので複製を返す
+  pub fn load(&self, offset: u64, index: u64, size: u32) -> Result<Cow<'_, [u8]>> {
+    let addr = offset.checked_add(index).ok_or(anyhow!("Out of memory"))?;
+    match &self.shared {
+      Some(shared) => {
+        let bytes = shared.lock();
+        let range = checked_range(addr, size as u64, bytes.len()).ok_or(anyhow!("Out of memory"))?;
+        Ok(Cow::Owned(bytes[range].to_vec()))
+      },
+      None => {
+        let range = checked_range(addr, size as u64, self.memory.len()).ok_or(anyhow!("Out of memory"))?;
+        Ok(Cow::Borrowed(&self.memory[range]))
+      },
+    }
+  }
+
+  pub fn write(&mut self, addr: u64, value: &[u8]) -> Result<()> {
+    self.with_bytes(|memory| {
+      let range = checked_range(addr, value.len() as u64, memory.len()).ok_or(anyhow!("Out of memory"))?;
+      memory[range].copy_from_slice(value);
+      Ok(())
+    })
+  }
+
+  pub fn read(&self, addr: u64, size: u64) -> Result<Vec<u8>> {
+    let size = u32::try_from(size).map_err(|_| anyhow!("Out of memory"))?;
+    Ok(self.load(addr, 0, size)?.into_owned())
+  }
+
+  // addrからsizeバイトをリトルエンディアンの整数として読み、f(old)で置き換えて元の値を返す。すべて一つのロックの中で行う
+  pub fn rmw(&mut self, addr: u64, size: u32, f: impl FnOnce(u64) -> u64) -> Result<u64> {
+    self.with_bytes(|memory| {
+      let range = checked_range(addr, size as u64, memory.len()).ok_or(anyhow!("Out of memory"))?;
+      let mut bytes = [0u8; 8];
+      bytes[..size as usize].copy_from_slice(&memory[range.clone()]);
+      let old = u64::from_le_bytes(bytes);
+      memory[range].copy_from_slice(&f(old).to_le_bytes()[..size as usize]);
+      Ok(old)
+    })
+  }
+
+  pub fn len(&self) -> usize {
+    match &self.shared {
+      Some(shared) => shared.lock().len(),
+      None => self.memory.len(),
     }
-    Ok(&self.memory[addr..addr + size])
   }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // memory64のメモリではページ数もi64で返す
   pub fn size(&self) -> Value {
-      Value::I32((self.memory.len() / PAGE_SIZE) as i32)
-  }
-  pub fn grow(&mut self, grow_size: usize) -> Value {
-      let current_size = self.memory.len() / PAGE_SIZE;
-      let new_size = current_size + grow_size;
-      let max = self.max.unwrap_or(u32::MAX / PAGE_SIZE as u32);
-      if new_size > max as usize {
-          Value::I32(-1)
-      } else {
-          self.memory.resize(new_size * PAGE_SIZE, 0);
-          Value::I32(current_size as i32)
-      }
+    let pages = (self.len() / PAGE_SIZE) as u64;
+    if self.memory64 { Value::I64(pages as i64) } else { Value::I32(pages as i32) }
   }
-  pub fn fill(&mut self, addr: usize, size: usize, value: u8) -> Result<()> {
-      if addr + size > self.memory.len() {
-          return Err(anyhow!("Out of memory"));
-      }
-      if size != 0 {
-          self.memory[addr..addr + size].fill(value);
-      }
+
+  pub fn grow(&mut self, grow_size: u64) -> Value {
+    let failed = if self.memory64 { Value::I64(-1) } else { Value::I32(-1) };
+    let max = self.max.unwrap_or(if self.memory64 { MAX_PAGES64 } else { MAX_PAGES });
+    let current_size = self.with_bytes(|memory| {
+      let current_size = (memory.len() / PAGE_SIZE) as u64;
+      let new_size = current_size.checked_add(grow_size).filter(|&size| size <= max)?;
+      // 確保できないほど大きな要求も失敗として扱う
+      let new_len = pages_to_bytes(new_size)?;
+      memory.try_reserve_exact(new_len - memory.len()).ok()?;
+      memory.resize(new_len, 0);
+      Some(current_size)
+    });
+    match current_size {
+      Some(size) if self.memory64 => Value::I64(size as i64),
+      Some(size) => Value::I32(size as i32),
+      None => failed,
+    }
+  }
+
+  pub fn fill(&mut self, addr: u64, size: u64, value: u8) -> Result<()> {
+    self.with_bytes(|memory| {
+      let range = checked_range(addr, size, memory.len()).ok_or(anyhow!("Out of memory"))?;
+      memory[range].fill(value);
       Ok(())
+    })
   }
-  pub fn copy(&mut self, src: usize, dest: usize, size: usize) -> Result<()> {
-      if src + size > self.memory.len() || dest + size > self.memory.len() {
-          return Err(anyhow!("Out of memory"));
-      }
-      if size != 0 {
-          let src_memory = self.memory[src..src + size].to_owned();
-          self.memory[dest..dest + size].copy_from_slice(&src_memory);
-      }
+
+  pub fn copy(&mut self, src: u64, dest: u64, size: u64) -> Result<()> {
+    self.with_bytes(|memory| {
+      let len = memory.len();
+      let (Some(src), Some(dest)) = (checked_range(src, size, len), checked_range(dest, size, len)) else {
+        return Err(anyhow!("Out of memory"));
+      };
+      memory.copy_within(src, dest.start);
       Ok(())
+    })
+  }
+}
+
+impl SharedMemory {
+  fn lock(&self) -> std::sync::MutexGuard<'_, Vec<u8>> {
+    self.bytes.lock().unwrap_or_else(PoisonError::into_inner)
   }
-}
\ No newline at end of file
+
+  // memory.atomic.wait。addrの値がまだexpectedと等しければ起こされるまで眠る
+  // 起こされたら0、値が違えば1、時間切れなら2を返す。timeout (ナノ秒) が負なら無期限に待つ
+  pub async fn wait(&self, addr: u64, expected: &[u8], timeout: i64) -> Result<i32> {
+    let Some(mut woken) = self.register(addr, expected)? else {
+      return Ok(1);
+    };
+    if timeout < 0 {
+      return Ok(if woken.await.is_ok() { 0 } else { 2 });
+    }
+    match tokio::time::timeout(Duration::from_nanos(timeout as u64), &mut woken).await {
+      Ok(_) => Ok(0),
+      // 時間切れと同時に起こされていれば起こされた方を優先する
+      Err(_) => Ok(if woken.try_recv().is_ok() { 0 } else { 2 }),
+    }
+  }
+
+  // 値の比較と待ち行列への登録を同じロックの中で行い、その間のnotifyを取りこぼさない
+  fn register(&self, addr: u64, expected: &[u8]) -> Result<Option<oneshot::Receiver<()>>> {
+    let bytes = self.lock();
+    let range = checked_range(addr, expected.len() as u64, bytes.len()).ok_or(anyhow!("Out of memory"))?;
+    if bytes[range] != *expected {
+      return Ok(None);
+    }
+    let (wake, woken) = oneshot::channel();
+    self.waiters.lock().unwrap_or_else(PoisonError::into_inner).push(Waiter { addr, wake });
+    Ok(Some(woken))
+  }
+
+  // memory.atomic.notify。addrで待っているものを最大count個起こし、起こした数を返す
+  pub fn notify(&self, addr: u64, count: u32) -> u32 {
+    let mut waiters = self.waiters.lock().unwrap_or_else(PoisonError::into_inner);
+    // 時間切れで諦めた待ち手は受け手が閉じているので数えずに捨てる
+    waiters.retain(|waiter| !waiter.wake.is_closed());
+    let mut woken = 0;
+    while woken < count {
+      let Some(pos) = waiters.iter().position(|waiter| waiter.addr == addr) else {
+        break;
+      };
+      if waiters.remove(pos).wake.send(()).is_ok() {
+        woken += 1;
+      }
+    }
+    woken
+  }
+}
+
+// 同じ中身を指していれば等しい
+impl PartialEq for SharedMemory {
+  fn eq(&self, other: &SharedMemory) -> bool {
+    std::ptr::eq(self, other)
+  }
+}
+
+// 共有メモリは他のスレッドが書き換え続けるので、ある時点の中身を保存できない
+fn reject_shared<S: Serializer>(_: &Option<Arc<SharedMemory>>, _: S) -> std::result::Result<S::Ok, S::Error> {
+  Err(S::Error::custom("cannot serialize a machine with a shared memory"))
+}
+
+fn pages_to_bytes(pages: u64) -> Option<usize> {
+  pages.checked_mul(PAGE_SIZE as u64).and_then(|bytes| usize::try_from(bytes).ok())
+}
+
+// start..start + sizeがlenに収まるときだけその範囲を返す
+fn checked_range(start: u64, size: u64, len: usize) -> Option<Range<usize>> {
+  let end = start.checked_add(size)?;
+  if end > len as u64 {
+    return None;
+  }
+  Some(start as usize..end as usize)
+}
+
+impl TableInst {
+  pub fn new(table: &TableSec) -> Result<TableInst> {
+    // 確保できないほど大きなテーブルはエラーにする
+    let mut elems = Vec::new();
+    elems.try_reserve_exact(table.min as usize).map_err(|_| anyhow!("table size too large"))?;
+    elems.resize(table.min as usize, Value::null(&table.reftype));
+    Ok(TableInst { reftype: table.reftype.clone(), elems, max: table.max })
+  }
+
+  // 現在の大きさを最小値とした型
+  pub fn ty(&self) -> TableSec {
+    TableSec { min: self.elems.len() as u32, max: self.max, reftype: self.reftype.clone() }
+  }
+
+  pub fn size(&self) -> Value {
+    Value::I32(self.elems.len() as i32)
+  }
+
+  // 成功すれば元のサイズ、maxを超えるか確保できなければ-1を返す
+  pub fn grow(&mut self, grow_size: u32, init: Value) -> Value {
+    let current_size = self.elems.len() as u32;
+    let max = self.max.unwrap_or(u32::MAX);
+    match current_size.checked_add(grow_size) {
+      // 確保できないほど大きな要求も失敗として扱う
+      Some(new_size) if new_size <= max && self.elems.try_reserve_exact(grow_size as usize).is_ok() => {
+        self.elems.resize(new_size as usize, init);
+        Value::I32(current_size as i32)
+      },
+      _ => Value::I32(-1),
+    }
+  }
+
+  pub fn fill(&mut self, index: usize, size: usize, value: Value) -> Result<()> {
+    if index + size > self.elems.len() {
+      return Err(anyhow!("out of bounds table access"));
+    }
+    self.elems[index..index + size].fill(value);
+    Ok(())
+  }
+}
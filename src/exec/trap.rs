@@ -0,0 +1,87 @@
+use std::fmt;
+
+use super::exec_machine::ExecMachine;
+
+// トラップの種類。表示はspecのassert_trapの文言に合わせている
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trap {
+  Unreachable,
+  MemoryOutOfBounds,
+  TableOutOfBounds,
+  UndefinedElement,
+  UninitializedElement,
+  IndirectCallTypeMismatch,
+  IntegerDivideByZero,
+  IntegerOverflow,
+  InvalidConversionToInteger,
+  StackExhausted,
+  // どのtry_tableにも捕まらずに呼び出し元まで抜けた例外
+  UncaughtException,
+  NullExceptionReference,
+  // アトミック命令の実効アドレスがアクセス幅に揃っていない
+  UnalignedAtomic,
+  // 共有していないメモリでmemory.atomic.waitした
+  ExpectedSharedMemory,
+  HostError(String),
+  // 検査済みのモジュールでは起きないはずのインタプリタ内部の不整合
+  Internal(String),
+}
+
+impl fmt::Display for Trap {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Trap::Unreachable => write!(f, "unreachable"),
+      Trap::MemoryOutOfBounds => write!(f, "out of bounds memory access"),
+      Trap::TableOutOfBounds => write!(f, "out of bounds table access"),
+      Trap::UndefinedElement => write!(f, "undefined element"),
+      Trap::UninitializedElement => write!(f, "uninitialized element"),
+      Trap::IndirectCallTypeMismatch => write!(f, "indirect call type mismatch"),
+      Trap::IntegerDivideByZero => write!(f, "integer divide by zero"),
+      Trap::IntegerOverflow => write!(f, "integer overflow"),
+      Trap::InvalidConversionToInteger => write!(f, "invalid conversion to integer"),
+      Trap::StackExhausted => write!(f, "call stack exhausted"),
+      Trap::UncaughtException => write!(f, "uncaught exception"),
+      Trap::NullExceptionReference => write!(f, "null exception reference"),
+      Trap::UnalignedAtomic => write!(f, "unaligned atomic"),
+      Trap::ExpectedSharedMemory => write!(f, "expected shared memory"),
+      Trap::HostError(e) => write!(f, "host error: {}", e),
+      Trap::Internal(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+// トラップとそれが起きた場所
+#[derive(Debug)]
+pub struct TrapError {
+  pub trap: Trap,
+  pub func_idx: Option<usize>,
+  pub pc: Option<usize>,
+  // トラップした関数から呼び出し元へ遡った関数の表示名
+  pub backtrace: Vec<String>,
+  // ExecMachine::snapshot_on_trap が有効なときだけトラップ時点のVMを持つ
+  pub vm: Option<Box<ExecMachine>>,
+}
+
+impl TrapError {
+  pub fn new(trap: Trap) -> TrapError {
+    TrapError { trap, func_idx: None, pc: None, backtrace: Vec::new(), vm: None }
+  }
+}
+
+impl From<Trap> for TrapError {
+  fn from(trap: Trap) -> TrapError {
+    TrapError::new(trap)
+  }
+}
+
+impl fmt::Display for TrapError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.trap)?;
+    for frame in &self.backtrace {
+      write!(f, "\n  at {}", frame)?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for TrapError {}
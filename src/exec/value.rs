@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use crate::binary::table_sec::RefType;
 use crate::binary::value_type::ValueType;
 
 #[derive(Debug, Clone, PartialEq , Serialize, Deserialize)]
@@ -7,6 +8,14 @@ pub enum Value {
   I64(i64),
   F32(f32),
   F64(f64),
+  // 128bitのベクタ。レーンはリトルエンディアンで並ぶ
+  V128(u128),
+  // 関数アドレス。Noneはref.null
+  FuncRef(Option<usize>),
+  // ホストが渡すオブジェクトのハンドル。Noneはref.null
+  ExternRef(Option<u32>),
+  // Store::exnsに積まれた例外のアドレス。Noneはref.null
+  ExnRef(Option<usize>),
 }
 
 impl Default for Value {
@@ -22,15 +31,36 @@ impl Value {
       | (Value::I64(_), ValueType::I64) 
       | (Value::F32(_), ValueType::F32) 
       | (Value::F64(_), ValueType::F64)
+      | (Value::V128(_), ValueType::V128)
+      | (Value::FuncRef(_), ValueType::FuncRef)
+      | (Value::ExternRef(_), ValueType::ExternRef)
+      | (Value::ExnRef(_), ValueType::ExnRef)
     )
   }
 
+  pub fn value_type(&self) -> ValueType {
+    match self {
+      Value::I32(_) => ValueType::I32,
+      Value::I64(_) => ValueType::I64,
+      Value::F32(_) => ValueType::F32,
+      Value::F64(_) => ValueType::F64,
+      Value::V128(_) => ValueType::V128,
+      Value::FuncRef(_) => ValueType::FuncRef,
+      Value::ExternRef(_) => ValueType::ExternRef,
+      Value::ExnRef(_) => ValueType::ExnRef,
+    }
+  }
+
   pub fn init_from_valtype(valtype: &ValueType) -> Value {
     match valtype {
       ValueType::I32 => Value::I32(0),
       ValueType::I64 => Value::I64(0),
       ValueType::F32 => Value::F32(0.0),
       ValueType::F64 => Value::F64(0.0),
+      ValueType::V128 => Value::V128(0),
+      ValueType::FuncRef => Value::FuncRef(None),
+      ValueType::ExternRef => Value::ExternRef(None),
+      ValueType::ExnRef => Value::ExnRef(None),
     }
   }
 
@@ -40,9 +70,25 @@ impl Value {
       | (&Value::I64(_), &Value::I64(_)) 
       | (&Value::F32(_), &Value::F32(_)) 
       | (&Value::F64(_), &Value::F64(_))
+      | (&Value::V128(_), &Value::V128(_))
+      | (&Value::FuncRef(_), &Value::FuncRef(_))
+      | (&Value::ExternRef(_), &Value::ExternRef(_))
+      | (&Value::ExnRef(_), &Value::ExnRef(_))
     )
   }
 
+  pub fn null(reftype: &RefType) -> Value {
+    match reftype {
+      RefType::FuncRef => Value::FuncRef(None),
+      RefType::ExternRef => Value::ExternRef(None),
+      RefType::ExnRef => Value::ExnRef(None),
+    }
+  }
+
+  pub fn is_null(&self) -> bool {
+    matches!(self, Value::FuncRef(None) | Value::ExternRef(None) | Value::ExnRef(None))
+  }
+
   pub fn parse_from_i64_vec(input: Vec<i64>) -> Vec<Value> {
     input.iter().map(|&x| Value::I64(x)).collect()
   }
@@ -84,6 +130,15 @@ impl From<Value> for f64 {
   }
 }
 
+impl From<Value> for u128 {
+  fn from(value: Value) -> Self {
+    match value {
+        Value::V128(value) => value,
+        _ => panic!("type mismatch"),
+    }
+  }
+}
+
 impl From<i32> for Value {
   fn from(value: i32) -> Self {
     Value::I32(value)
@@ -106,4 +161,10 @@ impl From<f64> for Value {
   fn from(value: f64) -> Self {
     Value::F64(value)
   }
-}
\ No newline at end of file
+}
+
+impl From<u128> for Value {
+  fn from(value: u128) -> Self {
+    Value::V128(value)
+  }
+}
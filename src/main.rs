@@ -1,8 +1,10 @@
 use std::fs::File;
 use std::io::{BufReader, Read, Write};
-use clap::{command, Parser};
+use clap::Parser;
+use read_wasm::binary::print::section_headers;
 use read_wasm::binary::wasm::Wasm;
 use read_wasm::exec::exec_machine::ExecMachine;
+use read_wasm::exec::linker::Linker;
 use read_wasm::exec::value::Value;
 use read_wasm::exec::wasi::WasiSnapshotPreview1;
 
@@ -36,6 +38,12 @@ enum SubCommand {
     #[clap(short, long)]
     locals: Vec<i64>,
   },
+  Dump {
+    filename: String,
+
+    #[clap(long)]
+    headers: bool,
+  },
   Server,
   Client {
     server_addr: String,
@@ -49,18 +57,29 @@ async fn main() {
   match args.subcmd {
     SubCommand::Run { filename, entry_point, locals } => {
       let file = File::open(filename).unwrap();
-      let wasm = Wasm::new(BufReader::new(file));
-
+      let wasm = match Wasm::new(BufReader::new(file)) {
+        Ok(wasm) => wasm,
+        Err(e) => {
+          println!("DecodeError: {}", e);
+          return;
+        },
+      };
 
       let locals = Value::parse_from_i64_vec(locals);
 
-      let mut machine = ExecMachine::init(wasm, &entry_point, locals);
-      dbg!(&machine.store.funcs);
       let mut wasi = WasiSnapshotPreview1::new();
-      match machine.exec(&mut wasi).await {
+      let mut linker = Linker::with_wasi();
+      let mut machine = match ExecMachine::init(wasm, &entry_point, locals, &mut wasi, &mut linker).await {
+        Ok(machine) => machine,
+        Err(e) => {
+          println!("InstantiationError: {:?}", e);
+          return;
+        },
+      };
+      match machine.exec(&mut wasi, &mut linker).await {
         Ok(_) => { println!("return {:?}", machine.value_stack.last()); },
         Err(e) => {
-          println!("ExecuteError: {:?}", e.message);
+          println!("ExecuteError: {}", e);
         },
       }
     }
@@ -69,24 +88,49 @@ async fn main() {
       let mut se  = Vec::new();
       file.read_to_end(&mut se).unwrap();
       let mut machine = ExecMachine::deserialize(&se).await.unwrap();
+      machine.snapshot_on_trap = true;
       let mut wasi = WasiSnapshotPreview1::new();
-      match machine.exec(&mut wasi).await {
+      let mut linker = Linker::with_wasi();
+      match machine.exec(&mut wasi, &mut linker).await {
         Ok(_) => { println!("return {:?}", machine.value_stack.last()); },
         Err(e) => {
-          println!("ExecuteError: {:?}", e.message);
+          println!("ExecuteError: {}", e);
           println!("VM: {:#?}", e.vm);
         },
       }
     }
     SubCommand::Serialize { filename, entry_point, locals } => {
       let file = File::open(filename).unwrap();
-      let wasm = Wasm::new(BufReader::new(file));
+      let wasm = Wasm::new(BufReader::new(file)).unwrap();
       let locals = Value::parse_from_i64_vec(locals);
 
-      let machine = ExecMachine::init(wasm, &entry_point, locals);
-      let data = machine.serialize_vm();
+      let mut wasi = WasiSnapshotPreview1::new();
+      let mut linker = Linker::with_wasi();
+      let machine = ExecMachine::init(wasm, &entry_point, locals, &mut wasi, &mut linker).await.unwrap();
+      let data = machine.serialize_vm().unwrap();
       File::create("vm.serialized").unwrap().write_all(&data).unwrap();
     }
+    SubCommand::Dump { filename, headers } => {
+      let mut file = File::open(filename).unwrap();
+      let mut data = Vec::new();
+      file.read_to_end(&mut data).unwrap();
+      if headers {
+        match section_headers(&data) {
+          Ok(headers) => {
+            println!("Sections:\n");
+            for header in headers {
+              println!("{}", header);
+            }
+          },
+          Err(e) => println!("DecodeError: {}", e),
+        }
+        return;
+      }
+      match Wasm::new(&data[..]) {
+        Ok(wasm) => print!("{}", wasm.to_wat()),
+        Err(e) => println!("DecodeError: {}", e),
+      }
+    }
     #[cfg(feature = "ucx")]
     SubCommand::Server => {
       let local = tokio::task::LocalSet::new();
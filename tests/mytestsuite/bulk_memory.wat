@@ -0,0 +1,15 @@
+(module
+  (memory 1)
+  (data $passive "hello")
+  (data $active (i32.const 16) "abc")
+  (func (export "init") (param i32 i32 i32)
+    (memory.init $passive (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "init_active") (param i32 i32 i32)
+    (memory.init $active (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "drop")
+    (data.drop $passive))
+  (func (export "copy") (param i32 i32 i32)
+    (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "fill") (param i32 i32 i32)
+    (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+)
@@ -0,0 +1,12 @@
+(module
+  (type $unary (func (param i32) (result i32)))
+  (type $void (func))
+  (table 5 funcref)
+  (elem (i32.const 0) $double $square $nop)
+  (func $double (type $unary) (i32.mul (local.get 0) (i32.const 2)))
+  (func $square (type $unary) (i32.mul (local.get 0) (local.get 0)))
+  (func $nop (type $void))
+  (func (export "dispatch") (param i32 i32) (result i32)
+    (call_indirect (type $unary) (local.get 1) (local.get 0))
+  )
+)
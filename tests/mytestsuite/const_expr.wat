@@ -0,0 +1,46 @@
+(module
+  (import "env" "memory_base" (global $memory_base i32))
+  (import "env" "table_base" (global $table_base i32))
+  (import "env" "scale" (global $scale i64))
+  (memory 1)
+  (table 4 funcref)
+  (global $f32 f32 (f32.const 1.5))
+  (global $f64 f64 (f64.const -2.25))
+  (global $sum i32 (i32.add (global.get $memory_base) (i32.const 8)))
+  (global $expr i64 (i64.sub (i64.mul (i64.add (global.get $scale) (i64.const 5)) (i64.const 3)) (i64.const 1)))
+  (global $ref funcref (ref.func $seven))
+  (data (offset (i32.add (global.get $memory_base) (i32.mul (i32.const 4) (i32.const 4)))) "\07")
+  (elem (offset (i32.add (global.get $table_base) (i32.const 1))) funcref (ref.func $seven) (ref.null func))
+  (func $seven (result i32)
+    i32.const 7
+  )
+  (func (export "f32") (result f32)
+    global.get $f32
+  )
+  (func (export "f64") (result f64)
+    global.get $f64
+  )
+  (func (export "sum") (result i32)
+    global.get $sum
+  )
+  (func (export "expr") (result i64)
+    global.get $expr
+  )
+  (func (export "load") (result i32)
+    global.get $memory_base
+    i32.load8_u offset=16
+  )
+  (func (export "call_elem") (result i32)
+    global.get $table_base
+    i32.const 1
+    i32.add
+    call_indirect (result i32)
+  )
+  (func (export "call_global") (result i32)
+    i32.const 0
+    global.get $ref
+    table.set
+    i32.const 0
+    call_indirect (result i32)
+  )
+)
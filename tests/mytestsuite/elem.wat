@@ -0,0 +1,9 @@
+(module
+  (table $t 4 funcref)
+  (func $one (result i32) i32.const 1)
+  (func $two (result i32) i32.const 2)
+  (elem (i32.const 1) $one $two)
+  (elem funcref (ref.func $two) (ref.null func))
+  (elem declare func $one)
+  (export "_start" (func $one))
+)
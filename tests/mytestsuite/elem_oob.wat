@@ -0,0 +1,6 @@
+(module
+  (table $t 2 funcref)
+  (func $one (result i32) i32.const 1)
+  (elem (i32.const 1) $one $one)
+  (export "_start" (func $one))
+)
@@ -0,0 +1,82 @@
+(module
+  (tag $e (param i32))
+  (tag $e2)
+  (func $thrower (param i32)
+    (throw $e (local.get 0)))
+  ;; 呼び出し先で投げられた例外のペイロードを受け取る
+  (func (export "catch") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (catch $e $h)
+        (call $thrower (local.get 0)))
+      (i32.const -1)))
+  (func (export "catch_all") (result i32)
+    (block $h
+      (try_table (catch_all $h)
+        (throw $e2))
+      (return (i32.const 0)))
+    (i32.const 1))
+  ;; catch_refで受け取った例外をthrow_refで投げ直す
+  (func $rethrow (param i32)
+    (block $h (result i32 exnref)
+      (try_table (catch_ref $e $h)
+        (call $thrower (local.get 0)))
+      (return))
+    (throw_ref))
+  (func (export "rethrow") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (result i32) (catch $e $h)
+        (call $rethrow (local.get 0))
+        (i32.const 0))))
+  ;; 内側のtry_tableはタグが合わないので外側まで抜ける
+  (func (export "nested") (result i32)
+    (block $outer (result i32)
+      (block $inner
+        (try_table (catch $e $outer)
+          (try_table (catch $e2 $inner)
+            (call $thrower (i32.const 3)))))
+      (i32.const -1)))
+  ;; catch節のラベルが関数本体を指すとそのまま返る
+  (func (export "catch_to_func") (result i32)
+    (try_table (catch $e 0)
+      (call $thrower (i32.const 7)))
+    (i32.const 0))
+  (func $make (export "make") (param i32) (result exnref)
+    (block $h (result exnref)
+      (try_table (catch_all_ref $h)
+        (call $thrower (local.get 0)))
+      (unreachable)))
+  (func $unpack (export "unpack") (param exnref) (result i32)
+    (block $h (result i32)
+      (try_table (catch $e $h)
+        (throw_ref (local.get 0)))
+      (i32.const -1)))
+  ;; C++のクリーンアップと同じくcatch_all_refで受けてthrow_refで投げ直す
+  (func $cleanup (param i32)
+    (block $h (result exnref)
+      (try_table (catch_all_ref $h)
+        (call $thrower (local.get 0)))
+      (return))
+    (throw_ref))
+  ;; クリーンアップを通った例外を外側のcatchでn回捕まえ、捕まえた数を返す
+  (func $cleanup_loop (export "cleanup_loop") (param $n i32) (result i32)
+    (local $caught i32)
+    (loop $l
+      (block $h (result i32)
+        (try_table (result i32) (catch $e $h)
+          (call $cleanup (local.get $n))
+          (i32.const 0)))
+      (drop)
+      (local.set $caught (i32.add (local.get $caught) (i32.const 1)))
+      (br_if $l (local.tee $n (i32.sub (local.get $n) (i32.const 1)))))
+    (local.get $caught))
+  ;; ローカル変数に持っている例外は、その間に他の例外が回収されても残る
+  (func (export "hold") (param i32) (result i32)
+    (local $exn exnref)
+    (local.set $exn (call $make (local.get 0)))
+    (drop (call $cleanup_loop (i32.const 200)))
+    (call $unpack (local.get $exn)))
+  (func (export "uncaught")
+    (call $thrower (i32.const 1)))
+  (func (export "throw_null")
+    (throw_ref (ref.null exn)))
+)
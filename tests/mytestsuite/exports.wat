@@ -0,0 +1,29 @@
+(module
+  (type $t (func (param i32) (result i32)))
+  (memory (export "memory") 1 4)
+  (table (export "__indirect_function_table") 2 funcref)
+  (global $counter (export "counter") (mut i32) (i32.const 5))
+  (global (export "pi") f64 (f64.const 3.25))
+  (tag (export "oops") (param i32))
+  (elem (i32.const 1) $inc)
+  (func $inc (export "inc") (type $t)
+    local.get 0
+    i32.const 1
+    i32.add
+  )
+  (func (export "bump") (result i32)
+    global.get $counter
+    i32.const 1
+    i32.add
+    global.set $counter
+    global.get $counter
+  )
+  (func (export "peek") (param i32) (result i32)
+    local.get 0
+    i32.load
+  )
+  (func (export "grow") (result i32)
+    i32.const 1
+    memory.grow
+  )
+)
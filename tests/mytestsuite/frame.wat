@@ -0,0 +1,38 @@
+(module
+  (func $extra (result i32)
+    i32.const 1
+    i32.const 2
+    i32.const 3
+    return
+  )
+  (func $br_func (param i32) (result i32)
+    block
+      i32.const 9
+      local.get 0
+      br_if 1
+      drop
+    end
+    i32.const 8
+  )
+  (func (export "return_trims") (result i32)
+    i32.const 100
+    call $extra
+    i32.add
+  )
+  (func (export "br_to_func") (param i32) (result i32)
+    i32.const 100
+    local.get 0
+    call $br_func
+    i32.add
+  )
+  (func (export "br_table_to_func") (result i32)
+    i32.const 1000
+    block (result i32)
+      i32.const 4
+      i32.const 5
+      i32.const 0
+      br_table 1 0
+    end
+    i32.add
+  )
+)
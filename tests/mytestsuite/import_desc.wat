@@ -0,0 +1,35 @@
+(module
+  (import "env" "memory" (memory 1 2))
+  (import "env" "table" (table 2 funcref))
+  (import "env" "counter" (global $counter (mut i32)))
+  (import "env" "base" (global $base i64))
+  (import "env" "add" (func $add (param i64 i64) (result i64)))
+  (table $own 1 externref)
+  (global $own i32 (i32.const 42))
+  (data (i32.const 0) "\2a\00\00\00")
+  (func (export "load") (result i32)
+    i32.const 0
+    i32.load
+  )
+  (func (export "own_global") (result i32)
+    global.get $own
+  )
+  (func (export "bump") (result i32)
+    global.get $counter
+    i32.const 1
+    i32.add
+    global.set $counter
+    global.get $counter
+  )
+  (func (export "sum") (result i64)
+    global.get $base
+    i64.const 2
+    call $add
+  )
+  (func (export "own_table_size") (result i32)
+    table.size $own
+  )
+  (func (export "imported_table_size") (result i32)
+    table.size 0
+  )
+)
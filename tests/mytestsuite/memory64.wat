@@ -0,0 +1,23 @@
+(module
+  (memory $m i64 1 3)
+  (memory $n 1)
+  (data (memory $m) (i64.const 16) "\2a")
+  (func (export "load64") (param i64) (result i32)
+    (i32.load8_u $m (local.get 0)))
+  (func (export "store64") (param i64 i32)
+    (i32.store $m offset=4 (local.get 0) (local.get 1)))
+  (func (export "size64") (result i64)
+    (memory.size $m))
+  (func (export "grow64") (param i64) (result i64)
+    (memory.grow $m (local.get 0)))
+  (func (export "fill64") (param i64 i32 i64)
+    (memory.fill $m (local.get 0) (local.get 1) (local.get 2)))
+  ;; 32bitのメモリへのコピーでは長さはi32になる
+  (func (export "copy_to_32") (param i32 i64 i32)
+    (memory.copy $n $m (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "load32") (param i32) (result i32)
+    (i32.load $n (local.get 0)))
+  ;; offset + addrは33bitで計算されるので桁あふれして先頭に戻ることはない
+  (func (export "load_high") (param i32) (result i32)
+    (i32.load8_u $n offset=4294967295 (local.get 0)))
+)
@@ -0,0 +1,22 @@
+(module
+  (memory $a 1)
+  ;; ホスト関数はexportされた"memory"を使う
+  (memory $b (export "memory") 1 2)
+  (data (memory $b) (i32.const 0) "\01\02\03\04")
+  (func (export "load_a") (param i32) (result i32)
+    (i32.load $a (local.get 0)))
+  (func (export "load_b") (param i32) (result i32)
+    (i32.load $b (local.get 0)))
+  (func (export "store_b") (param i32 i32)
+    (i32.store8 $b offset=1 (local.get 0) (local.get 1)))
+  (func (export "copy_b_to_a") (param i32 i32 i32)
+    (memory.copy $a $b (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "fill_a") (param i32 i32 i32)
+    (memory.fill $a (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "grow_b") (param i32) (result i32)
+    (memory.grow $b (local.get 0)))
+  (func (export "size_b") (result i32)
+    (memory.size $b))
+  (func (export "splat_b") (param i32) (result v128)
+    (v128.load32_splat $b (local.get 0)))
+)
@@ -0,0 +1,62 @@
+(module
+  (type $binop (func (param i32 i32) (result i32)))
+  (func $swap (param i32 i32) (result i32 i32)
+    local.get 1
+    local.get 0
+  )
+  (func $early (result i32 i32)
+    block
+      i32.const 1
+      i32.const 2
+      return
+    end
+    unreachable
+  )
+  (func (export "swap") (result i32 i32)
+    i32.const 7
+    i32.const 9
+    call $swap
+  )
+  (func (export "block_params") (result i32)
+    i32.const 10
+    i32.const 20
+    block (type $binop)
+      i32.add
+    end
+  )
+  (func (export "loop_params") (result i32) (local $n i32)
+    i32.const 0
+    i32.const 5
+    local.set $n
+    loop (param i32) (result i32)
+      local.get $n
+      i32.add
+      local.get $n
+      i32.const 1
+      i32.sub
+      local.tee $n
+      br_if 0
+    end
+  )
+  (func (export "if_params") (param i32) (result i32 i32)
+    i32.const 3
+    local.get 0
+    if (param i32) (result i32 i32)
+      i32.const 1
+    else
+      i32.const 2
+    end
+  )
+  (func (export "br_multi") (result i32 i32)
+    block (result i32 i32)
+      i32.const 1
+      i32.const 2
+      i32.const 3
+      br 0
+    end
+  )
+  (func (export "return_in_block") (result i32 i32 i32)
+    i32.const 5
+    call $early
+  )
+)
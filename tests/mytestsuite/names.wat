@@ -0,0 +1,15 @@
+(module $names
+  (memory 1)
+  (global $counter (mut i32) (i32.const 0))
+  (data $greeting (i32.const 0) "hello")
+  (func $_start (result i32)
+    (call $fail (i32.const 1))
+  )
+  (func $fail (param $x i32) (result i32)
+    (block $exit
+      unreachable
+    )
+    (local.get $x)
+  )
+  (export "_start" (func $_start))
+)
@@ -0,0 +1,41 @@
+(module
+  (func (export "i32.div_s") (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.div_s
+  )
+  (func (export "i32.rem_s") (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.rem_s
+  )
+  (func (export "i64.div_u") (param i64 i64) (result i64)
+    local.get 0
+    local.get 1
+    i64.div_u
+  )
+  (func (export "i32.trunc_f32_s") (param f32) (result i32)
+    local.get 0
+    i32.trunc_f32_s
+  )
+  (func (export "i64.trunc_f64_u") (param f64) (result i64)
+    local.get 0
+    i64.trunc_f64_u
+  )
+  (func (export "i32.trunc_sat_f32_s") (param f32) (result i32)
+    local.get 0
+    i32.trunc_sat_f32_s
+  )
+  (func (export "i32.trunc_sat_f64_u") (param f64) (result i32)
+    local.get 0
+    i32.trunc_sat_f64_u
+  )
+  (func (export "i64.trunc_sat_f32_u") (param f32) (result i64)
+    local.get 0
+    i64.trunc_sat_f32_u
+  )
+  (func (export "i64.trunc_sat_f64_s") (param f64) (result i64)
+    local.get 0
+    i64.trunc_sat_f64_s
+  )
+)
@@ -0,0 +1,6 @@
+(module
+  (func $loop (result i32)
+    call $loop
+  )
+  (export "_start" (func $loop))
+)
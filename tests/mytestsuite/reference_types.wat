@@ -0,0 +1,32 @@
+(module
+  (type $i_i (func (param i32) (result i32)))
+  (table $funcs 2 funcref)
+  (table $externs 0 3 externref)
+  (elem $passive funcref (ref.func $double) (ref.null func))
+  (func $double (type $i_i)
+    (i32.mul (local.get 0) (i32.const 2)))
+  (func (export "init") (param i32 i32 i32)
+    (table.init $funcs $passive (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "drop")
+    (elem.drop $passive))
+  (func (export "copy") (param i32 i32 i32)
+    (table.copy $funcs $funcs (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "call") (param i32 i32) (result i32)
+    (call_indirect $funcs (type $i_i) (local.get 1) (local.get 0)))
+  (func (export "is_null") (param i32) (result i32)
+    (ref.is_null (table.get $funcs (local.get 0))))
+  (func (export "func_ref") (result funcref)
+    (ref.func $double))
+  (func (export "grow") (param externref i32) (result i32)
+    (table.grow $externs (local.get 0) (local.get 1)))
+  (func (export "size") (result i32)
+    (table.size $externs))
+  (func (export "get") (param i32) (result externref)
+    (table.get $externs (local.get 0)))
+  (func (export "set") (param i32 externref)
+    (table.set $externs (local.get 0) (local.get 1)))
+  (func (export "fill") (param i32 externref i32)
+    (table.fill $externs (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "select") (param externref externref i32) (result externref)
+    (select (result externref) (local.get 0) (local.get 1) (local.get 2)))
+)
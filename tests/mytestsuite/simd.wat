@@ -0,0 +1,36 @@
+(module
+  (memory 1)
+  (data (i32.const 0) "\01\02\03\04\05\06\07\08\09\0a\0b\0c\0d\0e\0f\10")
+  (func (export "i32x4.add") (param v128 v128) (result v128)
+    (i32x4.add (local.get 0) (local.get 1)))
+  (func (export "i8x16.add_sat_u") (param v128 v128) (result v128)
+    (i8x16.add_sat_u (local.get 0) (local.get 1)))
+  (func (export "i16x8.lt_s") (param v128 v128) (result v128)
+    (i16x8.lt_s (local.get 0) (local.get 1)))
+  (func (export "f32x4.min") (param v128 v128) (result v128)
+    (f32x4.min (local.get 0) (local.get 1)))
+  (func (export "i8x16.shuffle") (param v128 v128) (result v128)
+    (i8x16.shuffle 0 16 1 17 2 18 3 19 4 20 5 21 6 22 7 23 (local.get 0) (local.get 1)))
+  (func (export "i8x16.swizzle") (param v128 v128) (result v128)
+    (i8x16.swizzle (local.get 0) (local.get 1)))
+  (func (export "i32x4.shl") (param v128 i32) (result v128)
+    (i32x4.shl (local.get 0) (local.get 1)))
+  (func (export "i8x16.bitmask") (param v128) (result i32)
+    (i8x16.bitmask (local.get 0)))
+  (func (export "f64x2.extract_lane") (param v128) (result f64)
+    (f64x2.extract_lane 1 (local.get 0)))
+  (func (export "i16x8.replace_lane") (param v128 i32) (result v128)
+    (i16x8.replace_lane 7 (local.get 0) (local.get 1)))
+  (func (export "i64x2.splat") (param i64) (result v128)
+    (i64x2.splat (local.get 0)))
+  (func (export "i32x4.trunc_sat_f32x4_s") (param v128) (result v128)
+    (i32x4.trunc_sat_f32x4_s (local.get 0)))
+  (func (export "v128.load8x8_s") (param i32) (result v128)
+    (v128.load8x8_s (local.get 0)))
+  (func (export "v128.load32_lane") (param i32 v128) (result v128)
+    (v128.load32_lane 3 (local.get 0) (local.get 1)))
+  (func (export "v128.store") (param i32 v128)
+    (v128.store (local.get 0) (local.get 1)))
+  (func (export "v128.const") (result v128)
+    (v128.const i32x4 1 2 3 4))
+)
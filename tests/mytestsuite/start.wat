@@ -0,0 +1,10 @@
+(module
+  (global $g (mut i32) (i32.const 0))
+  (func $init
+    (global.set $g (i32.const 42))
+  )
+  (func (export "_start") (result i32)
+    (global.get $g)
+  )
+  (start $init)
+)
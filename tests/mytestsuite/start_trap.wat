@@ -0,0 +1,7 @@
+(module
+  (func $init
+    unreachable
+  )
+  (func (export "_start"))
+  (start $init)
+)
@@ -0,0 +1,20 @@
+(module
+  (type $i64_i32 (func (param i64) (result i32)))
+  (table 2 funcref)
+  (elem (i32.const 0) $is_even $is_odd)
+  (func $is_even (export "is_even") (type $i64_i32)
+    (if (result i32) (i64.eqz (local.get 0))
+      (then (i32.const 1))
+      (else (return_call $is_odd (i64.sub (local.get 0) (i64.const 1))))))
+  (func $is_odd (export "is_odd") (type $i64_i32)
+    (if (result i32) (i64.eqz (local.get 0))
+      (then (i32.const 0))
+      (else (return_call_indirect (type $i64_i32) (i64.sub (local.get 0) (i64.const 1)) (i32.const 0)))))
+  ;; 末尾呼び出しの後に残った値は捨てられる
+  (func (export "count") (param i64 i64) (result i64)
+    (i64.const 42)
+    (drop)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (local.get 1))
+      (else (return_call 2 (i64.sub (local.get 0) (i64.const 1)) (i64.add (local.get 1) (i64.const 1))))))
+)
@@ -0,0 +1,46 @@
+(module
+  (memory (export "memory") 1 1 shared)
+  (memory $plain 1)
+
+  (func (export "load") (param i32) (result i32)
+    (i32.atomic.load (local.get 0)))
+  (func (export "load8") (param i32) (result i64)
+    (i64.atomic.load8_u (local.get 0)))
+  (func (export "store16") (param i32 i32)
+    (i32.atomic.store16 (local.get 0) (local.get 1)))
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.atomic.rmw.add (local.get 0) (local.get 1)))
+  (func (export "sub8") (param i32 i32) (result i32)
+    (i32.atomic.rmw8.sub_u (local.get 0) (local.get 1)))
+  (func (export "xchg64") (param i32 i64) (result i64)
+    (i64.atomic.rmw.xchg (local.get 0) (local.get 1)))
+  (func (export "cmpxchg16") (param i32 i32 i32) (result i32)
+    (i32.atomic.rmw16.cmpxchg_u (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "wait") (param i32 i32 i64) (result i32)
+    (memory.atomic.wait32 (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "notify") (param i32 i32) (result i32)
+    (memory.atomic.notify (local.get 0) (local.get 1)))
+  (func (export "wait_plain") (result i32)
+    (memory.atomic.wait64 $plain (i32.const 0) (i64.const 0) (i64.const 0)))
+  (func (export "fence")
+    (atomic.fence))
+
+  ;; 0番地のカウンタを$n回増やしてから、4番地の終了数を増やしてjoinを起こす
+  (func (export "worker") (param $n i32)
+    (loop $l
+      (drop (i32.atomic.rmw.add (i32.const 0) (i32.const 1)))
+      (br_if $l (local.tee $n (i32.sub (local.get $n) (i32.const 1)))))
+    (drop (i32.atomic.rmw.add (i32.const 4) (i32.const 1)))
+    (drop (memory.atomic.notify (i32.const 4) (i32.const 1))))
+
+  ;; $workers個のworkerが終わるまで眠り、カウンタの値を返す
+  (func (export "join") (param $workers i32) (result i32)
+    (local $done i32)
+    (block $b
+      (loop $l
+        (local.set $done (i32.atomic.load (i32.const 4)))
+        (br_if $b (i32.eq (local.get $done) (local.get $workers)))
+        (drop (memory.atomic.wait32 (i32.const 4) (local.get $done) (i64.const -1)))
+        (br $l)))
+    (i32.atomic.load (i32.const 0)))
+)
@@ -6,11 +6,22 @@ mod tests {
   use std::{path, vec};
 
   use read_wasm::binary;
-  use read_wasm::binary::table_sec::RefType;
+  use read_wasm::binary::data_sec::DataMode;
+  use read_wasm::binary::elem_sec::{ElemInit, ElemMode};
+  use read_wasm::binary::import_sec::ImportDesc;
+  use read_wasm::binary::instructions::Instructions;
+  use read_wasm::binary::memory_sec::MemorySec;
+  use read_wasm::binary::print::section_headers;
+  use read_wasm::binary::table_sec::{RefType, TableSec};
+  use read_wasm::binary::type_sec::FuncType;
+  use read_wasm::binary::value_type::ValueType;
+  use read_wasm::binary::validate::validate;
 use read_wasm::binary::wasm::Wasm;
   use read_wasm::exec::exec_machine::ExecMachine;
   use read_wasm::exec::func_instance::FuncInstance;
-  use read_wasm::exec::store::Store;
+  use read_wasm::exec::linker::Linker;
+  use read_wasm::exec::store::{ExternType, ExternVal, GlobalValue, MemoryInst, Store, TableInst};
+  use read_wasm::exec::trap::Trap;
   use read_wasm::exec::value::Value;
   use read_wasm::exec::wasi::WasiSnapshotPreview1;
 
@@ -19,7 +30,29 @@ use read_wasm::binary::wasm::Wasm;
     File::open(path).unwrap()
       .read_to_string(&mut test_suite).unwrap();
     let binary = wat::parse_str(&test_suite).unwrap();
-    Wasm::new(&binary[..])
+    Wasm::new(&binary[..]).unwrap()
+  }
+
+  // env.add: (i64, i64) -> i64 を定義したLinker
+  fn env_add_linker() -> Linker {
+    let mut linker = Linker::new();
+    let ty = FuncType { param_types: vec![ValueType::I64, ValueType::I64], return_types: vec![ValueType::I64] };
+    linker.define_func("env", "add", ty, |_, _, args| match (&args[0], &args[1]) {
+      (Value::I64(a), Value::I64(b)) => Ok(vec![Value::I64(a + b)]),
+      _ => unreachable!(),
+    }).unwrap();
+    linker
+  }
+
+  // import_desc.watのimportを定義したLinker
+  fn import_desc_linker(memory: MemoryInst, table: TableInst, counter: GlobalValue, base: GlobalValue) -> Linker {
+    let mut linker = env_add_linker();
+    linker
+      .define_memory("env", "memory", memory).unwrap()
+      .define_table("env", "table", table).unwrap()
+      .define_global("env", "counter", counter).unwrap()
+      .define_global("env", "base", base).unwrap();
+    linker
   }
 
   #[test]
@@ -63,6 +96,165 @@ use read_wasm::binary::wasm::Wasm;
     assert_eq!(imports[0].desc, binary::import_sec::ImportDesc::Func(0));
   }
 
+  #[tokio::test]
+  async fn test_import_descriptors() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/import_desc.wat");
+    let imports = wasm.import_section.as_ref().unwrap();
+    let descs: Vec<&ImportDesc> = imports.iter().map(|import| &import.desc).collect();
+    assert_eq!(descs, [
+      &ImportDesc::Memory(MemorySec { min: 1, max: Some(2), memory64: false, shared: false }),
+      &ImportDesc::Table(TableSec { min: 2, max: None, reftype: RefType::FuncRef }),
+      &ImportDesc::Global { valtype: ValueType::I32, mutability: true },
+      &ImportDesc::Global { valtype: ValueType::I64, mutability: false },
+      &ImportDesc::Func(0),
+    ]);
+    validate(&wasm).unwrap();
+
+    // importしたものがそれぞれのインデックス空間の先頭に来る
+    let mut linker = import_desc_linker(
+      MemoryInst::new(1, Some(2), false).unwrap(),
+      TableInst::new(&TableSec { min: 2, max: None, reftype: RefType::FuncRef }).unwrap(),
+      GlobalValue { value: Value::I32(0), mutability: true },
+      GlobalValue { value: Value::I64(40), mutability: false },
+    );
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    assert_eq!(em.store.memories.len(), 1);
+    assert_eq!(em.store.tables.len(), 2);
+    assert_eq!(em.store.globals.len(), 3);
+    assert_eq!(em.store.funcs[1].export_name(), Some(&"load".to_string()));
+    let mut wasi = WasiSnapshotPreview1::new();
+    let cases = [
+      ("load", Value::I32(42)),
+      ("own_global", Value::I32(42)),
+      ("bump", Value::I32(1)),
+      ("bump", Value::I32(2)),
+      ("sum", Value::I64(42)),
+      ("own_table_size", Value::I32(1)),
+      ("imported_table_size", Value::I32(2)),
+    ];
+    for (name, expected) in cases {
+      em.invoke(&mut wasi, &mut linker, name.to_string(), vec![]).await.unwrap();
+      assert_eq!(em.value_stack.pop(), Some(expected), "{}", name);
+    }
+
+    // 定義されていないものや型の合わないものはインスタンス化の時点で弾かれる
+    let err = ExecMachine::init_without_start(create_wasm_from_testsuite("tests/mytestsuite/import_desc.wat"), &env_add_linker()).unwrap_err();
+    assert_eq!(err.to_string(), "unknown import: env.memory");
+    let memory = |min, max| MemoryInst::new(min, max, false).unwrap();
+    let table = |reftype| TableInst::new(&TableSec { min: 2, max: None, reftype }).unwrap();
+    let global = |value, mutability| GlobalValue { value, mutability };
+    let cases = [
+      (memory(0, Some(2)), table(RefType::FuncRef), global(Value::I32(0), true), global(Value::I64(0), false), "env.memory"),
+      (memory(1, None), table(RefType::FuncRef), global(Value::I32(0), true), global(Value::I64(0), false), "env.memory"),
+      (memory(1, Some(3)), table(RefType::FuncRef), global(Value::I32(0), true), global(Value::I64(0), false), "env.memory"),
+      (memory(1, Some(2)), table(RefType::ExternRef), global(Value::I32(0), true), global(Value::I64(0), false), "env.table"),
+      (memory(1, Some(2)), table(RefType::FuncRef), global(Value::I32(0), false), global(Value::I64(0), false), "env.counter"),
+      (memory(1, Some(2)), table(RefType::FuncRef), global(Value::I32(0), true), global(Value::I32(0), false), "env.base"),
+    ];
+    for (memory, table, counter, base, name) in cases {
+      let linker = import_desc_linker(memory, table, counter, base);
+      let wasm = create_wasm_from_testsuite("tests/mytestsuite/import_desc.wat");
+      let err = ExecMachine::init_without_start(wasm, &linker).unwrap_err();
+      assert!(err.to_string().starts_with(&format!("incompatible import type for {}:", name)), "{}", err);
+    }
+
+    // importしたメモリの型も検査される
+    let binary = wat::parse_str(r#"(module (import "env" "m" (memory 1 shared)))"#).unwrap();
+    let err = validate(&Wasm::new(&binary[..]).unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "invalid module: shared memory must have maximum");
+  }
+
+  #[tokio::test]
+  async fn test_const_expr() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/const_expr.wat");
+    validate(&wasm).unwrap();
+    // importしたglobalの値はLinkerから受け取る。無ければインスタンス化できない
+    let err = ExecMachine::init_without_start(create_wasm_from_testsuite("tests/mytestsuite/const_expr.wat"), &Linker::new()).unwrap_err();
+    assert_eq!(err.to_string(), "unknown import: env.memory_base");
+    let mut linker = Linker::new();
+    linker
+      .define_global("env", "memory_base", GlobalValue { value: Value::I32(32), mutability: false }).unwrap()
+      .define_global("env", "table_base", GlobalValue { value: Value::I32(1), mutability: false }).unwrap()
+      .define_global("env", "scale", GlobalValue { value: Value::I64(2), mutability: false }).unwrap();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    let cases = [
+      ("f32", Value::F32(1.5)),
+      ("f64", Value::F64(-2.25)),
+      ("sum", Value::I32(40)),
+      ("expr", Value::I64(20)),
+      ("load", Value::I32(7)),
+      ("call_elem", Value::I32(7)),
+      ("call_global", Value::I32(7)),
+    ];
+    for (name, expected) in cases {
+      em.invoke(&mut wasi, &mut linker, name.to_string(), vec![]).await.unwrap();
+      assert_eq!(em.value_stack.pop(), Some(expected), "{}", name);
+    }
+    // データとエレメントはimportしたglobalの値だけずれた位置に置かれる
+    assert_eq!(em.store.memories[0].read(16, 1).unwrap(), [0]);
+    assert_eq!(em.store.memories[0].read(48, 1).unwrap(), [7]);
+    assert!(matches!(em.store.tables[0].elems[2], Value::FuncRef(Some(_))));
+    assert_eq!(em.store.tables[0].elems[3], Value::FuncRef(None));
+
+    let invalid = [
+      // 定義したglobalやmutableなglobalは読めない
+      (r#"(module (global $a i32 (i32.const 1)) (global i32 (global.get $a)))"#, "constant expression required"),
+      (r#"(module (import "env" "g" (global $g (mut i32))) (global i32 (global.get $g)))"#, "constant expression required"),
+      (r#"(module (global i32 (i32.add (i32.const 1) (i64.const 2))))"#, "type mismatch"),
+      (r#"(module (global i64 (i64.add (i64.const 1) (i64.const 2)) (i64.const 3)))"#, "type mismatch"),
+      (r#"(module (global f32 (f32.add (f32.const 1) (f32.const 2))))"#, "constant expression required"),
+    ];
+    for (text, message) in invalid {
+      let binary = wat::parse_str(text).unwrap();
+      let err = validate(&Wasm::new(&binary[..]).unwrap()).unwrap_err();
+      assert_eq!(err.to_string(), format!("invalid module: {}", message), "{}", text);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_exports() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/exports.wat");
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+
+    let i32_to_i32 = FuncType { param_types: vec![ValueType::I32], return_types: vec![ValueType::I32] };
+    let exports: Vec<(&str, ExternType)> = em.store.exports().collect();
+    assert_eq!(exports[..6], [
+      ("memory", ExternType::Memory(MemorySec { min: 1, max: Some(4), memory64: false, shared: false })),
+      ("__indirect_function_table", ExternType::Table(TableSec { min: 2, max: None, reftype: RefType::FuncRef })),
+      ("counter", ExternType::Global { valtype: ValueType::I32, mutability: true }),
+      ("pi", ExternType::Global { valtype: ValueType::F64, mutability: false }),
+      ("oops", ExternType::Tag(FuncType { param_types: vec![ValueType::I32], return_types: vec![] })),
+      ("inc", ExternType::Func(i32_to_i32)),
+    ]);
+    assert_eq!(em.store.export("bump"), Some(ExternVal::Func(1)));
+    assert_eq!(em.store.export("missing"), None);
+
+    // ホストからメモリとglobalを読み書きする
+    em.store.get_memory_mut("memory").unwrap().write(8, &42i32.to_le_bytes()).unwrap();
+    em.invoke(&mut wasi, &mut linker, "peek".to_string(), vec![Value::I32(8)]).await.unwrap();
+    assert_eq!(em.value_stack.pop(), Some(Value::I32(42)));
+    em.store.set_global("counter", Value::I32(10)).unwrap();
+    em.invoke(&mut wasi, &mut linker, "bump".to_string(), vec![]).await.unwrap();
+    assert_eq!(em.value_stack.pop(), Some(Value::I32(11)));
+    assert_eq!(em.store.get_global("counter").unwrap().value, Value::I32(11));
+    assert_eq!(em.store.get_global("pi").unwrap().value, Value::F64(3.25));
+    assert_eq!(em.store.get_table("__indirect_function_table").unwrap().elems[1], Value::FuncRef(Some(0)));
+
+    assert_eq!(em.store.set_global("pi", Value::F64(1.0)).unwrap_err().to_string(), "global \"pi\" is immutable");
+    assert!(em.store.set_global("counter", Value::I64(1)).is_err());
+    assert!(em.store.set_global("memory", Value::I32(1)).is_err());
+    assert!(em.store.get_memory("counter").is_none());
+    assert!(em.store.get_global("memory").is_none());
+
+    // メモリの型は現在のサイズを返す
+    em.invoke(&mut wasi, &mut linker, "grow".to_string(), vec![]).await.unwrap();
+    assert_eq!(em.store.exports().next().unwrap().1, ExternType::Memory(MemorySec { min: 2, max: Some(4), memory64: false, shared: false }));
+    assert_eq!(em.store.get_memory("memory").unwrap().len(), 2 * 65536);
+  }
+
   #[test]
   fn test_parse_memorysec_wasm() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/memorysec.wat");
@@ -91,8 +283,7 @@ use read_wasm::binary::wasm::Wasm;
 
     let data = wasm.data_section.unwrap();
     assert_eq!(data.len(), 1);
-    assert_eq!(data[0].memory_index, 0);
-    assert_eq!(data[0].offset, 1);
+    assert_eq!(data[0].mode, DataMode::Active { memory_index: 0, offset: vec![Instructions::I32Const(1), Instructions::End] });
     assert_eq!(&data[0].init, &[0x61, 0x62, 0x63, 0x64])
   }
 
@@ -131,10 +322,42 @@ use read_wasm::binary::wasm::Wasm;
     assert_eq!(tables[0].reftype, RefType::FuncRef);
   }
 
+  #[test]
+  fn test_parse_elemsec_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/elem.wat");
+    assert!(wasm.element_section.is_some());
+
+    let elems = wasm.element_section.unwrap();
+    assert_eq!(elems.len(), 3);
+    assert_eq!(elems[0].mode, ElemMode::Active { table_idx: 0, offset: vec![Instructions::I32Const(1), Instructions::End] });
+    assert_eq!(elems[0].init, ElemInit::FuncIndices(vec![0, 1]));
+    assert_eq!(elems[1].mode, ElemMode::Passive);
+    assert_eq!(elems[1].init, ElemInit::Exprs(vec![
+      vec![Instructions::RefFunc(1), Instructions::End],
+      vec![Instructions::RefNull(RefType::FuncRef), Instructions::End],
+    ]));
+    assert_eq!(elems[2].mode, ElemMode::Declarative);
+  }
+
+  #[test]
+  fn test_parse_namesec_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/names.wat");
+    assert!(wasm.custom_sections.iter().any(|c| c.name == "name"));
+
+    let names = wasm.name_section.unwrap();
+    assert_eq!(names.module.as_deref(), Some("names"));
+    assert_eq!(names.func_name(0).unwrap(), "_start");
+    assert_eq!(names.func_name(1).unwrap(), "fail");
+    assert_eq!(names.local_name(1, 0).unwrap(), "x");
+    assert_eq!(names.labels.get(&1).unwrap().get(&0).unwrap(), "exit");
+    assert_eq!(names.globals.get(&0).unwrap(), "counter");
+    assert_eq!(names.data.get(&0).unwrap(), "greeting");
+  }
+
   #[test]
   fn test_init_memory_store() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/memorysec.wat");
-    let store = Store::new(vec![], &wasm);
+    let store = Store::new(vec![], &wasm, &Linker::new()).unwrap();
     assert_eq!(store.memories.len(), 1);
     assert_eq!(store.memories[0].memory.len(), 65536)
   }
@@ -142,13 +365,31 @@ use read_wasm::binary::wasm::Wasm;
   #[test]
   fn test_init_data() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/data.wat");
-    let store = Store::new(vec![], &wasm);
+    let store = Store::new(vec![], &wasm, &Linker::new()).unwrap();
     assert_eq!(store.memories.len(), 1);
     assert_eq!(store.memories[0].memory.len(), 65536);
     assert_eq!(&store.memories[0].memory[0..5], b"hello");
     assert_eq!(&store.memories[0].memory[5..10], b"world");
   }
 
+  #[test]
+  fn test_init_table() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/elem.wat");
+    let store = Store::new(FuncInstance::new(&wasm), &wasm, &Linker::new()).unwrap();
+    assert_eq!(store.tables.len(), 1);
+    assert_eq!(store.tables[0].elems, vec![Value::FuncRef(None), Value::FuncRef(Some(0)), Value::FuncRef(Some(1)), Value::FuncRef(None)]);
+
+    // 存在しない関数をテーブルに入れるセグメントはインスタンス化できない
+    let err = Store::new(vec![], &wasm, &Linker::new()).unwrap_err();
+    assert_eq!(err.to_string(), "unknown function 0");
+  }
+
+  #[test]
+  fn test_init_table_out_of_bounds() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/elem_oob.wat");
+    assert!(Store::new(FuncInstance::new(&wasm), &wasm, &Linker::new()).is_err());
+  }
+
   #[tokio::test]
   async fn test_instantiate_with_import() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/func.wat");
@@ -161,56 +402,445 @@ use read_wasm::binary::wasm::Wasm;
     assert_eq!(func_instances.len(), 3);
     assert_eq!(func_instances[0].name().unwrap(), "one");
     assert_eq!(func_instances[1].name().unwrap(), "none");
-    assert_eq!(func_instances[2].name().unwrap(), "_start");
+    assert_eq!(func_instances[2].name().unwrap(), "hoge");
+    assert_eq!(func_instances[2].export_name().unwrap(), "_start");
 
-    let mut em = ExecMachine::init(wasm, "_start", vec![]);
+    let mut linker = Linker::new();
+    let one = FuncType { param_types: vec![], return_types: vec![ValueType::I64] };
+    linker
+      .define_func("env", "one", one, |_, _, _| Ok(vec![Value::I64(1)])).unwrap()
+      .define_func("env", "none", FuncType { param_types: vec![], return_types: vec![] }, |_, _, _| Ok(vec![])).unwrap();
     let mut wasi = WasiSnapshotPreview1::new();
-    em.exec(&mut wasi).await.unwrap();
+    let mut em = ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.unwrap();
+    em.exec(&mut wasi, &mut linker).await.unwrap();
     assert_eq!(em.value_stack.last().unwrap(), &Value::I64(3));
   }
 
   #[tokio::test]
   async fn test_import_func() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/import_func.wat");
-    let mut em = ExecMachine::init(wasm, "_start", vec![]);
+    let mut linker = env_add_linker();
     let mut wasi = WasiSnapshotPreview1::new();
-    em.exec(&mut wasi).await.unwrap();
+    let mut em = ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.unwrap();
+    em.exec(&mut wasi, &mut linker).await.unwrap();
     assert_eq!(em.value_stack.last().unwrap(), &Value::I64(3));
   }
 
+  #[tokio::test]
+  async fn test_linker() {
+    let wasm = || create_wasm_from_testsuite("tests/mytestsuite/import_func.wat");
+    let i64s = |params: usize| FuncType { param_types: vec![ValueType::I64; params], return_types: vec![ValueType::I64] };
+
+    // importはインスタンス化の時点で解決される
+    let err = ExecMachine::init_without_start(wasm(), &Linker::new()).unwrap_err();
+    assert_eq!(err.to_string(), "unknown import: env.add");
+    let mut linker = Linker::new();
+    linker.define_func("env", "add", i64s(1), |_, _, args| Ok(vec![args[0].clone()])).unwrap();
+    let err = ExecMachine::init_without_start(wasm(), &linker).unwrap_err();
+    assert_eq!(err.to_string(), "incompatible import type for env.add: expected [I64, I64] -> [I64], found [I64] -> [I64]");
+    let err = linker.define_func("env", "add", i64s(2), |_, _, _| Ok(vec![])).unwrap_err();
+    assert_eq!(err.to_string(), "env.add is already defined");
+
+    // ホスト関数は呼び出しをまたいで状態を持てる
+    let mut linker = Linker::with_wasi();
+    let mut calls = 0;
+    linker.define_func("env", "add", i64s(2), move |_, _, args| {
+      calls += 1;
+      match (&args[0], &args[1]) {
+        (Value::I64(a), Value::I64(b)) => Ok(vec![Value::I64(a * b + calls)]),
+        _ => unreachable!(),
+      }
+    }).unwrap();
+    let mut em = ExecMachine::init_without_start(wasm(), &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    for expected in [3, 4] {
+      em.invoke(&mut wasi, &mut linker, "_start".to_string(), vec![]).await.unwrap();
+      assert_eq!(em.value_stack.pop(), Some(Value::I64(expected)));
+    }
+
+    // 宣言と違う型の値を返すとトラップする
+    let mut linker = Linker::new();
+    linker.define_func("env", "add", i64s(2), |_, _, _| Ok(vec![Value::I32(0)])).unwrap();
+    let mut em = ExecMachine::init_without_start(wasm(), &linker).unwrap();
+    let err = em.invoke(&mut wasi, &mut linker, "_start".to_string(), vec![]).await.unwrap_err();
+    assert_eq!(err.trap, Trap::HostError("env.add returned [I32], expected [I64]".to_string()));
+    assert_eq!(err.backtrace, ["env.add", "$hoge"]);
+
+    // ホスト関数も複数の値を返せる
+    let binary = wat::parse_str(r#"(module
+      (import "env" "divmod" (func $divmod (param i32 i32) (result i32 i32)))
+      (func (export "_start") (result i32 i32)
+        (call $divmod (i32.const 17) (i32.const 5)))
+    )"#).unwrap();
+    let mut linker = Linker::new();
+    let i32s = |n| vec![ValueType::I32; n];
+    linker.define_func("env", "divmod", FuncType { param_types: i32s(2), return_types: i32s(2) }, |_, _, args| match (&args[0], &args[1]) {
+      (Value::I32(a), Value::I32(b)) => Ok(vec![Value::I32(a / b), Value::I32(a % b)]),
+      _ => unreachable!(),
+    }).unwrap();
+    let mut em = ExecMachine::init_without_start(Wasm::new(&binary[..]).unwrap(), &linker).unwrap();
+    em.invoke(&mut wasi, &mut linker, "_start".to_string(), vec![]).await.unwrap();
+    assert_eq!(em.value_stack, [Value::I32(3), Value::I32(2)]);
+  }
+
   #[tokio::test]
   async fn test_exec_add_wasm() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/add.wat");
-    let mut em = ExecMachine::init(wasm, "_start", vec![]);
+    let mut linker = Linker::new();
     let mut wasi = WasiSnapshotPreview1::new();
-    em.exec(&mut wasi).await.unwrap();
+    let mut em = ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.unwrap();
+    em.exec(&mut wasi, &mut linker).await.unwrap();
     assert_eq!(em.value_stack.last().unwrap(), &Value::I64(3));
   }
 
   #[tokio::test]
   async fn test_exec_block_wasm() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/block.wat");
-    let mut em = ExecMachine::init(wasm, "_start", vec![Value::I64(100)]);
+    let mut linker = Linker::new();
     let mut wasi = WasiSnapshotPreview1::new();
-    em.exec(&mut wasi).await.unwrap();
+    let mut em = ExecMachine::init(wasm, "_start", vec![Value::I64(100)], &mut wasi, &mut linker).await.unwrap();
+    em.exec(&mut wasi, &mut linker).await.unwrap();
     assert_eq!(em.value_stack.last().unwrap(), &Value::I64(5050));
   }
 
   #[tokio::test]
   async fn test_exec_block_table_wasm() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/block_table.wat");
-    let mut em = ExecMachine::init(wasm, "_start", vec![]);
+    let mut linker = Linker::new();
     let mut wasi = WasiSnapshotPreview1::new();
-    em.exec(&mut wasi).await.unwrap();
+    let mut em = ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.unwrap();
+    em.exec(&mut wasi, &mut linker).await.unwrap();
     assert_eq!(em.value_stack.last().unwrap(), &Value::I32(213));
   }
 
+  #[tokio::test]
+  async fn test_call_indirect_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/call_indirect.wat");
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    em.invoke(&mut wasi, &mut linker, "dispatch".to_string(), vec![Value::I32(0), Value::I32(21)]).await.unwrap();
+    assert_eq!(em.value_stack.pop().unwrap(), Value::I32(42));
+    em.invoke(&mut wasi, &mut linker, "dispatch".to_string(), vec![Value::I32(1), Value::I32(7)]).await.unwrap();
+    assert_eq!(em.value_stack.pop().unwrap(), Value::I32(49));
+
+    let traps = [(2, Trap::IndirectCallTypeMismatch), (3, Trap::UninitializedElement), (5, Trap::UndefinedElement)];
+    for (idx, trap) in traps {
+      let mut em = em.clone();
+      let err = em.invoke(&mut wasi, &mut linker, "dispatch".to_string(), vec![Value::I32(idx), Value::I32(1)]).await.unwrap_err();
+      assert_eq!(err.trap, trap);
+    }
+
+    // ストアに無い関数を指す要素はパニックせずにトラップする
+    em.store.tables[0].elems[4] = Value::FuncRef(Some(99));
+    let err = em.invoke(&mut wasi, &mut linker, "dispatch".to_string(), vec![Value::I32(4), Value::I32(1)]).await.unwrap_err();
+    assert_eq!(err.trap, Trap::UndefinedElement);
+  }
+
+  #[tokio::test]
+  async fn test_start_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/start.wat");
+    let mut linker = Linker::new();
+    let mut wasi = WasiSnapshotPreview1::new();
+    let mut em = ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.unwrap();
+    em.exec(&mut wasi, &mut linker).await.unwrap();
+    assert_eq!(em.value_stack.last().unwrap(), &Value::I32(42));
+
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/start.wat");
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    assert_eq!(em.start, Some(0));
+    em.invoke(&mut wasi, &mut linker, "_start".to_string(), vec![]).await.unwrap();
+    assert_eq!(em.value_stack.pop().unwrap(), Value::I32(0));
+    em.run_start(&mut wasi, &mut linker).await.unwrap();
+    assert_eq!(em.start, None);
+    em.invoke(&mut wasi, &mut linker, "_start".to_string(), vec![]).await.unwrap();
+    assert_eq!(em.value_stack.pop().unwrap(), Value::I32(42));
+
+    // exportの無いモジュールでもstart関数とテーブルに置いた関数が動く
+    let binary = wat::parse_str(r#"(module
+      (memory 1)
+      (table 1 funcref)
+      (elem (i32.const 0) $value)
+      (func $value (result i32) i32.const 42)
+      (func $init (i32.store (i32.const 0) (call_indirect (result i32) (i32.const 0))))
+      (start $init)
+    )"#).unwrap();
+    let wasm = Wasm::new(&binary[..]).unwrap();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    assert_eq!(em.start, Some(1));
+    em.run_start(&mut wasi, &mut linker).await.unwrap();
+    assert_eq!(em.store.memories[0].read(0, 4).unwrap(), 42i32.to_le_bytes());
+  }
+
+  #[tokio::test]
+  async fn test_start_trap_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/start_trap.wat");
+    let mut linker = Linker::new();
+    let mut wasi = WasiSnapshotPreview1::new();
+    assert!(ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn test_tail_call_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/tail_call.wat");
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    // 通常の呼び出しならMAX_CALL_DEPTHを超える深さ
+    em.invoke(&mut wasi, &mut linker, "is_even".to_string(), vec![Value::I64(20001)]).await.unwrap();
+    assert_eq!(em.value_stack, vec![Value::I32(0)]);
+    em.value_stack.clear();
+    em.invoke(&mut wasi, &mut linker, "is_odd".to_string(), vec![Value::I64(20001)]).await.unwrap();
+    assert_eq!(em.value_stack, vec![Value::I32(1)]);
+    em.value_stack.clear();
+    em.invoke(&mut wasi, &mut linker, "count".to_string(), vec![Value::I64(20000), Value::I64(0)]).await.unwrap();
+    assert_eq!(em.value_stack, vec![Value::I64(20000)]);
+    assert!(em.call_stack.is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_exceptions_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/exceptions.wat");
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    let cases = vec![
+      ("catch", vec![Value::I32(42)], Value::I32(42)),
+      ("catch_all", vec![], Value::I32(1)),
+      ("rethrow", vec![Value::I32(5)], Value::I32(5)),
+      ("nested", vec![], Value::I32(3)),
+      ("catch_to_func", vec![], Value::I32(7)),
+    ];
+    for (name, args, expected) in cases {
+      em.invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap();
+      assert_eq!(em.value_stack, vec![expected], "{}", name);
+      assert!(em.call_stack.is_empty(), "{}", name);
+      em.value_stack.clear();
+    }
+
+    // クリーンアップで投げ直された例外も、参照されなくなれば回収されて使い回される
+    let mut fresh = ExecMachine::init_without_start(create_wasm_from_testsuite("tests/mytestsuite/exceptions.wat"), &linker).unwrap();
+    fresh.invoke(&mut wasi, &mut linker, "cleanup_loop".to_string(), vec![Value::I32(70000)]).await.unwrap();
+    assert_eq!(fresh.value_stack.pop(), Some(Value::I32(70000)));
+    assert!(fresh.store.exns.len() <= 64, "{}", fresh.store.exns.len());
+    fresh.invoke(&mut wasi, &mut linker, "hold".to_string(), vec![Value::I32(11)]).await.unwrap();
+    assert_eq!(fresh.value_stack.pop(), Some(Value::I32(11)));
+
+    // 捕まえた例外はVMを復元した後でも投げ直せる
+    em.invoke(&mut wasi, &mut linker, "make".to_string(), vec![Value::I32(9)]).await.unwrap();
+    let exn = em.value_stack.pop().unwrap();
+    assert!(matches!(exn, Value::ExnRef(Some(_))));
+    let mut em = ExecMachine::deserialize(&em.serialize_vm().unwrap()).await.unwrap();
+    em.invoke(&mut wasi, &mut linker, "unpack".to_string(), vec![exn]).await.unwrap();
+    assert_eq!(em.value_stack, vec![Value::I32(9)]);
+    em.value_stack.clear();
+
+    let err = em.invoke(&mut wasi, &mut linker, "uncaught".to_string(), vec![]).await.unwrap_err();
+    assert_eq!(err.trap, Trap::UncaughtException);
+    assert_eq!(err.backtrace.len(), 2);
+    em.call_stack.clear();
+    em.value_stack.clear();
+    let err = em.invoke(&mut wasi, &mut linker, "throw_null".to_string(), vec![]).await.unwrap_err();
+    assert_eq!(err.trap, Trap::NullExceptionReference);
+  }
+
+  #[tokio::test]
+  async fn test_multi_memory_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/multi_memory.wat");
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    assert_eq!(em.store.memories.len(), 2);
+    assert_eq!(em.store.host_memory, 1);
+    let cases = vec![
+      ("load_b", vec![Value::I32(0)], Some(Value::I32(0x04030201))),
+      ("load_a", vec![Value::I32(0)], Some(Value::I32(0))),
+      ("store_b", vec![Value::I32(2), Value::I32(0xff)], None),
+      ("load_b", vec![Value::I32(0)], Some(Value::I32(0xff030201u32 as i32))),
+      ("copy_b_to_a", vec![Value::I32(8), Value::I32(0), Value::I32(4)], None),
+      ("load_a", vec![Value::I32(8)], Some(Value::I32(0xff030201u32 as i32))),
+      ("fill_a", vec![Value::I32(9), Value::I32(0), Value::I32(2)], None),
+      ("load_a", vec![Value::I32(8)], Some(Value::I32(0xff000001u32 as i32))),
+      ("splat_b", vec![Value::I32(0)], Some(Value::V128(0xff030201_ff030201_ff030201_ff030201))),
+      ("grow_b", vec![Value::I32(1)], Some(Value::I32(1))),
+      ("grow_b", vec![Value::I32(1)], Some(Value::I32(-1))),
+      ("size_b", vec![], Some(Value::I32(2))),
+    ];
+    for (name, args, expected) in cases {
+      em.invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap();
+      assert_eq!(em.value_stack.pop(), expected, "{}", name);
+      assert!(em.value_stack.is_empty(), "{}", name);
+    }
+    assert_eq!(em.store.memories[0].memory.len(), 65536);
+    assert_eq!(em.store.host_memory().unwrap().memory.len(), 2 * 65536);
+  }
+
+  #[tokio::test]
+  async fn test_memory64_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/memory64.wat");
+    let memories = wasm.memory_section.as_ref().unwrap();
+    assert_eq!(memories[0], MemorySec { min: 1, max: Some(3), memory64: true, shared: false });
+    assert_eq!(memories[1], MemorySec { min: 1, max: None, memory64: false, shared: false });
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    let cases = vec![
+      ("load64", vec![Value::I64(16)], Ok(Some(Value::I32(42)))),
+      ("load64", vec![Value::I64(0x1_0000_0000)], Err(Trap::MemoryOutOfBounds)),
+      ("load64", vec![Value::I64(-1)], Err(Trap::MemoryOutOfBounds)),
+      ("store64", vec![Value::I64(-4), Value::I32(1)], Err(Trap::MemoryOutOfBounds)),
+      ("store64", vec![Value::I64(12), Value::I32(0x0403_0201)], Ok(None)),
+      ("load64", vec![Value::I64(17)], Ok(Some(Value::I32(2)))),
+      ("fill64", vec![Value::I64(19), Value::I32(7), Value::I64(1)], Ok(None)),
+      ("copy_to_32", vec![Value::I32(0), Value::I64(16), Value::I32(4)], Ok(None)),
+      ("load32", vec![Value::I32(0)], Ok(Some(Value::I32(0x0703_0201)))),
+      ("load_high", vec![Value::I32(0)], Err(Trap::MemoryOutOfBounds)),
+      ("load_high", vec![Value::I32(1)], Err(Trap::MemoryOutOfBounds)),
+      ("size64", vec![], Ok(Some(Value::I64(1)))),
+      ("grow64", vec![Value::I64(2)], Ok(Some(Value::I64(1)))),
+      ("grow64", vec![Value::I64(1)], Ok(Some(Value::I64(-1)))),
+      ("grow64", vec![Value::I64(-1)], Ok(Some(Value::I64(-1)))),
+      ("size64", vec![], Ok(Some(Value::I64(3)))),
+    ];
+    for (name, args, expected) in cases {
+      match expected {
+        Ok(expected) => {
+          em.invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap();
+          assert_eq!(em.value_stack.pop(), expected, "{}", name);
+        },
+        Err(trap) => {
+          let err = em.invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap_err();
+          assert_eq!(err.trap, trap, "{}", name);
+          em.call_stack.clear();
+        },
+      }
+      assert!(em.value_stack.is_empty(), "{}", name);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_threads_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/threads.wat");
+    let memories = wasm.memory_section.as_ref().unwrap();
+    assert_eq!(memories[0], MemorySec { min: 1, max: Some(1), memory64: false, shared: true });
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    let cases = vec![
+      ("add", vec![Value::I32(8), Value::I32(5)], Ok(Some(Value::I32(0)))),
+      ("add", vec![Value::I32(8), Value::I32(-1)], Ok(Some(Value::I32(5)))),
+      ("load", vec![Value::I32(8)], Ok(Some(Value::I32(4)))),
+      ("sub8", vec![Value::I32(8), Value::I32(5)], Ok(Some(Value::I32(4)))),
+      ("load", vec![Value::I32(8)], Ok(Some(Value::I32(0xff)))),
+      ("load8", vec![Value::I32(8)], Ok(Some(Value::I64(0xff)))),
+      ("store16", vec![Value::I32(16), Value::I32(0x1_2345)], Ok(None)),
+      ("cmpxchg16", vec![Value::I32(16), Value::I32(0x1), Value::I32(0x7)], Ok(Some(Value::I32(0x2345)))),
+      ("cmpxchg16", vec![Value::I32(16), Value::I32(0x1_2345), Value::I32(0x7)], Ok(Some(Value::I32(0x2345)))),
+      ("load", vec![Value::I32(16)], Ok(Some(Value::I32(0x7)))),
+      ("xchg64", vec![Value::I32(16), Value::I64(-1)], Ok(Some(Value::I64(0x7)))),
+      ("load", vec![Value::I32(20)], Ok(Some(Value::I32(-1)))),
+      ("fence", vec![], Ok(None)),
+      ("load", vec![Value::I32(2)], Err(Trap::UnalignedAtomic)),
+      ("load", vec![Value::I32(65534)], Err(Trap::MemoryOutOfBounds)),
+      ("wait", vec![Value::I32(16), Value::I32(0), Value::I64(-1)], Ok(Some(Value::I32(1)))),
+      ("wait", vec![Value::I32(16), Value::I32(-1), Value::I64(1_000)], Ok(Some(Value::I32(2)))),
+      ("wait", vec![Value::I32(18), Value::I32(0), Value::I64(0)], Err(Trap::UnalignedAtomic)),
+      ("notify", vec![Value::I32(16), Value::I32(1)], Ok(Some(Value::I32(0)))),
+      ("wait_plain", vec![], Err(Trap::ExpectedSharedMemory)),
+    ];
+    for (name, args, expected) in cases {
+      match expected {
+        Ok(expected) => {
+          em.invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap();
+          assert_eq!(em.value_stack.pop(), expected, "{}", name);
+        },
+        Err(trap) => {
+          let err = em.invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap_err();
+          assert_eq!(err.trap, trap, "{}", name);
+          em.call_stack.clear();
+        },
+      }
+      assert!(em.value_stack.is_empty(), "{}", name);
+    }
+
+    // 共有メモリを持つVMはスナップショットを取れない
+    assert!(em.serialize_vm().is_err());
+    em.snapshot_on_trap = true;
+    let err = em.invoke(&mut wasi, &mut linker, "wait_plain".to_string(), vec![]).await.unwrap_err();
+    assert!(err.vm.is_none());
+
+    let binary = wat::parse_str("(module (memory 1 1 shared) (func (drop (i32.atomic.load align=2 (i32.const 0)))))").unwrap();
+    let err = validate(&Wasm::new(&binary[..]).unwrap()).unwrap_err();
+    assert!(err.to_string().ends_with("atomic alignment must be natural"), "{}", err);
+  }
+
+  #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+  async fn test_threads_shared_memory_across_tasks() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/threads.wat");
+    let em = ExecMachine::init_without_start(wasm, &Linker::new()).unwrap();
+    let joiner = em.new_thread("join", vec![Value::I32(3)]).unwrap();
+    let joiner = tokio::spawn(async move {
+      let mut joiner = joiner;
+      let mut wasi = WasiSnapshotPreview1::new();
+      let mut linker = Linker::new();
+      joiner.exec(&mut wasi, &mut linker).await.unwrap();
+      joiner.value_stack.pop()
+    });
+    let mut workers = Vec::new();
+    for _ in 0..3 {
+      let mut worker = em.new_thread("worker", vec![Value::I32(50)]).unwrap();
+      workers.push(tokio::spawn(async move {
+        let mut wasi = WasiSnapshotPreview1::new();
+        let mut linker = Linker::new();
+        worker.exec(&mut wasi, &mut linker).await.unwrap();
+      }));
+    }
+    for worker in workers {
+      worker.await.unwrap();
+    }
+    assert_eq!(joiner.await.unwrap(), Some(Value::I32(150)));
+    assert_eq!(em.store.memories[0].load(0, 0, 4).unwrap()[..], 150i32.to_le_bytes());
+    assert!(em.store.memories[1].memory.iter().all(|&b| b == 0));
+  }
+
+  #[tokio::test]
+  async fn test_trap_backtrace_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/names.wat");
+    let mut linker = Linker::new();
+    let mut wasi = WasiSnapshotPreview1::new();
+    let mut em = ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.unwrap();
+    let err = em.exec(&mut wasi, &mut linker).await.unwrap_err();
+    assert_eq!(err.trap, Trap::Unreachable);
+    assert_eq!(err.func_idx, Some(1));
+    assert_eq!(err.pc, Some(1));
+    assert_eq!(err.backtrace, vec!["$fail", "$_start"]);
+    assert_eq!(err.to_string(), "unreachable\n  at $fail\n  at $_start");
+    assert!(err.vm.is_none());
+
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/names.wat");
+    let mut em = ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.unwrap();
+    em.snapshot_on_trap = true;
+    let err = em.exec(&mut wasi, &mut linker).await.unwrap_err();
+    assert_eq!(err.vm.unwrap().call_stack.len(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_stack_exhausted_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/recursion.wat");
+    let mut linker = Linker::new();
+    let mut wasi = WasiSnapshotPreview1::new();
+    let mut em = ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.unwrap();
+    let err = em.exec(&mut wasi, &mut linker).await.unwrap_err();
+    assert_eq!(err.trap, Trap::StackExhausted);
+  }
+
   #[tokio::test]
   async fn test_i32_store_wasm() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/i32store.wat");
-    let mut em = ExecMachine::init(wasm, "i32_store",vec![]);
+    let mut linker = Linker::new();
     let mut wasi = WasiSnapshotPreview1::new();
-    em.exec(&mut wasi).await.unwrap();
+    let mut em = ExecMachine::init(wasm, "i32_store", vec![], &mut wasi, &mut linker).await.unwrap();
+    em.exec(&mut wasi, &mut linker).await.unwrap();
     let memory = &em.store.memories[0].memory;
     assert_eq!(memory[0], 42);
   }
@@ -218,9 +848,10 @@ use read_wasm::binary::wasm::Wasm;
   #[tokio::test]
   async fn test_i64_store_wasm() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/i64store.wat");
-    let mut em = ExecMachine::init(wasm, "i64_store",vec![]);
+    let mut linker = Linker::new();
     let mut wasi = WasiSnapshotPreview1::new();
-    em.exec(&mut wasi).await.unwrap();
+    let mut em = ExecMachine::init(wasm, "i64_store", vec![], &mut wasi, &mut linker).await.unwrap();
+    em.exec(&mut wasi, &mut linker).await.unwrap();
     let memory = &em.store.memories[0].memory;
     assert_eq!(memory[0], 42);
   }
@@ -228,9 +859,10 @@ use read_wasm::binary::wasm::Wasm;
   #[tokio::test]
   async fn test_global_wasm() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/global.wat");
-    let mut em = ExecMachine::init(wasm, "_start", vec![]);
+    let mut linker = Linker::new();
     let mut wasi = WasiSnapshotPreview1::new();
-    em.exec(&mut wasi).await.unwrap();
+    let mut em = ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.unwrap();
+    em.exec(&mut wasi, &mut linker).await.unwrap();
     assert_eq!(em.value_stack.last().unwrap(), &Value::I32(50));
   }
 
@@ -238,9 +870,10 @@ use read_wasm::binary::wasm::Wasm;
   #[tokio::test]
   async fn test_hello_world_wasm() {
     let wasm = create_wasm_from_testsuite("tests/mytestsuite/hello_world.wat");
-    let mut em = ExecMachine::init(wasm, "_start", vec![]);
+    let mut linker = Linker::with_wasi();
     let mut wasi = WasiSnapshotPreview1::new();
-    em.exec(&mut wasi).await.unwrap();
+    let mut em = ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.unwrap();
+    em.exec(&mut wasi, &mut linker).await.unwrap();
     let memory = &em.store.memories[0].memory;
     let hello = &memory[0..6];
     let world = &memory[6..13];
@@ -248,4 +881,499 @@ use read_wasm::binary::wasm::Wasm;
     assert_eq!(world, b" World!");
     assert_eq!(em.value_stack.last().unwrap(), &Value::I32(0));
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_parse_malformed_wasm() {
+    let header = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    // 2つ目の型が0x60ではなく0x61で始まっている
+    let mut binary = header.to_vec();
+    binary.extend([0x01, 0x07, 0x02, 0x60, 0x00, 0x00, 0x61, 0x00, 0x00]);
+    let err = Wasm::new(&binary[..]).unwrap_err();
+    assert_eq!(err.section.as_deref(), Some("type"));
+    assert_eq!(err.item, Some(1));
+    assert_eq!(err.offset, 14);
+    assert_eq!(err.to_string(), "malformed module at offset 0xe in type section (item 1): expected function type 0x60 (found 0x61)");
+
+    // セクションサイズがファイルの残りより大きい
+    let mut binary = header.to_vec();
+    binary.extend([0x01, 0x10, 0x00]);
+    let err = Wasm::new(&binary[..]).unwrap_err();
+    assert_eq!(err.offset, 9);
+
+    let err = Wasm::new(&[0x00, 0x61, 0x73, 0x6e][..]).unwrap_err();
+    assert_eq!(err.offset, 0);
+  }
+
+  #[test]
+  fn test_parse_unknown_opcode_wasm() {
+    // (func) の本体に未定義のオペコード0xffが入っている
+    let binary = [
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+      0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+      0x03, 0x02, 0x01, 0x00,
+      0x0a, 0x05, 0x01, 0x03, 0x00, 0xff, 0x0b,
+    ];
+    let err = Wasm::new(&binary[..]).unwrap_err();
+    assert_eq!(err.section.as_deref(), Some("code"));
+    assert_eq!(err.item, Some(0));
+    assert_eq!(err.offset, 23);
+  }
+
+  #[test]
+  fn test_encode_roundtrip() {
+    // watの出力は正規形なので、デコードして書き戻せば同じバイト列になる
+    for entry in std::fs::read_dir("tests/mytestsuite").unwrap() {
+      let path = entry.unwrap().path();
+      if path.extension().is_none_or(|ext| ext != "wat") {
+        continue;
+      }
+      let binary = wat::parse_file(&path).unwrap();
+      let wasm = Wasm::new(&binary[..]).unwrap();
+      let encoded = wasm.encode();
+      assert_eq!(Wasm::new(&encoded[..]).unwrap(), wasm, "{:?}", path);
+      assert_eq!(encoded, binary, "{:?}", path);
+    }
+  }
+
+  #[test]
+  fn test_print_wat_roundtrip() {
+    // 出力したテキストを組み立て直すと同じモジュールになる。カスタムセクションはコメントにしか残らない
+    for entry in std::fs::read_dir("tests/mytestsuite").unwrap() {
+      let path = entry.unwrap().path();
+      if path.extension().is_none_or(|ext| ext != "wat") {
+        continue;
+      }
+      let mut wasm = create_wasm_from_testsuite(path.to_str().unwrap());
+      let text = wasm.to_wat();
+      let binary = wat::parse_str(&text).unwrap_or_else(|e| panic!("{:?}: {}\n{}", path, e, text));
+      let mut reparsed = Wasm::new(&binary[..]).unwrap();
+      for wasm in [&mut wasm, &mut reparsed] {
+        wasm.custom_sections.clear();
+        wasm.name_section = None;
+      }
+      assert_eq!(reparsed, wasm, "{:?}", path);
+    }
+  }
+
+  #[test]
+  fn test_print_wat() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/names.wat");
+    let expected = [
+      "(module $names",
+      "  (type (;0;) (func (result i32)))",
+      "  (type (;1;) (func (param i32) (result i32)))",
+      "  (func $_start (type 0) (result i32)",
+      "    i32.const 1",
+      "    call $fail)",
+      "  (func $fail (type 1) (param i32) (result i32)",
+      "    block (; label = @1 ;)",
+      "      unreachable",
+      "    end",
+      "    local.get 0)",
+      "  (memory (;0;) 1)",
+      "  (global (;0;) (mut i32) (i32.const 0))",
+      "  (export \"_start\" (func $_start))",
+      "  (data (;0;) (i32.const 0) \"hello\")",
+      "  (; custom section \"name\" (69 bytes) ;))",
+    ];
+    assert_eq!(wasm.to_wat().lines().collect::<Vec<_>>(), expected);
+  }
+
+  #[test]
+  fn test_section_headers() {
+    let binary = wat::parse_file("tests/mytestsuite/names.wat").unwrap();
+    let headers = section_headers(&binary).unwrap();
+    let lines: Vec<String> = headers.iter().map(|header| header.to_string()).collect();
+    assert_eq!(lines, [
+      "     Type start=0x0000000a end=0x00000014 (size=0x0000000a) count: 2",
+      " Function start=0x00000016 end=0x00000019 (size=0x00000003) count: 2",
+      "   Memory start=0x0000001b end=0x0000001e (size=0x00000003) count: 1",
+      "   Global start=0x00000020 end=0x00000026 (size=0x00000006) count: 1",
+      "   Export start=0x00000028 end=0x00000032 (size=0x0000000a) count: 1",
+      "     Code start=0x00000034 end=0x00000045 (size=0x00000011) count: 2",
+      "     Data start=0x00000047 end=0x00000052 (size=0x0000000b) count: 1",
+      "   Custom start=0x00000054 end=0x0000009e (size=0x0000004a) \"name\"",
+    ]);
+
+    // セクションの中身が壊れていても一覧は出せる
+    let mut broken = binary.clone();
+    broken[headers[0].start] = 0xff;
+    assert!(Wasm::new(&broken[..]).is_err());
+    assert_eq!(section_headers(&broken).unwrap().len(), headers.len());
+    assert!(section_headers(&binary[..binary.len() - 1]).is_err());
+  }
+
+  #[tokio::test]
+  async fn test_validate_invalid_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/invalid.wat");
+    let err = validate(&wasm).unwrap_err();
+    assert_eq!(err.func_idx, Some(1));
+    assert_eq!(err.instr_offset, Some(2));
+    assert_eq!(err.to_string(), "invalid module in func 1 at instruction 2: type mismatch");
+
+    let mut linker = Linker::new();
+    let mut wasi = WasiSnapshotPreview1::new();
+    assert!(ExecMachine::init(wasm, "_start", vec![], &mut wasi, &mut linker).await.is_err());
+
+    // 0xffffffff個のローカル変数を宣言する関数は、確保する前にエラーになる
+    let binary = [
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+      0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+      0x03, 0x02, 0x01, 0x00,
+      0x0a, 0x0a, 0x01, 0x08, 0x01, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x7f, 0x0b,
+    ];
+    let wasm = Wasm::new(&binary[..]).unwrap();
+    let err = validate(&wasm).unwrap_err();
+    assert_eq!(err.to_string(), "invalid module in func 0: too many locals");
+    assert!(ExecMachine::init_without_start(wasm, &linker).is_err());
+  }
+
+  #[tokio::test]
+  async fn test_multi_value_wasm() {
+    let cases = [
+      ("swap", vec![], vec![Value::I32(9), Value::I32(7)]),
+      ("block_params", vec![], vec![Value::I32(30)]),
+      ("loop_params", vec![], vec![Value::I32(15)]),
+      ("if_params", vec![Value::I32(1)], vec![Value::I32(3), Value::I32(1)]),
+      ("if_params", vec![Value::I32(0)], vec![Value::I32(3), Value::I32(2)]),
+      ("br_multi", vec![], vec![Value::I32(2), Value::I32(3)]),
+      ("return_in_block", vec![], vec![Value::I32(5), Value::I32(1), Value::I32(2)]),
+    ];
+    for (name, args, expected) in cases {
+      let wasm = create_wasm_from_testsuite("tests/mytestsuite/multi_value.wat");
+      let mut linker = Linker::new();
+      let mut wasi = WasiSnapshotPreview1::new();
+      let mut em = ExecMachine::init(wasm, name, args, &mut wasi, &mut linker).await.unwrap();
+      em.exec(&mut wasi, &mut linker).await.unwrap();
+      assert_eq!(em.value_stack, expected, "{}", name);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_frame_wasm() {
+    let cases = [
+      ("return_trims", vec![], vec![Value::I32(103)]),
+      ("br_to_func", vec![Value::I32(1)], vec![Value::I32(109)]),
+      ("br_to_func", vec![Value::I32(0)], vec![Value::I32(108)]),
+      ("br_table_to_func", vec![], vec![Value::I32(5)]),
+    ];
+    for (name, args, expected) in cases {
+      let wasm = create_wasm_from_testsuite("tests/mytestsuite/frame.wat");
+      let mut linker = Linker::new();
+      let mut wasi = WasiSnapshotPreview1::new();
+      let mut em = ExecMachine::init(wasm, name, args, &mut wasi, &mut linker).await.unwrap();
+      em.exec(&mut wasi, &mut linker).await.unwrap();
+      assert_eq!(em.value_stack, expected, "{}", name);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_numeric_trap_wasm() {
+    let cases = [
+      ("i32.div_s", vec![Value::I32(1), Value::I32(0)], Err(Trap::IntegerDivideByZero)),
+      ("i32.div_s", vec![Value::I32(i32::MIN), Value::I32(-1)], Err(Trap::IntegerOverflow)),
+      ("i32.div_s", vec![Value::I32(-7), Value::I32(2)], Ok(Value::I32(-3))),
+      ("i32.rem_s", vec![Value::I32(i32::MIN), Value::I32(-1)], Ok(Value::I32(0))),
+      ("i32.rem_s", vec![Value::I32(1), Value::I32(0)], Err(Trap::IntegerDivideByZero)),
+      ("i64.div_u", vec![Value::I64(-1), Value::I64(2)], Ok(Value::I64(i64::MAX))),
+      ("i64.div_u", vec![Value::I64(1), Value::I64(0)], Err(Trap::IntegerDivideByZero)),
+      ("i32.trunc_f32_s", vec![Value::F32(f32::NAN)], Err(Trap::InvalidConversionToInteger)),
+      ("i32.trunc_f32_s", vec![Value::F32(2147483648.0)], Err(Trap::IntegerOverflow)),
+      ("i32.trunc_f32_s", vec![Value::F32(-2147483648.0)], Ok(Value::I32(i32::MIN))),
+      ("i64.trunc_f64_u", vec![Value::F64(-0.9)], Ok(Value::I64(0))),
+      ("i64.trunc_f64_u", vec![Value::F64(-1.0)], Err(Trap::IntegerOverflow)),
+      ("i64.trunc_f64_u", vec![Value::F64(1e19)], Ok(Value::I64(10000000000000000000u64 as i64))),
+      ("i32.trunc_sat_f32_s", vec![Value::F32(f32::NAN)], Ok(Value::I32(0))),
+      ("i32.trunc_sat_f32_s", vec![Value::F32(2147483648.0)], Ok(Value::I32(i32::MAX))),
+      ("i32.trunc_sat_f32_s", vec![Value::F32(-1e10)], Ok(Value::I32(i32::MIN))),
+      ("i32.trunc_sat_f32_s", vec![Value::F32(-1.9)], Ok(Value::I32(-1))),
+      ("i32.trunc_sat_f64_u", vec![Value::F64(-1.0)], Ok(Value::I32(0))),
+      ("i32.trunc_sat_f64_u", vec![Value::F64(4294967296.0)], Ok(Value::I32(-1))),
+      ("i64.trunc_sat_f32_u", vec![Value::F32(f32::INFINITY)], Ok(Value::I64(-1))),
+      ("i64.trunc_sat_f64_s", vec![Value::F64(f64::NEG_INFINITY)], Ok(Value::I64(i64::MIN))),
+      ("i64.trunc_sat_f64_s", vec![Value::F64(-0.0)], Ok(Value::I64(0))),
+    ];
+    for (name, args, expected) in cases {
+      let wasm = create_wasm_from_testsuite("tests/mytestsuite/numeric_trap.wat");
+      let mut linker = Linker::new();
+      let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+      let mut wasi = WasiSnapshotPreview1::new();
+      let result = em.invoke(&mut wasi, &mut linker, name.to_string(), args).await;
+      match expected {
+        Ok(value) => assert_eq!(result.unwrap().value_stack, vec![value], "{}", name),
+        Err(trap) => assert_eq!(result.unwrap_err().trap, trap, "{}", name),
+      }
+    }
+  }
+
+  // 仕様のconversions.wastから変換命令ごとに境界の値を抜き出したもの
+  #[tokio::test]
+  async fn test_conversions_wasm() {
+    let f32 = |bits: u32| Value::F32(f32::from_bits(bits));
+    let f64 = |bits: u64| Value::F64(f64::from_bits(bits));
+    let cases = [
+      ("i64.extend_i32_s", Value::I32(-10000), Ok(Value::I64(-10000))),
+      ("i64.extend_i32_s", Value::I32(i32::MIN), Ok(Value::I64(0xffffffff80000000u64 as i64))),
+      ("i64.extend_i32_u", Value::I32(-10000), Ok(Value::I64(0x00000000ffffd8f0))),
+      ("i64.extend_i32_u", Value::I32(-1), Ok(Value::I64(0xffffffff))),
+      ("i64.extend_i32_u", Value::I32(i32::MIN), Ok(Value::I64(0x80000000))),
+      ("i32.wrap_i64", Value::I64(0xffffffff7fffffffu64 as i64), Ok(Value::I32(0x7fffffff))),
+      ("i32.wrap_i64", Value::I64(0xfffffffeffffffffu64 as i64), Ok(Value::I32(-1))),
+      ("i32.wrap_i64", Value::I64(0x123456789abcdef0), Ok(Value::I32(0x9abcdef0u32 as i32))),
+      ("i32.trunc_f32_u", Value::F32(4294967040.0), Ok(Value::I32(-256))),
+      ("i32.trunc_f32_u", Value::F32(4294967296.0), Err(Trap::IntegerOverflow)),
+      ("i32.trunc_f64_s", Value::F64(-2147483648.9), Ok(Value::I32(i32::MIN))),
+      ("i32.trunc_f64_s", Value::F64(-2147483649.0), Err(Trap::IntegerOverflow)),
+      ("i64.trunc_f32_s", Value::F32(-9223372036854775808.0), Ok(Value::I64(i64::MIN))),
+      ("i64.trunc_f64_s", Value::F64(f64::NAN), Err(Trap::InvalidConversionToInteger)),
+      ("i32.trunc_sat_f32_s", f32(0x80000001), Ok(Value::I32(0))),
+      ("i32.trunc_sat_f32_s", Value::F32(-1.9), Ok(Value::I32(-1))),
+      ("i32.trunc_sat_f32_s", Value::F32(2147483520.0), Ok(Value::I32(2147483520))),
+      ("i32.trunc_sat_f32_s", Value::F32(2147483648.0), Ok(Value::I32(i32::MAX))),
+      ("i32.trunc_sat_f32_s", Value::F32(-2147483904.0), Ok(Value::I32(i32::MIN))),
+      ("i32.trunc_sat_f32_s", Value::F32(f32::NEG_INFINITY), Ok(Value::I32(i32::MIN))),
+      ("i32.trunc_sat_f32_s", f32(0xffc00000), Ok(Value::I32(0))),
+      ("i32.trunc_sat_f32_u", Value::F32(4294967040.0), Ok(Value::I32(-256))),
+      ("i32.trunc_sat_f32_u", f32(0xbf7fffff), Ok(Value::I32(0))),
+      ("i32.trunc_sat_f32_u", Value::F32(4294967296.0), Ok(Value::I32(-1))),
+      ("i32.trunc_sat_f32_u", Value::F32(-1.0), Ok(Value::I32(0))),
+      ("i32.trunc_sat_f64_s", Value::F64(2147483647.0), Ok(Value::I32(i32::MAX))),
+      ("i32.trunc_sat_f64_s", Value::F64(2147483648.0), Ok(Value::I32(i32::MAX))),
+      ("i32.trunc_sat_f64_s", Value::F64(-2147483649.0), Ok(Value::I32(i32::MIN))),
+      ("i32.trunc_sat_f64_s", Value::F64(f64::NAN), Ok(Value::I32(0))),
+      ("i32.trunc_sat_f64_u", Value::F64(4294967295.0), Ok(Value::I32(-1))),
+      ("i32.trunc_sat_f64_u", Value::F64(1e16), Ok(Value::I32(-1))),
+      ("i32.trunc_sat_f64_u", Value::F64(f64::NEG_INFINITY), Ok(Value::I32(0))),
+      ("i64.trunc_sat_f32_s", Value::F32(9223371487098961920.0), Ok(Value::I64(9223371487098961920))),
+      ("i64.trunc_sat_f32_s", Value::F32(9223372036854775808.0), Ok(Value::I64(i64::MAX))),
+      ("i64.trunc_sat_f32_s", Value::F32(-9223373136366403584.0), Ok(Value::I64(i64::MIN))),
+      ("i64.trunc_sat_f32_u", Value::F32(18446742974197923840.0), Ok(Value::I64(-1099511627776))),
+      ("i64.trunc_sat_f32_u", Value::F32(18446744073709551616.0), Ok(Value::I64(-1))),
+      ("i64.trunc_sat_f32_u", Value::F32(-1.0), Ok(Value::I64(0))),
+      ("i64.trunc_sat_f64_s", Value::F64(9223372036854774784.0), Ok(Value::I64(9223372036854774784))),
+      ("i64.trunc_sat_f64_s", Value::F64(9223372036854775808.0), Ok(Value::I64(i64::MAX))),
+      ("i64.trunc_sat_f64_s", Value::F64(-9223372036854777856.0), Ok(Value::I64(i64::MIN))),
+      ("i64.trunc_sat_f64_s", f64(0xfff8000000000000), Ok(Value::I64(0))),
+      ("i64.trunc_sat_f64_u", Value::F64(18446744073709549568.0), Ok(Value::I64(-2048))),
+      ("i64.trunc_sat_f64_u", Value::F64(9223372036854775808.0), Ok(Value::I64(i64::MIN))),
+      ("i64.trunc_sat_f64_u", Value::F64(18446744073709551616.0), Ok(Value::I64(-1))),
+      ("i64.trunc_sat_f64_u", Value::F64(f64::INFINITY), Ok(Value::I64(-1))),
+      ("f32.convert_i32_u", Value::I32(i32::MIN), Ok(Value::F32(2147483648.0))),
+      ("f32.convert_i32_u", Value::I32(-1), Ok(Value::F32(4294967296.0))),
+      ("f32.convert_i32_u", Value::I32(0x80000081u32 as i32), Ok(f32(0x4f000001))),
+      ("f32.convert_i32_u", Value::I32(0xfffffe81u32 as i32), Ok(f32(0x4f7fffff))),
+      ("f32.convert_i64_s", Value::I64(i64::MIN), Ok(Value::F32(-9223372036854775808.0))),
+      ("f32.convert_i64_u", Value::I64(-1), Ok(Value::F32(18446744073709551616.0))),
+      ("f32.convert_i64_u", Value::I64(0x8000008000000001u64 as i64), Ok(f32(0x5f000001))),
+      ("f64.convert_i32_u", Value::I32(-1), Ok(Value::F64(4294967295.0))),
+      ("f64.convert_i64_s", Value::I64(i64::MIN), Ok(Value::F64(-9223372036854775808.0))),
+      ("f64.convert_i64_u", Value::I64(-1), Ok(Value::F64(18446744073709551616.0))),
+      ("f64.convert_i64_u", Value::I64(0x8000000000000401u64 as i64), Ok(f64(0x43e0000000000001))),
+      ("f32.demote_f64", Value::F64(-0.0), Ok(Value::F32(-0.0))),
+      ("f32.demote_f64", f64(0x0000000000000001), Ok(Value::F32(0.0))),
+      ("f32.demote_f64", f64(0x47efffffefffffff), Ok(f32(0x7f7fffff))),
+      ("f32.demote_f64", f64(0x47effffff0000000), Ok(Value::F32(f32::INFINITY))),
+      ("f32.demote_f64", Value::F64(1e40), Ok(Value::F32(f32::INFINITY))),
+      ("f32.demote_f64", f64(0x3ff0000010000000), Ok(Value::F32(1.0))),
+      ("f32.demote_f64", f64(0x3ff0000030000000), Ok(f32(0x3f800002))),
+      ("f32.demote_f64", Value::F64(f64::NAN), Ok(Value::F32(f32::NAN))),
+      ("f64.promote_f32", f32(0x00000001), Ok(f64(0x36a0000000000000))),
+      ("f64.promote_f32", f32(0x7f7fffff), Ok(f64(0x47efffffe0000000))),
+      ("f64.promote_f32", Value::F32(f32::NEG_INFINITY), Ok(Value::F64(f64::NEG_INFINITY))),
+      ("f64.promote_f32", Value::F32(f32::NAN), Ok(Value::F64(f64::NAN))),
+      ("i32.reinterpret_f32", Value::F32(-0.0), Ok(Value::I32(i32::MIN))),
+      ("f64.reinterpret_i64", Value::I64(0x7ff0000000000000), Ok(Value::F64(f64::INFINITY))),
+    ];
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/conversions.wat");
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    for (name, arg, expected) in cases {
+      em.value_stack.clear();
+      let result = em.invoke(&mut wasi, &mut linker, name.to_string(), vec![arg.clone()]).await.map(|em| em.value_stack.clone());
+      match (result, expected) {
+        // NaNは互いに等しくならないのでNaNであることだけ確かめる
+        (Ok(stack), Ok(Value::F32(v))) if v.is_nan() => assert!(matches!(stack[..], [Value::F32(r)] if r.is_nan()), "{} {:?}", name, arg),
+        (Ok(stack), Ok(Value::F64(v))) if v.is_nan() => assert!(matches!(stack[..], [Value::F64(r)] if r.is_nan()), "{} {:?}", name, arg),
+        (Ok(stack), Ok(value)) => assert_eq!(stack, vec![value], "{} {:?}", name, arg),
+        (Err(err), Err(trap)) => {
+          assert_eq!(err.trap, trap, "{} {:?}", name, arg);
+          em.call_stack.clear();
+        },
+        (result, expected) => panic!("{} {:?}: expected {:?}, got {:?}", name, arg, expected, result.map_err(|e| e.trap)),
+      }
+    }
+  }
+
+  #[tokio::test]
+  async fn test_bulk_memory_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/bulk_memory.wat");
+    assert_eq!(wasm.data_count_section, Some(2));
+    let data = wasm.data_section.as_ref().unwrap();
+    assert_eq!(data[0].mode, DataMode::Passive);
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    assert_eq!(&em.store.memories[0].memory[16..19], b"abc");
+
+    let i32s = |args: [i32; 3]| args.map(Value::I32).to_vec();
+    em.invoke(&mut wasi, &mut linker, "init".to_string(), i32s([0, 0, 5])).await.unwrap();
+    // 重なった領域のコピー
+    em.invoke(&mut wasi, &mut linker, "copy".to_string(), i32s([2, 0, 5])).await.unwrap();
+    em.invoke(&mut wasi, &mut linker, "fill".to_string(), i32s([16, 0x7a, 2])).await.unwrap();
+    assert_eq!(&em.store.memories[0].memory[0..7], b"hehello");
+    assert_eq!(&em.store.memories[0].memory[16..19], b"zzc");
+
+    let cases = [
+      ("init", [65535, 0, 2], Err(Trap::MemoryOutOfBounds)),
+      ("init", [0, 3, 3], Err(Trap::MemoryOutOfBounds)),
+      ("init", [65536, 5, 0], Ok(())),
+      ("init_active", [0, 0, 1], Err(Trap::MemoryOutOfBounds)),
+      ("init_active", [0, 0, 0], Ok(())),
+      ("copy", [65535, 0, 2], Err(Trap::MemoryOutOfBounds)),
+      ("copy", [0, 65536, 0], Ok(())),
+      ("fill", [65535, 0, 2], Err(Trap::MemoryOutOfBounds)),
+      ("fill", [65536, 0, 0], Ok(())),
+    ];
+    for (name, args, expected) in cases {
+      let mut em = em.clone();
+      let result = em.invoke(&mut wasi, &mut linker, name.to_string(), i32s(args)).await;
+      match expected {
+        Ok(()) => assert!(result.is_ok(), "{} {:?}", name, args),
+        Err(trap) => assert_eq!(result.unwrap_err().trap, trap, "{} {:?}", name, args),
+      }
+    }
+
+    em.invoke(&mut wasi, &mut linker, "drop".to_string(), vec![]).await.unwrap();
+    let err = em.clone().invoke(&mut wasi, &mut linker, "init".to_string(), i32s([0, 0, 1])).await.unwrap_err();
+    assert_eq!(err.trap, Trap::MemoryOutOfBounds);
+    em.invoke(&mut wasi, &mut linker, "init".to_string(), i32s([0, 0, 0])).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_reference_types_wasm() {
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/reference_types.wat");
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    assert_eq!(em.store.elems[0], vec![Value::FuncRef(Some(0)), Value::FuncRef(None)]);
+
+    let cases = [
+      ("init", vec![Value::I32(0), Value::I32(0), Value::I32(2)], Ok(None)),
+      ("call", vec![Value::I32(0), Value::I32(21)], Ok(Some(Value::I32(42)))),
+      ("call", vec![Value::I32(1), Value::I32(21)], Err(Trap::UninitializedElement)),
+      ("is_null", vec![Value::I32(0)], Ok(Some(Value::I32(0)))),
+      ("is_null", vec![Value::I32(1)], Ok(Some(Value::I32(1)))),
+      ("init", vec![Value::I32(1), Value::I32(1), Value::I32(2)], Err(Trap::TableOutOfBounds)),
+      ("copy", vec![Value::I32(1), Value::I32(0), Value::I32(1)], Ok(None)),
+      ("call", vec![Value::I32(1), Value::I32(5)], Ok(Some(Value::I32(10)))),
+      ("func_ref", vec![], Ok(Some(Value::FuncRef(Some(0))))),
+      ("size", vec![], Ok(Some(Value::I32(0)))),
+      ("grow", vec![Value::ExternRef(Some(7)), Value::I32(2)], Ok(Some(Value::I32(0)))),
+      ("get", vec![Value::I32(1)], Ok(Some(Value::ExternRef(Some(7))))),
+      // maxを超えるgrowは失敗する
+      ("grow", vec![Value::ExternRef(None), Value::I32(2)], Ok(Some(Value::I32(-1)))),
+      ("grow", vec![Value::ExternRef(None), Value::I32(1)], Ok(Some(Value::I32(2)))),
+      ("get", vec![Value::I32(2)], Ok(Some(Value::ExternRef(None)))),
+      ("set", vec![Value::I32(2), Value::ExternRef(Some(3))], Ok(None)),
+      ("get", vec![Value::I32(2)], Ok(Some(Value::ExternRef(Some(3))))),
+      ("get", vec![Value::I32(3)], Err(Trap::TableOutOfBounds)),
+      ("fill", vec![Value::I32(0), Value::ExternRef(Some(9)), Value::I32(2)], Ok(None)),
+      ("get", vec![Value::I32(0)], Ok(Some(Value::ExternRef(Some(9))))),
+      ("fill", vec![Value::I32(2), Value::ExternRef(None), Value::I32(2)], Err(Trap::TableOutOfBounds)),
+      ("select", vec![Value::ExternRef(Some(1)), Value::ExternRef(None), Value::I32(0)], Ok(Some(Value::ExternRef(None)))),
+      ("drop", vec![], Ok(None)),
+      ("init", vec![Value::I32(0), Value::I32(0), Value::I32(1)], Err(Trap::TableOutOfBounds)),
+      ("init", vec![Value::I32(0), Value::I32(0), Value::I32(0)], Ok(None)),
+    ];
+    for (name, args, expected) in cases {
+      match expected {
+        Ok(value) => {
+          em.invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap();
+          assert_eq!(em.value_stack.pop(), value, "{}", name);
+        },
+        Err(trap) => {
+          let err = em.clone().invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap_err();
+          assert_eq!(err.trap, trap, "{}", name);
+        },
+      }
+    }
+
+    // 確保できないほど大きなテーブルは、growなら-1、インスタンス化ならエラーになる
+    let binary = wat::parse_str(r#"(module
+      (table 0 funcref)
+      (func (export "grow") (result i32)
+        (table.grow (ref.null func) (i32.const 0x7fffffff)))
+    )"#).unwrap();
+    let mut em = ExecMachine::init_without_start(Wasm::new(&binary[..]).unwrap(), &linker).unwrap();
+    em.invoke(&mut wasi, &mut linker, "grow".to_string(), vec![]).await.unwrap();
+    assert_eq!(em.value_stack.pop(), Some(Value::I32(-1)));
+    assert_eq!(em.store.tables[0].elems.len(), 0);
+    let binary = wat::parse_str(r#"(module (table 0xffffffff funcref))"#).unwrap();
+    let err = ExecMachine::init_without_start(Wasm::new(&binary[..]).unwrap(), &linker).unwrap_err();
+    assert_eq!(err.to_string(), "table size too large");
+  }
+
+  fn v128_from_i32x4(lanes: [i32; 4]) -> Value {
+    Value::V128(lanes.iter().rev().fold(0u128, |acc, &l| acc << 32 | l as u32 as u128))
+  }
+
+  fn v128_from_f32x4(lanes: [f32; 4]) -> Value {
+    Value::V128(lanes.iter().rev().fold(0u128, |acc, &l| acc << 32 | l.to_bits() as u128))
+  }
+
+  #[tokio::test]
+  async fn test_simd_wasm() {
+    let bytes = |b: [u8; 16]| Value::V128(u128::from_le_bytes(b));
+    let cases = [
+      ("i32x4.add", vec![v128_from_i32x4([1, 2, 3, i32::MAX]), v128_from_i32x4([10, 20, 30, 1])], Ok(v128_from_i32x4([11, 22, 33, i32::MIN]))),
+      ("i8x16.add_sat_u", vec![bytes([250; 16]), bytes([10; 16])], Ok(bytes([255; 16]))),
+      ("i16x8.lt_s", vec![v128_from_i32x4([-1, 0, 0, 0]), v128_from_i32x4([0, 0, 0, 0])], Ok(v128_from_i32x4([-1, 0, 0, 0]))),
+      ("f32x4.min", vec![v128_from_f32x4([0.0, 1.0, -2.0, f32::NAN]), v128_from_f32x4([-0.0, 2.0, 3.0, 1.0])], Ok(v128_from_f32x4([-0.0, 1.0, -2.0, f32::NAN]))),
+      ("i8x16.shuffle", vec![bytes([0; 16]), bytes([1; 16])], Ok(bytes([0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1]))),
+      ("i8x16.swizzle", vec![bytes([9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 0, 0, 0, 0, 0]), bytes([1, 0, 16, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0])], Ok(bytes([8, 9, 0, 0, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9]))),
+      // シフト量はレーン幅で剰余を取る
+      ("i32x4.shl", vec![v128_from_i32x4([1, 2, 3, 4]), Value::I32(33)], Ok(v128_from_i32x4([2, 4, 6, 8]))),
+      ("i8x16.bitmask", vec![bytes([0x80, 0, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80])], Ok(Value::I32(0b1000_0000_0000_0101))),
+      ("f64x2.extract_lane", vec![Value::V128((2.5f64.to_bits() as u128) << 64)], Ok(Value::F64(2.5))),
+      ("i16x8.replace_lane", vec![Value::V128(0), Value::I32(0x12345)], Ok(Value::V128(0x2345 << 112))),
+      ("i64x2.splat", vec![Value::I64(-2)], Ok(Value::V128(u128::MAX - 1 - (1 << 64)))),
+      ("i32x4.trunc_sat_f32x4_s", vec![v128_from_f32x4([f32::NAN, 1e10, -1e10, -1.5])], Ok(v128_from_i32x4([0, i32::MAX, i32::MIN, -1]))),
+      ("v128.load8x8_s", vec![Value::I32(8)], Ok(Value::V128(0x0010_000f_000e_000d_000c_000b_000a_0009))),
+      ("v128.load32_lane", vec![Value::I32(0), Value::V128(0)], Ok(Value::V128(0x04030201 << 96))),
+      ("v128.load8x8_s", vec![Value::I32(65530)], Err(Trap::MemoryOutOfBounds)),
+      ("v128.const", vec![], Ok(v128_from_i32x4([1, 2, 3, 4]))),
+    ];
+    let wasm = create_wasm_from_testsuite("tests/mytestsuite/simd.wat");
+    let mut linker = Linker::new();
+    let mut em = ExecMachine::init_without_start(wasm, &linker).unwrap();
+    let mut wasi = WasiSnapshotPreview1::new();
+    for (name, args, expected) in cases {
+      match expected {
+        Ok(Value::V128(expected)) if name == "f32x4.min" => {
+          em.invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap();
+          let Some(Value::V128(result)) = em.value_stack.pop() else { panic!("{}", name) };
+          // NaNのビットパターンは問わない
+          assert_eq!(result & !(u128::from(u32::MAX) << 96), expected & !(u128::from(u32::MAX) << 96), "{}", name);
+          assert!(f32::from_bits((result >> 96) as u32).is_nan(), "{}", name);
+        },
+        Ok(value) => {
+          em.invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap();
+          assert_eq!(em.value_stack.pop(), Some(value), "{}", name);
+        },
+        Err(trap) => {
+          let err = em.clone().invoke(&mut wasi, &mut linker, name.to_string(), args).await.unwrap_err();
+          assert_eq!(err.trap, trap, "{}", name);
+        },
+      }
+    }
+
+    // v128もスナップショットに含められる
+    em.invoke(&mut wasi, &mut linker, "v128.store".to_string(), vec![Value::I32(16), Value::V128(u128::MAX)]).await.unwrap();
+    em.value_stack.push(Value::V128(u128::MAX));
+    let restored = ExecMachine::deserialize(&em.serialize_vm().unwrap()).await.unwrap();
+    assert_eq!(restored.value_stack, vec![Value::V128(u128::MAX)]);
+    assert_eq!(&restored.store.memories[0].memory[16..32], &[0xff; 16]);
+  }
+}
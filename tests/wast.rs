@@ -1,7 +1,7 @@
 #[allow(unused_imports)]
 #[cfg(test)]
 mod tests {
-  use read_wasm::{binary::wasm::Wasm, exec::{exec_machine::ExecMachine, value::Value, wasi::{self, WasiSnapshotPreview1}}};
+  use read_wasm::{binary::{validate::validate, wasm::Wasm}, exec::{exec_machine::ExecMachine, linker::Linker, value::Value, wasi::{self, WasiSnapshotPreview1}}};
 use serde::{Deserialize, Serialize};
 use tokio::io::AsyncReadExt;
   use std::process::Command;
@@ -72,16 +72,14 @@ use tokio::io::AsyncReadExt;
       Externref { value: Option<String> },
       Funcref { value: Option<String> },
   }
-  impl Into<Value> for Val {
-    fn into(self) -> Value {
-      match self {
+  impl From<Val> for Value {
+    fn from(val: Val) -> Value {
+      match val {
         Val::I32 { value } => Value::I32(value.unwrap().parse::<u32>().unwrap() as i32),
         Val::I64 { value } => Value::I64(value.unwrap().parse::<u64>().unwrap() as i64),
         Val::F32 { value } => {
           let value = value.unwrap();
-          if value == "nan:canonical" {
-            Value::F32(f32::NAN)
-          } else if value == "nan:arithmetic" {
+          if value == "nan:canonical" || value == "nan:arithmetic" {
             Value::F32(f32::NAN)
           } else {
               Value::F32(f32::from_bits(value.parse::<u32>().unwrap()))
@@ -89,15 +87,20 @@ use tokio::io::AsyncReadExt;
         }
         Val::F64 { value } => {
           let value = value.unwrap();
-          if value == "nan:canonical" {
-            Value::F64(f64::NAN)
-          } else if value == "nan:arithmetic" {
+          if value == "nan:canonical" || value == "nan:arithmetic" {
             Value::F64(f64::NAN)
           } else {
             Value::F64(f64::from_bits(value.parse::<u64>().unwrap()))
           }
         }
-        _ => unimplemented!(),
+        Val::Externref { value } => match value.as_deref() {
+          Some("null") | None => Value::ExternRef(None),
+          Some(value) => Value::ExternRef(Some(value.parse::<u32>().unwrap())),
+        },
+        Val::Funcref { value } => match value.as_deref() {
+          Some("null") | None => Value::FuncRef(None),
+          Some(value) => Value::FuncRef(Some(value.parse::<usize>().unwrap())),
+        },
       }
     }
   }
@@ -105,7 +108,7 @@ use tokio::io::AsyncReadExt;
   async fn test_suite(file_path: &str) {
     // let temp_dir = async_tempfile::TempDir::new().await.unwrap();
     Command::new("wast2json")
-      .arg(&format!("./tests/testsuite/{file_path}"))
+      .arg(format!("./tests/testsuite/{file_path}"))
       .arg("-o")
       .arg("./target/tmp/test.json")
       .output()
@@ -116,6 +119,7 @@ use tokio::io::AsyncReadExt;
     let test_suite: TestSuite = serde_json::from_str(&json).unwrap();
     let tests = test_suite.commands;
     let mut vm = None;
+    let mut linker = Linker::new();
     let mut wasi = WasiSnapshotPreview1::new();
     let mut count = 0;
     for test in tests {
@@ -124,8 +128,9 @@ use tokio::io::AsyncReadExt;
       match test {
         Test::Module { filename, .. } => {
           let filename = format!("./target/tmp/{filename}");
-          let wasm = Wasm::new(std::fs::File::open(filename).unwrap());
-          vm = Some(ExecMachine::init_without_start(wasm));
+          let wasm = Wasm::new(std::fs::File::open(filename).unwrap()).unwrap();
+          vm = Some(ExecMachine::init_without_start(wasm, &linker).unwrap());
+          vm.as_mut().unwrap().run_start(&mut wasi, &mut linker).await.unwrap();
         }
         Test::AssertReturn { line: _, action, expected } => {
           vm.as_mut().unwrap().value_stack.clear();
@@ -133,7 +138,7 @@ use tokio::io::AsyncReadExt;
             Action::Invoke { field, args } => {
               let args = args.into_iter().map(|x| x.into()).collect();
               let field = field.to_owned();
-              vm.as_mut().unwrap().invoke(&mut wasi, field, args).await.unwrap();
+              vm.as_mut().unwrap().invoke(&mut wasi, &mut linker, field, args).await.unwrap();
               vm.as_mut().unwrap().value_stack.clone()
             }
           };
@@ -158,11 +163,31 @@ use tokio::io::AsyncReadExt;
             Action::Invoke { field, args } => {
               let args = args.into_iter().map(|x| x.into()).collect();
               let field = field.to_owned();
-              vm.as_mut().unwrap().invoke(&mut wasi, field, args).await.unwrap();
+              vm.as_mut().unwrap().invoke(&mut wasi, &mut linker, field, args).await.unwrap();
               vm.as_mut().unwrap().value_stack.clone()
             }
           };
         }
+        Test::AssertTrap { line, action, text, .. } => {
+          vm.as_mut().unwrap().value_stack.clear();
+          let Action::Invoke { field, args } = action;
+          let args = args.into_iter().map(|x| x.into()).collect();
+          let err = vm.as_mut().unwrap().invoke(&mut wasi, &mut linker, field, args).await.unwrap_err();
+          assert!(err.trap.to_string().starts_with(&text), "line {line}: expected trap \"{text}\", got \"{}\"", err.trap);
+          // トラップで中断した呼び出しが残らないようにする
+          vm.as_mut().unwrap().call_stack.clear();
+        }
+        Test::AssertInvalid { line, filename, text, module_type } if module_type == "binary" => {
+          let filename = format!("./target/tmp/{filename}");
+          let wasm = Wasm::new(std::fs::File::open(filename).unwrap()).unwrap();
+          assert!(validate(&wasm).is_err(), "line {line}: expected invalid module ({text})");
+        }
+        // テキスト形式の不正はwast2json側の話なのでバイナリだけ確かめる
+        Test::AssertMalformed { line, filename, text, module_type } if module_type == "binary" => {
+          let filename = format!("./target/tmp/{filename}");
+          let result = Wasm::new(std::fs::File::open(filename).unwrap());
+          assert!(result.is_err(), "line {line}: expected malformed module ({text})");
+        }
         _ => {},
       }
     }
@@ -188,6 +213,11 @@ use tokio::io::AsyncReadExt;
     test_suite("i64.wast").await;
   }
 
+  #[tokio::test]
+  async fn test_conversions() {
+    test_suite("conversions.wast").await;
+  }
+
   // #[tokio::test]
   // async fn test_memory() {
   //   test_suite("memory.wast").await;